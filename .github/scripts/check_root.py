@@ -0,0 +1,48 @@
+#!/usr/bin/env python3
+#
+#  Read the ROOT files written by the formats::root reference_1 test
+#  with uproot, a reader independent of ours, and check the axes,
+#  channels (including under/overflow) and entries.
+#
+#  Usage:  check_root.py directory
+#
+import sys
+
+import numpy as np
+import uproot
+
+
+def check(label, expected, actual):
+    if not np.array_equal(np.asarray(expected), np.asarray(actual)):
+        sys.exit(f"{label}: expected {expected} got {actual}")
+
+
+directory = sys.argv[1]
+
+with uproot.open(f"{directory}/energy.root") as f:
+    h = f["h_energy"]
+    check("TH1F class", "TH1F", h.classname)
+    check("TH1F title", "energy", h.title)
+    check("TH1F x edges", np.linspace(0.0, 100.0, 11), h.axis().edges())
+    expected = np.zeros(12)
+    expected[0] = 4.0
+    expected[1] = 2.0
+    expected[6] = 3.0
+    expected[11] = 1.0
+    check("TH1F channels", expected, h.values(flow=True))
+    check("TH1F entries", 10.0, h.member("fEntries"))
+
+with uproot.open(f"{directory}/xy.root") as f:
+    h = f["h_xy"]
+    check("TH2F class", "TH2F", h.classname)
+    check("TH2F title", "xy", h.title)
+    check("TH2F x edges", np.linspace(0.0, 10.0, 11), h.axis(0).edges())
+    check("TH2F y edges", np.linspace(-5.0, 5.0, 6), h.axis(1).edges())
+    expected = np.zeros((12, 7))  # [x, y] with the flow bins.
+    expected[1, 1] = 1.0
+    expected[10, 5] = 2.0
+    expected[11, 3] = 7.0
+    check("TH2F channels", expected, h.values(flow=True))
+    check("TH2F entries", 10.0, h.member("fEntries"))
+
+print("ROOT files read back by uproot", uproot.__version__)
@@ -0,0 +1,327 @@
+//! Baseline histogram fill performance.  Each benchmark handles a
+//! batch of synthetic events (testing::EventGenerator) for dictionaries
+//! of 64, 512 and 4096 parameters where 10%, 50% and 100% of the
+//! parameters are present in each event.  oned bulk compares
+//! fill_1d_bulk with filling the same values event by event.  batch
+//! compares fill_batch with fill_batch_parallel for 10 spectra and
+//! 100,000 events.  sparse events compares a 1-d spectrum filled from
+//! dense and sparse flat events with 8 of 4096 parameters present.
+//! event loads compares 1,000,000 loads of 512 parameter events into
+//! a pre-allocated flat event with loads into new flat events.
+//!
+//! Run with  cargo bench --bench fill
+//!
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use histogrammer::conditions::*;
+use histogrammer::parameters::*;
+use histogrammer::spectra::*;
+use histogrammer::testing::EventGenerator;
+use std::cell::RefCell;
+use std::hint::black_box;
+use std::rc::Rc;
+
+const DICTIONARY_SIZES: [u32; 3] = [64, 512, 4096];
+const FILL_FRACTIONS: [f32; 3] = [0.1, 0.5, 1.0];
+const BATCH_SIZE: usize = 1000;
+
+// Parameters p.1 ... p.n have ids 1..n as the generator expects:
+
+fn make_dictionary(n: u32) -> ParameterDictionary {
+    let mut pdict = ParameterDictionary::new();
+    for i in 1..=n {
+        let name = format!("p.{}", i);
+        pdict.add(&name).unwrap();
+        pdict
+            .lookup_mut(&name)
+            .unwrap()
+            .set_limits(0.0, 1024.0)
+            .set_bins(1024);
+    }
+    pdict
+}
+fn names(ids: std::ops::RangeInclusive<u32>) -> Vec<String> {
+    ids.map(|i| format!("p.{}", i)).collect()
+}
+fn make_events(n: u32, fill_fraction: f32) -> Vec<FlatEvent> {
+    EventGenerator::new(n, fill_fraction, 12345)
+        .event_stream(BATCH_SIZE)
+        .collect()
+}
+
+// Benchmark handle_event for a spectrum made by make_spectrum for each
+// dictionary size and fill fraction:
+
+fn bench_spectrum<F>(c: &mut Criterion, group_name: &str, make_spectrum: F)
+where
+    F: Fn(&ParameterDictionary) -> Box<dyn Spectrum>,
+{
+    let mut group = c.benchmark_group(group_name);
+    group.throughput(Throughput::Elements(BATCH_SIZE as u64));
+    for n in DICTIONARY_SIZES {
+        let pdict = make_dictionary(n);
+        for fraction in FILL_FRACTIONS {
+            let events = make_events(n, fraction);
+            let mut spectrum = make_spectrum(&pdict);
+            let id = BenchmarkId::new(
+                format!("{} parameters", n),
+                format!("{}%", fraction * 100.0),
+            );
+            group.bench_with_input(id, &events, |b, events| {
+                b.iter(|| {
+                    for e in events {
+                        spectrum.handle_event(black_box(e));
+                    }
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+fn oned(c: &mut Criterion) {
+    bench_spectrum(c, "oned", |pdict| {
+        Box::new(Oned::new("1d", "p.1", pdict, None, None, None).unwrap())
+    });
+}
+fn twod(c: &mut Criterion) {
+    bench_spectrum(c, "twod", |pdict| {
+        Box::new(
+            Twod::new(
+                "2d",
+                "p.1",
+                "p.2",
+                pdict,
+                None,
+                None,
+                Some(512),
+                None,
+                None,
+                Some(512),
+            )
+            .unwrap(),
+        )
+    });
+}
+fn pgamma(c: &mut Criterion) {
+    bench_spectrum(c, "pgamma 5x5", |pdict| {
+        Box::new(
+            PGamma::new(
+                "pgamma",
+                &names(1..=5),
+                &names(6..=10),
+                pdict,
+                None,
+                None,
+                Some(512),
+                None,
+                None,
+                Some(512),
+            )
+            .unwrap(),
+        )
+    });
+}
+fn summary(c: &mut Criterion) {
+    bench_spectrum(c, "summary 64", |pdict| {
+        Box::new(Summary::new("summary", names(1..=64), pdict, None, None, None).unwrap())
+    });
+}
+
+// A batch of values filled with fill_1d_bulk and as events:
+
+fn oned_bulk(c: &mut Criterion) {
+    let pdict = make_dictionary(1);
+    let values: Vec<f64> = make_events(1, 1.0).iter().filter_map(|e| e[1]).collect();
+    let events: Vec<FlatEvent> = values
+        .iter()
+        .map(|v| {
+            let mut e = FlatEvent::new();
+            e.load_event(&vec![EventParameter::new(1, *v)]);
+            e
+        })
+        .collect();
+
+    let mut group = c.benchmark_group("oned bulk");
+    group.throughput(Throughput::Elements(values.len() as u64));
+    let mut spectrum = Oned::new("1d", "p.1", &pdict, None, None, None).unwrap();
+    group.bench_function("fill_1d_bulk", |b| {
+        b.iter(|| fill_1d_bulk(&mut spectrum, black_box(&values)))
+    });
+    group.bench_function("handle_event", |b| {
+        b.iter(|| {
+            for e in events.iter() {
+                spectrum.handle_event(black_box(e));
+            }
+        })
+    });
+    group.finish();
+}
+
+// A 1-d spectrum on p.1 filled from Dense and Sparse flat events
+// with p.1 and 7 other parameters of 4096 set:
+
+fn sparse_events(c: &mut Criterion) {
+    let pdict = make_dictionary(4096);
+    let raw: Vec<Event> = (0..BATCH_SIZE)
+        .map(|i| {
+            (0..8)
+                .map(|p| EventParameter::new(1 + p * 585, ((i * 37 + p as usize) % 1024) as f64))
+                .collect()
+        })
+        .collect();
+    let dense: Vec<FlatEvent> = raw
+        .iter()
+        .map(|e| {
+            let mut f = FlatEvent::new_with_capacity(4096);
+            f.load_event(e);
+            f
+        })
+        .collect();
+    let sparse: Vec<FlatEvent> = raw
+        .iter()
+        .map(|e| {
+            let mut f = FlatEvent::new_for_dictionary(4096);
+            f.load_event(e);
+            f
+        })
+        .collect();
+
+    let mut group = c.benchmark_group("sparse events");
+    group.throughput(Throughput::Elements(BATCH_SIZE as u64));
+    let mut spectrum = Oned::new("1d", "p.1", &pdict, None, None, None).unwrap();
+    for (name, events) in [("dense", &dense), ("sparse", &sparse)] {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                for e in events.iter() {
+                    spectrum.handle_event(black_box(e));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+// 1,000,000 loads of events with half of 512 parameters set, reusing
+// a pre-allocated flat event vs. a new one for each load:
+
+const LOAD_COUNT: usize = 1_000_000;
+
+fn event_loads(c: &mut Criterion) {
+    let mut generator = EventGenerator::new(512, 0.5, 12345);
+    let raw: Vec<Event> = (0..BATCH_SIZE).map(|_| generator.next_event()).collect();
+
+    let mut group = c.benchmark_group("event loads");
+    group.throughput(Throughput::Elements(LOAD_COUNT as u64));
+    group.sample_size(10);
+    group.bench_function("pre-allocated", |b| {
+        let mut flat = FlatEvent::new_with_capacity(512);
+        b.iter(|| {
+            for e in raw.iter().cycle().take(LOAD_COUNT) {
+                flat.load_event_in_place(black_box(e));
+                black_box(&flat);
+            }
+        })
+    });
+    group.bench_function("not pre-allocated", |b| {
+        b.iter(|| {
+            for e in raw.iter().cycle().take(LOAD_COUNT) {
+                let mut flat = FlatEvent::new();
+                flat.load_event(black_box(e));
+                black_box(&flat);
+            }
+        })
+    });
+    group.finish();
+}
+
+// fill_batch and fill_batch_parallel on 10 2-d spectra, p.1 vs p.2
+// ... p.10 vs p.11, over 100,000 events for 64 parameters:
+
+const PARALLEL_BATCH_SIZE: usize = 100_000;
+
+fn batch(c: &mut Criterion) {
+    let pdict = make_dictionary(64);
+    let events: Vec<FlatEvent> = EventGenerator::new(64, 0.5, 12345)
+        .event_stream(PARALLEL_BATCH_SIZE)
+        .collect();
+    let mut dict = SpectrumDictionary::new();
+    for i in 1..=10 {
+        let name = format!("2d.{}", i);
+        let spectrum = Twod::new(
+            &name,
+            &format!("p.{}", i),
+            &format!("p.{}", i + 1),
+            &pdict,
+            None,
+            None,
+            Some(512),
+            None,
+            None,
+            Some(512),
+        )
+        .unwrap();
+        dict.insert(name, Rc::new(RefCell::new(spectrum)));
+    }
+    let mut cdict = ConditionDictionary::new();
+
+    let mut group = c.benchmark_group("batch 10 spectra");
+    group.throughput(Throughput::Elements(PARALLEL_BATCH_SIZE as u64));
+    group.sample_size(10);
+    group.bench_function("fill_batch", |b| {
+        b.iter(|| fill_batch(black_box(&events), &mut dict, &mut cdict))
+    });
+    group.bench_function("fill_batch_parallel", |b| {
+        b.iter(|| fill_batch_parallel(black_box(&events), &mut dict, &mut cdict))
+    });
+    group.finish();
+}
+
+// And::check on 10 cuts.  The caches are invalidated for each event
+// as the pipeline would.
+
+fn and_check(c: &mut Criterion) {
+    let mut group = c.benchmark_group("and 10 cuts");
+    group.throughput(Throughput::Elements(BATCH_SIZE as u64));
+    for n in DICTIONARY_SIZES {
+        let mut cdict = ConditionDictionary::new();
+        let mut and = And::new();
+        for i in 1..=10 {
+            let name = format!("cut.{}", i);
+            let cut: Container = Rc::new(RefCell::new(Cut::new(i, 0.0, 1000.0)));
+            and.add_condition(&name, &cut);
+            cdict.insert(name, cut);
+        }
+        let and: Container = Rc::new(RefCell::new(and));
+        cdict.insert(String::from("and"), and.clone());
+        for fraction in FILL_FRACTIONS {
+            let events = make_events(n, fraction);
+            let id = BenchmarkId::new(
+                format!("{} parameters", n),
+                format!("{}%", fraction * 100.0),
+            );
+            group.bench_with_input(id, &events, |b, events| {
+                b.iter(|| {
+                    for e in events {
+                        invalidate_cache(&mut cdict);
+                        black_box(and.borrow_mut().check(black_box(e)));
+                    }
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(
+    fill,
+    oned,
+    twod,
+    pgamma,
+    summary,
+    oned_bulk,
+    sparse_events,
+    event_loads,
+    batch,
+    and_check
+);
+criterion_main!(fill);
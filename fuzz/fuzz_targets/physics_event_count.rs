@@ -0,0 +1,10 @@
+#![no_main]
+//!  Conversion of arbitrary raw items to PhysicsEventCountItem.
+//!
+use histogrammer::ring_items::triggers_item::PhysicsEventCountItem;
+use histogrammer_fuzz::check_specific;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    check_specific::<PhysicsEventCountItem>(data);
+});
@@ -0,0 +1,14 @@
+#![no_main]
+//!  RingItem::from_slice must not panic and the items it produces
+//!  must serialize back to the bytes they came from.
+//!
+use histogrammer::ring_items::RingItem;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(item) = RingItem::from_slice(data) {
+        let size = item.size() as usize;
+        assert_eq!(&data[..size], item.to_bytes().as_slice());
+        assert_eq!(Some(item.size()), RingItem::parse_size(data));
+    }
+});
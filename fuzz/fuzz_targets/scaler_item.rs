@@ -0,0 +1,10 @@
+#![no_main]
+//!  Conversion of arbitrary raw items to ScalerItem.
+//!
+use histogrammer::ring_items::scaler_item::ScalerItem;
+use histogrammer_fuzz::check_specific;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    check_specific::<ScalerItem>(data);
+});
@@ -0,0 +1,10 @@
+#![no_main]
+//!  Conversion of arbitrary raw items to StateChange.
+//!
+use histogrammer::ring_items::state_change::StateChange;
+use histogrammer_fuzz::check_specific;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    check_specific::<StateChange>(data);
+});
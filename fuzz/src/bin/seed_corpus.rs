@@ -0,0 +1,70 @@
+//!  Seed the fuzz corpora from an event file:
+//!
+//!  ```text
+//!  seed_corpus event-file corpus-dir
+//!  ```
+//!
+//!  Every item in the file goes into corpus-dir/ring_item.  Items that
+//!  convert to a state change, physics event count or scaler item (in
+//!  either ring version) also go into the corpus for that target.
+//!  Corpus files are named by item number in the event file.  Large
+//!  files give large corpora;  cargo fuzz cmin can trim them.
+//!
+use histogrammer::ring_items::scaler_item::ScalerItem;
+use histogrammer::ring_items::state_change::StateChange;
+use histogrammer::ring_items::triggers_item::PhysicsEventCountItem;
+use histogrammer::ring_items::{FromRaw, RingItem, RingVersion};
+use std::fs;
+use std::path::Path;
+use std::process;
+
+fn converts<T>(item: &RingItem) -> bool
+where
+    RingItem: FromRaw<T>,
+{
+    [RingVersion::V11, RingVersion::V12]
+        .into_iter()
+        .any(|v| FromRaw::<T>::to_specific(item, v).is_ok())
+}
+
+fn write_seed(corpus: &Path, target: &str, n: usize, item: &RingItem) -> Result<(), String> {
+    let dir = corpus.join(target);
+    fs::create_dir_all(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let path = dir.join(format!("item-{:06}", n));
+    fs::write(&path, item.to_bytes()).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn seed(event_file: &Path, corpus: &Path) -> Result<usize, String> {
+    let mut file =
+        fs::File::open(event_file).map_err(|e| format!("{}: {}", event_file.display(), e))?;
+    let mut n = 0;
+    while let Ok(item) = RingItem::read_item(&mut file) {
+        write_seed(corpus, "ring_item", n, &item)?;
+        if converts::<StateChange>(&item) {
+            write_seed(corpus, "state_change", n, &item)?;
+        }
+        if converts::<PhysicsEventCountItem>(&item) {
+            write_seed(corpus, "physics_event_count", n, &item)?;
+        }
+        if converts::<ScalerItem>(&item) {
+            write_seed(corpus, "scaler_item", n, &item)?;
+        }
+        n += 1;
+    }
+    Ok(n)
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() != 3 {
+        eprintln!("Usage: seed_corpus event-file corpus-dir");
+        process::exit(1);
+    }
+    match seed(Path::new(&args[1]), Path::new(&args[2])) {
+        Ok(n) => println!("{} items written to the corpus", n),
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,145 @@
+//! Dead time is computed from the trigger counts in
+//! PhysicsEventCountItems and the number of physics events actually
+//! seen.  The dead time fraction is:
+//!
+//!   (triggers - accepted) / triggers
+//!
+//! Where triggers is the number of triggers since the start of the run
+//! and accepted the number of physics events seen since then.
+//!
+use crate::ring_items::state_change::{StateChange, StateChangeType};
+use crate::ring_items::triggers_item::PhysicsEventCountItem;
+
+pub struct DeadTimeTracker {
+    last_accepted: u64,
+    last_trigger_count: u64,
+}
+
+impl DeadTimeTracker {
+    pub fn new() -> DeadTimeTracker {
+        DeadTimeTracker {
+            last_accepted: 0,
+            last_trigger_count: 0,
+        }
+    }
+    /// A begin run state change resets the tracker.
+    /// Other state changes are ignored.
+    pub fn ingest_state_change(&mut self, item: &StateChange) {
+        if item.change_type() == StateChangeType::Begin {
+            self.last_accepted = 0;
+            self.last_trigger_count = 0;
+        }
+    }
+    /// Trigger count items carry the number of triggers since the
+    /// start of the run.
+    pub fn ingest_count_item(&mut self, item: &PhysicsEventCountItem) {
+        self.last_trigger_count = item.get_event_count();
+    }
+    /// Count an accepted event.
+    pub fn ingest_event(&mut self) {
+        self.last_accepted += 1;
+    }
+    pub fn triggers(&self) -> u64 {
+        self.last_trigger_count
+    }
+    pub fn accepted(&self) -> u64 {
+        self.last_accepted
+    }
+    /// The dead time fraction.  If no triggers have been counted
+    /// yet, this is 0.0.  Since counts items are only emitted
+    /// periodically, the number of accepted events can get ahead
+    /// of the trigger count.  In that case 0.0 is returned as well.
+    pub fn dead_time_fraction(&self) -> f64 {
+        let triggers = self.triggers();
+        if triggers == 0 || self.last_accepted >= triggers {
+            0.0
+        } else {
+            (triggers - self.last_accepted) as f64 / triggers as f64
+        }
+    }
+}
+
+#[cfg(test)]
+mod dead_time_tests {
+    use super::*;
+
+    #[test]
+    fn new_1() {
+        let t = DeadTimeTracker::new();
+        assert_eq!(0, t.triggers());
+        assert_eq!(0, t.accepted());
+        assert_eq!(0.0, t.dead_time_fraction());
+    }
+    #[test]
+    fn fraction_1() {
+        // 1000 triggers with 800 accepted:
+
+        let mut t = DeadTimeTracker::new();
+        for _ in 0..800 {
+            t.ingest_event();
+        }
+        t.ingest_count_item(&PhysicsEventCountItem::new(None, 10, 1, None, 1000));
+        assert_eq!(1000, t.triggers());
+        assert_eq!(800, t.accepted());
+        assert!((t.dead_time_fraction() - 0.20).abs() < 1.0e-6);
+    }
+    #[test]
+    fn fraction_2() {
+        // Only the last count item matters:
+
+        let mut t = DeadTimeTracker::new();
+        t.ingest_count_item(&PhysicsEventCountItem::new(None, 10, 1, None, 500));
+        for _ in 0..900 {
+            t.ingest_event();
+        }
+        t.ingest_count_item(&PhysicsEventCountItem::new(None, 20, 1, None, 1000));
+        assert!((t.dead_time_fraction() - 0.10).abs() < 1.0e-6);
+    }
+    #[test]
+    fn fraction_3() {
+        // Accepted ahead of the trigger count:
+
+        let mut t = DeadTimeTracker::new();
+        t.ingest_count_item(&PhysicsEventCountItem::new(None, 10, 1, None, 10));
+        for _ in 0..20 {
+            t.ingest_event();
+        }
+        assert_eq!(0.0, t.dead_time_fraction());
+    }
+    #[test]
+    fn begin_1() {
+        let mut t = DeadTimeTracker::new();
+        for _ in 0..800 {
+            t.ingest_event();
+        }
+        t.ingest_count_item(&PhysicsEventCountItem::new(None, 10, 1, None, 1000));
+
+        // End run does not reset:
+
+        t.ingest_state_change(&StateChange::new(
+            StateChangeType::End,
+            None,
+            1,
+            100,
+            1,
+            "A title",
+            None,
+        ));
+        assert_eq!(1000, t.triggers());
+
+        // Begin run does:
+
+        t.ingest_state_change(&StateChange::new(
+            StateChangeType::Begin,
+            None,
+            2,
+            0,
+            1,
+            "A title",
+            None,
+        ));
+        assert_eq!(0, t.triggers());
+        assert_eq!(0, t.accepted());
+        assert_eq!(0.0, t.dead_time_fraction());
+    }
+}
@@ -0,0 +1,9 @@
+//!  The analysis module contains code that derives run-level
+//!  quantities from the ring items in a run rather than from
+//!  the contents of individual events.  For example,
+//!  dead_time computes the fraction of triggers that were not
+//!  accepted.
+//!
+
+pub mod dead_time;
+pub use dead_time::*;
@@ -22,6 +22,16 @@
 //!  This is abstracted out as a ConditionList which has the cached value and
 //!  the dependent vector of conditions.
 //!
+//!  Since evaluation is short circuited, dependents that usually end the
+//!  evaluation (false for And, true for Or) are best evaluated first.
+//!  And and Or can optionally count how often each dependent ends the
+//!  evaluation and optimize_order then sorts the dependents by that
+//!  rate.  This can be done periodically (e.g. every 1000 events).
+//!
+//!  simplify_not removes redundant negations:  Not(Not(X)) is X,
+//!  Not(True) is False and Not(False) is True.  simplify_all applies
+//!  this throughout a condition dictionary.
+//!
 //! ### Note
 //!   conditions are stored as weak references to the underlying
 //!  condition.  If upgrading the condition gives a None, the underlying
@@ -55,6 +65,28 @@ impl Not {
     }
 }
 impl Condition for Not {
+    fn gate_type(&self) -> String {
+        String::from("Not")
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn dependent_gates(&self) -> Vec<ContainerReference> {
+        vec![self.dependent.clone()]
+    }
+    fn replace_dependent(&mut self, old: &Container, new: &Container) -> bool {
+        if self
+            .dependent
+            .upgrade()
+            .is_some_and(|d| Rc::ptr_eq(&d, old))
+        {
+            self.dependent = Rc::downgrade(new);
+            self.cache = None;
+            true
+        } else {
+            false
+        }
+    }
     fn evaluate(&mut self, event: &FlatEvent) -> bool {
         let result = if let Some(d) = self.dependent.upgrade() {
             !d.borrow_mut().check(&event)
@@ -78,23 +110,133 @@ impl Condition for Not {
 //  maintainng an arbitrary list of dependent conditions.
 //  A cache variable is also associated with the list so that
 //  common caching logic can be used.
-//  this struct need not be exposed to the world:
+//  this struct need not be exposed to the world.
+//  Each dependent condition is stored with its name so that it can be
+//  removed by name and with the counts used to optimize the
+//  evaluation order.
 struct ConditionList {
-    dependent_conditions: Vec<Weak<RefCell<dyn Condition>>>,
+    dependent_conditions: Vec<(String, ContainerReference, TerminationStats)>,
     cache: Option<bool>,
+    collect_stats: bool,
+}
+
+// How often a dependent was evaluated and how often that ended
+// the evaluation of the list:
+
+#[derive(Clone, Copy, Default)]
+struct TerminationStats {
+    evaluations: u64,
+    terminations: u64,
 }
+impl TerminationStats {
+    fn record(&mut self, terminated: bool) {
+        self.evaluations += 1;
+        if terminated {
+            self.terminations += 1;
+        }
+    }
+    fn rate(&self) -> f64 {
+        if self.evaluations == 0 {
+            0.0
+        } else {
+            self.terminations as f64 / self.evaluations as f64
+        }
+    }
+}
+
 impl ConditionList {
     pub fn new() -> ConditionList {
         ConditionList {
             dependent_conditions: Vec::new(),
             cache: None,
+            collect_stats: false,
         }
     }
-    pub fn add_condition(&mut self, c: &Container) -> &mut Self {
-        self.dependent_conditions.push(Rc::downgrade(&c.clone()));
+    pub fn add_condition(&mut self, name: &str, c: &Container) -> &mut Self {
+        self.dependent_conditions.push((
+            String::from(name),
+            Rc::downgrade(&c.clone()),
+            TerminationStats::default(),
+        ));
 
         self
     }
+    // Remove the dependent conditions called name.  Dependents that are
+    // the condition called name in dict are also removed in case the
+    // condition was added under another name.  Err if nothing was
+    // removed.
+    //
+    pub fn remove_condition(
+        &mut self,
+        name: &str,
+        dict: &ConditionDictionary,
+    ) -> Result<(), String> {
+        let target = dict.get(name).map(Rc::downgrade);
+        let before = self.dependent_conditions.len();
+        self.dependent_conditions
+            .retain(|(n, c, _)| n != name && !target.as_ref().is_some_and(|t| Weak::ptr_eq(t, c)));
+        if self.dependent_conditions.len() == before {
+            Err(format!("{} is not a dependent condition", name))
+        } else {
+            self.cache = None;
+            Ok(())
+        }
+    }
+    pub fn names(&self) -> Vec<String> {
+        self.dependent_conditions
+            .iter()
+            .map(|(n, _, _)| n.clone())
+            .collect()
+    }
+    pub fn conditions(&self) -> Vec<ContainerReference> {
+        self.dependent_conditions
+            .iter()
+            .map(|(_, c, _)| c.clone())
+            .collect()
+    }
+    // Turn the termination counts on or off.  Either way the counts
+    // restart.
+    //
+    pub fn collect_statistics(&mut self, enable: bool) {
+        self.collect_stats = enable;
+        self.reset_statistics();
+    }
+    pub fn reset_statistics(&mut self) {
+        for (_, _, stats) in self.dependent_conditions.iter_mut() {
+            *stats = TerminationStats::default();
+        }
+    }
+    pub fn termination_rates(&self) -> Vec<(String, f64)> {
+        self.dependent_conditions
+            .iter()
+            .map(|(n, _, stats)| (n.clone(), stats.rate()))
+            .collect()
+    }
+    // Sort by decreasing termination rate.  The sort is stable so
+    // dependents with the same rate keep their order.  The counts
+    // restart so the next optimization reflects recent events.
+    //
+    pub fn optimize_order(&mut self) {
+        self.dependent_conditions
+            .sort_by(|a, b| b.2.rate().total_cmp(&a.2.rate()));
+        self.reset_statistics();
+    }
+    // Point the dependents that are old at new, keeping their names
+    // and counts:
+    //
+    pub fn replace_condition(&mut self, old: &Container, new: &Container) -> bool {
+        let mut replaced = false;
+        for (_, c, _) in self.dependent_conditions.iter_mut() {
+            if c.upgrade().is_some_and(|d| Rc::ptr_eq(&d, old)) {
+                *c = Rc::downgrade(new);
+                replaced = true;
+            }
+        }
+        if replaced {
+            self.cache = None;
+        }
+        replaced
+    }
     // Clears the dependent conditions:
     //
     pub fn clear(&mut self) -> &mut Self {
@@ -122,29 +264,77 @@ impl And {
             dependencies: ConditionList::new(),
         }
     }
-    pub fn add_condition(&mut self, c: &Container) -> &mut Self {
-        self.dependencies.add_condition(c);
+    /// Add a dependent condition.  name is the name of the
+    /// condition in its dictionary.
+    pub fn add_condition(&mut self, name: &str, c: &Container) -> &mut Self {
+        self.dependencies.add_condition(name, c);
         self
     }
+    /// Remove the dependent condition with the name given.
+    /// See ConditionList::remove_condition.
+    pub fn remove_condition(
+        &mut self,
+        name: &str,
+        dict: &ConditionDictionary,
+    ) -> Result<(), String> {
+        self.dependencies.remove_condition(name, dict)
+    }
+    /// The names of the dependent conditions in evaluation order.  This
+    /// is the order they were added unless optimize_order changed it.
+    pub fn list_dependents(&self) -> Vec<String> {
+        self.dependencies.names()
+    }
     pub fn clear(&mut self) -> &mut Self {
         self.dependencies.clear();
         self
     }
+    /// Count how often each dependent is false and so ends the
+    /// evaluation (off by default).
+    pub fn collect_statistics(&mut self, enable: bool) -> &mut Self {
+        self.dependencies.collect_statistics(enable);
+        self
+    }
+    /// The names of the dependents and the fraction of their evaluations
+    /// that were false since the counts last restarted.
+    pub fn termination_rates(&self) -> Vec<(String, f64)> {
+        self.dependencies.termination_rates()
+    }
+    /// Evaluate the dependents most often false first and restart
+    /// the counts.
+    pub fn optimize_order(&mut self) {
+        self.dependencies.optimize_order();
+    }
 }
 impl Condition for And {
+    fn gate_type(&self) -> String {
+        String::from("And")
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn dependent_gates(&self) -> Vec<ContainerReference> {
+        self.dependencies.conditions()
+    }
+    fn replace_dependent(&mut self, old: &Container, new: &Container) -> bool {
+        self.dependencies.replace_condition(old, new)
+    }
     fn evaluate(&mut self, event: &FlatEvent) -> bool {
         let mut result = true; // Failed gates will contradict this.
 
         if let Some(c) = self.dependencies.cache {
             return c;
         } else {
-            for d in &self.dependencies.dependent_conditions {
-                if let Some(g) = d.upgrade() {
-                    if !g.borrow_mut().check(&event) {
-                        result = false;
-                        break;
-                    }
+            let collect = self.dependencies.collect_stats;
+            for (_, d, stats) in self.dependencies.dependent_conditions.iter_mut() {
+                let passed = if let Some(g) = d.upgrade() {
+                    g.borrow_mut().check(event)
                 } else {
+                    false
+                };
+                if collect {
+                    stats.record(!passed);
+                }
+                if !passed {
                     result = false;
                     break;
                 }
@@ -162,7 +352,7 @@ impl Condition for And {
     //
     fn invalidate_cache(&mut self) {
         self.dependencies.cache = None;
-        for d in &self.dependencies.dependent_conditions {
+        for (_, d, _) in &self.dependencies.dependent_conditions {
             if let Some(r) = d.upgrade() {
                 r.borrow_mut().invalidate_cache();
             }
@@ -181,26 +371,75 @@ impl Or {
             dependencies: ConditionList::new(),
         }
     }
-    pub fn add_condition(&mut self, c: &Container) -> &mut Self {
-        self.dependencies.add_condition(c);
+    /// Add a dependent condition.  name is the name of the
+    /// condition in its dictionary.
+    pub fn add_condition(&mut self, name: &str, c: &Container) -> &mut Self {
+        self.dependencies.add_condition(name, c);
         self
     }
+    /// Remove the dependent condition with the name given.
+    /// See ConditionList::remove_condition.
+    pub fn remove_condition(
+        &mut self,
+        name: &str,
+        dict: &ConditionDictionary,
+    ) -> Result<(), String> {
+        self.dependencies.remove_condition(name, dict)
+    }
+    /// The names of the dependent conditions in evaluation order.  This
+    /// is the order they were added unless optimize_order changed it.
+    pub fn list_dependents(&self) -> Vec<String> {
+        self.dependencies.names()
+    }
     pub fn clear(&mut self) -> &mut Self {
         self.dependencies.clear();
         self
     }
+    /// Count how often each dependent is true and so ends the
+    /// evaluation (off by default).
+    pub fn collect_statistics(&mut self, enable: bool) -> &mut Self {
+        self.dependencies.collect_statistics(enable);
+        self
+    }
+    /// The names of the dependents and the fraction of their evaluations
+    /// that were true since the counts last restarted.
+    pub fn termination_rates(&self) -> Vec<(String, f64)> {
+        self.dependencies.termination_rates()
+    }
+    /// Evaluate the dependents most often true first and restart
+    /// the counts.
+    pub fn optimize_order(&mut self) {
+        self.dependencies.optimize_order();
+    }
 }
 
 impl Condition for Or {
+    fn gate_type(&self) -> String {
+        String::from("Or")
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn dependent_gates(&self) -> Vec<ContainerReference> {
+        self.dependencies.conditions()
+    }
+    fn replace_dependent(&mut self, old: &Container, new: &Container) -> bool {
+        self.dependencies.replace_condition(old, new)
+    }
     fn evaluate(&mut self, event: &FlatEvent) -> bool {
         let mut result = true;
         let mut falses = 0;
         if let Some(b) = self.dependencies.cache {
             return b;
         } else {
-            for d in &self.dependencies.dependent_conditions {
+            let collect = self.dependencies.collect_stats;
+            for (_, d, stats) in self.dependencies.dependent_conditions.iter_mut() {
                 if let Some(c) = d.upgrade() {
-                    if c.borrow_mut().check(&event) {
+                    let passed = c.borrow_mut().check(event);
+                    if collect {
+                        stats.record(passed);
+                    }
+                    if passed {
                         break;
                     } else {
                         falses += 1;
@@ -222,13 +461,90 @@ impl Condition for Or {
     }
     fn invalidate_cache(&mut self) {
         self.dependencies.cache = None;
-        for d in &self.dependencies.dependent_conditions {
+        for (_, d, _) in &self.dependencies.dependent_conditions {
             if let Some(r) = d.upgrade() {
                 r.borrow_mut().invalidate_cache();
             }
         }
     }
 }
+///
+/// Simplify a condition by removing redundant negations.  If cond is
+/// a Not whose dependent (once simplified) is a Not, the result is the
+/// inner Not's dependent;  if the dependent is True or False, the result
+/// is a new False or True.  Not conditions whose dependent has been
+/// deleted are always false so they simplify to False.  Anything
+/// else is returned as is.
+///
+/// The result is a condition that evaluates identically to cond with
+/// fewer levels of dependent conditions to go through.  It may be a new
+/// condition, in which case the caller must keep it alive.
+///
+pub fn simplify_not(cond: &Container) -> Container {
+    let dependent = if let Some(not) = cond.borrow().as_any().downcast_ref::<Not>() {
+        not.dependent.upgrade()
+    } else {
+        return Rc::clone(cond);
+    };
+    let dependent = if let Some(d) = dependent {
+        d
+    } else {
+        return Rc::new(RefCell::new(False {}));
+    };
+    let simple = simplify_not(&dependent);
+    let s = simple.borrow();
+    let any = s.as_any();
+    if let Some(inner) = any.downcast_ref::<Not>() {
+        match inner.dependent.upgrade() {
+            Some(d) => d,
+            None => Rc::new(RefCell::new(False {})),
+        }
+    } else if any.is::<True>() {
+        Rc::new(RefCell::new(False {}))
+    } else if any.is::<False>() {
+        Rc::new(RefCell::new(True {}))
+    } else if Rc::ptr_eq(&simple, &dependent) {
+        Rc::clone(cond)
+    } else {
+        Rc::new(RefCell::new(Not::new(&simple)))
+    }
+}
+///
+/// Apply simplify_not throughout a condition dictionary:
+///
+/// *  Compound conditions whose dependents simplify to another
+///    condition that's still alive (e.g. Not(Not(X)) where X is in the
+///    dictionary) are made to depend on that condition instead.
+/// *  Entries that simplify are replaced by their simplification.
+///
+/// Since spectrum gates and compound conditions hold weak references,
+/// an entry that is still referred to from outside the dictionary
+/// (e.g. a gate on a spectrum) is left alone;  replacing it would
+/// leave those references dangling.
+///
+pub fn simplify_all(dict: &mut ConditionDictionary) {
+    let conditions: Vec<Container> = dict.values().cloned().collect();
+    for cond in &conditions {
+        let dependents = cond.borrow().dependent_gates();
+        for dependent in dependents.iter().filter_map(|d| d.upgrade()) {
+            let simple = simplify_not(&dependent);
+            // Retarget only to conditions something else keeps alive:
+
+            if !Rc::ptr_eq(&simple, &dependent) && Rc::strong_count(&simple) > 1 {
+                cond.borrow_mut().replace_dependent(&dependent, &simple);
+            }
+        }
+    }
+    drop(conditions);
+
+    for entry in dict.values_mut() {
+        let simple = simplify_not(entry);
+        if !Rc::ptr_eq(&simple, entry) && Rc::weak_count(entry) == 0 {
+            *entry = simple;
+        }
+    }
+}
+
 #[cfg(test)]
 mod not_tests {
     use super::*;
@@ -263,7 +579,6 @@ mod not_tests {
 }
 #[cfg(test)]
 mod and_tests {
-    use super::cut::*;
     use super::*;
     #[test]
     fn new_1() {
@@ -278,7 +593,7 @@ mod and_tests {
         let t = True {};
         let c: Container = Rc::new(RefCell::new(t));
         let mut a = And::new();
-        a.add_condition(&c);
+        a.add_condition("c", &c);
         assert_eq!(1, a.dependencies.dependent_conditions.len());
     }
     #[test]
@@ -291,8 +606,8 @@ mod and_tests {
         let cf: Container = Rc::new(RefCell::new(f));
 
         let mut a = And::new();
-        a.add_condition(&ct);
-        a.add_condition(&cf);
+        a.add_condition("ct", &ct);
+        a.add_condition("cf", &cf);
 
         assert_eq!(2, a.dependencies.dependent_conditions.len());
     }
@@ -304,8 +619,8 @@ mod and_tests {
         let cf: Container = Rc::new(RefCell::new(f));
 
         let mut a = And::new();
-        a.add_condition(&ct);
-        a.add_condition(&cf);
+        a.add_condition("ct", &ct);
+        a.add_condition("cf", &cf);
 
         assert_eq!(2, a.dependencies.dependent_conditions.len());
 
@@ -335,7 +650,7 @@ mod and_tests {
         let e = FlatEvent::new();
         let t = True {};
         let c: Container = Rc::new(RefCell::new(t));
-        a.add_condition(&c);
+        a.add_condition("c", &c);
         assert!(a.check(&e));
     }
     #[test]
@@ -349,8 +664,8 @@ mod and_tests {
         let t2 = True {};
         let c2: Container = Rc::new(RefCell::new(t2));
 
-        a.add_condition(&c1);
-        a.add_condition(&c2);
+        a.add_condition("c1", &c1);
+        a.add_condition("c2", &c2);
         assert!(a.check(&e));
     }
     #[test]
@@ -364,8 +679,8 @@ mod and_tests {
         let f2 = False {};
         let c2: Container = Rc::new(RefCell::new(f2));
 
-        a.add_condition(&c1);
-        a.add_condition(&c2);
+        a.add_condition("c1", &c1);
+        a.add_condition("c2", &c2);
         assert!(!a.check(&e));
 
         // Cache is set:
@@ -385,12 +700,131 @@ mod and_tests {
         let s = Cut::new(1, 100.0, 200.0);
         let c2: Container = Rc::new(RefCell::new(s));
 
-        a.add_condition(&c1);
-        a.add_condition(&c2);
+        a.add_condition("c1", &c1);
+        a.add_condition("c2", &c2);
 
         assert!(!a.check(&e));
         assert!(c2.borrow().get_cached_value().is_none());
     }
+    #[test]
+    fn remove_1() {
+        let mut dict = ConditionDictionary::new();
+        dict.insert(
+            String::from("c1"),
+            Rc::new(RefCell::new(Cut::new(1, 0.0, 10.0))),
+        );
+        dict.insert(String::from("c2"), Rc::new(RefCell::new(False {})));
+        dict.insert(
+            String::from("c3"),
+            Rc::new(RefCell::new(Cut::new(2, 0.0, 10.0))),
+        );
+        let mut a = And::new();
+        for name in ["c1", "c2", "c3"] {
+            a.add_condition(name, dict.get(name).unwrap());
+        }
+        assert_eq!(vec!["c1", "c2", "c3"], a.list_dependents());
+
+        let mut e = FlatEvent::new();
+        e.load_event(&vec![
+            EventParameter::new(1, 5.0),
+            EventParameter::new(2, 5.0),
+        ]);
+        assert!(!a.check(&e));
+
+        a.remove_condition("c2", &dict).unwrap();
+        assert_eq!(vec!["c1", "c3"], a.list_dependents());
+        invalidate_cache(&mut dict);
+        assert!(a.check(&e));
+
+        e.load_event(&vec![
+            EventParameter::new(1, 5.0),
+            EventParameter::new(2, 50.0),
+        ]);
+        a.invalidate_cache();
+        assert!(!a.check(&e));
+        e.load_event(&vec![
+            EventParameter::new(1, 50.0),
+            EventParameter::new(2, 5.0),
+        ]);
+        a.invalidate_cache();
+        assert!(!a.check(&e));
+
+        // Not a dependent:
+
+        assert!(a.remove_condition("c2", &dict).is_err());
+        assert!(a.remove_condition("nosuch", &dict).is_err());
+        assert_eq!(2, a.list_dependents().len());
+    }
+    #[test]
+    fn optimize_1() {
+        // c0 is always false but evaluated last;  c1 is false for
+        // a tenth of the events:
+
+        let c0: Container = Rc::new(RefCell::new(False {}));
+        let c1: Container = Rc::new(RefCell::new(Cut::new(1, 10.0, 100.0)));
+        let c2: Container = Rc::new(RefCell::new(True {}));
+        let mut a = And::new();
+        a.add_condition("c1", &c1)
+            .add_condition("c2", &c2)
+            .add_condition("c0", &c0)
+            .collect_statistics(true);
+
+        let mut e = FlatEvent::new();
+        for i in 0..100 {
+            e.load_event(&vec![EventParameter::new(1, i as f64)]);
+            a.invalidate_cache();
+            assert!(!a.check(&e));
+        }
+        assert_eq!(
+            vec![
+                (String::from("c1"), 0.1),
+                (String::from("c2"), 0.0),
+                (String::from("c0"), 1.0)
+            ],
+            a.termination_rates()
+        );
+        a.optimize_order();
+        assert_eq!(vec!["c0", "c1", "c2"], a.list_dependents());
+        assert!(a.termination_rates().iter().all(|(_, r)| *r == 0.0));
+
+        // Now only c0 is evaluated:
+
+        a.invalidate_cache();
+        assert!(!a.check(&e));
+        assert_eq!(1.0, a.termination_rates()[0].1);
+        assert!(c1.borrow().get_cached_value().is_none());
+    }
+    #[test]
+    fn optimize_2() {
+        // Without statistics the order doesn't change:
+
+        let c0: Container = Rc::new(RefCell::new(False {}));
+        let c1: Container = Rc::new(RefCell::new(True {}));
+        let mut a = And::new();
+        a.add_condition("c1", &c1).add_condition("c0", &c0);
+        for _ in 0..10 {
+            a.invalidate_cache();
+            a.check(&FlatEvent::new());
+        }
+        a.optimize_order();
+        assert_eq!(vec!["c1", "c0"], a.list_dependents());
+
+        // Deleted dependents end the evaluation:
+
+        a.collect_statistics(true);
+        drop(c1);
+        a.invalidate_cache();
+        assert!(!a.check(&FlatEvent::new()));
+        a.optimize_order();
+        assert_eq!(vec!["c1", "c0"], a.list_dependents());
+        assert_eq!(
+            1,
+            a.dependent_gates()
+                .iter()
+                .filter(|g| g.upgrade().is_some())
+                .count()
+        );
+    }
 }
 #[cfg(test)]
 mod or_tests {
@@ -408,7 +842,7 @@ mod or_tests {
         let t = True {};
         let c: Container = Rc::new(RefCell::new(t));
 
-        o.add_condition(&c);
+        o.add_condition("c", &c);
 
         assert_eq!(1, o.dependencies.dependent_conditions.len());
     }
@@ -420,8 +854,8 @@ mod or_tests {
         let f = False {};
         let cf: Container = Rc::new(RefCell::new(f));
 
-        o.add_condition(&ct);
-        o.add_condition(&cf);
+        o.add_condition("ct", &ct);
+        o.add_condition("cf", &cf);
 
         assert_eq!(2, o.dependencies.dependent_conditions.len());
     }
@@ -433,8 +867,8 @@ mod or_tests {
         let f = False {};
         let cf: Container = Rc::new(RefCell::new(f));
 
-        o.add_condition(&ct);
-        o.add_condition(&cf);
+        o.add_condition("ct", &ct);
+        o.add_condition("cf", &cf);
 
         assert_eq!(2, o.dependencies.dependent_conditions.len());
 
@@ -457,7 +891,7 @@ mod or_tests {
         let e = FlatEvent::new();
         let t = True {};
         let c: Container = Rc::new(RefCell::new(t));
-        o.add_condition(&c);
+        o.add_condition("c", &c);
 
         assert!(o.check(&e));
     }
@@ -472,8 +906,8 @@ mod or_tests {
         let ct: Container = Rc::new(RefCell::new(t));
         let cf: Container = Rc::new(RefCell::new(f));
 
-        o.add_condition(&ct);
-        o.add_condition(&cf);
+        o.add_condition("ct", &ct);
+        o.add_condition("cf", &cf);
 
         assert!(o.check(&e));
     }
@@ -488,8 +922,8 @@ mod or_tests {
         let ct: Container = Rc::new(RefCell::new(t));
         let cf: Container = Rc::new(RefCell::new(f));
 
-        o.add_condition(&cf);
-        o.add_condition(&ct);
+        o.add_condition("cf", &cf);
+        o.add_condition("ct", &ct);
 
         assert!(o.check(&e));
     }
@@ -501,7 +935,7 @@ mod or_tests {
         let f = False {};
         let cf: Container = Rc::new(RefCell::new(f));
 
-        o.add_condition(&cf);
+        o.add_condition("cf", &cf);
 
         assert!(!o.check(&e));
     }
@@ -516,9 +950,219 @@ mod or_tests {
         let f2 = False {};
         let cf2: Container = Rc::new(RefCell::new(f2));
 
-        o.add_condition(&cf1);
-        o.add_condition(&cf2);
+        o.add_condition("cf1", &cf1);
+        o.add_condition("cf2", &cf2);
 
         assert!(!o.check(&e));
     }
+    #[test]
+    fn remove_1() {
+        let mut dict = ConditionDictionary::new();
+        dict.insert(String::from("f1"), Rc::new(RefCell::new(False {})));
+        dict.insert(String::from("t"), Rc::new(RefCell::new(True {})));
+        dict.insert(String::from("f2"), Rc::new(RefCell::new(False {})));
+        let mut o = Or::new();
+        for name in ["f1", "t", "f2"] {
+            o.add_condition(name, dict.get(name).unwrap());
+        }
+        let e = FlatEvent::new();
+        assert!(o.check(&e));
+
+        // Removing clears the cache:
+
+        o.remove_condition("t", &dict).unwrap();
+        assert!(o.get_cached_value().is_none());
+        assert!(!o.check(&e));
+        assert_eq!(vec!["f1", "f2"], o.list_dependents());
+        assert!(o.remove_condition("t", &dict).is_err());
+    }
+    #[test]
+    fn optimize_1() {
+        // c0 is always true but evaluated last:
+
+        let c0: Container = Rc::new(RefCell::new(True {}));
+        let c1: Container = Rc::new(RefCell::new(False {}));
+        let c2: Container = Rc::new(RefCell::new(Cut::new(1, 0.0, 9.0)));
+        let mut o = Or::new();
+        o.add_condition("c1", &c1)
+            .add_condition("c2", &c2)
+            .add_condition("c0", &c0)
+            .collect_statistics(true);
+
+        let mut e = FlatEvent::new();
+        for i in 0..100 {
+            e.load_event(&vec![EventParameter::new(1, i as f64)]);
+            o.invalidate_cache();
+            assert!(o.check(&e));
+        }
+        assert_eq!(
+            vec![
+                (String::from("c1"), 0.0),
+                (String::from("c2"), 0.1),
+                (String::from("c0"), 1.0)
+            ],
+            o.termination_rates()
+        );
+        o.optimize_order();
+        assert_eq!(vec!["c0", "c2", "c1"], o.list_dependents());
+
+        // Only c0 is evaluated now:
+
+        o.invalidate_cache();
+        assert!(o.check(&e));
+        assert!(c2.borrow().get_cached_value().is_none());
+    }
+}
+#[cfg(test)]
+mod simplify_tests {
+    use super::*;
+
+    // Dictionary with a cut on parameter 1 in [0, 10]:
+
+    fn make_dict() -> ConditionDictionary {
+        let mut dict = ConditionDictionary::new();
+        let c: Container = Rc::new(RefCell::new(Cut::new(1, 0.0, 10.0)));
+        dict.insert(String::from("cut"), c);
+        dict
+    }
+    fn add_not(dict: &mut ConditionDictionary, name: &str, dependent: &str) {
+        let not: Container = Rc::new(RefCell::new(Not::new(&dict[dependent])));
+        dict.insert(String::from(name), not);
+    }
+    fn event(value: f64) -> FlatEvent {
+        let mut e = FlatEvent::new();
+        e.load_event(&vec![EventParameter::new(1, value)]);
+        e
+    }
+    // The number of levels of dependents:
+    fn depth(c: &Container) -> usize {
+        1 + c
+            .borrow()
+            .dependent_gates()
+            .iter()
+            .filter_map(|d| d.upgrade())
+            .map(|d| depth(&d))
+            .max()
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn not_1() {
+        // Not(Not(cut)) is cut:
+
+        let mut dict = make_dict();
+        add_not(&mut dict, "not", "cut");
+        add_not(&mut dict, "notnot", "not");
+
+        let simple = simplify_not(&dict["notnot"]);
+        assert!(Rc::ptr_eq(&dict["cut"], &simple));
+        assert_eq!(3, depth(&dict["notnot"]));
+        assert_eq!(1, depth(&simple));
+        for value in [-1.0, 5.0, 11.0] {
+            let e = event(value);
+            dict["notnot"].borrow_mut().invalidate_cache();
+            assert_eq!(
+                dict["notnot"].borrow_mut().check(&e),
+                simple.borrow_mut().check(&e)
+            );
+        }
+    }
+    #[test]
+    fn not_2() {
+        // Not(True) is False and Not(False) is True:
+
+        let t: Container = Rc::new(RefCell::new(True {}));
+        let f: Container = Rc::new(RefCell::new(False {}));
+        let not_t: Container = Rc::new(RefCell::new(Not::new(&t)));
+        let not_f: Container = Rc::new(RefCell::new(Not::new(&f)));
+
+        assert_eq!("False", simplify_not(&not_t).borrow().gate_type());
+        assert_eq!("True", simplify_not(&not_f).borrow().gate_type());
+
+        // Not(Not(True)) evaluates True:
+
+        let not_not_t: Container = Rc::new(RefCell::new(Not::new(&not_t)));
+        let simple = simplify_not(&not_not_t);
+        assert_eq!("True", simple.borrow().gate_type());
+    }
+    #[test]
+    fn not_3() {
+        // Single and triple negations:
+
+        let mut dict = make_dict();
+        add_not(&mut dict, "n1", "cut");
+        add_not(&mut dict, "n2", "n1");
+        add_not(&mut dict, "n3", "n2");
+
+        assert!(Rc::ptr_eq(&dict["n1"], &simplify_not(&dict["n1"])));
+        assert!(Rc::ptr_eq(&dict["cut"], &simplify_not(&dict["cut"])));
+
+        let simple = simplify_not(&dict["n3"]);
+        assert_eq!("Not", simple.borrow().gate_type());
+        assert_eq!(2, depth(&simple));
+        for value in [-1.0, 5.0, 11.0] {
+            let e = event(value);
+            dict["n3"].borrow_mut().invalidate_cache();
+            simple.borrow_mut().invalidate_cache();
+            assert_eq!(
+                dict["n3"].borrow_mut().check(&e),
+                simple.borrow_mut().check(&e)
+            );
+        }
+    }
+    #[test]
+    fn not_4() {
+        // Deleted dependent simplifies to False:
+
+        let mut dict = make_dict();
+        add_not(&mut dict, "not", "cut");
+        dict.remove("cut");
+        let simple = simplify_not(&dict["not"]);
+        assert_eq!("False", simple.borrow().gate_type());
+    }
+    #[test]
+    fn all_1() {
+        // An And on Not(Not(cut)) ends up depending on the cut and the
+        // unreferenced Not(Not(cut)) is replaced:
+
+        let mut dict = make_dict();
+        add_not(&mut dict, "not", "cut");
+        add_not(&mut dict, "notnot", "not");
+        let mut and = And::new();
+        and.add_condition("notnot", &dict["notnot"]);
+        let and: Container = Rc::new(RefCell::new(and));
+        dict.insert(String::from("and"), and);
+        assert_eq!(4, depth(&dict["and"]));
+
+        simplify_all(&mut dict);
+
+        assert_eq!(2, depth(&dict["and"]));
+        let dependents = dict["and"].borrow().dependent_gates();
+        assert!(Rc::ptr_eq(&dict["cut"], &dependents[0].upgrade().unwrap()));
+        assert!(Rc::ptr_eq(&dict["cut"], &dict["notnot"]));
+        assert_eq!("Not", dict["not"].borrow().gate_type());
+
+        assert!(dict["and"].borrow_mut().check(&event(5.0)));
+        dict["and"].borrow_mut().invalidate_cache();
+        assert!(!dict["and"].borrow_mut().check(&event(11.0)));
+    }
+    #[test]
+    fn all_2() {
+        // Entries referenced from outside (e.g. as a spectrum gate)
+        // are left alone:
+
+        let mut dict = make_dict();
+        let t: Container = Rc::new(RefCell::new(True {}));
+        dict.insert(String::from("true"), t);
+        add_not(&mut dict, "false", "true");
+        let gate = Rc::downgrade(&dict["false"]);
+
+        simplify_all(&mut dict);
+        assert_eq!("Not", dict["false"].borrow().gate_type());
+        assert!(gate.upgrade().is_some());
+
+        drop(gate);
+        simplify_all(&mut dict);
+        assert_eq!("False", dict["false"].borrow().gate_type());
+    }
 }
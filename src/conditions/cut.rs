@@ -10,6 +10,10 @@
 //! Will return Some containing the value of the last evaluation
 //! until the cache is explicitly invalidated.
 //!
+//! A cut can have a tolerance, in which case the range is widened to
+//! [low - tolerance, high + tolerance].  This accepts values that
+//! rounding has put just outside the limits.
+//!
 
 use super::*;
 use crate::parameters;
@@ -22,17 +26,39 @@ pub struct Cut {
     parameter_id: u32,
     low: f64,
     high: f64,
+    tolerance: f64,
     cache: Option<bool>,
 }
 impl Cut {
     pub fn new(id: u32, low: f64, high: f64) -> Cut {
+        Cut::with_tolerance(id, low, high, 0.0)
+    }
+    /// A cut whose range is widened by tolerance on each side.
+    pub fn with_tolerance(id: u32, low: f64, high: f64, tolerance: f64) -> Cut {
         Cut {
             parameter_id: id,
             low: low,
             high: high,
+            tolerance,
             cache: None, // Starts with invalid cache.
         }
     }
+    pub fn get_tolerance(&self) -> f64 {
+        self.tolerance
+    }
+    ///
+    /// A copy of the cut with the range contracted by
+    /// factor * (high - low) on each side.  The tolerance is kept.
+    ///
+    pub fn tighten(&self, factor: f64) -> Cut {
+        let delta = factor * (self.high - self.low);
+        Cut::with_tolerance(
+            self.parameter_id,
+            self.low + delta,
+            self.high - delta,
+            self.tolerance,
+        )
+    }
     pub fn replace_limits(&mut self, low: f64, high: f64) -> &Cut {
         self.low = low;
         self.high = high;
@@ -44,13 +70,28 @@ impl Cut {
 impl Condition for Cut {
     fn evaluate(&mut self, event: &parameters::FlatEvent) -> bool {
         let result = if let Some(p) = event[self.parameter_id] {
-            (p >= self.low) && (p <= self.high)
+            (p >= self.low - self.tolerance) && (p <= self.high + self.tolerance)
         } else {
             false
         };
         self.cache = Some(result);
         result
     }
+    fn gate_type(&self) -> String {
+        String::from("Cut")
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn dependent_parameters(&self) -> Vec<u32> {
+        vec![self.parameter_id]
+    }
+    fn gate_points(&self) -> Vec<(f64, f64)> {
+        vec![(self.low, 0.0), (self.high, 0.0)]
+    }
+    fn gate_tolerance(&self) -> f64 {
+        self.tolerance
+    }
     fn get_cached_value(&self) -> Option<bool> {
         self.cache
     }
@@ -71,6 +112,7 @@ mod cut_tests {
                 parameter_id: 12,
                 low: 100.0,
                 high: 200.0,
+                tolerance: 0.0,
                 cache: None
             },
             c
@@ -86,6 +128,7 @@ mod cut_tests {
                 parameter_id: 12,
                 low: 10.0,
                 high: 20.0,
+                tolerance: 0.0,
                 cache: None
             },
             c
@@ -194,4 +237,62 @@ mod cut_tests {
         assert!(dict.get(&k2).unwrap().borrow_mut().check(&e));
         invalidate_cache(&mut dict);
     }
+    #[test]
+    fn downcast_1() {
+        let mut dict = ConditionDictionary::new();
+        dict.insert(
+            String::from("cut"),
+            Rc::new(RefCell::new(Cut::new(1, 100.0, 200.0))),
+        );
+        dict.insert(String::from("true"), Rc::new(RefCell::new(True {})));
+
+        let cut = downcast_condition::<Cut>(dict.get("cut").unwrap()).unwrap();
+        assert_eq!(1, cut.parameter_id);
+        assert_eq!(100.0, cut.low);
+        assert_eq!(200.0, cut.high);
+        drop(cut);
+
+        assert!(downcast_condition::<True>(dict.get("cut").unwrap()).is_none());
+        assert!(downcast_condition::<Cut>(dict.get("true").unwrap()).is_none());
+
+        // Mutably borrowed:
+
+        let c = dict.get("cut").unwrap();
+        let _b = c.borrow_mut();
+        assert!(downcast_condition::<Cut>(c).is_none());
+    }
+    #[test]
+    fn tolerance_1() {
+        let mut e = FlatEvent::new();
+        e.load_event(&vec![EventParameter::new(1, 200.0 + 1e-10)]);
+
+        let mut c = Cut::new(1, 100.0, 200.0);
+        assert_eq!(0.0, c.get_tolerance());
+        assert!(!c.check(&e));
+
+        let mut c = Cut::with_tolerance(1, 100.0, 200.0, 1e-9);
+        assert_eq!(1e-9, c.get_tolerance());
+        assert!(c.check(&e));
+
+        e.load_event(&vec![EventParameter::new(1, 100.0 - 1e-10)]);
+        c.invalidate_cache();
+        assert!(c.check(&e));
+        e.load_event(&vec![EventParameter::new(1, 100.0 - 1e-8)]);
+        c.invalidate_cache();
+        assert!(!c.check(&e));
+
+        // Limits are still the nominal ones:
+
+        assert_eq!(vec![(100.0, 0.0), (200.0, 0.0)], c.gate_points());
+    }
+    #[test]
+    fn tighten_1() {
+        let c = Cut::with_tolerance(1, 100.0, 200.0, 0.5);
+        let t = c.tighten(0.1);
+        assert_eq!(1, t.parameter_id);
+        assert_eq!(110.0, t.low);
+        assert_eq!(190.0, t.high);
+        assert_eq!(0.5, t.tolerance);
+        assert!(t.cache.is_none());
+    }
 }
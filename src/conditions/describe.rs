@@ -0,0 +1,157 @@
+//!  Provides textual descriptions of conditions.  Since compound
+//!  conditions only hold weak references to their dependent conditions,
+//!  the names of the dependent conditions are gotten by finding the
+//!  conditions in the dictionary that are the same objects
+//!  as the dependent conditions.  This also means descriptions
+//!  reflect the current dictionary; dependent conditions that have
+//!  been deleted (or replaced) are shown as deleted.
+//!
+use super::*;
+
+/// Name given to dependent conditions that are no longer in the
+/// dictionary.
+pub const DELETED_CONDITION: &str = "<deleted>";
+
+// Conditions are compared by the address of the object in the
+// container.  The vtable part of the fat pointer is discarded
+// since it need not be unique.
+
+fn same_condition(a: &Container, b: &ContainerReference) -> bool {
+    Rc::as_ptr(a) as *const () == Weak::as_ptr(b) as *const ()
+}
+
+/// Find the name of a condition in the dictionary.
+///
+pub fn condition_name(c: &ContainerReference, dict: &ConditionDictionary) -> Option<String> {
+    dict.iter()
+        .find(|(_, v)| same_condition(v, c))
+        .map(|(k, _)| k.clone())
+}
+
+/// Names of the conditions the named condition depends on in the
+/// order the condition holds them. Deleted dependencies are given
+/// the name DELETED_CONDITION.  None is returned if there is no
+/// condition with that name.
+///
+pub fn dependency_names(name: &str, dict: &ConditionDictionary) -> Option<Vec<String>> {
+    dict.get(name).map(|c| {
+        c.borrow()
+            .dependent_gates()
+            .iter()
+            .map(|d| condition_name(d, dict).unwrap_or_else(|| String::from(DELETED_CONDITION)))
+            .collect()
+    })
+}
+
+///
+/// Describe the named condition as a tree.  Each line contains a
+/// condition name and its type, nested dependent conditions are
+/// indented two spaces more than the condition that depends on them.
+/// e.g.
+///
+/// ```text
+/// both (And)
+///   either (Or)
+///     c1 (Cut)
+///     c2 (Cut)
+///   not3 (Not)
+///     c3 (Cut)
+/// ```
+///
+pub fn describe_tree(name: &str, dict: &ConditionDictionary) -> String {
+    let mut result = String::new();
+    describe_node(name, 0, dict, &mut result);
+    result
+}
+
+fn describe_node(name: &str, level: usize, dict: &ConditionDictionary, result: &mut String) {
+    let indent = "  ".repeat(level);
+    if let Some(c) = dict.get(name) {
+        result.push_str(&format!(
+            "{}{} ({})\n",
+            indent,
+            name,
+            c.borrow().gate_type()
+        ));
+        for dependent in dependency_names(name, dict).unwrap() {
+            describe_node(&dependent, level + 1, dict, result);
+        }
+    } else if name == DELETED_CONDITION {
+        result.push_str(&format!("{}{}\n", indent, name));
+    } else {
+        result.push_str(&format!("{}{} (No such condition)\n", indent, name));
+    }
+}
+
+#[cfg(test)]
+mod describe_tests {
+    use super::*;
+
+    fn insert<T: Condition + 'static>(dict: &mut ConditionDictionary, name: &str, c: T) {
+        dict.insert(String::from(name), Rc::new(RefCell::new(c)));
+    }
+    // both = And(either = Or(c1, c2), not3 = Not(c3))
+
+    fn make_dict() -> ConditionDictionary {
+        let mut dict = ConditionDictionary::new();
+        insert(&mut dict, "c1", Cut::new(1, 0.0, 10.0));
+        insert(&mut dict, "c2", Cut::new(2, 0.0, 10.0));
+        insert(&mut dict, "c3", Cut::new(3, 0.0, 10.0));
+
+        let mut either = Or::new();
+        either
+            .add_condition("c1", dict.get("c1").unwrap())
+            .add_condition("c2", dict.get("c2").unwrap());
+        insert(&mut dict, "either", either);
+        let not3 = Not::new(dict.get("c3").unwrap());
+        insert(&mut dict, "not3", not3);
+
+        let mut both = And::new();
+        both.add_condition("either", dict.get("either").unwrap())
+            .add_condition("not3", dict.get("not3").unwrap());
+        insert(&mut dict, "both", both);
+        dict
+    }
+
+    #[test]
+    fn names_1() {
+        let dict = make_dict();
+        assert_eq!(
+            Some(String::from("c1")),
+            condition_name(&Rc::downgrade(dict.get("c1").unwrap()), &dict)
+        );
+        assert_eq!(
+            Some(vec![String::from("either"), String::from("not3")]),
+            dependency_names("both", &dict)
+        );
+        assert_eq!(Some(vec![]), dependency_names("c1", &dict));
+        assert!(dependency_names("nosuch", &dict).is_none());
+    }
+    #[test]
+    fn tree_1() {
+        let dict = make_dict();
+        let expected =
+            "both (And)\n  either (Or)\n    c1 (Cut)\n    c2 (Cut)\n  not3 (Not)\n    c3 (Cut)\n";
+        assert_eq!(expected, describe_tree("both", &dict));
+    }
+    #[test]
+    fn tree_2() {
+        // Leaf and nonexistent conditions:
+
+        let mut dict = make_dict();
+        insert(&mut dict, "t", True {});
+        assert_eq!("t (True)\n", describe_tree("t", &dict));
+        assert_eq!(
+            "nosuch (No such condition)\n",
+            describe_tree("nosuch", &dict)
+        );
+    }
+    #[test]
+    fn tree_3() {
+        // Deleted dependencies:
+
+        let mut dict = make_dict();
+        dict.remove("c3");
+        assert_eq!("not3 (Not)\n  <deleted>\n", describe_tree("not3", &dict));
+    }
+}
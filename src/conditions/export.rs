@@ -0,0 +1,325 @@
+//!  Exports conditions as SpecTcl gate commands so that conditions
+//!  defined here can be used in SpecTcl.  The gate commands are:
+//!
+//!  *  True  - gate name T {}
+//!  *  False - gate name F {}
+//!  *  Cut   - gate name s {parameter {low high}}  SpecTcl cuts have
+//!     no tolerance so the limits exported are widened by the tolerance.
+//!  *  Band  - gate name b {{xparameter yparameter} {{x1 y1} {x2 y2}...}}
+//!  *  Contour - gate name c {{xparameter yparameter} {{x1 y1} {x2 y2}...}}
+//!  *  Not   - gate name - {dependent}
+//!  *  And   - gate name * {dependent1 dependent2...}
+//!  *  Or    - gate name + {dependent1 dependent2...}
+//!
+//!  Conditions refer to parameters by id while SpecTcl refers to them by
+//!  name, so a parameter dictionary is needed to export conditions
+//!  that are defined on parameters.
+//!
+use super::*;
+use crate::parameters::ParameterDictionary;
+
+// Quote a word for Tcl if needed.  Words with whitespace or
+// Tcl special characters are enclosed in braces.
+
+fn tcl_word(s: &str) -> String {
+    if s.is_empty()
+        || s.chars()
+            .any(|c| c.is_whitespace() || "{}[]$\"\\;".contains(c))
+    {
+        format!("{{{}}}", s)
+    } else {
+        String::from(s)
+    }
+}
+fn parameter_name(id: u32, pdict: &ParameterDictionary) -> Option<String> {
+    pdict
+        .iter()
+        .find(|(_, p)| p.get_id() == id)
+        .map(|(name, _)| tcl_word(name))
+}
+fn point_list(points: &[(f64, f64)]) -> String {
+    let pts: Vec<String> = points
+        .iter()
+        .map(|(x, y)| format!("{{{} {}}}", x, y))
+        .collect();
+    pts.join(" ")
+}
+
+///
+/// Produce the SpecTcl gate command that defines the named condition.
+/// None is returned if:
+///
+/// *  There's no such condition.
+/// *  The condition depends on a parameter that is not in pdict.
+/// *  The condition depends on a condition that has been deleted.
+/// *  The condition type has no SpecTcl equivalent.
+///
+pub fn export_tcl(
+    name: &str,
+    dict: &ConditionDictionary,
+    pdict: &ParameterDictionary,
+) -> Option<String> {
+    let condition = dict.get(name)?.borrow();
+    let params: Option<Vec<String>> = condition
+        .dependent_parameters()
+        .iter()
+        .map(|id| parameter_name(*id, pdict))
+        .collect();
+    let params = params?;
+    let points = condition.gate_points();
+
+    let (code, description) = match condition.gate_type().as_str() {
+        "True" => ("T", String::new()),
+        "False" => ("F", String::new()),
+        "Cut" => {
+            let tolerance = condition.gate_tolerance();
+            (
+                "s",
+                format!(
+                    "{} {{{} {}}}",
+                    params[0],
+                    points[0].0 - tolerance,
+                    points[1].0 + tolerance
+                ),
+            )
+        }
+        "Band" => (
+            "b",
+            format!(
+                "{{{} {}}} {{{}}}",
+                params[0],
+                params[1],
+                point_list(&points)
+            ),
+        ),
+        "Contour" => (
+            "c",
+            format!(
+                "{{{} {}}} {{{}}}",
+                params[0],
+                params[1],
+                point_list(&points)
+            ),
+        ),
+        compound => {
+            let code = match compound {
+                "Not" => "-",
+                "And" => "*",
+                "Or" => "+",
+                _ => return None,
+            };
+            let dependents = dependency_names(name, dict)?;
+            if dependents.iter().any(|d| d == DELETED_CONDITION) {
+                return None;
+            }
+            let dependents: Vec<String> = dependents.iter().map(|d| tcl_word(d)).collect();
+            (code, dependents.join(" "))
+        }
+    };
+    Some(format!(
+        "gate {} {} {{{}}}",
+        tcl_word(name),
+        code,
+        description
+    ))
+}
+
+///
+/// Produce a script that defines all exportable conditions in the
+/// dictionary.  Conditions are ordered so that the conditions a
+/// compound condition depends on are defined before it.  Otherwise
+/// conditions are in name order.  Conditions that can't be exported
+/// are skipped.
+///
+pub fn export_all_tcl(dict: &ConditionDictionary, pdict: &ParameterDictionary) -> String {
+    let mut result = String::new();
+    for name in dependency_order(dict) {
+        if let Some(command) = export_tcl(&name, dict, pdict) {
+            result.push_str(&command);
+            result.push('\n');
+        }
+    }
+    result
+}
+
+///
+/// The names of the conditions in the dictionary ordered so that the
+/// conditions a compound condition depends on come before it.
+/// Otherwise conditions are in name order.  This is the order in
+/// which conditions must be defined when they are re-created.
+///
+pub fn dependency_order(dict: &ConditionDictionary) -> Vec<String> {
+    let mut names: Vec<&String> = dict.keys().collect();
+    names.sort();
+
+    let mut ordered: Vec<String> = Vec::new();
+    for name in names {
+        add_in_dependency_order(name, dict, &mut ordered);
+    }
+    ordered
+}
+
+// Depth first: dependencies are added before the name itself.
+
+fn add_in_dependency_order(name: &str, dict: &ConditionDictionary, ordered: &mut Vec<String>) {
+    if ordered.iter().any(|n| n == name) {
+        return;
+    }
+    if let Some(dependents) = dependency_names(name, dict) {
+        for d in dependents {
+            add_in_dependency_order(&d, dict, ordered);
+        }
+        ordered.push(String::from(name));
+    }
+}
+
+#[cfg(test)]
+mod export_tests {
+    use super::*;
+
+    fn insert<T: Condition + 'static>(dict: &mut ConditionDictionary, name: &str, c: T) {
+        dict.insert(String::from(name), Rc::new(RefCell::new(c)));
+    }
+    fn make_pdict() -> ParameterDictionary {
+        let mut pdict = ParameterDictionary::new();
+        for i in 0..4 {
+            pdict.add(&format!("param.{}", i)).unwrap(); // ids 1..4
+        }
+        pdict
+    }
+
+    #[test]
+    fn primitive_1() {
+        let pdict = make_pdict();
+        let mut dict = ConditionDictionary::new();
+        insert(&mut dict, "t", True {});
+        insert(&mut dict, "f", False {});
+        insert(&mut dict, "cut", Cut::new(1, 100.0, 200.5));
+        insert(&mut dict, "wide", Cut::with_tolerance(1, 100.0, 200.5, 0.5));
+
+        assert_eq!(
+            Some(String::from("gate t T {}")),
+            export_tcl("t", &dict, &pdict)
+        );
+        assert_eq!(
+            Some(String::from("gate f F {}")),
+            export_tcl("f", &dict, &pdict)
+        );
+        assert_eq!(
+            Some(String::from("gate cut s {param.0 {100 200.5}}")),
+            export_tcl("cut", &dict, &pdict)
+        );
+        assert_eq!(
+            Some(String::from("gate wide s {param.0 {99.5 201}}")),
+            export_tcl("wide", &dict, &pdict)
+        );
+        assert!(export_tcl("nosuch", &dict, &pdict).is_none());
+    }
+    #[test]
+    fn primitive_2() {
+        // 2-d conditions:
+
+        let pdict = make_pdict();
+        let mut dict = ConditionDictionary::new();
+        let pts = vec![
+            Point::new(0.0, 0.0),
+            Point::new(10.0, 0.0),
+            Point::new(5.0, 10.0),
+        ];
+        insert(&mut dict, "c", Contour::new(1, 2, pts.clone()).unwrap());
+        insert(&mut dict, "b", Band::new(3, 4, pts).unwrap());
+
+        assert_eq!(
+            Some(String::from(
+                "gate c c {{param.0 param.1} {{0 0} {10 0} {5 10}}}"
+            )),
+            export_tcl("c", &dict, &pdict)
+        );
+        assert_eq!(
+            Some(String::from(
+                "gate b b {{param.2 param.3} {{0 0} {10 0} {5 10}}}"
+            )),
+            export_tcl("b", &dict, &pdict)
+        );
+    }
+    #[test]
+    fn primitive_3() {
+        // Unknown parameters and names that need quoting:
+
+        let pdict = make_pdict();
+        let mut dict = ConditionDictionary::new();
+        insert(&mut dict, "bad", Cut::new(100, 0.0, 1.0));
+        insert(&mut dict, "my cut", Cut::new(2, 0.0, 1.0));
+        assert!(export_tcl("bad", &dict, &pdict).is_none());
+        assert_eq!(
+            Some(String::from("gate {my cut} s {param.1 {0 1}}")),
+            export_tcl("my cut", &dict, &pdict)
+        );
+    }
+    #[test]
+    fn compound_1() {
+        let pdict = make_pdict();
+        let mut dict = ConditionDictionary::new();
+        insert(&mut dict, "c1", Cut::new(1, 0.0, 10.0));
+        insert(&mut dict, "c2", Cut::new(2, 0.0, 10.0));
+        let mut or = Or::new();
+        or.add_condition("c1", dict.get("c1").unwrap())
+            .add_condition("c2", dict.get("c2").unwrap());
+        insert(&mut dict, "either", or);
+        let not = Not::new(dict.get("either").unwrap());
+        insert(&mut dict, "neither", not);
+        let mut and = And::new();
+        and.add_condition("neither", dict.get("neither").unwrap())
+            .add_condition("c1", dict.get("c1").unwrap());
+        insert(&mut dict, "all", and);
+
+        assert_eq!(
+            Some(String::from("gate either + {c1 c2}")),
+            export_tcl("either", &dict, &pdict)
+        );
+        assert_eq!(
+            Some(String::from("gate neither - {either}")),
+            export_tcl("neither", &dict, &pdict)
+        );
+        assert_eq!(
+            Some(String::from("gate all * {neither c1}")),
+            export_tcl("all", &dict, &pdict)
+        );
+
+        // Deleted dependencies can't be exported:
+
+        dict.remove("c2");
+        assert!(export_tcl("either", &dict, &pdict).is_none());
+    }
+    #[test]
+    fn all_1() {
+        // Dependencies come first:
+
+        let pdict = make_pdict();
+        let mut dict = ConditionDictionary::new();
+        insert(&mut dict, "z", Cut::new(1, 0.0, 10.0));
+        insert(&mut dict, "y", Cut::new(2, 0.0, 10.0));
+        let mut or = Or::new();
+        or.add_condition("z", dict.get("z").unwrap())
+            .add_condition("y", dict.get("y").unwrap());
+        insert(&mut dict, "b", or);
+        let not = Not::new(dict.get("b").unwrap());
+        insert(&mut dict, "a", not);
+
+        let script = export_all_tcl(&dict, &pdict);
+        let lines: Vec<&str> = script.lines().collect();
+        assert_eq!(
+            vec![
+                "gate z s {param.0 {0 10}}",
+                "gate y s {param.1 {0 10}}",
+                "gate b + {z y}",
+                "gate a - {b}",
+            ],
+            lines
+        );
+        for line in lines {
+            assert!(line.starts_with("gate "));
+            assert_eq!(line.matches('{').count(), line.matches('}').count());
+        }
+    }
+}
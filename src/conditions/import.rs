@@ -0,0 +1,355 @@
+//!  Imports SpecTcl gate commands into a condition dictionary.  This is
+//!  the inverse of the export module; see it for the form of the gate
+//!  commands that are understood.  Parameters are referred to by name
+//!  in the gate commands so a parameter dictionary is needed to map
+//!  them to parameter ids.
+//!
+//!  Compound conditions can only refer to conditions that are already
+//!  in the condition dictionary, so scripts must define dependencies
+//!  before the conditions that use them.  export_all_tcl produces
+//!  scripts in that order.
+//!
+use super::*;
+use crate::parameters::ParameterDictionary;
+
+// Split a Tcl list into its elements.  Elements are either
+// whitespace separated words, or brace enclosed strings (which may
+// contain nested braces).  The outer braces around an element are
+// removed.
+
+fn tcl_list(s: &str) -> Result<Vec<String>, String> {
+    let mut result = Vec::new();
+    let mut chars = s.chars().peekable();
+    loop {
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        match chars.next() {
+            None => break,
+            Some('{') => {
+                let mut depth = 1;
+                let mut word = String::new();
+                loop {
+                    let c = chars
+                        .next()
+                        .ok_or_else(|| format!("Unbalanced braces in '{}'", s))?;
+                    match c {
+                        '{' => depth += 1,
+                        '}' => {
+                            depth -= 1;
+                            if depth == 0 {
+                                break;
+                            }
+                        }
+                        _ => {}
+                    }
+                    word.push(c);
+                }
+                result.push(word);
+            }
+            Some(c) => {
+                let mut word = String::from(c);
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                    word.push(c);
+                }
+                result.push(word);
+            }
+        }
+    }
+    Ok(result)
+}
+
+fn parameter_id(name: &str, pdict: &ParameterDictionary) -> Result<u32, String> {
+    pdict
+        .lookup(name)
+        .map(|p| p.get_id())
+        .ok_or_else(|| format!("No such parameter {}", name))
+}
+fn number(s: &str) -> Result<f64, String> {
+    s.parse::<f64>()
+        .map_err(|_| format!("'{}' is not a number", s))
+}
+fn dependency(name: &str, cdict: &ConditionDictionary) -> Result<Container, String> {
+    cdict
+        .get(name)
+        .cloned()
+        .ok_or_else(|| format!("No such condition {}", name))
+}
+
+// Parse {{xparam yparam} {{x1 y1} ...}} into the parameter ids and
+// points of a 2-d condition.
+
+fn twod_description(
+    description: &[String],
+    pdict: &ParameterDictionary,
+) -> Result<(u32, u32, Points), String> {
+    if description.len() != 2 {
+        return Err(String::from(
+            "2-d conditions need a parameter list and a point list",
+        ));
+    }
+    let params = tcl_list(&description[0])?;
+    if params.len() != 2 {
+        return Err(String::from("2-d conditions need two parameters"));
+    }
+    let mut points = Points::new();
+    for pt in tcl_list(&description[1])? {
+        let coords = tcl_list(&pt)?;
+        if coords.len() != 2 {
+            return Err(format!("'{}' is not an x y point", pt));
+        }
+        points.push(Point::new(number(&coords[0])?, number(&coords[1])?));
+    }
+    Ok((
+        parameter_id(&params[0], pdict)?,
+        parameter_id(&params[1], pdict)?,
+        points,
+    ))
+}
+
+// Build the condition described by a single gate command.
+
+fn parse_gate(
+    line: &str,
+    pdict: &ParameterDictionary,
+    cdict: &ConditionDictionary,
+) -> Result<(String, Container), String> {
+    let words = tcl_list(line)?;
+    if words.len() != 4 || words[0] != "gate" {
+        return Err(String::from("Not of the form 'gate name type description'"));
+    }
+    let description = tcl_list(&words[3])?;
+    let condition: Container = match words[2].as_str() {
+        "T" => Rc::new(RefCell::new(True {})),
+        "F" => Rc::new(RefCell::new(False {})),
+        "s" => {
+            if description.len() != 2 {
+                return Err(String::from("Cuts need a parameter and limits"));
+            }
+            let limits = tcl_list(&description[1])?;
+            if limits.len() != 2 {
+                return Err(String::from("Cuts need a low and high limit"));
+            }
+            Rc::new(RefCell::new(Cut::new(
+                parameter_id(&description[0], pdict)?,
+                number(&limits[0])?,
+                number(&limits[1])?,
+            )))
+        }
+        "c" => {
+            let (p1, p2, pts) = twod_description(&description, pdict)?;
+            Rc::new(RefCell::new(
+                Contour::new(p1, p2, pts).ok_or("Invalid contour points")?,
+            ))
+        }
+        "b" => {
+            let (p1, p2, pts) = twod_description(&description, pdict)?;
+            Rc::new(RefCell::new(
+                Band::new(p1, p2, pts).ok_or("Invalid band points")?,
+            ))
+        }
+        "-" => {
+            if description.len() != 1 {
+                return Err(String::from("Not conditions need exactly one dependency"));
+            }
+            Rc::new(RefCell::new(Not::new(&dependency(&description[0], cdict)?)))
+        }
+        "*" => {
+            let mut and = And::new();
+            for d in &description {
+                and.add_condition(d, &dependency(d, cdict)?);
+            }
+            Rc::new(RefCell::new(and))
+        }
+        "+" => {
+            let mut or = Or::new();
+            for d in &description {
+                or.add_condition(d, &dependency(d, cdict)?);
+            }
+            Rc::new(RefCell::new(or))
+        }
+        unknown => return Err(format!("Unsupported gate type {}", unknown)),
+    };
+    Ok((words[1].clone(), condition))
+}
+
+///
+/// Import a script of SpecTcl gate commands into cdict.  Each non-blank
+/// line is a single gate command; lines starting with # are comments.
+/// A condition with the same name as an existing condition replaces it.
+///
+/// Lines that can't be parsed, or that refer to parameters or
+/// conditions that don't exist, are skipped; they don't abort the
+/// import.  The number of conditions successfully imported is returned
+/// along with a warning for each skipped line.  The Result leaves room
+/// for errors that abort the import.
+///
+pub fn import_tcl(
+    script: &str,
+    pdict: &ParameterDictionary,
+    cdict: &mut ConditionDictionary,
+) -> Result<(usize, Vec<String>), String> {
+    let mut count = 0;
+    let mut warnings = Vec::new();
+    for (n, line) in script.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match parse_gate(line, pdict, cdict) {
+            Ok((name, condition)) => {
+                cdict.insert(name, condition);
+                count += 1;
+            }
+            Err(msg) => warnings.push(format!("line {} '{}' not imported: {}", n + 1, line, msg)),
+        }
+    }
+    Ok((count, warnings))
+}
+
+#[cfg(test)]
+mod import_tests {
+    use super::*;
+    use crate::parameters::*;
+
+    fn insert<T: Condition + 'static>(dict: &mut ConditionDictionary, name: &str, c: T) {
+        dict.insert(String::from(name), Rc::new(RefCell::new(c)));
+    }
+    fn make_pdict() -> ParameterDictionary {
+        let mut pdict = ParameterDictionary::new();
+        for i in 0..4 {
+            pdict.add(&format!("param.{}", i)).unwrap(); // ids 1..4
+        }
+        pdict
+    }
+    fn make_cdict() -> ConditionDictionary {
+        let mut dict = ConditionDictionary::new();
+        insert(&mut dict, "t", True {});
+        insert(&mut dict, "f", False {});
+        insert(&mut dict, "c1", Cut::new(1, 0.0, 10.0));
+        insert(&mut dict, "my cut", Cut::new(2, 5.0, 15.5));
+        let pts = vec![
+            Point::new(0.0, 0.0),
+            Point::new(10.0, 0.0),
+            Point::new(5.0, 10.0),
+        ];
+        insert(
+            &mut dict,
+            "contour",
+            Contour::new(1, 2, pts.clone()).unwrap(),
+        );
+        insert(&mut dict, "band", Band::new(3, 4, pts).unwrap());
+        let mut or = Or::new();
+        or.add_condition("c1", dict.get("c1").unwrap())
+            .add_condition("my cut", dict.get("my cut").unwrap());
+        insert(&mut dict, "either", or);
+        let not = Not::new(dict.get("either").unwrap());
+        insert(&mut dict, "neither", not);
+        let mut and = And::new();
+        and.add_condition("contour", dict.get("contour").unwrap())
+            .add_condition("band", dict.get("band").unwrap())
+            .add_condition("t", dict.get("t").unwrap());
+        insert(&mut dict, "all", and);
+        dict
+    }
+
+    #[test]
+    fn list_1() {
+        assert_eq!(Vec::<String>::new(), tcl_list("  ").unwrap());
+        assert_eq!(
+            vec!["gate", "my cut", "s", "param.1 {0 1}"],
+            tcl_list("gate {my cut} s {param.1 {0 1}}").unwrap()
+        );
+        assert_eq!(vec![""], tcl_list("{}").unwrap());
+        assert!(tcl_list("gate a s {param.1 {0 1}").is_err());
+    }
+    #[test]
+    fn import_1() {
+        let pdict = make_pdict();
+        let mut cdict = ConditionDictionary::new();
+        let script = "# A comment\n\ngate t T {}\ngate c s {param.0 {1 2}}\n";
+        assert_eq!(Ok((2, vec![])), import_tcl(script, &pdict, &mut cdict));
+        assert_eq!(2, cdict.len());
+        assert_eq!("True", cdict.get("t").unwrap().borrow().gate_type());
+        let c = cdict.get("c").unwrap().borrow();
+        assert_eq!("Cut", c.gate_type());
+        assert_eq!(vec![1], c.dependent_parameters());
+        assert_eq!(vec![(1.0, 0.0), (2.0, 0.0)], c.gate_points());
+    }
+    #[test]
+    fn import_2() {
+        // Bad lines are skipped:
+
+        let pdict = make_pdict();
+        let mut cdict = ConditionDictionary::new();
+        let script = "gate bad1 s {nosuch {1 2}}\n\
+                      gate bad2 s {param.0 {1 x}}\n\
+                      gate bad3 - {nosuch}\n\
+                      gate bad4 q {}\n\
+                      gate bad5 c {{param.0 param.1} {{0 0}}}\n\
+                      spectrum bad6 1 param.0 {{0 10 10}}\n\
+                      gate bad7 s {param.0 {1 2}\n\
+                      gate good F {}\n";
+        let (count, warnings) = import_tcl(script, &pdict, &mut cdict).unwrap();
+        assert_eq!(1, count);
+        assert_eq!(1, cdict.len());
+        assert!(cdict.contains_key("good"));
+
+        // One warning for each bad line:
+
+        assert_eq!(7, warnings.len());
+        for (i, w) in warnings.iter().enumerate() {
+            assert!(
+                w.starts_with(&format!("line {} 'gate bad{} ", i + 1, i + 1))
+                    || w.starts_with(&format!("line {} 'spectrum bad{} ", i + 1, i + 1)),
+                "{}",
+                w
+            );
+        }
+    }
+    #[test]
+    fn round_trip_1() {
+        // Export then import and check the conditions evaluate the same.
+
+        let pdict = make_pdict();
+        let mut original = make_cdict();
+        let script = export_all_tcl(&original, &pdict);
+
+        let mut imported = ConditionDictionary::new();
+        assert_eq!(
+            Ok((original.len(), vec![])),
+            import_tcl(&script, &pdict, &mut imported)
+        );
+        assert_eq!(original.len(), imported.len());
+        for (name, c) in original.iter() {
+            assert_eq!(
+                c.borrow().gate_type(),
+                imported.get(name).unwrap().borrow().gate_type()
+            );
+        }
+
+        let mut fe = FlatEvent::new();
+        for x in [-1.0, 2.0, 5.0, 8.0, 12.0] {
+            for y in [-1.0, 1.0, 4.0, 9.0, 16.0] {
+                invalidate_cache(&mut original);
+                invalidate_cache(&mut imported);
+                fe.load_event(&vec![
+                    EventParameter::new(1, x),
+                    EventParameter::new(2, y),
+                    EventParameter::new(3, x),
+                    EventParameter::new(4, y),
+                ]);
+                for (name, c) in original.iter() {
+                    assert_eq!(
+                        c.borrow_mut().check(&fe),
+                        imported.get(name).unwrap().borrow_mut().check(&fe),
+                        "{} at ({}, {})",
+                        name,
+                        x,
+                        y
+                    );
+                }
+            }
+        }
+    }
+}
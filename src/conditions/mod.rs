@@ -50,7 +50,8 @@
 //!
 
 use crate::parameters;
-use std::cell::RefCell;
+use std::any::Any;
+use std::cell::{Ref, RefCell};
 use std::collections::HashMap;
 use std::rc::{Rc, Weak};
 
@@ -62,6 +63,18 @@ pub mod compound;
 pub use compound::*;
 pub mod twod;
 pub use twod::*;
+pub mod not_contour;
+pub use not_contour::*;
+pub mod describe;
+pub use describe::*;
+pub mod export;
+pub use export::*;
+pub mod import;
+pub use import::*;
+pub mod shared;
+pub use shared::*;
+pub mod snapshot;
+pub use snapshot::*;
 
 /// The Container trait defines the interface to a condition through
 /// a gate container.   This interface includes:
@@ -81,6 +94,15 @@ pub trait Condition {
     ///
     fn evaluate(&mut self, event: &parameters::FlatEvent) -> bool;
 
+    /// The type of condition, e.g. "Cut" or "And".  This supports
+    /// producing descriptions of conditions.
+    ///
+    fn gate_type(&self) -> String;
+
+    /// Allows downcasting to the concrete condition type:
+    ///
+    fn as_any(&self) -> &dyn Any;
+
     /// Optional methods:
     /// Caching not implemented is the default.
     ///
@@ -89,6 +111,37 @@ pub trait Condition {
     }
     fn invalidate_cache(&mut self) {}
     ///
+    /// Compound conditions return their dependent conditions.
+    /// Primitive conditions have none.
+    ///
+    fn dependent_gates(&self) -> Vec<ContainerReference> {
+        Vec::new()
+    }
+    ///
+    /// Compound conditions make dependents that are old refer to
+    /// new instead and return true if there were any.  Primitive
+    /// conditions have no dependents to replace.
+    ///
+    fn replace_dependent(&mut self, _old: &Container, _new: &Container) -> bool {
+        false
+    }
+    ///
+    /// Primitive conditions return the ids of the parameters they
+    /// are defined on and the points that define them.  For
+    /// Cuts, the points are (low, 0.0), (high, 0.0).
+    ///
+    fn dependent_parameters(&self) -> Vec<u32> {
+        Vec::new()
+    }
+    fn gate_points(&self) -> Vec<(f64, f64)> {
+        Vec::new()
+    }
+    /// How far outside its gate_points a condition still accepts
+    /// values (see Cut::with_tolerance).  Zero for most conditions.
+    fn gate_tolerance(&self) -> f64 {
+        0.0
+    }
+    ///
     /// The method that really sould be called to check a gate:
     /// If the object has a cached value, the cached value
     /// is returned, otherwise the evaluate, required method is
@@ -129,6 +182,149 @@ pub fn invalidate_cache(d: &mut ConditionDictionary) {
     }
 }
 
+///
+/// Borrow a condition as its concrete type.  None is returned
+/// if the condition is not a T or is currently mutably borrowed.
+///
+pub fn downcast_condition<T: Condition + 'static>(cond: &Container) -> Option<Ref<'_, T>> {
+    let c = cond.try_borrow().ok()?;
+    Ref::filter_map(c, |c| c.as_any().downcast_ref::<T>()).ok()
+}
+
+///
+/// Return the names of the conditions in the dictionary that match
+/// a glob pattern (e.g. "cut.*" or "cut.?"), sorted.  An invalid pattern
+/// matches nothing.
+///
+pub fn find_matching(d: &ConditionDictionary, pattern: &str) -> Vec<String> {
+    let mut result: Vec<String> = if let Ok(p) = glob::Pattern::new(pattern) {
+        d.keys().filter(|k| p.matches(k)).cloned().collect()
+    } else {
+        Vec::new()
+    };
+    result.sort();
+    result
+}
+///
+/// Remove the conditions whose names match a glob pattern.  Conditions
+/// that are still held by something other than the dictionary (e.g.
+/// a caller holding a clone of its container) are not removed.  Weak
+/// references (e.g. gates on spectra) don't prevent removal.  The
+/// number of conditions removed is returned.
+///
+pub fn delete_matching(d: &mut ConditionDictionary, pattern: &str) -> usize {
+    let mut count = 0;
+    for name in find_matching(d, pattern) {
+        if Rc::strong_count(&d[&name]) == 1 {
+            d.remove(&name);
+            count += 1;
+        }
+    }
+    count
+}
+
+///
+/// The concrete types of conditions.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ConditionType {
+    True,
+    False,
+    Not,
+    And,
+    Or,
+    Cut,
+    Band,
+    Contour,
+    NotContour,
+    Frozen,
+}
+
+// The type of a condition object:
+
+fn type_of(c: &dyn Condition) -> Option<ConditionType> {
+    let a = c.as_any();
+    if a.is::<True>() {
+        Some(ConditionType::True)
+    } else if a.is::<False>() {
+        Some(ConditionType::False)
+    } else if a.is::<Not>() {
+        Some(ConditionType::Not)
+    } else if a.is::<And>() {
+        Some(ConditionType::And)
+    } else if a.is::<Or>() {
+        Some(ConditionType::Or)
+    } else if a.is::<Cut>() {
+        Some(ConditionType::Cut)
+    } else if a.is::<Band>() {
+        Some(ConditionType::Band)
+    } else if a.is::<Contour>() {
+        Some(ConditionType::Contour)
+    } else if a.is::<NotContour>() {
+        Some(ConditionType::NotContour)
+    } else if a.is::<FrozenCondition>() {
+        Some(ConditionType::Frozen)
+    } else {
+        None
+    }
+}
+///
+/// The type of the named condition.  None if there's no such
+/// condition (or it's of a type not in ConditionType).
+///
+pub fn condition_type(name: &str, dict: &ConditionDictionary) -> Option<ConditionType> {
+    dict.get(name).and_then(|c| type_of(&*c.borrow()))
+}
+///
+/// The names of the conditions in the dictionary, sorted, grouped by
+/// condition type.  Types with no conditions have no entry.
+///
+pub fn list_by_type(dict: &ConditionDictionary) -> HashMap<ConditionType, Vec<String>> {
+    let mut result: HashMap<ConditionType, Vec<String>> = HashMap::new();
+    for (name, c) in dict.iter() {
+        if let Some(t) = type_of(&*c.borrow()) {
+            result.entry(t).or_default().push(name.clone());
+        }
+    }
+    for names in result.values_mut() {
+        names.sort();
+    }
+    result
+}
+
+///
+/// Diagnostic information about a condition:  its cached value, if
+/// any, and the number of dependent conditions (0 for primitive
+/// conditions).
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConditionInfo {
+    pub name: String,
+    pub kind: ConditionType,
+    pub cached_value: Option<bool>,
+    pub dependent_count: usize,
+}
+///
+/// Describe all conditions in the dictionary, sorted by name e.g. for a
+/// display of which conditions are cached, and to what values.
+///
+pub fn enumerate_conditions(dict: &ConditionDictionary) -> Vec<ConditionInfo> {
+    let mut result: Vec<ConditionInfo> = dict
+        .iter()
+        .filter_map(|(name, c)| {
+            let c = c.borrow();
+            type_of(&*c).map(|kind| ConditionInfo {
+                name: name.clone(),
+                kind,
+                cached_value: c.get_cached_value(),
+                dependent_count: c.dependent_gates().len(),
+            })
+        })
+        .collect();
+    result.sort_by(|a, b| a.name.cmp(&b.name));
+    result
+}
+
 /// The True gate is implemented in this module and returns True
 /// no matter what the event contains.  It serves as a trival example
 /// of how conditions can be implemented.  No caching is required
@@ -138,6 +334,12 @@ impl Condition for True {
     fn evaluate(&mut self, _event: &parameters::FlatEvent) -> bool {
         true
     }
+    fn gate_type(&self) -> String {
+        String::from("True")
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
 }
 
 /// The false gate is implemented in this module and returns
@@ -149,6 +351,12 @@ impl Condition for False {
     fn evaluate(&mut self, _event: &parameters::FlatEvent) -> bool {
         false
     }
+    fn gate_type(&self) -> String {
+        String::from("False")
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
 }
 
 #[cfg(test)]
@@ -198,4 +406,185 @@ mod condition_tests {
         assert!(dict.get(&k1).unwrap().borrow_mut().check(&e));
         assert!(!(dict.get(&k2).unwrap().borrow_mut().check(&e)));
     }
+    fn make_dict() -> ConditionDictionary {
+        let mut dict = ConditionDictionary::new();
+        for name in [
+            "cut.1",
+            "cut.2",
+            "cut.10",
+            "cut.a",
+            "band.1",
+            "band.2",
+            "contour",
+            "contour.1",
+            "true",
+            "false",
+        ] {
+            dict.insert(String::from(name), Rc::new(RefCell::new(True {})));
+        }
+        dict
+    }
+    #[test]
+    fn find_1() {
+        let dict = make_dict();
+        assert_eq!(
+            vec!["cut.1", "cut.10", "cut.2", "cut.a"],
+            find_matching(&dict, "cut.*")
+        );
+        assert_eq!(
+            vec!["cut.1", "cut.2", "cut.a"],
+            find_matching(&dict, "cut.?")
+        );
+        assert_eq!(
+            vec!["band.1", "contour.1", "cut.1"],
+            find_matching(&dict, "*.1")
+        );
+        assert_eq!(
+            vec!["contour", "contour.1"],
+            find_matching(&dict, "contour*")
+        );
+        assert_eq!(vec!["true"], find_matching(&dict, "true"));
+        assert_eq!(10, find_matching(&dict, "*").len());
+        assert!(find_matching(&dict, "nosuch*").is_empty());
+        assert!(find_matching(&dict, "[").is_empty()); // Bad pattern.
+    }
+    #[test]
+    fn delete_1() {
+        let mut dict = make_dict();
+        assert_eq!(4, delete_matching(&mut dict, "cut.*"));
+        assert_eq!(6, dict.len());
+        assert!(find_matching(&dict, "cut*").is_empty());
+        assert_eq!(0, delete_matching(&mut dict, "nosuch"));
+        assert_eq!(6, dict.len());
+    }
+    #[test]
+    fn delete_2() {
+        // Conditions held outside the dictionary are not deleted but
+        // weak references don't count:
+
+        let mut dict = make_dict();
+        let held = Rc::clone(dict.get("band.1").unwrap());
+        let weak = Rc::downgrade(dict.get("band.2").unwrap());
+        assert_eq!(1, delete_matching(&mut dict, "band.*"));
+        assert_eq!(vec!["band.1"], find_matching(&dict, "band.*"));
+        assert!(weak.upgrade().is_none());
+
+        drop(held);
+        assert_eq!(1, delete_matching(&mut dict, "band.*"));
+        assert_eq!(8, dict.len());
+    }
+    #[test]
+    fn list_by_type_1() {
+        let mut dict = ConditionDictionary::new();
+        let c1: Container = Rc::new(RefCell::new(Cut::new(1, 10.0, 20.0)));
+        let c2: Container = Rc::new(RefCell::new(Cut::new(2, 10.0, 20.0)));
+        let contour = Contour::new(
+            1,
+            2,
+            vec![
+                Point::new(0.0, 0.0),
+                Point::new(10.0, 0.0),
+                Point::new(5.0, 10.0),
+            ],
+        )
+        .unwrap();
+        let mut and = And::new();
+        and.add_condition("c1", &c1).add_condition("c2", &c2);
+        dict.insert(String::from("c2"), c2.clone());
+        dict.insert(String::from("c1"), c1.clone());
+        dict.insert(String::from("contour"), Rc::new(RefCell::new(contour)));
+        dict.insert(String::from("and"), Rc::new(RefCell::new(and)));
+
+        let listing = list_by_type(&dict);
+        assert_eq!(3, listing.len());
+        assert_eq!(
+            vec![String::from("c1"), String::from("c2")],
+            listing[&ConditionType::Cut]
+        );
+        assert_eq!(
+            vec![String::from("contour")],
+            listing[&ConditionType::Contour]
+        );
+        assert_eq!(vec![String::from("and")], listing[&ConditionType::And]);
+
+        assert_eq!(Some(ConditionType::Cut), condition_type("c1", &dict));
+        assert_eq!(Some(ConditionType::And), condition_type("and", &dict));
+        assert_eq!(None, condition_type("nosuch", &dict));
+    }
+    #[test]
+    fn enumerate_1() {
+        let mut dict = ConditionDictionary::new();
+        let c1: Container = Rc::new(RefCell::new(Cut::new(1, 10.0, 20.0)));
+        let c2: Container = Rc::new(RefCell::new(Cut::new(2, 10.0, 20.0)));
+        let mut and = And::new();
+        and.add_condition("c1", &c1).add_condition("c2", &c2);
+        let and: Container = Rc::new(RefCell::new(and));
+        dict.insert(String::from("c1"), c1);
+        dict.insert(String::from("c2"), c2);
+        dict.insert(String::from("and"), and.clone());
+
+        // Nothing's cached yet:
+
+        let info = enumerate_conditions(&dict);
+        assert_eq!(3, info.len());
+        assert_eq!(
+            ConditionInfo {
+                name: String::from("and"),
+                kind: ConditionType::And,
+                cached_value: None,
+                dependent_count: 2
+            },
+            info[0]
+        );
+
+        let mut event = FlatEvent::new();
+        event.load_event(&vec![
+            EventParameter::new(1, 15.0),
+            EventParameter::new(2, 15.0),
+        ]);
+        assert!(and.borrow_mut().check(&event));
+
+        let info = enumerate_conditions(&dict);
+        assert_eq!(
+            vec!["and", "c1", "c2"],
+            info.iter().map(|i| i.name.as_str()).collect::<Vec<&str>>()
+        );
+        assert_eq!(Some(true), info[0].cached_value);
+        assert_eq!(2, info[0].dependent_count);
+        assert_eq!(ConditionType::Cut, info[1].kind);
+        assert_eq!(Some(true), info[1].cached_value);
+        assert_eq!(0, info[1].dependent_count);
+
+        invalidate_cache(&mut dict);
+        assert!(enumerate_conditions(&dict)
+            .iter()
+            .all(|i| i.cached_value.is_none()));
+    }
+    #[test]
+    fn enumerate_2() {
+        // Frozen conditions are listed too:
+
+        let mut dict = ConditionDictionary::new();
+        let cut: Container = Rc::new(RefCell::new(Cut::new(1, 10.0, 20.0)));
+        let mut frozen = FrozenCondition::new(&cut);
+        frozen.freeze();
+        dict.insert(String::from("cut"), cut);
+        dict.insert(String::from("frozen"), Rc::new(RefCell::new(frozen)));
+
+        let info = enumerate_conditions(&dict);
+        assert_eq!(
+            ConditionInfo {
+                name: String::from("frozen"),
+                kind: ConditionType::Frozen,
+                cached_value: Some(false),
+                dependent_count: 1
+            },
+            info[1]
+        );
+        assert_eq!(Some(ConditionType::Frozen), condition_type("frozen", &dict));
+        assert_eq!(
+            vec![String::from("frozen")],
+            list_by_type(&dict)[&ConditionType::Frozen]
+        );
+    }
 }
@@ -0,0 +1,189 @@
+//!  A NotContour is the complement of a Contour.  It is defined in
+//!  exactly the same way as a Contour (a pair of dependent parameters
+//!  and at least three points) but accepts events whose point lies
+//!  *outside* the figure, e.g. for beam rejection.
+//!
+//!  The same effect can be had with a Not condition whose dependent
+//!  is a Contour, but that requires upgrading the Not's weak reference
+//!  to the contour for each event.  A NotContour owns its contour.
+//!
+//!  Note that, as with a Contour, events that don't define both
+//!  dependent parameters are not accepted.  In this NotContour differs
+//!  from Not(Contour) which would accept them.
+//!
+use super::*;
+use crate::parameters::*;
+
+pub struct NotContour {
+    contour: Contour,
+    cache: Option<bool>,
+}
+impl NotContour {
+    /// Construction; as with Contour, there must be at least three
+    /// points.
+    pub fn new(p1: u32, p2: u32, pts: Points) -> Option<NotContour> {
+        Contour::new(p1, p2, pts).map(|contour| NotContour {
+            contour,
+            cache: None,
+        })
+    }
+    /// Construct given the names of the parameters.  None is returned
+    /// if either parameter is not in pdict.
+    pub fn from_names(
+        xname: &str,
+        yname: &str,
+        pdict: &ParameterDictionary,
+        pts: Points,
+    ) -> Option<NotContour> {
+        let p1 = pdict.lookup(xname)?.get_id();
+        let p2 = pdict.lookup(yname)?.get_id();
+        Self::new(p1, p2, pts)
+    }
+    pub fn get_points(&self) -> Points {
+        self.contour.get_points()
+    }
+}
+impl Condition for NotContour {
+    fn gate_type(&self) -> String {
+        String::from("NotContour")
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn dependent_parameters(&self) -> Vec<u32> {
+        self.contour.dependent_parameters()
+    }
+    fn gate_points(&self) -> Vec<(f64, f64)> {
+        self.contour.gate_points()
+    }
+    fn evaluate(&mut self, event: &FlatEvent) -> bool {
+        let (p1, p2) = self.contour.get_parameters();
+        let result = if event[p1].is_none() || event[p2].is_none() {
+            false
+        } else {
+            !self.contour.evaluate(event)
+        };
+        self.cache = Some(result);
+        result
+    }
+    fn get_cached_value(&self) -> Option<bool> {
+        self.cache
+    }
+    fn invalidate_cache(&mut self) {
+        self.cache = None;
+        self.contour.invalidate_cache();
+    }
+}
+
+#[cfg(test)]
+mod not_contour_tests {
+    use super::*;
+
+    // The same figures used by the Contour tests:
+
+    fn diamond() -> Points {
+        vec![
+            Point::new(0.0, 50.0),
+            Point::new(50.0, 0.0),
+            Point::new(100.0, 50.0),
+            Point::new(50.0, 100.0),
+        ]
+    }
+    fn hourglass() -> Points {
+        vec![
+            Point::new(0.0, 0.0),
+            Point::new(50.0, 50.0),
+            Point::new(50.0, 0.0),
+            Point::new(0.0, 50.0),
+        ]
+    }
+
+    #[test]
+    fn new_1() {
+        // Too few points:
+
+        assert!(NotContour::new(1, 2, vec![]).is_none());
+        assert!(NotContour::new(1, 2, vec![Point::new(0.0, 0.0), Point::new(1.0, 1.0)]).is_none());
+        let nc = NotContour::new(1, 2, diamond()).unwrap();
+        assert_eq!(diamond(), nc.get_points());
+        assert_eq!("NotContour", nc.gate_type());
+        assert_eq!(vec![1, 2], nc.dependent_parameters());
+    }
+    #[test]
+    fn new_2() {
+        let mut pdict = ParameterDictionary::new();
+        pdict.add("x").unwrap();
+        pdict.add("y").unwrap();
+        let nc = NotContour::from_names("x", "y", &pdict, diamond()).unwrap();
+        assert_eq!(vec![1, 2], nc.dependent_parameters());
+
+        assert!(NotContour::from_names("x", "nosuch", &pdict, diamond()).is_none());
+        assert!(NotContour::from_names("x", "y", &pdict, vec![]).is_none());
+    }
+    #[test]
+    fn check_1() {
+        // Every Contour test point gives the opposite result:
+
+        let cases = vec![
+            (diamond(), -1.0, 10.0),
+            (diamond(), 101.0, 10.0),
+            (diamond(), 50.0, -1.0),
+            (diamond(), 50.0, 101.0),
+            (diamond(), 1.0, 10.0),
+            (diamond(), 88.0, 99.0),
+            (diamond(), 50.0, 50.0),
+            (diamond(), 50.0, 56.0),
+            (diamond(), 50.0, 100.0),
+            (diamond(), 50.0, 48.0),
+            (diamond(), 50.0, 0.0),
+            (hourglass(), 10.0, 5.0),
+            (hourglass(), 10.0, 25.0),
+            (hourglass(), 10.0, 27.0),
+            (hourglass(), 40.0, 27.0),
+            (hourglass(), 10.0, 22.0),
+        ];
+        let mut e = FlatEvent::new();
+        for (pts, x, y) in cases {
+            let mut c = Contour::new(1, 2, pts.clone()).unwrap();
+            let mut nc = NotContour::new(1, 2, pts).unwrap();
+            e.load_event(&vec![EventParameter::new(1, x), EventParameter::new(2, y)]);
+            assert_eq!(!c.check(&e), nc.check(&e), "({}, {})", x, y);
+        }
+    }
+    #[test]
+    fn check_2() {
+        // Missing parameters are not accepted:
+
+        let mut nc = NotContour::new(1, 2, diamond()).unwrap();
+        let mut e = FlatEvent::new();
+        e.load_event(&vec![EventParameter::new(1, -1.0)]);
+        assert!(!nc.check(&e));
+        e.load_event(&vec![EventParameter::new(2, -1.0)]);
+        nc.invalidate_cache();
+        assert!(!nc.check(&e));
+    }
+    #[test]
+    fn cache_1() {
+        let mut nc = NotContour::new(1, 2, diamond()).unwrap();
+        assert!(nc.get_cached_value().is_none());
+        let mut e = FlatEvent::new();
+        e.load_event(&vec![
+            EventParameter::new(1, -1.0),
+            EventParameter::new(2, 10.0),
+        ]);
+        assert!(nc.check(&e));
+        assert_eq!(Some(true), nc.get_cached_value());
+
+        // Cached value is used until invalidated:
+
+        e.load_event(&vec![
+            EventParameter::new(1, 50.0),
+            EventParameter::new(2, 50.0),
+        ]);
+        assert!(nc.check(&e));
+        nc.invalidate_cache();
+        assert!(nc.get_cached_value().is_none());
+        assert!(!nc.check(&e));
+        assert_eq!(Some(false), nc.get_cached_value());
+    }
+}
@@ -0,0 +1,201 @@
+//!  ConditionDictionary is not Send because its conditions are held in
+//!  Rc<RefCell<>> containers.  This module provides a thread-safe
+//!  analog for multi-threaded analysis, where, e.g., one thread fills
+//!  spectra and another updates conditions:
+//!
+//!  *  SharedCondition - a condition in an Arc<Mutex<>>.
+//!  *  SharedConditionDictionary - a name to SharedCondition map in an
+//!     Arc<RwLock<>>.
+//!
+//!  The primitive conditions (True, False, Cut, Band, Contour and
+//!  NotContour) are all Send.  The compound conditions (Not, And, Or)
+//!  are not, and cannot be, since they refer to their dependencies
+//!  through Weak references into a ConditionDictionary.  Therefore
+//!  only primitive conditions can live in a SharedConditionDictionary.
+//!
+use super::*;
+use std::sync::{Arc, Mutex, RwLock};
+
+pub type SharedCondition = Arc<Mutex<dyn Condition + Send>>;
+pub type SharedConditionDictionary = Arc<RwLock<HashMap<String, SharedCondition>>>;
+
+///
+/// Make a shareable copy of a primitive condition.  The copy is built
+/// from the condition's type, parameters and points.  None is returned
+/// for compound conditions and conditions whose type is not known.
+///
+pub fn shared_copy(condition: &dyn Condition) -> Option<SharedCondition> {
+    let params = condition.dependent_parameters();
+    let points: Points = condition
+        .gate_points()
+        .iter()
+        .map(|(x, y)| Point::new(*x, *y))
+        .collect();
+    let result: SharedCondition = match condition.gate_type().as_str() {
+        "True" => Arc::new(Mutex::new(True {})),
+        "False" => Arc::new(Mutex::new(False {})),
+        "Cut" => {
+            let limits = condition.gate_points();
+            Arc::new(Mutex::new(Cut::with_tolerance(
+                params[0],
+                limits[0].0,
+                limits[1].0,
+                condition.gate_tolerance(),
+            )))
+        }
+        "Band" => Arc::new(Mutex::new(Band::new(params[0], params[1], points)?)),
+        "Contour" => Arc::new(Mutex::new(Contour::new(params[0], params[1], points)?)),
+        "NotContour" => Arc::new(Mutex::new(NotContour::new(params[0], params[1], points)?)),
+        _ => return None,
+    };
+    Some(result)
+}
+
+///
+/// Create a SharedConditionDictionary from a ConditionDictionary by
+/// copying its primitive conditions.  Compound conditions are not
+/// copied.
+///
+pub fn to_shared(local: &ConditionDictionary) -> SharedConditionDictionary {
+    let mut result = HashMap::new();
+    for (name, condition) in local.iter() {
+        if let Some(shared) = shared_copy(&*condition.borrow()) {
+            result.insert(name.clone(), shared);
+        }
+    }
+    Arc::new(RwLock::new(result))
+}
+
+///
+/// The shared analog of invalidate_cache.
+///
+pub fn invalidate_shared_cache(d: &SharedConditionDictionary) {
+    for v in d.read().unwrap().values() {
+        v.lock().unwrap().invalidate_cache();
+    }
+}
+
+#[cfg(test)]
+mod shared_tests {
+    use super::*;
+    use crate::parameters::*;
+    use std::thread;
+
+    fn make_local() -> ConditionDictionary {
+        let mut dict = ConditionDictionary::new();
+        let pts = vec![
+            Point::new(0.0, 0.0),
+            Point::new(10.0, 0.0),
+            Point::new(5.0, 10.0),
+        ];
+        let conditions: Vec<(&str, Container)> = vec![
+            ("t", Rc::new(RefCell::new(True {}))),
+            ("f", Rc::new(RefCell::new(False {}))),
+            (
+                "cut",
+                Rc::new(RefCell::new(Cut::with_tolerance(1, 0.0, 10.0, 0.5))),
+            ),
+            (
+                "band",
+                Rc::new(RefCell::new(Band::new(1, 2, pts.clone()).unwrap())),
+            ),
+            (
+                "contour",
+                Rc::new(RefCell::new(Contour::new(1, 2, pts.clone()).unwrap())),
+            ),
+            (
+                "notcontour",
+                Rc::new(RefCell::new(NotContour::new(1, 2, pts).unwrap())),
+            ),
+        ];
+        for (name, c) in conditions {
+            dict.insert(String::from(name), c);
+        }
+        let not = Not::new(dict.get("cut").unwrap());
+        dict.insert(String::from("not"), Rc::new(RefCell::new(not)));
+        dict
+    }
+
+    #[test]
+    fn to_shared_1() {
+        // Compound conditions are not copied:
+
+        let local = make_local();
+        let shared = to_shared(&local);
+        let shared = shared.read().unwrap();
+        assert_eq!(local.len() - 1, shared.len());
+        assert!(!shared.contains_key("not"));
+        for (name, c) in shared.iter() {
+            let l = local.get(name).unwrap().borrow();
+            let s = c.lock().unwrap();
+            assert_eq!(l.gate_type(), s.gate_type());
+            assert_eq!(l.dependent_parameters(), s.dependent_parameters());
+            assert_eq!(l.gate_points(), s.gate_points());
+            assert_eq!(l.gate_tolerance(), s.gate_tolerance());
+        }
+    }
+    #[test]
+    fn to_shared_2() {
+        // Copies evaluate the same as the originals:
+
+        let mut local = make_local();
+        let shared = to_shared(&local);
+        let mut fe = FlatEvent::new();
+        for (x, y) in [
+            (-1.0, 1.0),
+            (-0.25, 1.0),
+            (5.0, 1.0),
+            (5.0, 9.0),
+            (9.0, 9.0),
+        ] {
+            invalidate_cache(&mut local);
+            invalidate_shared_cache(&shared);
+            fe.load_event(&vec![EventParameter::new(1, x), EventParameter::new(2, y)]);
+            for (name, c) in shared.read().unwrap().iter() {
+                assert_eq!(
+                    local.get(name).unwrap().borrow_mut().check(&fe),
+                    c.lock().unwrap().check(&fe)
+                );
+            }
+        }
+    }
+    #[test]
+    fn thread_1() {
+        // Created in one thread, evaluated in another:
+
+        let shared: SharedConditionDictionary = Arc::new(RwLock::new(HashMap::new()));
+        let writer = Arc::clone(&shared);
+        thread::spawn(move || {
+            let c: SharedCondition = Arc::new(Mutex::new(Cut::new(1, 0.0, 10.0)));
+            writer.write().unwrap().insert(String::from("cut"), c);
+        })
+        .join()
+        .unwrap();
+
+        let reader = Arc::clone(&shared);
+        let result = thread::spawn(move || {
+            let mut fe = FlatEvent::new();
+            fe.load_event(&vec![EventParameter::new(1, 5.0)]);
+            let dict = reader.read().unwrap();
+            let result = dict.get("cut").unwrap().lock().unwrap().check(&fe);
+            result
+        })
+        .join()
+        .unwrap();
+        assert!(result);
+
+        // The cached value is visible from this thread:
+
+        assert_eq!(
+            Some(true),
+            shared
+                .read()
+                .unwrap()
+                .get("cut")
+                .unwrap()
+                .lock()
+                .unwrap()
+                .get_cached_value()
+        );
+    }
+}
@@ -0,0 +1,177 @@
+//!  FrozenCondition wraps another condition so that it can be made to
+//!  stop changing mid-run, e.g. to keep using the value a gate had for
+//!  some event.  Until freeze is called, the condition delegates to the
+//!  condition it wraps and remembers the last result.  After freeze,
+//!  that remembered result is what the condition evaluates to
+//!  regardless of the event.  unfreeze goes back to delegating.
+//!
+//!  Unlike the compound conditions, FrozenCondition holds a strong
+//!  reference to the condition it wraps so it keeps working even if
+//!  that condition is removed from the dictionary.
+//!
+use super::*;
+use crate::parameters::FlatEvent;
+
+pub struct FrozenCondition {
+    inner: Container,
+    frozen_value: Option<bool>,
+    frozen: bool,
+}
+
+impl FrozenCondition {
+    pub fn new(inner: &Container) -> FrozenCondition {
+        FrozenCondition {
+            inner: Rc::clone(inner),
+            frozen_value: None,
+            frozen: false,
+        }
+    }
+    /// Stop delegating:  from now on the condition evaluates to the
+    /// last result of the wrapped condition (false if there's never
+    /// been one).
+    pub fn freeze(&mut self) {
+        self.frozen = true;
+    }
+    /// Go back to delegating to the wrapped condition.
+    pub fn unfreeze(&mut self) {
+        self.frozen = false;
+    }
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+    /// The last result of the wrapped condition, if it's been evaluated.
+    pub fn frozen_value(&self) -> Option<bool> {
+        self.frozen_value
+    }
+    pub fn inner(&self) -> &Container {
+        &self.inner
+    }
+}
+
+impl Condition for FrozenCondition {
+    fn gate_type(&self) -> String {
+        String::from("Frozen")
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn dependent_gates(&self) -> Vec<ContainerReference> {
+        vec![Rc::downgrade(&self.inner)]
+    }
+    fn evaluate(&mut self, event: &FlatEvent) -> bool {
+        if self.frozen {
+            self.frozen_value.unwrap_or(false)
+        } else {
+            let result = self.inner.borrow_mut().check(event);
+            self.frozen_value = Some(result);
+            result
+        }
+    }
+    // While frozen the value never changes so it is always cached.
+    // Otherwise the wrapped condition's cache does the work.
+
+    fn get_cached_value(&self) -> Option<bool> {
+        if self.frozen {
+            Some(self.frozen_value.unwrap_or(false))
+        } else {
+            None
+        }
+    }
+    fn invalidate_cache(&mut self) {
+        self.inner.borrow_mut().invalidate_cache();
+    }
+}
+
+#[cfg(test)]
+mod snapshot_tests {
+    use super::*;
+    use crate::parameters::*;
+
+    fn event(x: f64) -> FlatEvent {
+        let mut fe = FlatEvent::new();
+        fe.load_event(&vec![EventParameter::new(1, x)]);
+        fe
+    }
+
+    #[test]
+    fn new_1() {
+        let cut: Container = Rc::new(RefCell::new(Cut::new(1, 100.0, 200.0)));
+        let frozen = FrozenCondition::new(&cut);
+        assert!(!frozen.is_frozen());
+        assert!(frozen.frozen_value().is_none());
+        assert!(Rc::ptr_eq(&cut, frozen.inner()));
+        assert_eq!("Frozen", frozen.gate_type());
+        assert_eq!(1, frozen.dependent_gates().len());
+        assert!(Rc::ptr_eq(
+            &cut,
+            &frozen.dependent_gates()[0].upgrade().unwrap()
+        ));
+    }
+    #[test]
+    fn evaluate_1() {
+        // Delegates and remembers until frozen:
+
+        let cut: Container = Rc::new(RefCell::new(Cut::new(1, 100.0, 200.0)));
+        let mut frozen = FrozenCondition::new(&cut);
+        assert!(frozen.check(&event(150.0)));
+        assert_eq!(Some(true), frozen.frozen_value());
+        assert!(frozen.get_cached_value().is_none());
+
+        frozen.freeze();
+        frozen.invalidate_cache();
+        assert!(frozen.is_frozen());
+        assert_eq!(Some(true), frozen.get_cached_value());
+        assert!(frozen.check(&event(50.0)));
+        assert!(frozen.evaluate(&event(50.0)));
+        assert_eq!(Some(true), frozen.frozen_value());
+
+        // Delegates again after unfreeze:
+
+        frozen.unfreeze();
+        frozen.invalidate_cache();
+        assert!(!frozen.check(&event(50.0)));
+        assert_eq!(Some(false), frozen.frozen_value());
+    }
+    #[test]
+    fn evaluate_2() {
+        // Frozen before any evaluation is false:
+
+        let cut: Container = Rc::new(RefCell::new(Cut::new(1, 100.0, 200.0)));
+        let mut frozen = FrozenCondition::new(&cut);
+        frozen.freeze();
+        assert!(!frozen.check(&event(150.0)));
+        assert!(frozen.frozen_value().is_none());
+
+        // Frozen false stays false:
+
+        frozen.unfreeze();
+        assert!(!frozen.check(&event(50.0)));
+        frozen.freeze();
+        frozen.invalidate_cache();
+        assert!(!frozen.check(&event(150.0)));
+    }
+    #[test]
+    fn dict_1() {
+        // In a dictionary.  Keeping the concrete container allows
+        // freezing;  the wrapped condition can be removed from the
+        // dictionary.
+
+        let mut dict = ConditionDictionary::new();
+        let cut: Container = Rc::new(RefCell::new(Cut::new(1, 100.0, 200.0)));
+        dict.insert(String::from("cut"), cut.clone());
+        let frozen = Rc::new(RefCell::new(FrozenCondition::new(&cut)));
+        let container: Container = frozen.clone();
+        dict.insert(String::from("frozen"), container);
+        drop(cut);
+        dict.remove("cut");
+
+        let gate = dict.get("frozen").unwrap().clone();
+        assert!(gate.borrow_mut().check(&event(150.0)));
+        frozen.borrow_mut().freeze();
+        invalidate_cache(&mut dict);
+        assert!(gate.borrow_mut().check(&event(50.0)));
+        frozen.borrow_mut().unfreeze();
+        invalidate_cache(&mut dict);
+        assert!(!gate.borrow_mut().check(&event(50.0)));
+    }
+}
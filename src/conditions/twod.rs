@@ -141,7 +141,7 @@ pub struct Band {
     cache: Option<bool>,
 }
 impl Band {
-    fn new(p1: u32, p2: u32, pts: Points) -> Option<Band> {
+    pub fn new(p1: u32, p2: u32, pts: Points) -> Option<Band> {
         if pts.len() >= 2 {
             let mut etbl: EdgeTable = Vec::<Edge>::new();
             for i in 0..(pts.len() - 1) {
@@ -164,6 +164,18 @@ impl Band {
     }
 }
 impl Condition for Band {
+    fn gate_type(&self) -> String {
+        String::from("Band")
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn dependent_parameters(&self) -> Vec<u32> {
+        vec![self.parameters.0, self.parameters.1]
+    }
+    fn gate_points(&self) -> Vec<(f64, f64)> {
+        self.points.iter().map(|p| (p.x, p.y)).collect()
+    }
     fn evaluate(&mut self, event: &FlatEvent) -> bool {
         // Need both parameters:
 
@@ -298,8 +310,24 @@ impl Contour {
     pub fn get_points(&self) -> Points {
         self.pts.clone()
     }
+    /// The x and y parameter ids.
+    pub fn get_parameters(&self) -> (u32, u32) {
+        (self.p1, self.p2)
+    }
 }
 impl Condition for Contour {
+    fn gate_type(&self) -> String {
+        String::from("Contour")
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn dependent_parameters(&self) -> Vec<u32> {
+        vec![self.p1, self.p2]
+    }
+    fn gate_points(&self) -> Vec<(f64, f64)> {
+        self.pts.iter().map(|p| (p.x, p.y)).collect()
+    }
     fn evaluate(&mut self, event: &FlatEvent) -> bool {
         let result = if event[self.p1].is_none() || event[self.p2].is_none() {
             false
@@ -688,6 +716,7 @@ mod contour_tests {
         let c = Contour::new(1, 2, pts.clone());
         assert!(c.is_some());
         let c = c.unwrap();
+        assert_eq!((1, 2), c.get_parameters());
 
         let cpts = c.get_points();
         assert_eq!(pts.len(), cpts.len());
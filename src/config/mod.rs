@@ -0,0 +1,1007 @@
+//!  The config module saves and restores analysis setups: the
+//!  parameter definitions, conditions and spectra that researchers
+//!  reuse from run to run.
+//!
+//!  A setup is described by a ConfigSchema which serde can translate
+//!  to/from configuration files:
+//!
+//!  *  yaml submodule - load_yaml/save_yaml for YAML files.
+//!  *  toml submodule - load_toml/save_toml for TOML files.
+//!
+//!  The schema has three lists:
+//!
+//!  *  parameters - name and, optionally, low, high, bins and units.
+//!  *  conditions - type, name, and, depending on the type, params
+//!     (parameter names), low/high (cut limits), points (2-d
+//!     conditions), tolerance (cuts with a tolerance) or dependencies
+//!     (names of the conditions a compound condition depends on).
+//!     Condition types are true, false, cut, band, contour, notcontour,
+//!     not, and, or, frozen.  Frozen conditions are restored unfrozen.
+//!  *  spectra - type, name, parameters, y_parameters, axes and gate.
+//!     Spectrum types are 1d, 2d, summary, multi1d, multi2d, 2dsum and
+//!     pgamma.  parameters are the x parameters, y_parameters the y
+//!     parameters of 2dsum (paired with parameters) and pgamma spectra.
+//!     axes are the x then y axes; summary spectra only have the y axis.
+//!     Missing axes are defaulted from the parameters.  An axis with
+//!     log: true is logarithmic and its low/high are the real (not ln)
+//!     limits.  Only 1d spectra and one axis of 2d spectra can be
+//!     logarithmic.
+//!
+//!  Conditions must be listed after the conditions they depend on.
+//!  Saved setups are in that order.
+//!
+use crate::conditions::*;
+use crate::parameters::ParameterDictionary;
+use crate::spectra::*;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+pub mod toml;
+pub mod yaml;
+pub use self::toml::*;
+pub use yaml::*;
+
+/// Errors loading and saving configurations:
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    Io(String),
+    Parse(String),
+    Invalid(String),
+}
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(msg) => write!(f, "I/O error: {}", msg),
+            ConfigError::Parse(msg) => write!(f, "Unable to parse configuration: {}", msg),
+            ConfigError::Invalid(msg) => write!(f, "Invalid configuration: {}", msg),
+        }
+    }
+}
+impl std::error::Error for ConfigError {}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ParameterConfig {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub low: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub high: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bins: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub units: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ConditionConfig {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub params: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub low: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub high: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tolerance: Option<f64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub points: Vec<(f64, f64)>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AxisConfig {
+    pub low: f64,
+    pub high: f64,
+    pub bins: u32,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub log: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SpectrumConfig {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub name: String,
+    pub parameters: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub y_parameters: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub axes: Vec<AxisConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gate: Option<String>,
+}
+
+/// A complete setup:
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct ConfigSchema {
+    #[serde(default)]
+    pub parameters: Vec<ParameterConfig>,
+    #[serde(default)]
+    pub conditions: Vec<ConditionConfig>,
+    #[serde(default)]
+    pub spectra: Vec<SpectrumConfig>,
+}
+
+fn invalid(msg: String) -> ConfigError {
+    ConfigError::Invalid(msg)
+}
+fn parameter_id(name: &str, pdict: &ParameterDictionary) -> Result<u32, ConfigError> {
+    pdict
+        .lookup(name)
+        .map(|p| p.get_id())
+        .ok_or_else(|| invalid(format!("No such parameter {}", name)))
+}
+fn parameter_name(id: u32, pdict: &ParameterDictionary) -> Option<String> {
+    pdict
+        .iter()
+        .find(|(_, p)| p.get_id() == id)
+        .map(|(name, _)| name.clone())
+}
+
+// Make the condition a ConditionConfig describes:
+
+fn make_condition(
+    c: &ConditionConfig,
+    pdict: &ParameterDictionary,
+    cdict: &ConditionDictionary,
+) -> Result<Container, ConfigError> {
+    let params = c
+        .params
+        .iter()
+        .map(|p| parameter_id(p, pdict))
+        .collect::<Result<Vec<u32>, ConfigError>>()?;
+    let need_params = |n: usize| {
+        if params.len() == n {
+            Ok(())
+        } else {
+            Err(invalid(format!(
+                "Condition {} needs {} parameter(s)",
+                c.name, n
+            )))
+        }
+    };
+    let points: Points = c.points.iter().map(|(x, y)| Point::new(*x, *y)).collect();
+    let bad_points = || invalid(format!("Condition {} has too few points", c.name));
+    let dependencies = c
+        .dependencies
+        .iter()
+        .map(|d| {
+            cdict
+                .get(d)
+                .cloned()
+                .ok_or_else(|| invalid(format!("No such condition {}", d)))
+        })
+        .collect::<Result<Vec<Container>, ConfigError>>()?;
+
+    let result: Container = match c.kind.as_str() {
+        "true" => Rc::new(RefCell::new(True {})),
+        "false" => Rc::new(RefCell::new(False {})),
+        "cut" => {
+            need_params(1)?;
+            match (c.low, c.high) {
+                (Some(low), Some(high)) => Rc::new(RefCell::new(Cut::with_tolerance(
+                    params[0],
+                    low,
+                    high,
+                    c.tolerance.unwrap_or(0.0),
+                ))),
+                _ => return Err(invalid(format!("Cut {} needs low and high", c.name))),
+            }
+        }
+        "band" => {
+            need_params(2)?;
+            Rc::new(RefCell::new(
+                Band::new(params[0], params[1], points).ok_or_else(bad_points)?,
+            ))
+        }
+        "contour" => {
+            need_params(2)?;
+            Rc::new(RefCell::new(
+                Contour::new(params[0], params[1], points).ok_or_else(bad_points)?,
+            ))
+        }
+        "notcontour" => {
+            need_params(2)?;
+            Rc::new(RefCell::new(
+                NotContour::new(params[0], params[1], points).ok_or_else(bad_points)?,
+            ))
+        }
+        "not" => {
+            if dependencies.len() != 1 {
+                return Err(invalid(format!(
+                    "Not condition {} needs one dependency",
+                    c.name
+                )));
+            }
+            Rc::new(RefCell::new(Not::new(&dependencies[0])))
+        }
+        "frozen" => {
+            if dependencies.len() != 1 {
+                return Err(invalid(format!(
+                    "Frozen condition {} needs one dependency",
+                    c.name
+                )));
+            }
+            Rc::new(RefCell::new(FrozenCondition::new(&dependencies[0])))
+        }
+        "and" => {
+            let mut and = And::new();
+            for (name, d) in c.dependencies.iter().zip(dependencies.iter()) {
+                and.add_condition(name, d);
+            }
+            Rc::new(RefCell::new(and))
+        }
+        "or" => {
+            let mut or = Or::new();
+            for (name, d) in c.dependencies.iter().zip(dependencies.iter()) {
+                or.add_condition(name, d);
+            }
+            Rc::new(RefCell::new(or))
+        }
+        unknown => return Err(invalid(format!("Unknown condition type {}", unknown))),
+    };
+    Ok(result)
+}
+
+// Make the spectrum a SpectrumConfig describes:
+
+fn make_spectrum(
+    s: &SpectrumConfig,
+    pdict: &ParameterDictionary,
+) -> Result<SpectrumContainer, ConfigError> {
+    let axis = |i: usize| {
+        s.axes.get(i).map_or((None, None, None), |a| {
+            (Some(a.low), Some(a.high), Some(a.bins))
+        })
+    };
+    let (xlow, xhigh, xbins) = axis(0);
+    let (ylow, yhigh, ybins) = axis(1);
+    let log = |i: usize| s.axes.get(i).is_some_and(|a| a.log);
+    if (0..s.axes.len()).any(log) && !matches!(s.kind.as_str(), "1d" | "2d") {
+        return Err(invalid(format!(
+            "Spectrum {}: only 1d and 2d spectra can have log axes",
+            s.name
+        )));
+    }
+    let need_params = |n: usize| {
+        if s.parameters.len() == n {
+            Ok(())
+        } else {
+            Err(invalid(format!(
+                "Spectrum {} needs {} parameter(s)",
+                s.name, n
+            )))
+        }
+    };
+    let result: Result<SpectrumContainer, String> = match s.kind.as_str() {
+        "1d" => {
+            need_params(1)?;
+            let make = if log(0) { Oned::new_log } else { Oned::new };
+            make(&s.name, &s.parameters[0], pdict, xlow, xhigh, xbins)
+                .map(|spec| Rc::new(RefCell::new(spec)) as SpectrumContainer)
+        }
+        "2d" => {
+            need_params(2)?;
+            let (xname, yname) = (&s.parameters[0], &s.parameters[1]);
+            let linear = |i: usize| {
+                s.axes.get(i).map(|a| AxisDescription {
+                    low: a.low,
+                    high: a.high,
+                    bins: a.bins,
+                })
+            };
+            let logarithmic =
+                |i: usize| LogUniform::new(s.axes[i].bins as usize, s.axes[i].low, s.axes[i].high);
+            match (log(0), log(1)) {
+                (false, false) => Twod::new(
+                    &s.name, xname, yname, pdict, xlow, xhigh, xbins, ylow, yhigh, ybins,
+                ),
+                (true, false) => logarithmic(0)
+                    .and_then(|x| Twod::new_log_x(&s.name, xname, yname, pdict, x, linear(1))),
+                (false, true) => logarithmic(1)
+                    .and_then(|y| Twod::new_log_y(&s.name, xname, yname, pdict, linear(0), y)),
+                (true, true) => {
+                    return Err(invalid(format!(
+                        "Spectrum {}: only one axis of a 2d spectrum can be logarithmic",
+                        s.name
+                    )))
+                }
+            }
+            .map(|spec| Rc::new(RefCell::new(spec)) as SpectrumContainer)
+        }
+        "summary" => Summary::new(&s.name, s.parameters.clone(), pdict, xlow, xhigh, xbins)
+            .map(|spec| Rc::new(RefCell::new(spec)) as SpectrumContainer),
+        "multi1d" => Multi1d::new(&s.name, s.parameters.clone(), pdict, xlow, xhigh, xbins)
+            .map(|spec| Rc::new(RefCell::new(spec)) as SpectrumContainer),
+        "multi2d" => Multi2d::new(
+            &s.name,
+            s.parameters.clone(),
+            pdict,
+            xlow,
+            xhigh,
+            xbins,
+            ylow,
+            yhigh,
+            ybins,
+        )
+        .map(|spec| Rc::new(RefCell::new(spec)) as SpectrumContainer),
+        "2dsum" => {
+            if s.parameters.len() != s.y_parameters.len() {
+                return Err(invalid(format!(
+                    "Spectrum {} needs the same number of x and y parameters",
+                    s.name
+                )));
+            }
+            let pairs: XYParameters = s
+                .parameters
+                .iter()
+                .cloned()
+                .zip(s.y_parameters.iter().cloned())
+                .collect();
+            TwodSum::new(
+                &s.name, pairs, pdict, xlow, xhigh, xbins, ylow, yhigh, ybins,
+            )
+            .map(|spec| Rc::new(RefCell::new(spec)) as SpectrumContainer)
+        }
+        "pgamma" => PGamma::new(
+            &s.name,
+            &s.parameters,
+            &s.y_parameters,
+            pdict,
+            xlow,
+            xhigh,
+            xbins,
+            ylow,
+            yhigh,
+            ybins,
+        )
+        .map(|spec| Rc::new(RefCell::new(spec)) as SpectrumContainer),
+        unknown => return Err(invalid(format!("Unknown spectrum type {}", unknown))),
+    };
+    result.map_err(|msg| invalid(format!("Spectrum {}: {}", s.name, msg)))
+}
+
+///
+/// Create the parameters, conditions and spectra a schema describes.
+/// Parameters that already exist have their definitions updated.
+/// Conditions and spectra replace existing ones with the same name.
+/// Processing stops at the first error, so on error some of the
+/// schema may have been applied.
+///
+pub fn apply_schema(
+    schema: &ConfigSchema,
+    pdict: &mut ParameterDictionary,
+    sdict: &mut SpectrumDictionary,
+    cdict: &mut ConditionDictionary,
+) -> Result<(), ConfigError> {
+    for p in schema.parameters.iter() {
+        if pdict.lookup(&p.name).is_none() {
+            pdict.add(&p.name).map_err(invalid)?;
+        }
+        let param = pdict.lookup_mut(&p.name).unwrap();
+        if let (Some(low), Some(high)) = (p.low, p.high) {
+            param.set_limits(low, high);
+        }
+        if let Some(bins) = p.bins {
+            param.set_bins(bins);
+        }
+        if let Some(units) = &p.units {
+            param.set_units(units);
+        }
+    }
+    for c in schema.conditions.iter() {
+        let condition = make_condition(c, pdict, cdict)?;
+        cdict.insert(c.name.clone(), condition);
+    }
+    for s in schema.spectra.iter() {
+        let spectrum = make_spectrum(s, pdict)?;
+        if let Some(gate) = &s.gate {
+            spectrum
+                .borrow_mut()
+                .gate(gate, cdict)
+                .map_err(|msg| invalid(format!("Spectrum {}: {}", s.name, msg)))?;
+        }
+        sdict.insert(s.name.clone(), spectrum);
+    }
+    Ok(())
+}
+
+// Describe a condition.  None if it can't be described (unknown
+// type, dependencies on parameters not in pdict or deleted conditions).
+
+fn condition_config(
+    name: &str,
+    pdict: &ParameterDictionary,
+    cdict: &ConditionDictionary,
+) -> Option<ConditionConfig> {
+    let condition = cdict.get(name)?.borrow();
+    let params = condition
+        .dependent_parameters()
+        .iter()
+        .map(|id| parameter_name(*id, pdict))
+        .collect::<Option<Vec<String>>>()?;
+    let points = condition.gate_points();
+    let mut result = ConditionConfig {
+        kind: String::new(),
+        name: String::from(name),
+        params,
+        low: None,
+        high: None,
+        tolerance: None,
+        points: vec![],
+        dependencies: vec![],
+    };
+    match condition.gate_type().as_str() {
+        "True" => result.kind = String::from("true"),
+        "False" => result.kind = String::from("false"),
+        "Cut" => {
+            result.kind = String::from("cut");
+            result.low = Some(points[0].0);
+            result.high = Some(points[1].0);
+            let tolerance = condition.gate_tolerance();
+            if tolerance != 0.0 {
+                result.tolerance = Some(tolerance);
+            }
+        }
+        twod @ ("Band" | "Contour" | "NotContour") => {
+            result.kind = twod.to_lowercase();
+            result.points = points;
+        }
+        compound @ ("Not" | "And" | "Or" | "Frozen") => {
+            let dependencies = dependency_names(name, cdict)?;
+            if dependencies.iter().any(|d| d == DELETED_CONDITION) {
+                return None;
+            }
+            result.kind = compound.to_lowercase();
+            result.dependencies = dependencies;
+        }
+        _ => return None,
+    }
+    Some(result)
+}
+
+// Log axes are described by their real limits:
+
+fn axis_config(a: &Option<AxisDescription>, log: &Option<AxisDescription>) -> Option<AxisConfig> {
+    log.as_ref().or(a.as_ref()).map(|a| AxisConfig {
+        low: a.low,
+        high: a.high,
+        bins: a.bins,
+        log: log.is_some(),
+    })
+}
+
+fn spectrum_config(spectrum: &SpectrumContainer) -> SpectrumConfig {
+    let mut d = spectrum.borrow().description();
+    let x = axis_config(&d.x_axis, &d.x_log);
+    let y = axis_config(&d.y_axis, &d.y_log);
+    let (kind, axes) = match d.kind {
+        SpectrumKind::Oned => ("1d", vec![x]),
+        SpectrumKind::Twod => ("2d", vec![x, y]),
+        SpectrumKind::Summary => ("summary", vec![y]),
+        SpectrumKind::Multi1D => ("multi1d", vec![x]),
+        SpectrumKind::Multi2D => ("multi2d", vec![x, y]),
+        SpectrumKind::TwodSum => ("2dsum", vec![x, y]),
+        SpectrumKind::PGamma => ("pgamma", vec![x, y]),
+    };
+    // 2d spectra list both parameters in parameters:
+
+    if d.kind == SpectrumKind::Twod {
+        let y = std::mem::take(&mut d.y_params);
+        d.x_params.extend(y);
+    }
+    SpectrumConfig {
+        kind: String::from(kind),
+        name: d.name,
+        parameters: d.x_params,
+        y_parameters: d.y_params,
+        axes: axes.into_iter().flatten().collect(),
+        gate: d.gate,
+    }
+}
+
+///
+/// Describe the current setup.  Parameters and spectra are in name
+/// order;  conditions are in dependency order.  Conditions that
+/// can't be described (e.g. they depend on deleted conditions) are
+/// left out.
+///
+pub fn capture_schema(
+    pdict: &ParameterDictionary,
+    sdict: &SpectrumDictionary,
+    cdict: &ConditionDictionary,
+) -> ConfigSchema {
+    let mut parameters: Vec<ParameterConfig> = pdict
+        .iter()
+        .map(|(name, p)| {
+            let (low, high) = p.get_limits();
+            ParameterConfig {
+                name: name.clone(),
+                low,
+                high,
+                bins: p.get_bins(),
+                units: p.get_units(),
+            }
+        })
+        .collect();
+    parameters.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let conditions = dependency_order(cdict)
+        .iter()
+        .filter_map(|name| condition_config(name, pdict, cdict))
+        .collect();
+
+    let mut names: Vec<&String> = sdict.keys().collect();
+    names.sort();
+    let spectra = names
+        .into_iter()
+        .map(|name| spectrum_config(&sdict[name]))
+        .collect();
+
+    ConfigSchema {
+        parameters,
+        conditions,
+        spectra,
+    }
+}
+
+// A non-trivial setup for the file format tests:
+
+#[cfg(test)]
+pub(crate) mod config_test_setup {
+    use super::*;
+
+    pub fn make_setup() -> (ParameterDictionary, SpectrumDictionary, ConditionDictionary) {
+        let mut pdict = ParameterDictionary::new();
+        for i in 0..4 {
+            let name = format!("x.{}", i);
+            pdict.add(&name).unwrap();
+            pdict
+                .lookup_mut(&name)
+                .unwrap()
+                .set_limits(0.0, 1024.0)
+                .set_bins(512)
+                .set_units("channels");
+        }
+        pdict.add("e").unwrap();
+        pdict.lookup_mut("e").unwrap().set_limits(-1.5, 10.0);
+
+        let text = "gate c1 s {x.0 {10 500.5}}\n\
+                    gate c2 s {x.1 {0 100}}\n\
+                    gate t T {}\n\
+                    gate band b {{x.0 x.1} {{0 10} {100 20} {200 5}}}\n\
+                    gate contour c {{x.2 x.3} {{0 0} {100 0} {50 100}}}\n\
+                    gate either + {c1 c2}\n\
+                    gate neither - {either}\n\
+                    gate all * {contour band t}\n";
+        let mut cdict = ConditionDictionary::new();
+        assert_eq!(Ok((8, vec![])), import_tcl(text, &pdict, &mut cdict));
+        let pts = vec![
+            Point::new(1.0, 1.0),
+            Point::new(2.0, 1.0),
+            Point::new(1.5, 2.0),
+        ];
+        cdict.insert(
+            String::from("outside"),
+            Rc::new(RefCell::new(NotContour::new(1, 2, pts).unwrap())),
+        );
+
+        let names: Vec<String> = (0..4).map(|i| format!("x.{}", i)).collect();
+        let spectra: Vec<SpectrumContainer> = vec![
+            Rc::new(RefCell::new(
+                Oned::new("oned", "e", &pdict, None, None, Some(100)).unwrap(),
+            )),
+            Rc::new(RefCell::new(
+                Twod::new(
+                    "twod",
+                    "x.0",
+                    "x.1",
+                    &pdict,
+                    None,
+                    None,
+                    None,
+                    Some(0.0),
+                    Some(10.0),
+                    None,
+                )
+                .unwrap(),
+            )),
+            Rc::new(RefCell::new(
+                Summary::new("summary", names.clone(), &pdict, None, None, Some(64)).unwrap(),
+            )),
+            Rc::new(RefCell::new(
+                Multi1d::new("m1", names.clone(), &pdict, None, None, None).unwrap(),
+            )),
+            Rc::new(RefCell::new(
+                Multi2d::new(
+                    "m2",
+                    names.clone(),
+                    &pdict,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .unwrap(),
+            )),
+            Rc::new(RefCell::new(
+                TwodSum::new(
+                    "sum",
+                    vec![
+                        (names[0].clone(), names[1].clone()),
+                        (names[2].clone(), names[3].clone()),
+                    ],
+                    &pdict,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .unwrap(),
+            )),
+            Rc::new(RefCell::new(
+                PGamma::new(
+                    "pgamma",
+                    &names[0..1].to_vec(),
+                    &names[1..].to_vec(),
+                    &pdict,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .unwrap(),
+            )),
+        ];
+        let mut sdict = SpectrumDictionary::new();
+        for (spec, gate) in spectra.into_iter().zip(
+            [
+                Some("c1"),
+                Some("all"),
+                None,
+                Some("neither"),
+                Some("outside"),
+                None,
+                Some("either"),
+            ]
+            .iter(),
+        ) {
+            if let Some(g) = gate {
+                spec.borrow_mut().gate(g, &cdict).unwrap();
+            }
+            let name = spec.borrow().get_name();
+            sdict.insert(name, spec);
+        }
+        (pdict, sdict, cdict)
+    }
+    // The setups describe the same things:
+    pub fn check_same(
+        pdict: &ParameterDictionary,
+        sdict: &SpectrumDictionary,
+        cdict: &ConditionDictionary,
+        pdict2: &ParameterDictionary,
+        sdict2: &SpectrumDictionary,
+        cdict2: &ConditionDictionary,
+    ) {
+        let original = capture_schema(pdict, sdict, cdict);
+        assert_eq!(5, original.parameters.len());
+        assert_eq!(9, original.conditions.len());
+        assert_eq!(7, original.spectra.len());
+        assert_eq!(original, capture_schema(pdict2, sdict2, cdict2));
+    }
+}
+
+#[cfg(test)]
+mod config_tests {
+    use super::*;
+
+    #[test]
+    fn apply_1() {
+        let schema = ConfigSchema {
+            parameters: vec![ParameterConfig {
+                name: String::from("x"),
+                low: Some(0.0),
+                high: Some(100.0),
+                bins: Some(100),
+                units: Some(String::from("MeV")),
+            }],
+            conditions: vec![ConditionConfig {
+                kind: String::from("cut"),
+                name: String::from("c"),
+                params: vec![String::from("x")],
+                low: Some(10.0),
+                high: Some(20.0),
+                tolerance: None,
+                points: vec![],
+                dependencies: vec![],
+            }],
+            spectra: vec![SpectrumConfig {
+                kind: String::from("1d"),
+                name: String::from("s"),
+                parameters: vec![String::from("x")],
+                y_parameters: vec![],
+                axes: vec![],
+                gate: Some(String::from("c")),
+            }],
+        };
+        let mut pdict = ParameterDictionary::new();
+        let mut sdict = SpectrumDictionary::new();
+        let mut cdict = ConditionDictionary::new();
+        apply_schema(&schema, &mut pdict, &mut sdict, &mut cdict).unwrap();
+
+        let x = pdict.lookup("x").unwrap();
+        assert_eq!((Some(0.0), Some(100.0)), x.get_limits());
+        assert_eq!(Some(String::from("MeV")), x.get_units());
+        assert_eq!("Cut", cdict.get("c").unwrap().borrow().gate_type());
+        let d = sdict.get("s").unwrap().borrow().description();
+        assert_eq!(Some(String::from("c")), d.gate);
+        assert_eq!(100, d.x_axis.unwrap().bins); // defaulted.
+
+        assert_eq!(
+            schema,
+            capture_schema(&pdict, &sdict, &cdict).with_axes_cleared()
+        );
+    }
+    #[test]
+    fn apply_2() {
+        // Errors:
+
+        let mut pdict = ParameterDictionary::new();
+        pdict.add("x").unwrap();
+        let mut sdict = SpectrumDictionary::new();
+        let mut cdict = ConditionDictionary::new();
+        let cut = ConditionConfig {
+            kind: String::from("cut"),
+            name: String::from("c"),
+            params: vec![String::from("nosuch")],
+            low: Some(10.0),
+            high: Some(20.0),
+            tolerance: None,
+            points: vec![],
+            dependencies: vec![],
+        };
+        let mut schema = ConfigSchema::default();
+        schema.conditions.push(cut.clone());
+        assert!(apply_schema(&schema, &mut pdict, &mut sdict, &mut cdict).is_err());
+
+        schema.conditions[0].params = vec![String::from("x")];
+        schema.conditions[0].high = None;
+        assert!(apply_schema(&schema, &mut pdict, &mut sdict, &mut cdict).is_err());
+
+        schema.conditions[0].kind = String::from("nosuch");
+        assert!(apply_schema(&schema, &mut pdict, &mut sdict, &mut cdict).is_err());
+
+        let mut schema = ConfigSchema::default();
+        schema.spectra.push(SpectrumConfig {
+            kind: String::from("1d"),
+            name: String::from("s"),
+            parameters: vec![String::from("x")],
+            y_parameters: vec![],
+            axes: vec![],
+            gate: None,
+        });
+        // x has no default axis:
+        assert!(apply_schema(&schema, &mut pdict, &mut sdict, &mut cdict).is_err());
+        schema.spectra[0].axes.push(AxisConfig {
+            low: 0.0,
+            high: 10.0,
+            bins: 10,
+            log: false,
+        });
+        schema.spectra[0].gate = Some(String::from("nosuch"));
+        assert!(apply_schema(&schema, &mut pdict, &mut sdict, &mut cdict).is_err());
+        schema.spectra[0].gate = None;
+        assert!(apply_schema(&schema, &mut pdict, &mut sdict, &mut cdict).is_ok());
+    }
+
+    #[test]
+    fn apply_3() {
+        // Frozen conditions are saved with the condition they wrap:
+
+        let mut pdict = ParameterDictionary::new();
+        pdict.add("x").unwrap();
+        let sdict = SpectrumDictionary::new();
+        let mut cdict = ConditionDictionary::new();
+        let cut: Container = Rc::new(RefCell::new(Cut::new(1, 10.0, 20.0)));
+        cdict.insert(String::from("c"), cut.clone());
+        cdict.insert(
+            String::from("f"),
+            Rc::new(RefCell::new(FrozenCondition::new(&cut))),
+        );
+
+        let schema = capture_schema(&pdict, &sdict, &cdict);
+        let frozen = schema.conditions.iter().find(|c| c.name == "f").unwrap();
+        assert_eq!("frozen", frozen.kind);
+        assert_eq!(vec![String::from("c")], frozen.dependencies);
+
+        let mut pdict = ParameterDictionary::new();
+        let mut sdict = SpectrumDictionary::new();
+        let mut cdict = ConditionDictionary::new();
+        apply_schema(&schema, &mut pdict, &mut sdict, &mut cdict).unwrap();
+        assert_eq!("Frozen", cdict.get("f").unwrap().borrow().gate_type());
+        assert_eq!(schema, capture_schema(&pdict, &sdict, &cdict));
+    }
+
+    #[test]
+    fn apply_4() {
+        // Cut tolerances survive a save and restore:
+
+        let mut pdict = ParameterDictionary::new();
+        pdict.add("x").unwrap();
+        let sdict = SpectrumDictionary::new();
+        let mut cdict = ConditionDictionary::new();
+        cdict.insert(
+            String::from("wide"),
+            Rc::new(RefCell::new(Cut::with_tolerance(1, 10.0, 20.0, 0.5))),
+        );
+        cdict.insert(
+            String::from("exact"),
+            Rc::new(RefCell::new(Cut::new(1, 10.0, 20.0))),
+        );
+
+        let schema = capture_schema(&pdict, &sdict, &cdict);
+        let tolerance = |name: &str| {
+            schema
+                .conditions
+                .iter()
+                .find(|c| c.name == name)
+                .unwrap()
+                .tolerance
+        };
+        assert_eq!(Some(0.5), tolerance("wide"));
+        assert_eq!(None, tolerance("exact"));
+
+        let mut pdict = ParameterDictionary::new();
+        let mut sdict = SpectrumDictionary::new();
+        let mut cdict = ConditionDictionary::new();
+        apply_schema(&schema, &mut pdict, &mut sdict, &mut cdict).unwrap();
+        assert_eq!(0.5, cdict.get("wide").unwrap().borrow().gate_tolerance());
+        assert_eq!(0.0, cdict.get("exact").unwrap().borrow().gate_tolerance());
+        assert_eq!(schema, capture_schema(&pdict, &sdict, &cdict));
+    }
+    #[test]
+    fn log_1() {
+        // Log axes are saved with their real limits and restored as
+        // log axes:
+
+        let mut pdict = ParameterDictionary::new();
+        pdict.add("e").unwrap();
+        pdict.add("t").unwrap();
+        let mut sdict = SpectrumDictionary::new();
+        let cdict = ConditionDictionary::new();
+        let spectra: Vec<SpectrumContainer> = vec![
+            Rc::new(RefCell::new(
+                Oned::new_log("log1", "e", &pdict, Some(1.0), Some(1000.0), Some(30)).unwrap(),
+            )),
+            Rc::new(RefCell::new(
+                Twod::new_log_x(
+                    "logx",
+                    "e",
+                    "t",
+                    &pdict,
+                    LogUniform::new(30, 1.0, 1000.0).unwrap(),
+                    Some(AxisDescription {
+                        low: 0.0,
+                        high: 100.0,
+                        bins: 100,
+                    }),
+                )
+                .unwrap(),
+            )),
+            Rc::new(RefCell::new(
+                Twod::new_log_y(
+                    "logy",
+                    "t",
+                    "e",
+                    &pdict,
+                    Some(AxisDescription {
+                        low: 0.0,
+                        high: 100.0,
+                        bins: 100,
+                    }),
+                    LogUniform::new(60, 10.0, 1.0e4).unwrap(),
+                )
+                .unwrap(),
+            )),
+        ];
+        for s in spectra {
+            let name = s.borrow().get_name();
+            sdict.insert(name, s);
+        }
+
+        let schema = capture_schema(&pdict, &sdict, &cdict);
+        let log = AxisConfig {
+            low: 1.0,
+            high: 1000.0,
+            bins: 30,
+            log: true,
+        };
+        let linear = AxisConfig {
+            low: 0.0,
+            high: 100.0,
+            bins: 100,
+            log: false,
+        };
+        assert_eq!(vec![log.clone()], schema.spectra[0].axes);
+        assert_eq!(vec![log, linear.clone()], schema.spectra[1].axes);
+        assert_eq!(
+            vec![
+                linear,
+                AxisConfig {
+                    low: 10.0,
+                    high: 1.0e4,
+                    bins: 60,
+                    log: true
+                }
+            ],
+            schema.spectra[2].axes
+        );
+
+        // Through YAML and back into spectra:
+
+        let text = serde_yaml::to_string(&schema).unwrap();
+        let loaded: ConfigSchema = serde_yaml::from_str(&text).unwrap();
+        assert_eq!(schema, loaded);
+        let mut pdict = ParameterDictionary::new();
+        let mut sdict = SpectrumDictionary::new();
+        let mut cdict = ConditionDictionary::new();
+        apply_schema(&loaded, &mut pdict, &mut sdict, &mut cdict).unwrap();
+        assert_eq!(schema, capture_schema(&pdict, &sdict, &cdict));
+        let spec = sdict.get("log1").unwrap().borrow();
+        let oned = spec.as_any().downcast_ref::<Oned>().unwrap();
+        assert_eq!(LogUniform::new(30, 1.0, 1000.0).ok(), oned.get_log_axis());
+    }
+    #[test]
+    fn log_2() {
+        // Log axes the spectrum type can't have:
+
+        let mut pdict = ParameterDictionary::new();
+        pdict.add("e").unwrap();
+        pdict.add("t").unwrap();
+        let log = AxisConfig {
+            low: 1.0,
+            high: 1000.0,
+            bins: 30,
+            log: true,
+        };
+        let mut schema = ConfigSchema {
+            parameters: vec![],
+            conditions: vec![],
+            spectra: vec![SpectrumConfig {
+                kind: String::from("2d"),
+                name: String::from("s"),
+                parameters: vec![String::from("e"), String::from("t")],
+                y_parameters: vec![],
+                axes: vec![log.clone(), log],
+                gate: None,
+            }],
+        };
+        let mut sdict = SpectrumDictionary::new();
+        let mut cdict = ConditionDictionary::new();
+        assert!(apply_schema(&schema, &mut pdict, &mut sdict, &mut cdict).is_err());
+        schema.spectra[0].kind = String::from("multi2d");
+        assert!(apply_schema(&schema, &mut pdict, &mut sdict, &mut cdict).is_err());
+        assert!(sdict.is_empty());
+    }
+
+    impl ConfigSchema {
+        // Defaulted axes come back in captures:
+        fn with_axes_cleared(mut self) -> ConfigSchema {
+            for s in self.spectra.iter_mut() {
+                s.axes.clear();
+            }
+            self
+        }
+    }
+}
@@ -0,0 +1,146 @@
+//!  TOML configuration files.  The schema is the same as for YAML
+//!  files, e.g.:
+//!
+//!  ```toml
+//!  [[parameters]]
+//!  name = "e"
+//!  low = 0.0
+//!  high = 4096.0
+//!  bins = 4096
+//!  units = "keV"
+//!
+//!  [[conditions]]
+//!  type = "cut"
+//!  name = "peak"
+//!  params = ["e"]
+//!  low = 1000.0
+//!  high = 1200.0
+//!
+//!  [[spectra]]
+//!  type = "1d"
+//!  name = "e.gated"
+//!  parameters = ["e"]
+//!  axes = [{low = 0.0, high = 4096.0, bins = 1024}]
+//!  gate = "peak"
+//!  ```
+//!
+use super::*;
+use std::path::Path;
+
+///
+/// Apply the TOML configuration in a file to the dictionaries.  See
+/// apply_schema for how the configuration is applied.
+///
+pub fn load_toml(
+    path: &Path,
+    pdict: &mut ParameterDictionary,
+    sdict: &mut SpectrumDictionary,
+    cdict: &mut ConditionDictionary,
+) -> Result<(), ConfigError> {
+    let text = std::fs::read_to_string(path).map_err(|e| ConfigError::Io(e.to_string()))?;
+    let schema: ConfigSchema =
+        ::toml::from_str(&text).map_err(|e| ConfigError::Parse(e.to_string()))?;
+    apply_schema(&schema, pdict, sdict, cdict)
+}
+
+///
+/// Save the current setup as a TOML configuration file.
+///
+pub fn save_toml(
+    path: &Path,
+    pdict: &ParameterDictionary,
+    sdict: &SpectrumDictionary,
+    cdict: &ConditionDictionary,
+) -> Result<(), ConfigError> {
+    let text = ::toml::to_string(&capture_schema(pdict, sdict, cdict))
+        .map_err(|e| ConfigError::Parse(e.to_string()))?;
+    std::fs::write(path, text).map_err(|e| ConfigError::Io(e.to_string()))
+}
+
+#[cfg(test)]
+mod toml_tests {
+    use super::*;
+    use crate::config::config_test_setup::*;
+
+    #[test]
+    fn round_trip_1() {
+        let (pdict, sdict, cdict) = make_setup();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("setup.toml");
+        save_toml(&path, &pdict, &sdict, &cdict).unwrap();
+
+        let mut pdict2 = ParameterDictionary::new();
+        let mut sdict2 = SpectrumDictionary::new();
+        let mut cdict2 = ConditionDictionary::new();
+        load_toml(&path, &mut pdict2, &mut sdict2, &mut cdict2).unwrap();
+        check_same(&pdict, &sdict, &cdict, &pdict2, &sdict2, &cdict2);
+    }
+    #[test]
+    fn edit_1() {
+        // Hand edits to a saved file take effect when it's reloaded:
+
+        let (pdict, sdict, cdict) = make_setup();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("setup.toml");
+        save_toml(&path, &pdict, &sdict, &cdict).unwrap();
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(1, text.matches("high = 500.5").count());
+        assert_eq!(1, text.matches("bins = 100\n").count());
+        let text = text
+            .replace("high = 500.5", "high = 300.0")
+            .replace("bins = 100\n", "bins = 250\n")
+            .replace("gate = \"c1\"", "gate = \"c2\"");
+        std::fs::write(&path, text).unwrap();
+
+        let mut pdict2 = ParameterDictionary::new();
+        let mut sdict2 = SpectrumDictionary::new();
+        let mut cdict2 = ConditionDictionary::new();
+        load_toml(&path, &mut pdict2, &mut sdict2, &mut cdict2).unwrap();
+
+        let c1 = cdict2.get("c1").unwrap().borrow();
+        assert_eq!(vec![(10.0, 0.0), (300.0, 0.0)], c1.gate_points());
+
+        let d = sdict2.get("oned").unwrap().borrow().description();
+        assert_eq!(250, d.x_axis.unwrap().bins);
+        assert_eq!(Some(String::from("c2")), d.gate);
+
+        // Everything else is unchanged:
+
+        let mut original = capture_schema(&pdict, &sdict, &cdict);
+        let mut edited = capture_schema(&pdict2, &sdict2, &cdict2);
+        original.conditions.retain(|c| c.name != "c1");
+        edited.conditions.retain(|c| c.name != "c1");
+        original.spectra.retain(|s| s.name != "oned");
+        edited.spectra.retain(|s| s.name != "oned");
+        assert_eq!(original, edited);
+    }
+    #[test]
+    fn load_1() {
+        // Errors:
+
+        let mut pdict = ParameterDictionary::new();
+        let mut sdict = SpectrumDictionary::new();
+        let mut cdict = ConditionDictionary::new();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("setup.toml");
+        assert!(matches!(
+            load_toml(&path, &mut pdict, &mut sdict, &mut cdict),
+            Err(ConfigError::Io(_))
+        ));
+        std::fs::write(&path, "[[parameters]]\nname = 1\n").unwrap();
+        assert!(matches!(
+            load_toml(&path, &mut pdict, &mut sdict, &mut cdict),
+            Err(ConfigError::Parse(_))
+        ));
+        std::fs::write(
+            &path,
+            "[[conditions]]\ntype = \"not\"\nname = \"n\"\ndependencies = [\"x\"]\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            load_toml(&path, &mut pdict, &mut sdict, &mut cdict),
+            Err(ConfigError::Invalid(_))
+        ));
+    }
+}
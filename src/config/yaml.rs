@@ -0,0 +1,141 @@
+//!  YAML configuration files.  A YAML configuration looks like:
+//!
+//!  ```yaml
+//!  parameters:
+//!    - name: e
+//!      low: 0.0
+//!      high: 4096.0
+//!      bins: 4096
+//!      units: keV
+//!  conditions:
+//!    - type: cut
+//!      name: peak
+//!      params: [e]
+//!      low: 1000.0
+//!      high: 1200.0
+//!  spectra:
+//!    - type: 1d
+//!      name: e.gated
+//!      parameters: [e]
+//!      axes:
+//!        - {low: 0.0, high: 4096.0, bins: 1024}
+//!      gate: peak
+//!  ```
+//!
+use super::*;
+use std::path::Path;
+
+///
+/// Apply the YAML configuration in a file to the dictionaries.  See
+/// apply_schema for how the configuration is applied.
+///
+pub fn load_yaml(
+    path: &Path,
+    pdict: &mut ParameterDictionary,
+    sdict: &mut SpectrumDictionary,
+    cdict: &mut ConditionDictionary,
+) -> Result<(), ConfigError> {
+    let text = std::fs::read_to_string(path).map_err(|e| ConfigError::Io(e.to_string()))?;
+    let schema: ConfigSchema =
+        serde_yaml::from_str(&text).map_err(|e| ConfigError::Parse(e.to_string()))?;
+    apply_schema(&schema, pdict, sdict, cdict)
+}
+
+///
+/// Save the current setup as a YAML configuration file.
+///
+pub fn save_yaml(
+    path: &Path,
+    pdict: &ParameterDictionary,
+    sdict: &SpectrumDictionary,
+    cdict: &ConditionDictionary,
+) -> Result<(), ConfigError> {
+    let text = serde_yaml::to_string(&capture_schema(pdict, sdict, cdict))
+        .map_err(|e| ConfigError::Parse(e.to_string()))?;
+    std::fs::write(path, text).map_err(|e| ConfigError::Io(e.to_string()))
+}
+
+#[cfg(test)]
+mod yaml_tests {
+    use super::*;
+    use crate::config::config_test_setup::*;
+
+    #[test]
+    fn round_trip_1() {
+        let (pdict, sdict, cdict) = make_setup();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("setup.yaml");
+        save_yaml(&path, &pdict, &sdict, &cdict).unwrap();
+
+        let mut pdict2 = ParameterDictionary::new();
+        let mut sdict2 = SpectrumDictionary::new();
+        let mut cdict2 = ConditionDictionary::new();
+        load_yaml(&path, &mut pdict2, &mut sdict2, &mut cdict2).unwrap();
+        check_same(&pdict, &sdict, &cdict, &pdict2, &sdict2, &cdict2);
+    }
+    #[test]
+    fn load_1() {
+        // The example in the module documentation:
+
+        let text = "parameters:\n\
+                    \x20 - name: e\n\
+                    \x20   low: 0.0\n\
+                    \x20   high: 4096.0\n\
+                    \x20   bins: 4096\n\
+                    \x20   units: keV\n\
+                    conditions:\n\
+                    \x20 - type: cut\n\
+                    \x20   name: peak\n\
+                    \x20   params: [e]\n\
+                    \x20   low: 1000.0\n\
+                    \x20   high: 1200.0\n\
+                    spectra:\n\
+                    \x20 - type: 1d\n\
+                    \x20   name: e.gated\n\
+                    \x20   parameters: [e]\n\
+                    \x20   axes:\n\
+                    \x20     - {low: 0.0, high: 4096.0, bins: 1024}\n\
+                    \x20   gate: peak\n";
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("setup.yaml");
+        std::fs::write(&path, text).unwrap();
+
+        let mut pdict = ParameterDictionary::new();
+        let mut sdict = SpectrumDictionary::new();
+        let mut cdict = ConditionDictionary::new();
+        load_yaml(&path, &mut pdict, &mut sdict, &mut cdict).unwrap();
+        assert_eq!(Some(4096), pdict.lookup("e").unwrap().get_bins());
+        assert!(cdict.contains_key("peak"));
+        let d = sdict.get("e.gated").unwrap().borrow().description();
+        assert_eq!(1024, d.x_axis.unwrap().bins);
+        assert_eq!(Some(String::from("peak")), d.gate);
+    }
+    #[test]
+    fn load_2() {
+        // Errors:
+
+        let mut pdict = ParameterDictionary::new();
+        let mut sdict = SpectrumDictionary::new();
+        let mut cdict = ConditionDictionary::new();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("setup.yaml");
+        assert!(matches!(
+            load_yaml(&path, &mut pdict, &mut sdict, &mut cdict),
+            Err(ConfigError::Io(_))
+        ));
+        std::fs::write(&path, "parameters: 3\n").unwrap();
+        assert!(matches!(
+            load_yaml(&path, &mut pdict, &mut sdict, &mut cdict),
+            Err(ConfigError::Parse(_))
+        ));
+        std::fs::write(
+            &path,
+            "spectra:\n  - {type: 1d, name: s, parameters: [x]}\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            load_yaml(&path, &mut pdict, &mut sdict, &mut cdict),
+            Err(ConfigError::Invalid(_))
+        ));
+    }
+}
@@ -0,0 +1,280 @@
+//!  Make spectra from CSV data, e.g. reference spectra from
+//!  calibration measurements exported by a spreadsheet.  The data
+//!  must have a header row.  Rows give the low edge of a channel
+//!  (one coordinate per axis) and the counts in that channel.  The
+//!  distinct coordinates of each axis must be uniformly spaced and the
+//!  axis is built from that spacing:  from the smallest coordinate to
+//!  one spacing past the largest.  For 2-d data, channels with no
+//!  counts may be omitted as long as every coordinate appears in some
+//!  row.  Columns are counted from zero.
+//!
+//!  As with restored .spe files, the spectrum's parameters (named
+//!  from the header of the coordinate columns) are in a dictionary of
+//!  their own.
+//!
+use crate::parameters::ParameterDictionary;
+use crate::spectra::{AxisDescription, Oned, Spectrum, Twod};
+use std::io::Read;
+
+// Relative tolerance on the channel spacing:
+
+const SPACING_TOLERANCE: f64 = 1.0e-6;
+
+// The header and the rows of the selected columns as numbers:
+
+fn read_columns<R: Read>(
+    reader: R,
+    columns: &[usize],
+) -> Result<(Vec<String>, Vec<Vec<f64>>), String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+    let header = reader.headers().map_err(|e| e.to_string())?.clone();
+    let names = columns
+        .iter()
+        .map(|c| {
+            header
+                .get(*c)
+                .map(String::from)
+                .ok_or_else(|| format!("There is no column {}", c))
+        })
+        .collect::<Result<Vec<String>, String>>()?;
+
+    let mut rows = Vec::new();
+    for (i, record) in reader.records().enumerate() {
+        let record = record.map_err(|e| e.to_string())?;
+        let row = columns
+            .iter()
+            .map(|c| {
+                let field = record.get(*c).unwrap_or("");
+                field
+                    .parse::<f64>()
+                    .map_err(|_| format!("Row {} column {}: '{}' is not a number", i + 1, c, field))
+            })
+            .collect::<Result<Vec<f64>, String>>()?;
+        rows.push(row);
+    }
+    Ok((names, rows))
+}
+
+// The axis a set of uniformly spaced channel coordinates is on:
+
+fn uniform_axis(
+    name: &str,
+    coordinates: impl Iterator<Item = f64>,
+) -> Result<AxisDescription, String> {
+    let mut values: Vec<f64> = coordinates.collect();
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    values.dedup();
+    if values.len() < 2 {
+        return Err(format!(
+            "{} needs at least two channels to determine the spacing",
+            name
+        ));
+    }
+    let low = values[0];
+    let bins = values.len() as u32;
+    let spacing = (values[values.len() - 1] - low) / (bins - 1) as f64;
+    if values
+        .windows(2)
+        .any(|w| ((w[1] - w[0]) - spacing).abs() > SPACING_TOLERANCE * spacing)
+    {
+        return Err(format!("{} values are not uniformly spaced", name));
+    }
+    Ok(AxisDescription {
+        low,
+        high: low + bins as f64 * spacing,
+        bins,
+    })
+}
+
+fn check_counts(rows: &[Vec<f64>]) -> Result<(), String> {
+    if let Some(i) = rows.iter().position(|r| !r.iter().all(|v| v.is_finite())) {
+        Err(format!("Row {} has a value that is not finite", i + 1))
+    } else {
+        Ok(())
+    }
+}
+
+///
+/// Make a Oned from the x_col and count_col columns of CSV data.
+///
+pub fn oned_from_csv<R: Read>(
+    reader: R,
+    name: &str,
+    x_col: usize,
+    count_col: usize,
+) -> Result<Oned, String> {
+    let (names, rows) = read_columns(reader, &[x_col, count_col])?;
+    check_counts(&rows)?;
+    let axis = uniform_axis(&names[0], rows.iter().map(|r| r[0]))?;
+
+    let mut pdict = ParameterDictionary::new();
+    pdict.add(&names[0])?;
+    let mut result = Oned::new(
+        name,
+        &names[0],
+        &pdict,
+        Some(axis.low),
+        Some(axis.high),
+        Some(axis.bins),
+    )?;
+    let half_bin = (axis.high - axis.low) / axis.bins as f64 / 2.0;
+    for r in rows {
+        result.fill_channel_1d(r[0] + half_bin, r[1]);
+    }
+    Ok(result)
+}
+
+///
+/// Make a Twod from the x_col, y_col and count_col columns of CSV
+/// data.  The x and y column headers must differ.
+///
+pub fn twod_from_csv<R: Read>(
+    reader: R,
+    name: &str,
+    x_col: usize,
+    y_col: usize,
+    count_col: usize,
+) -> Result<Twod, String> {
+    let (names, rows) = read_columns(reader, &[x_col, y_col, count_col])?;
+    check_counts(&rows)?;
+    let x_axis = uniform_axis(&names[0], rows.iter().map(|r| r[0]))?;
+    let y_axis = uniform_axis(&names[1], rows.iter().map(|r| r[1]))?;
+
+    let mut pdict = ParameterDictionary::new();
+    pdict.add(&names[0])?;
+    pdict.add(&names[1])?;
+    let mut result = Twod::new(
+        name,
+        &names[0],
+        &names[1],
+        &pdict,
+        Some(x_axis.low),
+        Some(x_axis.high),
+        Some(x_axis.bins),
+        Some(y_axis.low),
+        Some(y_axis.high),
+        Some(y_axis.bins),
+    )?;
+    let x_half = (x_axis.high - x_axis.low) / x_axis.bins as f64 / 2.0;
+    let y_half = (y_axis.high - y_axis.low) / y_axis.bins as f64 / 2.0;
+    for r in rows {
+        result.fill_channel_2d(r[0] + x_half, r[1] + y_half, r[2]);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod csv_import_tests {
+    use super::*;
+    use ndhistogram::Histogram;
+    use std::io::Cursor;
+
+    fn value_1d(spec: &dyn Spectrum, x: f64) -> f64 {
+        let h = spec.get_histogram_1d().unwrap();
+        let h = h.borrow();
+        h.value(&x).unwrap().get()
+    }
+    fn value_2d(spec: &dyn Spectrum, x: f64, y: f64) -> f64 {
+        let h = spec.get_histogram_2d().unwrap();
+        let h = h.borrow();
+        h.value(&(x, y)).unwrap().get()
+    }
+
+    #[test]
+    fn oned_1() {
+        let data = "energy, counts\n0.0, 5\n0.5, 10\n1.0, 20\n1.5, 10\n";
+        let spec = oned_from_csv(Cursor::new(data.as_bytes()), "ref", 0, 1).unwrap();
+        assert_eq!("ref", spec.get_name());
+        assert_eq!(vec![String::from("energy")], spec.description().x_params);
+        let axis = spec.x_axis();
+        assert_eq!(0.0, axis.low);
+        assert_eq!(2.0, axis.high);
+        assert_eq!(4, axis.bins);
+        assert_eq!(5.0, value_1d(&spec, 0.25));
+        assert_eq!(10.0, value_1d(&spec, 0.75));
+        assert_eq!(20.0, value_1d(&spec, 1.25));
+        assert_eq!(10.0, value_1d(&spec, 1.75));
+    }
+    #[test]
+    fn oned_2() {
+        // Other columns and unordered rows:
+
+        let data = "name,counts,channel\na,7,30\nb,3,10\nc,1,0\nd,0,20\n";
+        let spec = oned_from_csv(Cursor::new(data.as_bytes()), "ref", 2, 1).unwrap();
+        let axis = spec.x_axis();
+        assert_eq!(0.0, axis.low);
+        assert_eq!(40.0, axis.high);
+        assert_eq!(4, axis.bins);
+        assert_eq!(1.0, value_1d(&spec, 5.0));
+        assert_eq!(3.0, value_1d(&spec, 15.0));
+        assert_eq!(0.0, value_1d(&spec, 25.0));
+        assert_eq!(7.0, value_1d(&spec, 35.0));
+    }
+    #[test]
+    fn oned_3() {
+        // Errors:
+
+        let bad = [
+            "x,counts\n0,1\n1,1\n3,1\n4,1\n", // Not uniform
+            "x,counts\n0,1\n",                // Spacing unknown
+            "x,counts\n0,1\n1,one\n",         // Not a number
+            "x,counts\n0,1\n1,inf\n",         // Not finite
+            "x\n0\n1\n",                      // No count column
+        ];
+        for data in bad {
+            assert!(
+                oned_from_csv(Cursor::new(data.as_bytes()), "ref", 0, 1).is_err(),
+                "{}",
+                data
+            );
+        }
+    }
+    #[test]
+    fn twod_1() {
+        let data = "x,y,counts\n0,0,1\n10,0,2\n0,5,3\n20,10,4\n";
+        let spec = twod_from_csv(Cursor::new(data.as_bytes()), "ref2", 0, 1, 2).unwrap();
+        let d = spec.description();
+        assert_eq!(vec![String::from("x")], d.x_params);
+        assert_eq!(vec![String::from("y")], d.y_params);
+        assert_eq!(
+            AxisDescription {
+                low: 0.0,
+                high: 30.0,
+                bins: 3
+            },
+            spec.x_axis()
+        );
+        assert_eq!(
+            Some(AxisDescription {
+                low: 0.0,
+                high: 15.0,
+                bins: 3
+            }),
+            spec.y_axis()
+        );
+        assert_eq!(1.0, value_2d(&spec, 5.0, 2.5));
+        assert_eq!(2.0, value_2d(&spec, 15.0, 2.5));
+        assert_eq!(3.0, value_2d(&spec, 5.0, 7.5));
+        assert_eq!(4.0, value_2d(&spec, 25.0, 12.5));
+        assert_eq!(0.0, value_2d(&spec, 25.0, 2.5));
+    }
+    #[test]
+    fn twod_2() {
+        // Errors:
+
+        let bad = [
+            "x,y,counts\n0,0,1\n1,0,1\n3,1,1\n", // x not uniform
+            "x,y,counts\n0,0,1\n1,0,1\n",        // y spacing unknown
+            "x,x,counts\n0,0,1\n1,1,1\n",        // Same parameter name
+        ];
+        for data in bad {
+            assert!(
+                twod_from_csv(Cursor::new(data.as_bytes()), "ref", 0, 1, 2).is_err(),
+                "{}",
+                data
+            );
+        }
+    }
+}
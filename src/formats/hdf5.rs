@@ -0,0 +1,421 @@
+//!  Save and restore whole spectrum dictionaries in HDF5 files so they
+//!  can be analyzed with the usual scientific tools (h5py, MATLAB...).
+//!  Built only with the hdf5 feature which needs the system HDF5
+//!  library.
+//!
+//!  Each spectrum is a group at the top of the file named after the
+//!  spectrum.  The group attributes describe the spectrum:
+//!
+//!  *  kind - "Oned", "Twod" or "Summary".
+//!  *  x_params, y_params - the parameter names (y_params only for Twod).
+//!  *  x_low, x_high, x_bins, y_low, y_high, y_bins - the axes
+//!     (y only for 2-d spectra).
+//!
+//!  The channels are in the group's "channels" dataset.  For 1-d
+//!  spectra that's every channel value, underflow first and overflow
+//!  last.  2-d spectra are usually sparse so their dataset is a
+//!  compound of the x and y bin numbers and value of each channel
+//!  that has counts.  Bin 0 is the underflow and bins + 1 the overflow.
+//!
+//!  Gates are not saved.  Restored spectra use the parameters of the
+//!  dictionary passed to load_all which must define all of the
+//!  parameters the saved spectra need.
+//!
+use crate::parameters::ParameterDictionary;
+use crate::spectra::{
+    AxisDescription, Oned, Spectrum, SpectrumContainer, SpectrumDictionary, SpectrumKind, Summary,
+    Twod,
+};
+use hdf5::types::VarLenUnicode;
+use hdf5::{File, Group, H5Type};
+use ndhistogram::Histogram;
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+
+const CHANNELS: &str = "channels";
+
+/// One channel of a 2-d spectrum in the channels dataset.
+#[derive(H5Type, Clone, Copy, Debug, PartialEq)]
+#[repr(C)]
+pub struct Channel2d {
+    pub x_bin: u32,
+    pub y_bin: u32,
+    pub value: f64,
+}
+
+// A coordinate in bin number bin of an axis:
+
+fn bin_coordinate(axis: &AxisDescription, bin: u32) -> f64 {
+    if bin == 0 {
+        f64::NEG_INFINITY
+    } else if bin > axis.bins {
+        axis.high
+    } else {
+        let width = (axis.high - axis.low) / axis.bins as f64;
+        axis.low + (bin as f64 - 0.5) * width
+    }
+}
+
+fn kind_name(kind: SpectrumKind) -> Option<&'static str> {
+    match kind {
+        SpectrumKind::Oned => Some("Oned"),
+        SpectrumKind::Twod => Some("Twod"),
+        SpectrumKind::Summary => Some("Summary"),
+        _ => None,
+    }
+}
+
+fn unicode(s: &str) -> hdf5::Result<VarLenUnicode> {
+    s.parse::<VarLenUnicode>()
+        .map_err(|e| hdf5::Error::from(e.to_string()))
+}
+
+fn write_string(group: &Group, name: &str, value: &str) -> hdf5::Result<()> {
+    group
+        .new_attr::<VarLenUnicode>()
+        .create(name)?
+        .write_scalar(&unicode(value)?)
+}
+
+fn write_strings(group: &Group, name: &str, values: &[String]) -> hdf5::Result<()> {
+    let values = values
+        .iter()
+        .map(|s| unicode(s))
+        .collect::<hdf5::Result<Vec<VarLenUnicode>>>()?;
+    group
+        .new_attr_builder()
+        .with_data(values.as_slice())
+        .create(name)?;
+    Ok(())
+}
+
+fn write_axis(group: &Group, prefix: &str, axis: &AxisDescription) -> hdf5::Result<()> {
+    group
+        .new_attr::<f64>()
+        .create(format!("{}_low", prefix).as_str())?
+        .write_scalar(&axis.low)?;
+    group
+        .new_attr::<f64>()
+        .create(format!("{}_high", prefix).as_str())?
+        .write_scalar(&axis.high)?;
+    group
+        .new_attr::<u32>()
+        .create(format!("{}_bins", prefix).as_str())?
+        .write_scalar(&axis.bins)
+}
+
+fn read_string(group: &Group, name: &str) -> hdf5::Result<String> {
+    Ok(group
+        .attr(name)?
+        .read_scalar::<VarLenUnicode>()?
+        .as_str()
+        .to_string())
+}
+
+fn read_strings(group: &Group, name: &str) -> hdf5::Result<Vec<String>> {
+    Ok(group
+        .attr(name)?
+        .read_raw::<VarLenUnicode>()?
+        .iter()
+        .map(|s| s.as_str().to_string())
+        .collect())
+}
+
+fn read_axis(group: &Group, prefix: &str) -> hdf5::Result<AxisDescription> {
+    Ok(AxisDescription {
+        low: group
+            .attr(format!("{}_low", prefix).as_str())?
+            .read_scalar()?,
+        high: group
+            .attr(format!("{}_high", prefix).as_str())?
+            .read_scalar()?,
+        bins: group
+            .attr(format!("{}_bins", prefix).as_str())?
+            .read_scalar()?,
+    })
+}
+
+fn save_spectrum(file: &File, spectrum: &dyn Spectrum) -> hdf5::Result<()> {
+    let description = spectrum.description();
+    let kind = kind_name(description.kind).unwrap();
+    let group = file.create_group(&description.name)?;
+
+    write_string(&group, "kind", kind)?;
+    write_strings(&group, "x_params", &description.x_params)?;
+    if description.kind == SpectrumKind::Twod {
+        write_strings(&group, "y_params", &description.y_params)?;
+    }
+    write_axis(&group, "x", &spectrum.x_axis())?;
+
+    if let Some(h) = spectrum.get_histogram_1d() {
+        let values: Vec<f64> = h.borrow().values().map(|v| v.get()).collect();
+        group
+            .new_dataset_builder()
+            .with_data(values.as_slice())
+            .create(CHANNELS)?;
+    } else if let Some(h) = spectrum.get_histogram_2d() {
+        let y_axis = spectrum.y_axis().unwrap();
+        write_axis(&group, "y", &y_axis)?;
+
+        // ndhistogram's index runs fastest in x, flow bins included:
+
+        let x_size = spectrum.x_axis().bins as usize + 2;
+        let channels: Vec<Channel2d> = h
+            .borrow()
+            .values()
+            .enumerate()
+            .filter(|(_, v)| v.get() != 0.0)
+            .map(|(i, v)| Channel2d {
+                x_bin: (i % x_size) as u32,
+                y_bin: (i / x_size) as u32,
+                value: v.get(),
+            })
+            .collect();
+        group
+            .new_dataset_builder()
+            .with_data(channels.as_slice())
+            .create(CHANNELS)?;
+    }
+    Ok(())
+}
+
+fn load_spectrum(
+    group: &Group,
+    name: &str,
+    pdict: &ParameterDictionary,
+) -> hdf5::Result<SpectrumContainer> {
+    let kind = read_string(group, "kind")?;
+    let x_params = read_strings(group, "x_params")?;
+    let x_axis = read_axis(group, "x")?;
+    let channels = group.dataset(CHANNELS)?;
+
+    if kind == "Oned" {
+        if x_params.len() != 1 {
+            return Err(format!("{} must have one parameter", name).into());
+        }
+        let mut spectrum = Oned::new(
+            name,
+            &x_params[0],
+            pdict,
+            Some(x_axis.low),
+            Some(x_axis.high),
+            Some(x_axis.bins),
+        )?;
+        let values = channels.read_raw::<f64>()?;
+        if values.len() != x_axis.bins as usize + 2 {
+            return Err(format!("{} has the wrong number of channels", name).into());
+        }
+        for (bin, value) in values.into_iter().enumerate() {
+            if value != 0.0 {
+                spectrum.fill_channel_1d(bin_coordinate(&x_axis, bin as u32), value);
+            }
+        }
+        return Ok(Rc::new(RefCell::new(spectrum)));
+    }
+
+    let y_axis = read_axis(group, "y")?;
+    let spectrum: SpectrumContainer = match kind.as_str() {
+        "Twod" => {
+            let y_params = read_strings(group, "y_params")?;
+            if x_params.len() != 1 || y_params.len() != 1 {
+                return Err(format!("{} must have one x and one y parameter", name).into());
+            }
+            Rc::new(RefCell::new(Twod::new(
+                name,
+                &x_params[0],
+                &y_params[0],
+                pdict,
+                Some(x_axis.low),
+                Some(x_axis.high),
+                Some(x_axis.bins),
+                Some(y_axis.low),
+                Some(y_axis.high),
+                Some(y_axis.bins),
+            )?))
+        }
+        "Summary" => Rc::new(RefCell::new(Summary::new(
+            name,
+            x_params,
+            pdict,
+            Some(y_axis.low),
+            Some(y_axis.high),
+            Some(y_axis.bins),
+        )?)),
+        _ => return Err(format!("{} has unsupported spectrum kind {}", name, kind).into()),
+    };
+    // The x axis of a summary comes from its parameters:
+
+    let x_axis = spectrum.borrow().x_axis();
+    for c in channels.read_raw::<Channel2d>()? {
+        if c.x_bin > x_axis.bins + 1 || c.y_bin > y_axis.bins + 1 {
+            return Err(format!("{} has a channel outside its axes", name).into());
+        }
+        spectrum.borrow_mut().fill_channel_2d(
+            bin_coordinate(&x_axis, c.x_bin),
+            bin_coordinate(&y_axis, c.y_bin),
+            c.value,
+        );
+    }
+    Ok(spectrum)
+}
+
+///
+/// Save the Oned, Twod and Summary spectra of a dictionary to a new
+/// HDF5 file.  Nothing is written if the dictionary has spectra of
+/// other kinds or with names that can't be group names.
+///
+pub fn save_all(sdict: &SpectrumDictionary, path: &Path) -> hdf5::Result<()> {
+    let mut names: Vec<&String> = sdict.keys().collect();
+    names.sort();
+    for name in &names {
+        let kind = sdict[*name].borrow().description().kind;
+        if kind_name(kind).is_none() {
+            return Err(format!("{} is a {:?} spectrum which can't be saved", name, kind).into());
+        }
+        if name.is_empty() || name.contains('/') || name.as_str() == "." {
+            return Err(format!("'{}' can't be an HDF5 group name", name).into());
+        }
+    }
+    let file = File::create(path)?;
+    for name in names {
+        save_spectrum(&file, &*sdict[name].borrow())?;
+    }
+    Ok(())
+}
+
+///
+/// Restore the spectra saved by save_all.
+///
+pub fn load_all(path: &Path, pdict: &ParameterDictionary) -> hdf5::Result<SpectrumDictionary> {
+    let file = File::open(path)?;
+    let mut result = SpectrumDictionary::new();
+    for name in file.member_names()? {
+        let spectrum = load_spectrum(&file.group(&name)?, &name, pdict)?;
+        result.insert(name, spectrum);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod hdf5_tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn pdict() -> ParameterDictionary {
+        let mut pdict = ParameterDictionary::new();
+        for name in ["p1", "p2", "p3"] {
+            pdict.add(name).unwrap();
+            pdict.lookup_mut(name).unwrap().set_limits(-10.0, 10.0);
+        }
+        pdict
+    }
+    fn values(spectrum: &SpectrumContainer) -> Vec<f64> {
+        let s = spectrum.borrow();
+        if let Some(h) = s.get_histogram_1d() {
+            let h = h.borrow();
+            h.values().map(|v| v.get()).collect()
+        } else {
+            let h = s.get_histogram_2d().unwrap();
+            let h = h.borrow();
+            h.values().map(|v| v.get()).collect()
+        }
+    }
+    fn dict(pdict: &ParameterDictionary) -> SpectrumDictionary {
+        let mut oned = Oned::new("oned", "p1", pdict, None, None, Some(20)).unwrap();
+        let mut twod = Twod::new(
+            "twod",
+            "p1",
+            "p2",
+            pdict,
+            Some(-5.0),
+            Some(5.0),
+            Some(10),
+            None,
+            None,
+            Some(8),
+        )
+        .unwrap();
+        let mut summary = Summary::new(
+            "summary",
+            vec![String::from("p1"), String::from("p2"), String::from("p3")],
+            pdict,
+            None,
+            None,
+            Some(16),
+        )
+        .unwrap();
+        for i in 0..25 {
+            let x = -12.0 + i as f64;
+            oned.fill_channel_1d(x, i as f64 + 1.0);
+            twod.fill_channel_2d(x, x / 2.0, 2.0 * i as f64 + 0.5);
+            summary.fill_channel_2d((i % 3) as f64 + 0.5, x, i as f64);
+        }
+        let mut sdict = SpectrumDictionary::new();
+        sdict.insert(String::from("oned"), Rc::new(RefCell::new(oned)));
+        sdict.insert(String::from("twod"), Rc::new(RefCell::new(twod)));
+        sdict.insert(String::from("summary"), Rc::new(RefCell::new(summary)));
+        sdict
+    }
+
+    #[test]
+    fn roundtrip_1() {
+        let pdict = pdict();
+        let sdict = dict(&pdict);
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("spectra.h5");
+        save_all(&sdict, &path).unwrap();
+
+        let restored = load_all(&path, &pdict).unwrap();
+        assert_eq!(sdict.len(), restored.len());
+        for (name, spectrum) in &sdict {
+            let copy = restored.get(name).unwrap();
+            assert_eq!(spectrum.borrow().description(), copy.borrow().description());
+            assert_eq!(values(spectrum), values(copy), "{}", name);
+        }
+    }
+    #[test]
+    fn save_1() {
+        // Spectrum kinds that can't be saved:
+
+        let pdict = pdict();
+        let mut sdict = dict(&pdict);
+        let x = vec![String::from("p1")];
+        let y = vec![String::from("p2")];
+        sdict.insert(
+            String::from("pgamma"),
+            Rc::new(RefCell::new(
+                crate::spectra::PGamma::new(
+                    "pgamma",
+                    &x,
+                    &y,
+                    &pdict,
+                    None,
+                    None,
+                    Some(10),
+                    None,
+                    None,
+                    Some(10),
+                )
+                .unwrap(),
+            )),
+        );
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("spectra.h5");
+        assert!(save_all(&sdict, &path).is_err());
+        assert!(!path.exists());
+    }
+    #[test]
+    fn load_1() {
+        // Parameters must be defined:
+
+        let pdict = pdict();
+        let sdict = dict(&pdict);
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("spectra.h5");
+        save_all(&sdict, &path).unwrap();
+
+        assert!(load_all(&path, &ParameterDictionary::new()).is_err());
+        assert!(load_all(&dir.path().join("missing.h5"), &pdict).is_err());
+    }
+}
@@ -0,0 +1,17 @@
+//!  Spectrum file formats for exchanging spectra with other tools.
+//!
+//!  *  spe - SpecTcl style binary spectrum files.
+//!  *  csv_import - spectra from CSV data.
+//!  *  root - ROOT TH1F/TH2F export.
+//!  *  hdf5 - HDF5 files of whole spectrum dictionaries (hdf5 feature).
+//!
+pub mod spe;
+pub use spe::*;
+pub mod csv_import;
+pub use csv_import::*;
+pub mod root;
+pub use root::*;
+#[cfg(feature = "hdf5")]
+pub mod hdf5;
+#[cfg(feature = "hdf5")]
+pub use self::hdf5::*;
@@ -0,0 +1,874 @@
+//!  Export spectra as ROOT histograms.  There's no crate that writes
+//!  ROOT files (root-io only reads TTrees) so this module writes the
+//!  minimal file itself:  an uncompressed ROOT file whose top directory
+//!  holds a single TH1F (1-d spectra) or TH2F (2-d spectra).
+//!
+//!  The file is laid out the way TFile lays out a new file:
+//!
+//!  *  The file header, padded to 100 bytes.
+//!  *  The top directory record:  its key, name/title and directory
+//!     header.
+//!  *  The histogram record:  its key and the streamed histogram.
+//!  *  The StreamerInfo record.  The list is empty since the histogram
+//!     classes are written at the class versions ROOT itself has
+//!     (TH1F/TH2F 3, TH1 8, TH2 5, TAxis 10) for which ROOT needs no
+//!     streamer information from the file.
+//!  *  The keys list record of the top directory.
+//!  *  The free segments record.
+//!
+//!  Everything is big endian as ROOT requires.  Channel values are
+//!  stored as f32 including the under/overflow channels.  The
+//!  statistics (entries, sums of weights and moments) are computed
+//!  assuming each count is a fill of weight one at the bin center.
+//!
+//!  **Experimental:**  the files are checked here with our own reader
+//!  and in CI (the root job) by reading the reference_1 test's files
+//!  with uproot, but not yet against ROOT itself, so the format may
+//!  still change.
+//!
+use crate::spectra::{AxisDescription, Spectrum};
+use ndhistogram::Histogram;
+use std::path::Path;
+
+const ROOT_VERSION: i32 = 62206;
+const BEGIN: usize = 100;
+const START_BIG_FILE: i32 = 2000000000;
+const BYTE_COUNT_MASK: u32 = 0x40000000;
+const KEY_VERSION: i16 = 4;
+const DIRECTORY_VERSION: i16 = 5;
+const TOBJECT_BITS: u32 = 0x03000000;
+
+// Serialization into a big endian buffer.  begin_object/end_object
+// bracket objects that ROOT writes with a byte count and version.
+
+struct Buffer {
+    bytes: Vec<u8>,
+}
+impl Buffer {
+    fn new() -> Buffer {
+        Buffer { bytes: Vec::new() }
+    }
+    fn len(&self) -> usize {
+        self.bytes.len()
+    }
+    fn u8(&mut self, v: u8) -> &mut Buffer {
+        self.bytes.push(v);
+        self
+    }
+    fn i16(&mut self, v: i16) -> &mut Buffer {
+        self.bytes.extend_from_slice(&v.to_be_bytes());
+        self
+    }
+    fn i32(&mut self, v: i32) -> &mut Buffer {
+        self.bytes.extend_from_slice(&v.to_be_bytes());
+        self
+    }
+    fn u32(&mut self, v: u32) -> &mut Buffer {
+        self.bytes.extend_from_slice(&v.to_be_bytes());
+        self
+    }
+    fn f32(&mut self, v: f32) -> &mut Buffer {
+        self.bytes.extend_from_slice(&v.to_be_bytes());
+        self
+    }
+    fn f64(&mut self, v: f64) -> &mut Buffer {
+        self.bytes.extend_from_slice(&v.to_be_bytes());
+        self
+    }
+    // A TString:  one byte length or 255 followed by a four byte length.
+    fn string(&mut self, s: &str) -> &mut Buffer {
+        if s.len() < 255 {
+            self.u8(s.len() as u8);
+        } else {
+            self.u8(255).i32(s.len() as i32);
+        }
+        self.bytes.extend_from_slice(s.as_bytes());
+        self
+    }
+    fn bytes(&mut self, b: &[u8]) -> &mut Buffer {
+        self.bytes.extend_from_slice(b);
+        self
+    }
+    fn begin_object(&mut self, version: i16) -> usize {
+        let start = self.len();
+        self.u32(0).i16(version);
+        start
+    }
+    fn end_object(&mut self, start: usize) {
+        let count = (self.len() - start - 4) as u32 | BYTE_COUNT_MASK;
+        self.bytes[start..start + 4].copy_from_slice(&count.to_be_bytes());
+    }
+}
+
+fn string_size(s: &str) -> usize {
+    if s.len() < 255 {
+        s.len() + 1
+    } else {
+        s.len() + 5
+    }
+}
+
+// The streamers of the classes making up a histogram.
+
+fn tobject(b: &mut Buffer) {
+    b.i16(1).u32(0).u32(TOBJECT_BITS);
+}
+fn tnamed(b: &mut Buffer, name: &str, title: &str) {
+    let start = b.begin_object(1);
+    tobject(b);
+    b.string(name).string(title);
+    b.end_object(start);
+}
+fn empty_tlist(b: &mut Buffer) {
+    let start = b.begin_object(5);
+    tobject(b);
+    b.string("").i32(0);
+    b.end_object(start);
+}
+fn taxis(b: &mut Buffer, name: &str, axis: &AxisDescription) {
+    let start = b.begin_object(10);
+    tnamed(b, name, "");
+
+    // TAttAxis with ROOT's default style:
+
+    let att = b.begin_object(4);
+    b.i32(510).i16(1).i16(1).i16(42);
+    b.f32(0.005).f32(0.035).f32(0.03).f32(1.0).f32(0.035);
+    b.i16(1).i16(42);
+    b.end_object(att);
+
+    b.i32(axis.bins as i32).f64(axis.low).f64(axis.high);
+    b.i32(0); // fXbins (empty - the axis is uniform)
+    b.i32(0).i32(0).i16(0).u8(0); // fFirst, fLast, fBits2, fTimeDisplay
+    b.string(""); // fTimeFormat
+    b.u32(0).u32(0); // null fLabels and fModLabs
+    b.end_object(start);
+}
+
+// What TH1 needs to know about the histogram being written:
+
+struct Contents {
+    x_axis: AxisDescription,
+    y_axis: Option<AxisDescription>,
+    channels: Vec<f64>,
+}
+
+// Sums over the in range channels with each channel's counts at the
+// bin center:  (w, wx, wx2, wy, wy2, wxy).
+
+impl Contents {
+    fn sums(&self) -> [f64; 6] {
+        let nx = self.x_axis.bins as usize;
+        let center = |a: &AxisDescription, i: usize| {
+            a.low + (i as f64 - 0.5) * (a.high - a.low) / a.bins as f64
+        };
+        let mut result = [0.0; 6];
+        for (index, w) in self.channels.iter().enumerate() {
+            let ix = index % (nx + 2);
+            let iy = index / (nx + 2);
+            if ix == 0 || ix == nx + 1 {
+                continue;
+            }
+            let x = center(&self.x_axis, ix);
+            let y = match &self.y_axis {
+                Some(a) if iy == 0 || iy == a.bins as usize + 1 => continue,
+                Some(a) => center(a, iy),
+                None => 0.0,
+            };
+            for (r, v) in result.iter_mut().zip([1.0, x, x * x, y, y * y, x * y]) {
+                *r += w * v;
+            }
+        }
+        result
+    }
+}
+
+fn th1(b: &mut Buffer, name: &str, title: &str, contents: &Contents, sums: &[f64; 6]) {
+    let unused_axis = AxisDescription {
+        low: 0.0,
+        high: 1.0,
+        bins: 1,
+    };
+    let start = b.begin_object(8);
+    tnamed(b, name, title);
+    let att = b.begin_object(2); // TAttLine
+    b.i16(602).i16(1).i16(1);
+    b.end_object(att);
+    let att = b.begin_object(2); // TAttFill
+    b.i16(0).i16(1001);
+    b.end_object(att);
+    let att = b.begin_object(2); // TAttMarker
+    b.i16(1).i16(1).f32(1.0);
+    b.end_object(att);
+
+    b.i32(contents.channels.len() as i32);
+    taxis(b, "xaxis", &contents.x_axis);
+    taxis(b, "yaxis", contents.y_axis.as_ref().unwrap_or(&unused_axis));
+    taxis(b, "zaxis", &unused_axis);
+    b.i16(0).i16(1000); // fBarOffset, fBarWidth
+    b.f64(contents.channels.iter().sum()); // fEntries
+    b.f64(sums[0]).f64(sums[0]).f64(sums[1]).f64(sums[2]); // fTsumw, fTsumw2, fTsumwx, fTsumwx2
+    b.f64(-1111.0).f64(-1111.0).f64(0.0); // fMaximum, fMinimum, fNormFactor
+    b.i32(0).i32(0); // fContour, fSumw2
+    b.string(""); // fOption
+    empty_tlist(b); // fFunctions
+    b.i32(0).u8(0); // fBufferSize, (null) fBuffer
+    b.i32(0).i32(2); // fBinStatErrOpt (kNormal), fStatOverflows (kNeutral)
+    b.end_object(start);
+}
+
+// The streamed TH1F or TH2F:
+
+fn histogram_object(name: &str, title: &str, contents: &Contents) -> Vec<u8> {
+    let sums = contents.sums();
+    let mut b = Buffer::new();
+    let start = b.begin_object(3);
+    if contents.y_axis.is_some() {
+        let th2 = b.begin_object(5);
+        th1(&mut b, name, title, contents, &sums);
+        b.f64(1.0).f64(sums[3]).f64(sums[4]).f64(sums[5]);
+        b.end_object(th2);
+    } else {
+        th1(&mut b, name, title, contents, &sums);
+    }
+    b.i32(contents.channels.len() as i32); // TArrayF
+    for c in &contents.channels {
+        b.f32(*c as f32);
+    }
+    b.end_object(start);
+    b.bytes
+}
+
+// TDatime packing of the current (UTC) time:
+
+fn datime() -> u32 {
+    let now = humantime::format_rfc3339_seconds(std::time::SystemTime::now()).to_string();
+    let field = |r: std::ops::Range<usize>| now[r].parse::<u32>().unwrap_or(0);
+    let year = field(0..4).max(1995);
+    (year - 1995) << 26
+        | field(5..7) << 22
+        | field(8..10) << 17
+        | field(11..13) << 12
+        | field(14..16) << 6
+        | field(17..19)
+}
+
+// A key (record header).  Keys are written both in front of their
+// data and, for the top directory's keys list, in that list.
+
+struct Key {
+    object_length: usize,
+    datime: u32,
+    class_name: String,
+    name: String,
+    title: String,
+    seek_key: usize,
+    seek_parent: usize,
+}
+impl Key {
+    fn key_length(&self) -> usize {
+        26 + string_size(&self.class_name) + string_size(&self.name) + string_size(&self.title)
+    }
+    fn nbytes(&self) -> usize {
+        self.key_length() + self.object_length
+    }
+    fn write(&self, b: &mut Buffer) {
+        b.i32(self.nbytes() as i32)
+            .i16(KEY_VERSION)
+            .i32(self.object_length as i32)
+            .u32(self.datime)
+            .i16(self.key_length() as i16)
+            .i16(1)
+            .i32(self.seek_key as i32)
+            .i32(self.seek_parent as i32)
+            .string(&self.class_name)
+            .string(&self.name)
+            .string(&self.title);
+    }
+}
+
+// Write the file given the streamed histogram:
+
+fn write_file(
+    path: &Path,
+    class_name: &str,
+    name: &str,
+    title: &str,
+    object: &[u8],
+) -> Result<(), String> {
+    let file_name = path.to_string_lossy().to_string();
+    let datime = datime();
+    let key = |class_name: &str, name: &str, title: &str, length: usize, seek: usize, parent| Key {
+        object_length: length,
+        datime,
+        class_name: String::from(class_name),
+        name: String::from(name),
+        title: String::from(title),
+        seek_key: seek,
+        seek_parent: parent,
+    };
+    const DIRECTORY_SIZE: usize = 60;
+    let named_size = string_size(&file_name) + string_size("");
+
+    // Lay out the records:
+
+    let top = key(
+        "TFile",
+        &file_name,
+        "",
+        named_size + DIRECTORY_SIZE,
+        BEGIN,
+        0,
+    );
+    let histogram = key(
+        class_name,
+        name,
+        title,
+        object.len(),
+        top.seek_key + top.nbytes(),
+        BEGIN,
+    );
+
+    let mut streamer_info = Buffer::new();
+    empty_tlist(&mut streamer_info);
+    let streamer_info = streamer_info.bytes;
+    let info = key(
+        "TList",
+        "StreamerInfo",
+        "Doubly linked list",
+        streamer_info.len(),
+        histogram.seek_key + histogram.nbytes(),
+        BEGIN,
+    );
+    let keys = key(
+        "TFile",
+        &file_name,
+        "",
+        4 + histogram.key_length(),
+        info.seek_key + info.nbytes(),
+        BEGIN,
+    );
+    let free = key(
+        "TFile",
+        &file_name,
+        "",
+        10,
+        keys.seek_key + keys.nbytes(),
+        BEGIN,
+    );
+    let end = free.seek_key + free.nbytes();
+    if end > START_BIG_FILE as usize {
+        return Err(String::from(
+            "The spectrum is too big for a small ROOT file",
+        ));
+    }
+    let name_bytes = top.key_length() + named_size;
+
+    // File header:
+
+    let mut b = Buffer::new();
+    b.bytes(b"root")
+        .i32(ROOT_VERSION)
+        .i32(BEGIN as i32)
+        .i32(end as i32)
+        .i32(free.seek_key as i32)
+        .i32(free.nbytes() as i32)
+        .i32(1) // one free segment
+        .i32(name_bytes as i32)
+        .u8(4) // fUnits
+        .i32(0) // Not compressed.
+        .i32(info.seek_key as i32)
+        .i32(info.nbytes() as i32)
+        .i16(1)
+        .bytes(&[0; 16]); // UUID
+    b.bytes.resize(BEGIN, 0);
+
+    // Top directory:
+
+    top.write(&mut b);
+    b.string(&file_name).string("");
+    b.i16(DIRECTORY_VERSION)
+        .u32(datime)
+        .u32(datime)
+        .i32(keys.nbytes() as i32)
+        .i32(name_bytes as i32)
+        .i32(BEGIN as i32) // fSeekDir
+        .i32(0) // fSeekParent
+        .i32(keys.seek_key as i32)
+        .i16(1)
+        .bytes(&[0; 16]) // UUID
+        .i32(0)
+        .i32(0)
+        .i32(0);
+
+    // The histogram, streamer info, keys list and free segments:
+
+    histogram.write(&mut b);
+    b.bytes(object);
+    info.write(&mut b);
+    b.bytes(&streamer_info);
+    keys.write(&mut b);
+    b.i32(1);
+    histogram.write(&mut b);
+    free.write(&mut b);
+    b.i16(1).i32(end as i32).i32(START_BIG_FILE);
+    assert_eq!(end, b.len());
+
+    std::fs::write(path, b.bytes).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+///
+/// Write a 1-d spectrum to a ROOT file as a TH1F named hist_name.
+/// The histogram title is the spectrum name.  The file is replaced if
+/// it exists.  Experimental, see the module documentation.
+///
+pub fn export_root_th1(spec: &dyn Spectrum, path: &Path, hist_name: &str) -> Result<(), String> {
+    let h = spec
+        .get_histogram_1d()
+        .ok_or_else(|| format!("{} is not a 1-d spectrum", spec.get_name()))?;
+    let contents = Contents {
+        x_axis: spec.x_axis(),
+        y_axis: None,
+        channels: h.borrow().values().map(|v| v.get()).collect(),
+    };
+    write_file(
+        path,
+        "TH1F",
+        hist_name,
+        &spec.get_name(),
+        &histogram_object(hist_name, &spec.get_name(), &contents),
+    )
+}
+
+///
+/// Write a 2-d spectrum to a ROOT file as a TH2F named hist_name.
+/// The histogram title is the spectrum name.  The file is replaced if
+/// it exists.  Experimental, see the module documentation.
+///
+pub fn export_root_th2(spec: &dyn Spectrum, path: &Path, hist_name: &str) -> Result<(), String> {
+    let h = spec
+        .get_histogram_2d()
+        .ok_or_else(|| format!("{} is not a 2-d spectrum", spec.get_name()))?;
+    let contents = Contents {
+        x_axis: spec.x_axis(),
+        y_axis: spec.y_axis(),
+        channels: h.borrow().values().map(|v| v.get()).collect(),
+    };
+    write_file(
+        path,
+        "TH2F",
+        hist_name,
+        &spec.get_name(),
+        &histogram_object(hist_name, &spec.get_name(), &contents),
+    )
+}
+
+#[cfg(test)]
+mod root_tests {
+    use super::*;
+    use crate::parameters::ParameterDictionary;
+    use crate::spectra::{Oned, Twod};
+
+    // A reader for what we write.  It checks that the byte counts and
+    // record pointers are consistent as it goes.
+
+    struct Reader<'a> {
+        bytes: &'a [u8],
+        offset: usize,
+    }
+    impl<'a> Reader<'a> {
+        fn at(bytes: &'a [u8], offset: usize) -> Reader<'a> {
+            Reader { bytes, offset }
+        }
+        fn take<const N: usize>(&mut self) -> [u8; N] {
+            let result = self.bytes[self.offset..self.offset + N].try_into().unwrap();
+            self.offset += N;
+            result
+        }
+        fn u8(&mut self) -> u8 {
+            self.take::<1>()[0]
+        }
+        fn i16(&mut self) -> i16 {
+            i16::from_be_bytes(self.take())
+        }
+        fn i32(&mut self) -> i32 {
+            i32::from_be_bytes(self.take())
+        }
+        fn u32(&mut self) -> u32 {
+            u32::from_be_bytes(self.take())
+        }
+        fn f32(&mut self) -> f32 {
+            f32::from_be_bytes(self.take())
+        }
+        fn f64(&mut self) -> f64 {
+            f64::from_be_bytes(self.take())
+        }
+        fn string(&mut self) -> String {
+            let mut len = self.u8() as usize;
+            if len == 255 {
+                len = self.i32() as usize;
+            }
+            let s = String::from_utf8(self.bytes[self.offset..self.offset + len].to_vec());
+            self.offset += len;
+            s.unwrap()
+        }
+        // Start of an object with a byte count;  returns where it ends.
+        fn object(&mut self, version: i16) -> usize {
+            let count = self.u32();
+            assert_eq!(BYTE_COUNT_MASK, count & BYTE_COUNT_MASK);
+            let end = self.offset + (count & !BYTE_COUNT_MASK) as usize;
+            assert_eq!(version, self.i16());
+            end
+        }
+        fn skip_object(&mut self, version: i16) {
+            self.offset = self.object(version);
+        }
+        fn tnamed(&mut self) -> (String, String) {
+            let end = self.object(1);
+            assert_eq!(1, self.i16());
+            self.u32();
+            assert_eq!(TOBJECT_BITS, self.u32());
+            let result = (self.string(), self.string());
+            assert_eq!(end, self.offset);
+            result
+        }
+        fn key(&mut self) -> ReadKey {
+            let start = self.offset;
+            let nbytes = self.i32() as usize;
+            assert_eq!(KEY_VERSION, self.i16());
+            let object_length = self.i32() as usize;
+            self.u32();
+            let key_length = self.i16() as usize;
+            assert_eq!(1, self.i16());
+            let seek_key = self.i32() as usize;
+            let seek_parent = self.i32() as usize;
+            let class_name = self.string();
+            let name = self.string();
+            let _title = self.string();
+            assert_eq!(key_length, self.offset - start);
+            assert_eq!(nbytes, key_length + object_length);
+            ReadKey {
+                nbytes,
+                object_length,
+                seek_key,
+                seek_parent,
+                class_name,
+                name,
+            }
+        }
+        fn taxis(&mut self, name: &str) -> AxisDescription {
+            let end = self.object(10);
+            assert_eq!(name, self.tnamed().0);
+            self.skip_object(4);
+            let bins = self.i32() as u32;
+            let low = self.f64();
+            let high = self.f64();
+            assert_eq!(0, self.i32()); // fXbins
+            self.offset += 4 + 4 + 2 + 1;
+            assert_eq!("", self.string());
+            assert_eq!(0, self.u32());
+            assert_eq!(0, self.u32());
+            assert_eq!(end, self.offset);
+            AxisDescription { low, high, bins }
+        }
+    }
+    struct ReadKey {
+        nbytes: usize,
+        object_length: usize,
+        seek_key: usize,
+        seek_parent: usize,
+        class_name: String,
+        name: String,
+    }
+    // What's read back from the histogram object:
+
+    struct ReadHistogram {
+        title: String,
+        x_axis: AxisDescription,
+        y_axis: AxisDescription,
+        entries: f64,
+        sums: Vec<f64>,
+        channels: Vec<f32>,
+    }
+
+    // Follow the file structure to the histogram:
+
+    fn read_file(path: &Path, class_name: &str, name: &str) -> ReadHistogram {
+        let bytes = std::fs::read(path).unwrap();
+        let mut r = Reader::at(&bytes, 0);
+        assert_eq!(b"root", &r.take::<4>());
+        assert_eq!(ROOT_VERSION, r.i32());
+        assert_eq!(BEGIN as i32, r.i32());
+        assert_eq!(bytes.len() as i32, r.i32());
+        let seek_free = r.i32() as usize;
+        let free_bytes = r.i32() as usize;
+        assert_eq!(1, r.i32());
+        r.i32();
+        assert_eq!(4, r.u8());
+        assert_eq!(0, r.i32());
+        let seek_info = r.i32() as usize;
+        let info_bytes = r.i32() as usize;
+
+        // The top directory gives the keys list:
+
+        let mut r = Reader::at(&bytes, BEGIN);
+        let top = r.key();
+        assert_eq!("TFile", top.class_name);
+        assert_eq!((BEGIN, 0), (top.seek_key, top.seek_parent));
+        r.string();
+        r.string();
+        assert_eq!(DIRECTORY_VERSION, r.i16());
+        r.offset += 8;
+        let keys_bytes = r.i32() as usize;
+        r.i32();
+        assert_eq!(BEGIN as i32, r.i32());
+        assert_eq!(0, r.i32());
+        let seek_keys = r.i32() as usize;
+        r.offset += 18 + 12;
+        assert_eq!(BEGIN + top.nbytes, r.offset);
+
+        let mut r = Reader::at(&bytes, seek_keys);
+        let keys = r.key();
+        assert_eq!(keys_bytes, r.offset - seek_keys + keys.object_length);
+        assert_eq!(1, r.i32());
+        let entry = r.key();
+        assert_eq!(class_name, entry.class_name);
+        assert_eq!(name, entry.name);
+        assert_eq!(BEGIN, entry.seek_parent);
+
+        // The streamer info and free segments:
+
+        let mut r = Reader::at(&bytes, seek_info);
+        let info = r.key();
+        assert_eq!(
+            ("TList", "StreamerInfo"),
+            (info.class_name.as_str(), info.name.as_str())
+        );
+        assert_eq!(info_bytes, r.offset - seek_info + info.object_length);
+        let mut r = Reader::at(&bytes, seek_free);
+        let free = r.key();
+        assert_eq!(free_bytes, r.offset - seek_free + free.object_length);
+        assert_eq!(1, r.i16());
+        assert_eq!(bytes.len() as i32, r.i32());
+        assert_eq!(START_BIG_FILE, r.i32());
+
+        // The histogram itself:
+
+        let mut r = Reader::at(&bytes, entry.seek_key);
+        let key = r.key();
+        assert_eq!(entry.object_length, key.object_length);
+        let object_end = r.offset + key.object_length;
+        assert_eq!(object_end, r.object(3));
+        let th2_end = if class_name == "TH2F" {
+            Some(r.object(5))
+        } else {
+            None
+        };
+        let th1_end = r.object(8);
+        let (hist_name, title) = r.tnamed();
+        assert_eq!(name, hist_name);
+        r.skip_object(2);
+        r.skip_object(2);
+        r.skip_object(2);
+        let ncells = r.i32() as usize;
+        let x_axis = r.taxis("xaxis");
+        let y_axis = r.taxis("yaxis");
+        r.taxis("zaxis");
+        r.offset += 4;
+        let entries = r.f64();
+        let mut sums: Vec<f64> = (0..4).map(|_| r.f64()).collect();
+        r.offset += 3 * 8;
+        assert_eq!(0, r.i32());
+        assert_eq!(0, r.i32());
+        assert_eq!("", r.string());
+        r.skip_object(5);
+        assert_eq!(0, r.i32());
+        assert_eq!(0, r.u8());
+        r.offset += 8;
+        assert_eq!(th1_end, r.offset);
+        if let Some(end) = th2_end {
+            assert_eq!(1.0, r.f64());
+            sums.extend((0..3).map(|_| r.f64()));
+            assert_eq!(end, r.offset);
+        }
+        assert_eq!(ncells as i32, r.i32());
+        let channels = (0..ncells).map(|_| r.f32()).collect();
+        assert_eq!(object_end, r.offset);
+
+        ReadHistogram {
+            title,
+            x_axis,
+            y_axis,
+            entries,
+            sums,
+            channels,
+        }
+    }
+
+    fn make_pdict() -> ParameterDictionary {
+        let mut pdict = ParameterDictionary::new();
+        pdict.add("x").unwrap();
+        pdict.add("y").unwrap();
+        pdict
+    }
+
+    #[test]
+    fn th1_1() {
+        let pdict = make_pdict();
+        let mut spec = Oned::new("energy", "x", &pdict, Some(0.0), Some(100.0), Some(10)).unwrap();
+        spec.fill_channel_1d(5.0, 2.0);
+        spec.fill_channel_1d(55.0, 3.0);
+        spec.fill_channel_1d(-1.0, 4.0); // underflow
+        spec.fill_channel_1d(200.0, 1.0); // overflow
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("energy.root");
+        export_root_th1(&spec, &path, "h_energy").unwrap();
+        let h = read_file(&path, "TH1F", "h_energy");
+        assert_eq!("energy", h.title);
+        assert_eq!(spec.x_axis(), h.x_axis);
+        assert_eq!(1, h.y_axis.bins);
+        let mut expected = vec![0.0_f32; 12];
+        expected[0] = 4.0;
+        expected[1] = 2.0;
+        expected[6] = 3.0;
+        expected[11] = 1.0;
+        assert_eq!(expected, h.channels);
+
+        // Statistics:
+
+        assert_eq!(10.0, h.entries);
+        assert_eq!(
+            vec![5.0, 5.0, 2.0 * 5.0 + 3.0 * 55.0, 2.0 * 25.0 + 3.0 * 3025.0],
+            h.sums
+        );
+    }
+    #[test]
+    fn th2_1() {
+        let pdict = make_pdict();
+        let mut spec = Twod::new(
+            "xy",
+            "x",
+            "y",
+            &pdict,
+            Some(0.0),
+            Some(10.0),
+            Some(10),
+            Some(-5.0),
+            Some(5.0),
+            Some(5),
+        )
+        .unwrap();
+        spec.fill_channel_2d(0.5, -4.5, 1.0);
+        spec.fill_channel_2d(9.5, 4.5, 2.0);
+        spec.fill_channel_2d(20.0, 0.0, 7.0); // x overflow.
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("xy.root");
+        export_root_th2(&spec, &path, "h_xy").unwrap();
+        let h = read_file(&path, "TH2F", "h_xy");
+        assert_eq!("xy", h.title);
+        assert_eq!(spec.x_axis(), h.x_axis);
+        assert_eq!(spec.y_axis().unwrap(), h.y_axis);
+
+        // ROOT's bin numbering:  x + (nx + 2) * y with 0 the underflow:
+
+        assert_eq!(12 * 7, h.channels.len());
+        let mut expected = vec![0.0_f32; 12 * 7];
+        expected[1 + 12] = 1.0;
+        expected[10 + 12 * 5] = 2.0;
+        expected[11 + 12 * 3] = 7.0;
+        assert_eq!(expected, h.channels);
+
+        // The y bins are 2 wide so their centers are at -4 and 4:
+
+        assert_eq!(10.0, h.entries);
+        assert_eq!(
+            vec![
+                3.0,
+                3.0,
+                0.5 + 2.0 * 9.5,
+                0.25 + 2.0 * 90.25,
+                -4.0 + 2.0 * 4.0,
+                16.0 + 2.0 * 16.0,
+                0.5 * -4.0 + 2.0 * 9.5 * 4.0
+            ],
+            h.sums
+        );
+    }
+    #[test]
+    fn reference_1() {
+        // With ROOT_EXPORT_DIR set, the files are kept there for
+        // .github/scripts/check_root.py to read with uproot.
+
+        let pdict = make_pdict();
+        let mut oned = Oned::new("energy", "x", &pdict, Some(0.0), Some(100.0), Some(10)).unwrap();
+        oned.fill_channel_1d(5.0, 2.0);
+        oned.fill_channel_1d(55.0, 3.0);
+        oned.fill_channel_1d(-1.0, 4.0);
+        oned.fill_channel_1d(200.0, 1.0);
+        let mut twod = Twod::new(
+            "xy",
+            "x",
+            "y",
+            &pdict,
+            Some(0.0),
+            Some(10.0),
+            Some(10),
+            Some(-5.0),
+            Some(5.0),
+            Some(5),
+        )
+        .unwrap();
+        twod.fill_channel_2d(0.5, -4.5, 1.0);
+        twod.fill_channel_2d(9.5, 4.5, 2.0);
+        twod.fill_channel_2d(20.0, 0.0, 7.0);
+
+        let temp = tempfile::tempdir().unwrap();
+        let dir = match std::env::var("ROOT_EXPORT_DIR") {
+            Ok(d) => {
+                std::fs::create_dir_all(&d).unwrap();
+                std::path::PathBuf::from(d)
+            }
+            Err(_) => temp.path().to_path_buf(),
+        };
+        export_root_th1(&oned, &dir.join("energy.root"), "h_energy").unwrap();
+        export_root_th2(&twod, &dir.join("xy.root"), "h_xy").unwrap();
+        assert_eq!(
+            12,
+            read_file(&dir.join("energy.root"), "TH1F", "h_energy")
+                .channels
+                .len()
+        );
+        assert_eq!(
+            12 * 7,
+            read_file(&dir.join("xy.root"), "TH2F", "h_xy")
+                .channels
+                .len()
+        );
+    }
+    #[test]
+    fn errors_1() {
+        let pdict = make_pdict();
+        let oned = Oned::new("1d", "x", &pdict, Some(0.0), Some(1.0), Some(1)).unwrap();
+        let twod = Twod::new(
+            "2d",
+            "x",
+            "y",
+            &pdict,
+            Some(0.0),
+            Some(1.0),
+            Some(1),
+            Some(0.0),
+            Some(1.0),
+            Some(1),
+        )
+        .unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bad.root");
+        assert!(export_root_th1(&twod, &path, "h").is_err());
+        assert!(export_root_th2(&oned, &path, "h").is_err());
+        assert!(!path.exists());
+        assert!(export_root_th1(&oned, &dir.path().join("no/such/dir.root"), "h").is_err());
+    }
+}
@@ -0,0 +1,395 @@
+//!  Binary spectrum files in the style of SpecTcl's .spe files:  a
+//!  fixed size header followed by u32 channel counts.  All numbers are
+//!  little endian.  The header is:
+//!
+//!  *  The magic bytes "SPE1".
+//!  *  The spectrum name, 128 bytes, null padded.
+//!  *  The dimension (u32, 1 or 2).
+//!  *  The x axis:  low and high (f64), bins (u32) and the parameter
+//!     name (64 bytes, null padded).
+//!  *  The y axis in the same form.  For 1-d spectra this is all zeros.
+//!
+//!  The counts follow with x varying fastest:  bins (x bins * y bins
+//!  for 2-d) u32 values.  Under/overflow counts are not saved and
+//!  channel values are rounded and clamped to the u32 range.
+//!
+//!  Any spectrum with a 1-d histogram is saved as a 1-d spectrum and
+//!  any with a 2-d histogram as a 2-d spectrum.  They are restored as
+//!  Oned and Twod spectra respectively.  The axes saved are those of
+//!  the histogram so a log axis is restored as a uniform axis on the
+//!  log of the parameter.
+//!
+use crate::parameters::ParameterDictionary;
+use crate::spectra::{AxisDescription, Oned, Spectrum, Twod};
+use ndhistogram::Histogram;
+use std::io;
+use std::path::Path;
+
+const MAGIC: &[u8; 4] = b"SPE1";
+const NAME_SIZE: usize = 128;
+const PARAMETER_SIZE: usize = 64;
+const AXIS_SIZE: usize = 8 + 8 + 4 + PARAMETER_SIZE;
+const HEADER_SIZE: usize = 4 + NAME_SIZE + 4 + 2 * AXIS_SIZE;
+
+// Append a string as a null padded field.  There must be room for at
+// least one null.
+
+fn put_string(bytes: &mut Vec<u8>, s: &str, size: usize) -> io::Result<()> {
+    if s.len() >= size {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("'{}' does not fit in a {} byte field", s, size),
+        ));
+    }
+    bytes.extend_from_slice(s.as_bytes());
+    bytes.resize(bytes.len() + size - s.len(), 0);
+    Ok(())
+}
+fn put_axis(bytes: &mut Vec<u8>, axis: &AxisDescription, parameter: &str) -> io::Result<()> {
+    bytes.extend_from_slice(&axis.low.to_le_bytes());
+    bytes.extend_from_slice(&axis.high.to_le_bytes());
+    bytes.extend_from_slice(&axis.bins.to_le_bytes());
+    put_string(bytes, parameter, PARAMETER_SIZE)
+}
+fn to_count(value: f64) -> u32 {
+    value.round().clamp(0.0, u32::MAX as f64) as u32
+}
+// Center of bin i of an axis (histogram coordinates):
+
+fn bin_center(axis: &AxisDescription, i: u32) -> f64 {
+    axis.low + (i as f64 + 0.5) * (axis.high - axis.low) / axis.bins as f64
+}
+
+///
+/// Write a spectrum to a .spe file.  The file is replaced if it
+/// exists.  Names too long for their header fields are InvalidInput
+/// errors.
+///
+pub fn save_spe(spec: &dyn Spectrum, path: &Path) -> io::Result<()> {
+    let description = spec.description();
+    let x_param = description.x_params.first().cloned().unwrap_or_default();
+    let y_param = description.y_params.first().cloned().unwrap_or_default();
+    let x_axis = spec.x_axis();
+
+    let mut bytes = Vec::with_capacity(HEADER_SIZE);
+    bytes.extend_from_slice(MAGIC);
+    put_string(&mut bytes, &spec.get_name(), NAME_SIZE)?;
+    if let Some(h) = spec.get_histogram_1d() {
+        bytes.extend_from_slice(&1_u32.to_le_bytes());
+        put_axis(&mut bytes, &x_axis, &x_param)?;
+        bytes.resize(HEADER_SIZE, 0);
+        let h = h.borrow();
+        for i in 0..x_axis.bins {
+            let value = h.value(&bin_center(&x_axis, i)).map_or(0.0, |v| v.get());
+            bytes.extend_from_slice(&to_count(value).to_le_bytes());
+        }
+    } else if let (Some(h), Some(y_axis)) = (spec.get_histogram_2d(), spec.y_axis()) {
+        bytes.extend_from_slice(&2_u32.to_le_bytes());
+        put_axis(&mut bytes, &x_axis, &x_param)?;
+        put_axis(&mut bytes, &y_axis, &y_param)?;
+        let h = h.borrow();
+        for iy in 0..y_axis.bins {
+            let y = bin_center(&y_axis, iy);
+            for ix in 0..x_axis.bins {
+                let value = h
+                    .value(&(bin_center(&x_axis, ix), y))
+                    .map_or(0.0, |v| v.get());
+                bytes.extend_from_slice(&to_count(value).to_le_bytes());
+            }
+        }
+    }
+    std::fs::write(path, bytes)
+}
+
+// Sequential little endian reads of a .spe file:
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let result = self
+            .bytes
+            .get(self.offset..self.offset + n)
+            .ok_or_else(|| String::from("Spectrum file is truncated"))?;
+        self.offset += n;
+        Ok(result)
+    }
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+    fn f64(&mut self) -> Result<f64, String> {
+        Ok(f64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+    fn string(&mut self, size: usize) -> Result<String, String> {
+        let field = self.take(size)?;
+        let len = field.iter().position(|b| *b == 0).unwrap_or(size);
+        String::from_utf8(field[..len].to_vec())
+            .map_err(|_| String::from("Spectrum file has a name that is not UTF-8"))
+    }
+    fn axis(&mut self) -> Result<(AxisDescription, String), String> {
+        let low = self.f64()?;
+        let high = self.f64()?;
+        let bins = self.u32()?;
+        let parameter = self.string(PARAMETER_SIZE)?;
+        Ok((AxisDescription { low, high, bins }, parameter))
+    }
+}
+
+// Parameter names for a restored spectrum.  Files need not have them
+// (e.g. summary spectra have no y parameter) and the x and y
+// parameters of a Twod must be distinct.
+
+fn parameter_names(x: &str, y: &str) -> (String, String) {
+    let x = if x.is_empty() { "x" } else { x };
+    let y = if y.is_empty() || y == x {
+        format!("{}.y", x)
+    } else {
+        String::from(y)
+    };
+    (String::from(x), y)
+}
+
+///
+/// Restore a spectrum from a .spe file.  The spectrum's parameters are
+/// defined in a dictionary of their own so their ids need not match
+/// those of any analysis dictionary;  the spectrum is meant to be
+/// looked at, not filled.
+///
+pub fn load_spe(path: &Path) -> Result<Box<dyn Spectrum>, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut reader = Reader {
+        bytes: &bytes,
+        offset: 0,
+    };
+    if reader.take(4)? != MAGIC {
+        return Err(format!("{} is not a .spe file", path.display()));
+    }
+    let name = reader.string(NAME_SIZE)?;
+    let dimension = reader.u32()?;
+    let (x_axis, x_param) = reader.axis()?;
+    let (y_axis, y_param) = reader.axis()?;
+    let (x_param, y_param) = parameter_names(&x_param, &y_param);
+
+    let mut pdict = ParameterDictionary::new();
+    pdict.add(&x_param)?;
+    pdict.add(&y_param)?;
+    let counts = match dimension {
+        1 => x_axis.bins as usize,
+        2 => x_axis.bins as usize * y_axis.bins as usize,
+        d => return Err(format!("Unsupported spectrum dimension {}", d)),
+    };
+    if bytes.len() != HEADER_SIZE + 4 * counts {
+        return Err(format!(
+            "{} should have {} channels",
+            path.display(),
+            counts
+        ));
+    }
+    reader.offset = HEADER_SIZE;
+
+    let mut result: Box<dyn Spectrum> = if dimension == 1 {
+        Box::new(Oned::new(
+            &name,
+            &x_param,
+            &pdict,
+            Some(x_axis.low),
+            Some(x_axis.high),
+            Some(x_axis.bins),
+        )?)
+    } else {
+        Box::new(Twod::new(
+            &name,
+            &x_param,
+            &y_param,
+            &pdict,
+            Some(x_axis.low),
+            Some(x_axis.high),
+            Some(x_axis.bins),
+            Some(y_axis.low),
+            Some(y_axis.high),
+            Some(y_axis.bins),
+        )?)
+    };
+    for i in 0..counts as u32 {
+        let value = reader.u32()?;
+        if value != 0 {
+            let x = bin_center(&x_axis, i % x_axis.bins);
+            if dimension == 1 {
+                result.fill_channel_1d(x, value as f64);
+            } else {
+                result.fill_channel_2d(x, bin_center(&y_axis, i / x_axis.bins), value as f64);
+            }
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod spe_tests {
+    use super::*;
+    use crate::spectra::Summary;
+
+    fn make_pdict() -> ParameterDictionary {
+        let mut pdict = ParameterDictionary::new();
+        for name in ["x", "y", "z"] {
+            pdict.add(name).unwrap();
+        }
+        pdict
+    }
+    // The non-zero channels of a spectrum as (x, y, value) with y 0 for 1-d:
+
+    fn channels(spec: &dyn Spectrum) -> Vec<(f64, f64, f64)> {
+        let mut result = Vec::new();
+        if let Some(h) = spec.get_histogram_1d() {
+            for c in h.borrow().iter() {
+                if let (Some(x), Some(_)) = (c.bin.start(), c.bin.end()) {
+                    if c.value.get() != 0.0 {
+                        result.push((x, 0.0, c.value.get()));
+                    }
+                }
+            }
+        } else if let Some(h) = spec.get_histogram_2d() {
+            for c in h.borrow().iter() {
+                let (x, y) = c.bin;
+                if let (Some(x), Some(_), Some(y), Some(_)) =
+                    (x.start(), x.end(), y.start(), y.end())
+                {
+                    if c.value.get() != 0.0 {
+                        result.push((x, y, c.value.get()));
+                    }
+                }
+            }
+        }
+        result.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        result
+    }
+
+    #[test]
+    fn oned_1() {
+        let pdict = make_pdict();
+        let mut spec = Oned::new("1d", "x", &pdict, Some(-10.0), Some(10.0), Some(20)).unwrap();
+        spec.fill_channel_1d(-9.5, 3.0);
+        spec.fill_channel_1d(0.5, 100.0);
+        spec.fill_channel_1d(9.5, 7.0);
+        spec.fill_channel_1d(20.0, 5.0); // overflow - not saved.
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("1d.spe");
+        save_spe(&spec, &path).unwrap();
+        assert_eq!(
+            (HEADER_SIZE + 20 * 4) as u64,
+            std::fs::metadata(&path).unwrap().len()
+        );
+
+        let loaded = load_spe(&path).unwrap();
+        assert_eq!("1d", loaded.get_name());
+        assert!(loaded.get_histogram_1d().is_some());
+        assert_eq!(spec.x_axis(), loaded.x_axis());
+        assert!(loaded.y_axis().is_none());
+        assert_eq!(vec![String::from("x")], loaded.description().x_params);
+        assert_eq!(
+            vec![(-10.0, 0.0, 3.0), (0.0, 0.0, 100.0), (9.0, 0.0, 7.0)],
+            channels(loaded.as_ref())
+        );
+    }
+    #[test]
+    fn twod_1() {
+        let pdict = make_pdict();
+        let mut spec = Twod::new(
+            "2d",
+            "x",
+            "y",
+            &pdict,
+            Some(0.0),
+            Some(100.0),
+            Some(100),
+            Some(0.0),
+            Some(50.0),
+            Some(25),
+        )
+        .unwrap();
+        spec.fill_channel_2d(0.5, 0.5, 1.0);
+        spec.fill_channel_2d(10.5, 20.5, 2.0);
+        spec.fill_channel_2d(99.5, 49.5, 3.0);
+        spec.fill_channel_2d(50.5, 10.5, 2.6); // Rounded.
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("2d.spe");
+        save_spe(&spec, &path).unwrap();
+        let loaded = load_spe(&path).unwrap();
+        assert_eq!("2d", loaded.get_name());
+        assert_eq!(spec.x_axis(), loaded.x_axis());
+        assert_eq!(spec.y_axis(), loaded.y_axis());
+        let d = loaded.description();
+        assert_eq!(vec![String::from("x")], d.x_params);
+        assert_eq!(vec![String::from("y")], d.y_params);
+        assert_eq!(
+            vec![
+                (0.0, 0.0, 1.0),
+                (10.0, 20.0, 2.0),
+                (50.0, 10.0, 3.0),
+                (99.0, 48.0, 3.0)
+            ],
+            channels(loaded.as_ref())
+        );
+    }
+    #[test]
+    fn summary_1() {
+        // Other 2-d spectra are restored as Twod:
+
+        let pdict = make_pdict();
+        let mut spec = Summary::new(
+            "summary",
+            vec![String::from("x"), String::from("y"), String::from("z")],
+            &pdict,
+            Some(0.0),
+            Some(16.0),
+            Some(16),
+        )
+        .unwrap();
+        spec.fill_channel_2d(1.5, 5.5, 10.0);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("summary.spe");
+        save_spe(&spec, &path).unwrap();
+        let loaded = load_spe(&path).unwrap();
+        assert!(loaded.as_any().is::<Twod>());
+        assert_eq!(spec.x_axis(), loaded.x_axis());
+        assert_eq!(spec.y_axis(), loaded.y_axis());
+        assert_eq!(channels(&spec), channels(loaded.as_ref()));
+    }
+    #[test]
+    fn errors_1() {
+        let pdict = make_pdict();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bad.spe");
+
+        // Name too long:
+
+        let spec = Oned::new(
+            &"n".repeat(200),
+            "x",
+            &pdict,
+            Some(0.0),
+            Some(10.0),
+            Some(10),
+        )
+        .unwrap();
+        let e = save_spe(&spec, &path).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidInput, e.kind());
+
+        // No file, not a spe file, truncated:
+
+        assert!(load_spe(&path).is_err());
+        std::fs::write(&path, b"not a spectrum").unwrap();
+        assert!(load_spe(&path).is_err());
+        let spec = Oned::new("1d", "x", &pdict, Some(0.0), Some(10.0), Some(10)).unwrap();
+        save_spe(&spec, &path).unwrap();
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes.pop();
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(load_spe(&path).is_err());
+    }
+}
@@ -0,0 +1,22 @@
+//!  The histogrammer library.  The histogrammer program (main.rs) and
+//!  the benchmarks (benches/) are built on it.
+//!
+// These modules were written as part of the program, where clippy does
+// not ask public types with new() to also implement Default (or public
+// len methods to have is_empty).
+
+#![allow(clippy::new_without_default, clippy::len_without_is_empty)]
+
+pub mod analysis;
+pub mod conditions;
+pub mod config;
+pub mod formats;
+pub mod messaging;
+pub mod parameters;
+pub mod pipeline;
+pub mod ring_items;
+pub mod server;
+pub mod shm;
+pub mod sources;
+pub mod spectra;
+pub mod testing;
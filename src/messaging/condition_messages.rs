@@ -0,0 +1,401 @@
+//! Condition requests and their client/server wrappers.
+//!
+//! Clients use a ConditionClient to make requests of a
+//! ConditionServer which owns a ConditionDictionary.  The requests
+//! carry what's needed to construct each of the supported condition
+//! types.  Compound conditions are described in terms of the names
+//! of the conditions they depend on.
+//!
+use crate::conditions::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+use tokio::sync::{mpsc, oneshot};
+
+/// Replies from the condition server:
+///
+pub enum ConditionReply {
+    Error(String),
+    Processed,
+    Listing(Vec<String>),
+}
+
+/// Requests to the condition server.
+///
+pub enum ConditionRequest {
+    CreateTrue {
+        name: String,
+        reply: oneshot::Sender<ConditionReply>,
+    },
+    CreateFalse {
+        name: String,
+        reply: oneshot::Sender<ConditionReply>,
+    },
+    CreateCut {
+        name: String,
+        parameter_id: u32,
+        low: f64,
+        high: f64,
+        reply: oneshot::Sender<ConditionReply>,
+    },
+    CreateNot {
+        name: String,
+        dependent: String,
+        reply: oneshot::Sender<ConditionReply>,
+    },
+    CreateAnd {
+        name: String,
+        dependents: Vec<String>,
+        reply: oneshot::Sender<ConditionReply>,
+    },
+    CreateOr {
+        name: String,
+        dependents: Vec<String>,
+        reply: oneshot::Sender<ConditionReply>,
+    },
+    Delete {
+        name: String,
+        reply: oneshot::Sender<ConditionReply>,
+    },
+    List {
+        reply: oneshot::Sender<ConditionReply>,
+    },
+}
+
+///
+/// The client side of condition messaging.  As with the
+/// SpectrumClient, the methods are async.
+///
+#[derive(Clone)]
+pub struct ConditionClient {
+    send: mpsc::Sender<ConditionRequest>,
+}
+
+impl ConditionClient {
+    pub fn new(send: mpsc::Sender<ConditionRequest>) -> ConditionClient {
+        ConditionClient { send }
+    }
+    async fn transact<F>(&self, f: F) -> Result<ConditionReply, String>
+    where
+        F: FnOnce(oneshot::Sender<ConditionReply>) -> ConditionRequest,
+    {
+        let (reply_send, reply_recv) = oneshot::channel();
+        if self.send.send(f(reply_send)).await.is_err() {
+            return Err(String::from("Condition server is not running"));
+        }
+        match reply_recv.await {
+            Ok(ConditionReply::Error(msg)) => Err(msg),
+            Ok(reply) => Ok(reply),
+            Err(_) => Err(String::from("Condition server did not reply")),
+        }
+    }
+    fn processed(reply: Result<ConditionReply, String>) -> Result<(), String> {
+        match reply? {
+            ConditionReply::Processed => Ok(()),
+            _ => Err(String::from("Unexpected reply from condition server")),
+        }
+    }
+    fn names(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| String::from(*s)).collect()
+    }
+
+    pub async fn create_true(&self, name: &str) -> Result<(), String> {
+        Self::processed(
+            self.transact(|reply| ConditionRequest::CreateTrue {
+                name: String::from(name),
+                reply,
+            })
+            .await,
+        )
+    }
+    pub async fn create_false(&self, name: &str) -> Result<(), String> {
+        Self::processed(
+            self.transact(|reply| ConditionRequest::CreateFalse {
+                name: String::from(name),
+                reply,
+            })
+            .await,
+        )
+    }
+    /// Create a cut on the parameter with the id given that
+    /// is true for values in [low, high].
+    pub async fn create_cut(
+        &self,
+        name: &str,
+        parameter_id: u32,
+        low: f64,
+        high: f64,
+    ) -> Result<(), String> {
+        Self::processed(
+            self.transact(|reply| ConditionRequest::CreateCut {
+                name: String::from(name),
+                parameter_id,
+                low,
+                high,
+                reply,
+            })
+            .await,
+        )
+    }
+    pub async fn create_not(&self, name: &str, dependent: &str) -> Result<(), String> {
+        Self::processed(
+            self.transact(|reply| ConditionRequest::CreateNot {
+                name: String::from(name),
+                dependent: String::from(dependent),
+                reply,
+            })
+            .await,
+        )
+    }
+    pub async fn create_and(&self, name: &str, dependents: &[&str]) -> Result<(), String> {
+        Self::processed(
+            self.transact(|reply| ConditionRequest::CreateAnd {
+                name: String::from(name),
+                dependents: Self::names(dependents),
+                reply,
+            })
+            .await,
+        )
+    }
+    pub async fn create_or(&self, name: &str, dependents: &[&str]) -> Result<(), String> {
+        Self::processed(
+            self.transact(|reply| ConditionRequest::CreateOr {
+                name: String::from(name),
+                dependents: Self::names(dependents),
+                reply,
+            })
+            .await,
+        )
+    }
+    pub async fn delete(&self, name: &str) -> Result<(), String> {
+        Self::processed(
+            self.transact(|reply| ConditionRequest::Delete {
+                name: String::from(name),
+                reply,
+            })
+            .await,
+        )
+    }
+    /// Names of the conditions (sorted).
+    pub async fn list(&self) -> Result<Vec<String>, String> {
+        match self
+            .transact(|reply| ConditionRequest::List { reply })
+            .await?
+        {
+            ConditionReply::Listing(names) => Ok(names),
+            _ => Err(String::from("Unexpected reply from condition server")),
+        }
+    }
+}
+
+///
+/// The server side of condition messaging.  The server owns
+/// the condition dictionary.
+///
+pub struct ConditionServer {
+    dict: ConditionDictionary,
+}
+
+impl ConditionServer {
+    pub fn new() -> ConditionServer {
+        ConditionServer {
+            dict: ConditionDictionary::new(),
+        }
+    }
+    pub fn get_dict(&self) -> &ConditionDictionary {
+        &self.dict
+    }
+    pub fn get_dict_mut(&mut self) -> &mut ConditionDictionary {
+        &mut self.dict
+    }
+
+    fn add(&mut self, name: &str, condition: Result<Container, String>) -> ConditionReply {
+        if self.dict.contains_key(name) {
+            return ConditionReply::Error(format!("Condition {} already exists", name));
+        }
+        match condition {
+            Ok(c) => {
+                self.dict.insert(String::from(name), c);
+                ConditionReply::Processed
+            }
+            Err(msg) => ConditionReply::Error(msg),
+        }
+    }
+    fn lookup(&self, name: &str) -> Result<&Container, String> {
+        self.dict
+            .get(name)
+            .ok_or_else(|| format!("No such condition {}", name))
+    }
+    fn dependents(&self, names: &[String]) -> Result<Vec<&Container>, String> {
+        names.iter().map(|n| self.lookup(n)).collect()
+    }
+
+    /// Process a single request and send the reply.
+    pub fn process_request(&mut self, req: ConditionRequest) {
+        match req {
+            ConditionRequest::CreateTrue { name, reply } => {
+                let _ = reply.send(self.add(&name, Ok(Rc::new(RefCell::new(True {})))));
+            }
+            ConditionRequest::CreateFalse { name, reply } => {
+                let _ = reply.send(self.add(&name, Ok(Rc::new(RefCell::new(False {})))));
+            }
+            ConditionRequest::CreateCut {
+                name,
+                parameter_id,
+                low,
+                high,
+                reply,
+            } => {
+                let cut = Cut::new(parameter_id, low, high);
+                let _ = reply.send(self.add(&name, Ok(Rc::new(RefCell::new(cut)))));
+            }
+            ConditionRequest::CreateNot {
+                name,
+                dependent,
+                reply,
+            } => {
+                let not = self
+                    .lookup(&dependent)
+                    .map(|d| Rc::new(RefCell::new(Not::new(d))) as Container);
+                let _ = reply.send(self.add(&name, not));
+            }
+            ConditionRequest::CreateAnd {
+                name,
+                dependents,
+                reply,
+            } => {
+                let and = self.dependents(&dependents).map(|deps| {
+                    let mut and = And::new();
+                    for (name, d) in dependents.iter().zip(deps) {
+                        and.add_condition(name, d);
+                    }
+                    Rc::new(RefCell::new(and)) as Container
+                });
+                let _ = reply.send(self.add(&name, and));
+            }
+            ConditionRequest::CreateOr {
+                name,
+                dependents,
+                reply,
+            } => {
+                let or = self.dependents(&dependents).map(|deps| {
+                    let mut or = Or::new();
+                    for (name, d) in dependents.iter().zip(deps) {
+                        or.add_condition(name, d);
+                    }
+                    Rc::new(RefCell::new(or)) as Container
+                });
+                let _ = reply.send(self.add(&name, or));
+            }
+            ConditionRequest::Delete { name, reply } => {
+                let result = if self.dict.remove(&name).is_some() {
+                    ConditionReply::Processed
+                } else {
+                    ConditionReply::Error(format!("No such condition {}", name))
+                };
+                let _ = reply.send(result);
+            }
+            ConditionRequest::List { reply } => {
+                let mut names: Vec<String> = self.dict.keys().cloned().collect();
+                names.sort();
+                let _ = reply.send(ConditionReply::Listing(names));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod condition_client_tests {
+    use super::*;
+    use crate::messaging::*;
+    use crate::parameters::ParameterDictionary;
+    use std::thread;
+
+    fn start_server() -> (ConditionClient, thread::JoinHandle<()>) {
+        let (spectra, conditions, handle) = start_histogram_server(ParameterDictionary::new());
+        drop(spectra);
+        (conditions, handle)
+    }
+
+    #[test]
+    fn create_1() {
+        let (client, handle) = start_server();
+        assert!(block_on(client.create_true("t")).is_ok());
+        assert!(block_on(client.create_false("f")).is_ok());
+        assert_eq!(
+            vec![String::from("f"), String::from("t")],
+            block_on(client.list()).unwrap()
+        );
+        assert_eq!(
+            String::from("Condition t already exists"),
+            block_on(client.create_false("t")).unwrap_err()
+        );
+        drop(client);
+        handle.join().unwrap();
+    }
+    #[test]
+    fn cut_1() {
+        let (client, handle) = start_server();
+        assert!(block_on(client.create_cut("mycut", 1, 100.0, 200.0)).is_ok());
+        assert!(block_on(client.list())
+            .unwrap()
+            .contains(&String::from("mycut")));
+        drop(client);
+        handle.join().unwrap();
+    }
+    #[test]
+    fn compound_1() {
+        let (client, handle) = start_server();
+        block_on(client.create_cut("c1", 1, 100.0, 200.0)).unwrap();
+        block_on(client.create_cut("c2", 2, 100.0, 200.0)).unwrap();
+        assert!(block_on(client.create_not("n", "c1")).is_ok());
+        assert!(block_on(client.create_and("a", &["c1", "c2"])).is_ok());
+        assert!(block_on(client.create_or("o", &["c1", "c2", "n"])).is_ok());
+        assert_eq!(5, block_on(client.list()).unwrap().len());
+        drop(client);
+        handle.join().unwrap();
+    }
+    #[test]
+    fn compound_2() {
+        // Missing dependencies fail:
+
+        let (client, handle) = start_server();
+        block_on(client.create_cut("c1", 1, 100.0, 200.0)).unwrap();
+        assert_eq!(
+            String::from("No such condition c2"),
+            block_on(client.create_not("n", "c2")).unwrap_err()
+        );
+        assert!(block_on(client.create_and("a", &["c1", "c2"])).is_err());
+        assert!(block_on(client.create_or("o", &["c2"])).is_err());
+        assert_eq!(vec![String::from("c1")], block_on(client.list()).unwrap());
+        drop(client);
+        handle.join().unwrap();
+    }
+    #[test]
+    fn delete_1() {
+        let (client, handle) = start_server();
+        block_on(client.create_true("t")).unwrap();
+        assert!(block_on(client.delete("t")).is_ok());
+        assert!(block_on(client.list()).unwrap().is_empty());
+        assert_eq!(
+            String::from("No such condition t"),
+            block_on(client.delete("t")).unwrap_err()
+        );
+        drop(client);
+        handle.join().unwrap();
+    }
+    #[test]
+    fn server_1() {
+        // Evaluate a condition made through the server:
+
+        let mut server = ConditionServer::new();
+        let (send, mut recv) = mpsc::channel(1);
+        let client = ConditionClient::new(send);
+        let h = thread::spawn(move || block_on(client.create_cut("c", 1, 10.0, 20.0)));
+        server.process_request(recv.blocking_recv().unwrap());
+        assert!(h.join().unwrap().is_ok());
+
+        let mut e = crate::parameters::FlatEvent::new();
+        e.load_event(&vec![crate::parameters::EventParameter::new(1, 15.0)]);
+        assert!(server.get_dict().get("c").unwrap().borrow_mut().check(&e));
+    }
+}
@@ -0,0 +1,194 @@
+//! The histogram server.  This owns the parameter dictionary and
+//! the condition and spectrum servers.  It runs as a tokio task
+//! that uses select! to service  spectrum and condition requests
+//! as they arrive.
+//!
+//! Spectra and conditions are held in Rc/RefCell containers and are
+//! therefore not Send.  The server task is therefore a local task
+//! running on a dedicated thread with its own single threaded
+//! runtime.  The clients, on the other hand, are Send and can be
+//! used from any thread or task.
+//!
+use super::*;
+use crate::parameters::ParameterDictionary;
+use std::thread;
+use tokio::sync::mpsc;
+
+/// Depth of the request queues.   Requesters block when the
+/// queue is full.
+const REQUEST_QUEUE_DEPTH: usize = 100;
+
+pub struct HistogramServer {
+    parameters: ParameterDictionary,
+    spectra: SpectrumServer,
+    conditions: ConditionServer,
+}
+
+impl HistogramServer {
+    pub fn new(parameters: ParameterDictionary) -> HistogramServer {
+        HistogramServer {
+            parameters,
+            spectra: SpectrumServer::new(),
+            conditions: ConditionServer::new(),
+        }
+    }
+    /// Service requests until both request channels are closed.
+    pub async fn run(
+        mut self,
+        mut spectrum_requests: mpsc::Receiver<SpectrumRequest>,
+        mut condition_requests: mpsc::Receiver<ConditionRequest>,
+    ) {
+        loop {
+            tokio::select! {
+                Some(req) = spectrum_requests.recv() => {
+                    self.spectra.process_request(
+                        req, &self.parameters, self.conditions.get_dict_mut()
+                    );
+                }
+                Some(req) = condition_requests.recv() => {
+                    self.conditions.process_request(req);
+                }
+                else => break,
+            }
+        }
+    }
+}
+
+///
+/// Start a histogram server on its own thread.  The server
+/// is given the parameter dictionary.  Returned are clients
+/// for the spectrum and condition requests and the handle of the
+/// thread.  The thread exits when all clients have been dropped.
+///
+pub fn start_histogram_server(
+    parameters: ParameterDictionary,
+) -> (SpectrumClient, ConditionClient, thread::JoinHandle<()>) {
+    let (spectrum_send, spectrum_recv) = mpsc::channel(REQUEST_QUEUE_DEPTH);
+    let (condition_send, condition_recv) = mpsc::channel(REQUEST_QUEUE_DEPTH);
+
+    let handle = thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .expect("Unable to create histogram server runtime");
+        let local = tokio::task::LocalSet::new();
+        local.block_on(&runtime, async move {
+            let server = HistogramServer::new(parameters);
+            tokio::task::spawn_local(server.run(spectrum_recv, condition_recv))
+                .await
+                .expect("Histogram server task failed");
+        });
+    });
+    (
+        SpectrumClient::new(spectrum_send),
+        ConditionClient::new(condition_send),
+        handle,
+    )
+}
+
+#[cfg(test)]
+mod histogram_server_tests {
+    use super::*;
+    use crate::parameters::EventParameter;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn fill_1() {
+        // Five tasks concurrently fill ten spectra.
+
+        let mut pdict = ParameterDictionary::new();
+        for i in 0..10 {
+            pdict.add(&format!("p{}", i)).unwrap();
+        }
+        let ids: Vec<u32> = (0..10)
+            .map(|i| pdict.lookup(&format!("p{}", i)).unwrap().get_id())
+            .collect();
+        let (spectra, conditions, handle) = start_histogram_server(pdict);
+        for i in 0..10 {
+            spectra
+                .create_1d(&format!("s{}", i), &format!("p{}", i), 0.0, 100.0, 100)
+                .await
+                .unwrap();
+        }
+
+        let mut tasks = Vec::new();
+        for t in 0..5 {
+            let client = spectra.clone();
+            let ids = ids.clone();
+            tasks.push(tokio::spawn(async move {
+                for batch in 0..10 {
+                    let events = (0..100)
+                        .map(|i| {
+                            ids.iter()
+                                .map(|id| EventParameter::new(*id, ((t + batch + i) % 100) as f64))
+                                .collect()
+                        })
+                        .collect();
+                    client.process_events(events).await.unwrap();
+                }
+            }));
+        }
+        for t in tasks {
+            t.await.unwrap();
+        }
+
+        // 5 tasks * 10 batches * 100 events each:
+
+        for i in 0..10 {
+            let total: f64 = spectra
+                .get_contents(&format!("s{}", i))
+                .await
+                .unwrap()
+                .iter()
+                .map(|c| c.value)
+                .sum();
+            assert_eq!(5000.0, total);
+        }
+        drop(spectra);
+        drop(conditions);
+        tokio::task::spawn_blocking(move || handle.join().unwrap())
+            .await
+            .unwrap();
+    }
+    #[tokio::test]
+    async fn gated_1() {
+        // Each event is checked against the gate, not the cached
+        // result of the first event:
+
+        let mut pdict = ParameterDictionary::new();
+        pdict.add("p").unwrap();
+        let id = pdict.lookup("p").unwrap().get_id();
+        let (spectra, conditions, handle) = start_histogram_server(pdict);
+        conditions.create_cut("low", id, 0.0, 10.0).await.unwrap();
+        spectra.create_1d("s", "p", 0.0, 100.0, 100).await.unwrap();
+        spectra.gate("s", "low").await.unwrap();
+
+        let events = vec![
+            vec![EventParameter::new(id, 5.0)],
+            vec![EventParameter::new(id, 50.0)],
+            vec![EventParameter::new(id, 60.0)],
+        ];
+        spectra.process_events(events).await.unwrap();
+        let total: f64 = spectra
+            .get_contents("s")
+            .await
+            .unwrap()
+            .iter()
+            .map(|c| c.value)
+            .sum();
+        assert_eq!(1.0, total);
+
+        drop(spectra);
+        drop(conditions);
+        tokio::task::spawn_blocking(move || handle.join().unwrap())
+            .await
+            .unwrap();
+    }
+    #[test]
+    fn shutdown_1() {
+        // Dropping the clients stops the server:
+
+        let (spectra, conditions, handle) = start_histogram_server(ParameterDictionary::new());
+        drop(spectra);
+        drop(conditions);
+        handle.join().unwrap();
+    }
+}
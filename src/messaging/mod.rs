@@ -0,0 +1,44 @@
+//!  The messaging module defines the interfaces used by clients
+//!  of the histogramming server.  The histogramming server owns
+//!  the parameter, condition and spectrum dictionaries.  Clients
+//!  in other threads manipulate these by sending requests over a
+//!  channel.  Each request carries the sending side of a reply
+//!  channel the server uses to return the result.
+//!
+//!  Rather than having clients build requests and reply channels
+//!  by hand, each set of requests has a client struct that wraps
+//!  the channel to the server.  The client methods build the
+//!  request and its reply channel, send it and wait for the reply.
+//!
+//!  Spectrum and condition requests use tokio channels and their
+//!  client methods are async.  Callers that are not running in
+//!  an async context can use block_on to wait for them.
+//!  Parameter requests use std::sync::mpsc and block.
+//!
+//!  The server side of each set of requests is a struct that
+//!  owns (or is given) the dictionaries it needs and dispatches
+//!  requests to them.  The histogram_server submodule combines the
+//!  spectrum and condition servers into a single task.
+//!
+use std::future::Future;
+
+pub mod spectrum_messages;
+pub use spectrum_messages::*;
+pub mod condition_messages;
+pub use condition_messages::*;
+pub mod parameter_messages;
+pub use parameter_messages::*;
+pub mod histogram_server;
+pub use histogram_server::*;
+
+///
+/// Run a future to completion from synchronous code.  This must not
+/// be called from within an async context.
+///
+pub fn block_on<F: Future>(f: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("Unable to create a runtime for block_on")
+        .block_on(f)
+}
@@ -0,0 +1,329 @@
+//! Parameter requests and their client/server wrappers.
+//!
+//! Clients use a ParameterClient to make requests of a
+//! ParameterServer which owns the ParameterDictionary.
+//! The requests map to the Parameter and ParameterDictionary
+//! methods.
+//!
+use crate::parameters::*;
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+/// Replies from the parameter server:
+///
+pub enum ParameterReply {
+    Error(String),
+    Processed,
+    Defined(u32),
+    Listing(Vec<Parameter>),
+}
+
+/// Requests to the parameter server.
+///
+pub enum ParameterRequest {
+    Define {
+        name: String,
+        reply: Sender<ParameterReply>,
+    },
+    Undefine {
+        name: String,
+        reply: Sender<ParameterReply>,
+    },
+    List {
+        reply: Sender<ParameterReply>,
+    },
+    SetLimits {
+        name: String,
+        low: f64,
+        high: f64,
+        reply: Sender<ParameterReply>,
+    },
+    SetBins {
+        name: String,
+        bins: u32,
+        reply: Sender<ParameterReply>,
+    },
+    SetUnits {
+        name: String,
+        units: String,
+        reply: Sender<ParameterReply>,
+    },
+    SetDescription {
+        name: String,
+        description: String,
+        reply: Sender<ParameterReply>,
+    },
+}
+
+///
+/// The client side of parameter messaging.
+///
+pub struct ParameterClient {
+    send: Sender<ParameterRequest>,
+}
+
+impl ParameterClient {
+    pub fn new(send: Sender<ParameterRequest>) -> ParameterClient {
+        ParameterClient { send }
+    }
+    fn transact<F>(&self, f: F) -> Result<ParameterReply, String>
+    where
+        F: FnOnce(Sender<ParameterReply>) -> ParameterRequest,
+    {
+        let (reply_send, reply_recv) = channel();
+        if self.send.send(f(reply_send)).is_err() {
+            return Err(String::from("Parameter server is not running"));
+        }
+        match reply_recv.recv() {
+            Ok(ParameterReply::Error(msg)) => Err(msg),
+            Ok(reply) => Ok(reply),
+            Err(_) => Err(String::from("Parameter server did not reply")),
+        }
+    }
+    fn processed(&self, reply: Result<ParameterReply, String>) -> Result<(), String> {
+        match reply? {
+            ParameterReply::Processed => Ok(()),
+            _ => Err(String::from("Unexpected reply from parameter server")),
+        }
+    }
+
+    /// Define a new parameter, returning the id it was assigned.
+    pub fn define(&self, name: &str) -> Result<u32, String> {
+        match self.transact(|reply| ParameterRequest::Define {
+            name: String::from(name),
+            reply,
+        })? {
+            ParameterReply::Defined(id) => Ok(id),
+            _ => Err(String::from("Unexpected reply from parameter server")),
+        }
+    }
+    /// Request a parameter be undefined.  Parameters are permanent
+    /// (their ids may be in use by spectra and conditions) so the
+    /// server refuses this.
+    pub fn undefine(&self, name: &str) -> Result<(), String> {
+        self.processed(self.transact(|reply| ParameterRequest::Undefine {
+            name: String::from(name),
+            reply,
+        }))
+    }
+    /// The parameter definitions sorted by id.
+    pub fn list(&self) -> Result<Vec<Parameter>, String> {
+        match self.transact(|reply| ParameterRequest::List { reply })? {
+            ParameterReply::Listing(params) => Ok(params),
+            _ => Err(String::from("Unexpected reply from parameter server")),
+        }
+    }
+    pub fn set_limits(&self, name: &str, low: f64, high: f64) -> Result<(), String> {
+        self.processed(self.transact(|reply| ParameterRequest::SetLimits {
+            name: String::from(name),
+            low,
+            high,
+            reply,
+        }))
+    }
+    pub fn set_bins(&self, name: &str, bins: u32) -> Result<(), String> {
+        self.processed(self.transact(|reply| ParameterRequest::SetBins {
+            name: String::from(name),
+            bins,
+            reply,
+        }))
+    }
+    pub fn set_units(&self, name: &str, units: &str) -> Result<(), String> {
+        self.processed(self.transact(|reply| ParameterRequest::SetUnits {
+            name: String::from(name),
+            units: String::from(units),
+            reply,
+        }))
+    }
+    pub fn set_description(&self, name: &str, description: &str) -> Result<(), String> {
+        self.processed(self.transact(|reply| ParameterRequest::SetDescription {
+            name: String::from(name),
+            description: String::from(description),
+            reply,
+        }))
+    }
+}
+
+///
+/// The server side of parameter messaging.  It owns the
+/// parameter dictionary.
+///
+pub struct ParameterServer {
+    dict: ParameterDictionary,
+}
+
+impl ParameterServer {
+    pub fn new() -> ParameterServer {
+        ParameterServer {
+            dict: ParameterDictionary::new(),
+        }
+    }
+    pub fn get_dict(&self) -> &ParameterDictionary {
+        &self.dict
+    }
+    // Apply f to the named parameter:
+
+    fn modify<F>(&mut self, name: &str, f: F) -> ParameterReply
+    where
+        F: FnOnce(&mut Parameter),
+    {
+        if let Some(p) = self.dict.lookup_mut(name) {
+            f(p);
+            ParameterReply::Processed
+        } else {
+            ParameterReply::Error(format!("No such parameter: {}", name))
+        }
+    }
+
+    /// Process a single request and send the reply.
+    pub fn process_request(&mut self, req: ParameterRequest) {
+        match req {
+            ParameterRequest::Define { name, reply } => {
+                let result = match self.dict.add(&name) {
+                    Ok(_) => ParameterReply::Defined(self.dict.lookup(&name).unwrap().get_id()),
+                    Err(msg) => ParameterReply::Error(msg),
+                };
+                let _ = reply.send(result);
+            }
+            ParameterRequest::Undefine { name, reply } => {
+                let result = if self.dict.lookup(&name).is_some() {
+                    ParameterReply::Error(format!("Parameter {} cannot be undefined", name))
+                } else {
+                    ParameterReply::Error(format!("No such parameter: {}", name))
+                };
+                let _ = reply.send(result);
+            }
+            ParameterRequest::List { reply } => {
+                let mut params: Vec<Parameter> = self.dict.iter().map(|(_, p)| p.clone()).collect();
+                params.sort_by_key(|p| p.get_id());
+                let _ = reply.send(ParameterReply::Listing(params));
+            }
+            ParameterRequest::SetLimits {
+                name,
+                low,
+                high,
+                reply,
+            } => {
+                let _ = reply.send(self.modify(&name, |p| {
+                    p.set_limits(low, high);
+                }));
+            }
+            ParameterRequest::SetBins { name, bins, reply } => {
+                let _ = reply.send(self.modify(&name, |p| {
+                    p.set_bins(bins);
+                }));
+            }
+            ParameterRequest::SetUnits { name, units, reply } => {
+                let _ = reply.send(self.modify(&name, |p| {
+                    p.set_units(&units);
+                }));
+            }
+            ParameterRequest::SetDescription {
+                name,
+                description,
+                reply,
+            } => {
+                let _ = reply.send(self.modify(&name, |p| {
+                    p.set_description(&description);
+                }));
+            }
+        }
+    }
+    /// Process requests until all clients have gone away.
+    pub fn run(&mut self, requests: Receiver<ParameterRequest>) {
+        for req in requests.iter() {
+            self.process_request(req);
+        }
+    }
+}
+
+#[cfg(test)]
+mod parameter_client_tests {
+    use super::*;
+    use std::thread;
+
+    fn start_server() -> (ParameterClient, thread::JoinHandle<()>) {
+        let (send, recv) = channel();
+        let handle = thread::spawn(move || {
+            let mut server = ParameterServer::new();
+            server.run(recv);
+        });
+        (ParameterClient::new(send), handle)
+    }
+
+    #[test]
+    fn define_1() {
+        // Ids are unique and sequential:
+
+        let (client, handle) = start_server();
+        let mut ids = Vec::new();
+        for i in 0..10 {
+            ids.push(client.define(&format!("param.{}", i)).unwrap());
+        }
+        for i in 1..ids.len() {
+            assert_eq!(ids[i - 1] + 1, ids[i]);
+        }
+        assert_eq!(
+            String::from("Duplicate parameter"),
+            client.define("param.0").unwrap_err()
+        );
+        drop(client);
+        handle.join().unwrap();
+    }
+    #[test]
+    fn list_1() {
+        let (client, handle) = start_server();
+        for i in 0..10 {
+            client.define(&format!("param.{}", i)).unwrap();
+        }
+        let params = client.list().unwrap();
+        assert_eq!(10, params.len());
+        for (i, p) in params.iter().enumerate() {
+            assert_eq!(format!("param.{}", i), p.get_name());
+        }
+        drop(client);
+        handle.join().unwrap();
+    }
+    #[test]
+    fn set_1() {
+        let (client, handle) = start_server();
+        client.define("p").unwrap();
+        assert!(client.set_limits("p", -1.0, 1.0).is_ok());
+        assert!(client.set_bins("p", 200).is_ok());
+        assert!(client.set_units("p", "cm").is_ok());
+        assert!(client.set_description("p", "A parameter").is_ok());
+
+        let p = &client.list().unwrap()[0];
+        assert_eq!((Some(-1.0), Some(1.0)), p.get_limits());
+        assert_eq!(Some(200), p.get_bins());
+        assert_eq!(Some(String::from("cm")), p.get_units());
+        assert_eq!(Some(String::from("A parameter")), p.get_description());
+        drop(client);
+        handle.join().unwrap();
+    }
+    #[test]
+    fn set_2() {
+        let (client, handle) = start_server();
+        assert_eq!(
+            String::from("No such parameter: p"),
+            client.set_limits("p", -1.0, 1.0).unwrap_err()
+        );
+        assert!(client.set_bins("p", 1).is_err());
+        assert!(client.set_units("p", "cm").is_err());
+        assert!(client.set_description("p", "x").is_err());
+        drop(client);
+        handle.join().unwrap();
+    }
+    #[test]
+    fn undefine_1() {
+        let (client, handle) = start_server();
+        client.define("p").unwrap();
+        assert_eq!(
+            String::from("Parameter p cannot be undefined"),
+            client.undefine("p").unwrap_err()
+        );
+        assert!(client.undefine("nosuch").is_err());
+        assert_eq!(1, client.list().unwrap().len());
+        drop(client);
+        handle.join().unwrap();
+    }
+}
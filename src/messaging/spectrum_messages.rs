@@ -0,0 +1,687 @@
+//! Spectrum requests and their client/server wrappers.
+//!
+//! Clients use a SpectrumClient to make requests of a
+//! SpectrumServer which maintains a SpectrumStorage.
+//! Creating and gating spectra require the parameter and condition
+//! dictionaries.   These are not owned by the spectrum server but are
+//! passed in when requests are processed.
+//!
+//! Requests are sent over a tokio mpsc channel and replies come back
+//! on a oneshot channel so the client methods are async.
+//!
+//! The server keeps a generation number for each spectrum that
+//! increases whenever the spectrum may have changed, so clients that
+//! poll spectra (e.g. the WebSocket server) can avoid fetching
+//! contents that haven't changed.
+//!
+use crate::conditions::{invalidate_cache, ConditionDictionary};
+use crate::parameters::{Event, ParameterDictionary};
+use crate::spectra::*;
+use ndhistogram::Histogram;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use tokio::sync::{mpsc, oneshot};
+
+/// A non-empty channel of a spectrum.  x, y are the low edges
+/// of the bin.  For 1-d spectra, y is 0.0
+///
+#[derive(Debug, PartialEq, Clone, Copy, Serialize)]
+pub struct Channel {
+    pub x: f64,
+    pub y: f64,
+    pub value: f64,
+}
+
+/// Replies from the spectrum server:
+///
+pub enum SpectrumReply {
+    Error(String),
+    Processed,
+    Listing(Vec<String>),
+    Contents(Vec<Channel>),
+    Generation(u64),
+}
+
+/// Requests to the spectrum server.  Each carries the fields
+/// needed by the request and a channel on which the reply is sent.
+///
+pub enum SpectrumRequest {
+    Create1D {
+        name: String,
+        parameter: String,
+        low: f64,
+        high: f64,
+        bins: u32,
+        reply: oneshot::Sender<SpectrumReply>,
+    },
+    Create2D {
+        name: String,
+        xparameter: String,
+        yparameter: String,
+        xlow: f64,
+        xhigh: f64,
+        xbins: u32,
+        ylow: f64,
+        yhigh: f64,
+        ybins: u32,
+        reply: oneshot::Sender<SpectrumReply>,
+    },
+    Delete {
+        name: String,
+        reply: oneshot::Sender<SpectrumReply>,
+    },
+    Gate {
+        spectrum: String,
+        gate: String,
+        reply: oneshot::Sender<SpectrumReply>,
+    },
+    Ungate {
+        name: String,
+        reply: oneshot::Sender<SpectrumReply>,
+    },
+    List {
+        reply: oneshot::Sender<SpectrumReply>,
+    },
+    Clear {
+        name: String,
+        reply: oneshot::Sender<SpectrumReply>,
+    },
+    Events {
+        events: Vec<Event>,
+        reply: oneshot::Sender<SpectrumReply>,
+    },
+    Contents {
+        name: String,
+        reply: oneshot::Sender<SpectrumReply>,
+    },
+    Generation {
+        name: String,
+        reply: oneshot::Sender<SpectrumReply>,
+    },
+}
+
+///
+/// The client side of the spectrum messaging.  Methods are async
+/// and complete when the server replies.  A closed channel
+/// is reported as an error.  Non-async callers can use
+/// messaging::block_on to wait for the results.
+/// Clients can be cloned so that several tasks can talk to the
+/// same server.
+///
+#[derive(Clone)]
+pub struct SpectrumClient {
+    send: mpsc::Sender<SpectrumRequest>,
+}
+
+impl SpectrumClient {
+    pub fn new(send: mpsc::Sender<SpectrumRequest>) -> SpectrumClient {
+        SpectrumClient { send }
+    }
+    // Send a request built by f around the reply sender and wait
+    // for the reply.
+
+    async fn transact<F>(&self, f: F) -> Result<SpectrumReply, String>
+    where
+        F: FnOnce(oneshot::Sender<SpectrumReply>) -> SpectrumRequest,
+    {
+        let (reply_send, reply_recv) = oneshot::channel();
+        if self.send.send(f(reply_send)).await.is_err() {
+            return Err(String::from("Spectrum server is not running"));
+        }
+        match reply_recv.await {
+            Ok(SpectrumReply::Error(msg)) => Err(msg),
+            Ok(reply) => Ok(reply),
+            Err(_) => Err(String::from("Spectrum server did not reply")),
+        }
+    }
+    fn processed(reply: Result<SpectrumReply, String>) -> Result<(), String> {
+        match reply? {
+            SpectrumReply::Processed => Ok(()),
+            _ => Err(String::from("Unexpected reply from spectrum server")),
+        }
+    }
+
+    /// Create a 1-d spectrum on parameter.
+    pub async fn create_1d(
+        &self,
+        name: &str,
+        parameter: &str,
+        low: f64,
+        high: f64,
+        bins: u32,
+    ) -> Result<(), String> {
+        Self::processed(
+            self.transact(|reply| SpectrumRequest::Create1D {
+                name: String::from(name),
+                parameter: String::from(parameter),
+                low,
+                high,
+                bins,
+                reply,
+            })
+            .await,
+        )
+    }
+    /// Create a 2-d spectrum with xparameter on the x axis and
+    /// yparameter on the y axis.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_2d(
+        &self,
+        name: &str,
+        xparameter: &str,
+        yparameter: &str,
+        xlow: f64,
+        xhigh: f64,
+        xbins: u32,
+        ylow: f64,
+        yhigh: f64,
+        ybins: u32,
+    ) -> Result<(), String> {
+        Self::processed(
+            self.transact(|reply| SpectrumRequest::Create2D {
+                name: String::from(name),
+                xparameter: String::from(xparameter),
+                yparameter: String::from(yparameter),
+                xlow,
+                xhigh,
+                xbins,
+                ylow,
+                yhigh,
+                ybins,
+                reply,
+            })
+            .await,
+        )
+    }
+    /// Delete a spectrum.
+    pub async fn delete(&self, name: &str) -> Result<(), String> {
+        Self::processed(
+            self.transact(|reply| SpectrumRequest::Delete {
+                name: String::from(name),
+                reply,
+            })
+            .await,
+        )
+    }
+    /// Apply a gate to a spectrum.
+    pub async fn gate(&self, spectrum: &str, gate: &str) -> Result<(), String> {
+        Self::processed(
+            self.transact(|reply| SpectrumRequest::Gate {
+                spectrum: String::from(spectrum),
+                gate: String::from(gate),
+                reply,
+            })
+            .await,
+        )
+    }
+    /// Remove the gate from a spectrum.
+    pub async fn ungate(&self, name: &str) -> Result<(), String> {
+        Self::processed(
+            self.transact(|reply| SpectrumRequest::Ungate {
+                name: String::from(name),
+                reply,
+            })
+            .await,
+        )
+    }
+    /// Get the names of the spectra (sorted).
+    pub async fn list(&self) -> Result<Vec<String>, String> {
+        match self
+            .transact(|reply| SpectrumRequest::List { reply })
+            .await?
+        {
+            SpectrumReply::Listing(names) => Ok(names),
+            _ => Err(String::from("Unexpected reply from spectrum server")),
+        }
+    }
+    /// Clear the counts in a spectrum.
+    pub async fn clear(&self, name: &str) -> Result<(), String> {
+        Self::processed(
+            self.transact(|reply| SpectrumRequest::Clear {
+                name: String::from(name),
+                reply,
+            })
+            .await,
+        )
+    }
+    /// Histogram a batch of events.
+    pub async fn process_events(&self, events: Vec<Event>) -> Result<(), String> {
+        Self::processed(
+            self.transact(|reply| SpectrumRequest::Events { events, reply })
+                .await,
+        )
+    }
+    /// Get the non-zero channels of a spectrum.
+    pub async fn get_contents(&self, name: &str) -> Result<Vec<Channel>, String> {
+        match self
+            .transact(|reply| SpectrumRequest::Contents {
+                name: String::from(name),
+                reply,
+            })
+            .await?
+        {
+            SpectrumReply::Contents(channels) => Ok(channels),
+            _ => Err(String::from("Unexpected reply from spectrum server")),
+        }
+    }
+    /// Get the generation of a spectrum.  If this is the same as
+    /// an earlier generation, the spectrum has not changed since.
+    pub async fn generation(&self, name: &str) -> Result<u64, String> {
+        match self
+            .transact(|reply| SpectrumRequest::Generation {
+                name: String::from(name),
+                reply,
+            })
+            .await?
+        {
+            SpectrumReply::Generation(generation) => Ok(generation),
+            _ => Err(String::from("Unexpected reply from spectrum server")),
+        }
+    }
+}
+
+///
+/// The server side of spectrum messaging.  The server owns
+/// the spectrum storage and the spectrum generations.  Events
+/// change the generation of all spectra since the storage
+/// doesn't say which spectra an event incremented.
+///
+pub struct SpectrumServer {
+    spectra: SpectrumStorage,
+    generation: u64,
+    generations: HashMap<String, u64>,
+}
+
+impl SpectrumServer {
+    pub fn new() -> SpectrumServer {
+        SpectrumServer {
+            spectra: SpectrumStorage::new(),
+            generation: 0,
+            generations: HashMap::new(),
+        }
+    }
+    pub fn get_spectra(&self) -> &SpectrumStorage {
+        &self.spectra
+    }
+    pub fn get_spectra_mut(&mut self) -> &mut SpectrumStorage {
+        &mut self.spectra
+    }
+
+    fn add(&mut self, name: &str, spectrum: Result<SpectrumContainer, String>) -> SpectrumReply {
+        if self.spectra.get(name).is_some() {
+            return SpectrumReply::Error(format!("Spectrum {} already exists", name));
+        }
+        match spectrum {
+            Ok(s) => {
+                self.spectra.add(s);
+                self.changed(name);
+                SpectrumReply::Processed
+            }
+            Err(msg) => SpectrumReply::Error(msg),
+        }
+    }
+    fn with_spectrum<F>(&self, name: &str, f: F) -> SpectrumReply
+    where
+        F: FnOnce(&SpectrumContainer) -> SpectrumReply,
+    {
+        if let Some(s) = self.spectra.get(name) {
+            f(s)
+        } else {
+            SpectrumReply::Error(format!("No such spectrum {}", name))
+        }
+    }
+    // Give a spectrum a new generation:
+
+    fn changed(&mut self, name: &str) {
+        self.generation += 1;
+        self.generations.insert(String::from(name), self.generation);
+    }
+    // Non zero channels of a spectrum, excluding under/overflows.
+
+    fn contents(spectrum: &SpectrumContainer) -> Vec<Channel> {
+        let mut result = Vec::new();
+        let s = spectrum.borrow();
+        if let Some(h) = s.get_histogram_1d() {
+            for c in h.borrow().iter() {
+                if let (Some(x), v) = (c.bin.start(), c.value.get()) {
+                    if c.bin.end().is_some() && v != 0.0 {
+                        result.push(Channel {
+                            x,
+                            y: 0.0,
+                            value: v,
+                        });
+                    }
+                }
+            }
+        } else if let Some(h) = s.get_histogram_2d() {
+            for c in h.borrow().iter() {
+                let (x, y) = c.bin;
+                if let (Some(xl), Some(_), Some(yl), Some(_)) =
+                    (x.start(), x.end(), y.start(), y.end())
+                {
+                    if c.value.get() != 0.0 {
+                        result.push(Channel {
+                            x: xl,
+                            y: yl,
+                            value: c.value.get(),
+                        });
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// Process a single request and send the reply.
+    /// Failure to send the reply just means the client went away
+    /// so it is ignored.  The condition caches are invalidated
+    /// before each event is processed.
+    ///
+    pub fn process_request(
+        &mut self,
+        req: SpectrumRequest,
+        pdict: &ParameterDictionary,
+        cdict: &mut ConditionDictionary,
+    ) {
+        match req {
+            SpectrumRequest::Create1D {
+                name,
+                parameter,
+                low,
+                high,
+                bins,
+                reply,
+            } => {
+                let spectrum =
+                    Oned::new(&name, &parameter, pdict, Some(low), Some(high), Some(bins))
+                        .map(|s| Rc::new(RefCell::new(s)) as SpectrumContainer);
+                let _ = reply.send(self.add(&name, spectrum));
+            }
+            SpectrumRequest::Create2D {
+                name,
+                xparameter,
+                yparameter,
+                xlow,
+                xhigh,
+                xbins,
+                ylow,
+                yhigh,
+                ybins,
+                reply,
+            } => {
+                let spectrum = Twod::new(
+                    &name,
+                    &xparameter,
+                    &yparameter,
+                    pdict,
+                    Some(xlow),
+                    Some(xhigh),
+                    Some(xbins),
+                    Some(ylow),
+                    Some(yhigh),
+                    Some(ybins),
+                )
+                .map(|s| Rc::new(RefCell::new(s)) as SpectrumContainer);
+                let _ = reply.send(self.add(&name, spectrum));
+            }
+            SpectrumRequest::Delete { name, reply } => {
+                let result = if self.spectra.remove(&name).is_some() {
+                    self.generations.remove(&name);
+                    SpectrumReply::Processed
+                } else {
+                    SpectrumReply::Error(format!("No such spectrum {}", name))
+                };
+                let _ = reply.send(result);
+            }
+            SpectrumRequest::Gate {
+                spectrum,
+                gate,
+                reply,
+            } => {
+                let _ = reply.send(self.with_spectrum(&spectrum, |s| {
+                    match s.borrow_mut().gate(&gate, cdict) {
+                        Ok(()) => SpectrumReply::Processed,
+                        Err(msg) => SpectrumReply::Error(msg),
+                    }
+                }));
+            }
+            SpectrumRequest::Ungate { name, reply } => {
+                let _ = reply.send(self.with_spectrum(&name, |s| {
+                    s.borrow_mut().ungate();
+                    SpectrumReply::Processed
+                }));
+            }
+            SpectrumRequest::List { reply } => {
+                let mut names: Vec<String> = self.spectra.iter().map(|(k, _)| k.clone()).collect();
+                names.sort();
+                let _ = reply.send(SpectrumReply::Listing(names));
+            }
+            SpectrumRequest::Clear { name, reply } => {
+                let result = self.with_spectrum(&name, |s| {
+                    s.borrow_mut().clear();
+                    SpectrumReply::Processed
+                });
+                if matches!(result, SpectrumReply::Processed) {
+                    self.changed(&name);
+                }
+                let _ = reply.send(result);
+            }
+            SpectrumRequest::Events { events, reply } => {
+                for e in events.iter() {
+                    invalidate_cache(cdict);
+                    self.spectra.process_event(e);
+                }
+                if !events.is_empty() {
+                    self.generation += 1;
+                    for (name, _) in self.spectra.iter() {
+                        self.generations.insert(name.clone(), self.generation);
+                    }
+                }
+                let _ = reply.send(SpectrumReply::Processed);
+            }
+            SpectrumRequest::Contents { name, reply } => {
+                let _ = reply.send(
+                    self.with_spectrum(&name, |s| SpectrumReply::Contents(Self::contents(s))),
+                );
+            }
+            SpectrumRequest::Generation { name, reply } => {
+                let generation = self.generations.get(&name).copied().unwrap_or(0);
+                let _ = reply
+                    .send(self.with_spectrum(&name, |_| SpectrumReply::Generation(generation)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod spectrum_client_tests {
+    use super::*;
+    use crate::messaging::*;
+    use crate::parameters::EventParameter;
+    use std::thread;
+
+    // Start a histogram server with parameters p1, p2 and a True
+    // condition named "true".  Returns the client and the
+    // join handle.
+
+    fn start_server() -> (SpectrumClient, thread::JoinHandle<()>) {
+        let mut pdict = ParameterDictionary::new();
+        pdict.add("p1").unwrap();
+        pdict.add("p2").unwrap();
+        let (client, conditions, handle) = start_histogram_server(pdict);
+        block_on(conditions.create_true("true")).unwrap();
+        (client, handle)
+    }
+
+    #[test]
+    fn create1d_1() {
+        let (client, handle) = start_server();
+        assert!(block_on(client.create_1d("s1", "p1", 0.0, 1024.0, 1024)).is_ok());
+        assert_eq!(vec![String::from("s1")], block_on(client.list()).unwrap());
+        drop(client);
+        handle.join().unwrap();
+    }
+    #[test]
+    fn create1d_2() {
+        // Bad parameter and duplicate names:
+
+        let (client, handle) = start_server();
+        assert_eq!(
+            String::from("No such parameter: nosuch"),
+            block_on(client.create_1d("s1", "nosuch", 0.0, 1024.0, 1024)).unwrap_err()
+        );
+        block_on(client.create_1d("s1", "p1", 0.0, 1024.0, 1024)).unwrap();
+        assert_eq!(
+            String::from("Spectrum s1 already exists"),
+            block_on(client.create_1d("s1", "p2", 0.0, 1024.0, 1024)).unwrap_err()
+        );
+        drop(client);
+        handle.join().unwrap();
+    }
+    #[test]
+    fn create2d_1() {
+        let (client, handle) = start_server();
+        assert!(
+            block_on(client.create_2d("s2", "p1", "p2", 0.0, 512.0, 512, 0.0, 512.0, 512)).is_ok()
+        );
+        assert!(block_on(
+            client.create_2d("bad", "p1", "nosuch", 0.0, 512.0, 512, 0.0, 512.0, 512)
+        )
+        .is_err());
+        assert_eq!(vec![String::from("s2")], block_on(client.list()).unwrap());
+        drop(client);
+        handle.join().unwrap();
+    }
+    #[test]
+    fn delete_1() {
+        let (client, handle) = start_server();
+        block_on(client.create_1d("s1", "p1", 0.0, 1024.0, 1024)).unwrap();
+        block_on(client.create_1d("s2", "p2", 0.0, 1024.0, 1024)).unwrap();
+        assert!(block_on(client.delete("s1")).is_ok());
+        assert_eq!(vec![String::from("s2")], block_on(client.list()).unwrap());
+        assert_eq!(
+            String::from("No such spectrum s1"),
+            block_on(client.delete("s1")).unwrap_err()
+        );
+        drop(client);
+        handle.join().unwrap();
+    }
+    #[test]
+    fn gate_1() {
+        let (client, handle) = start_server();
+        block_on(client.create_1d("s1", "p1", 0.0, 1024.0, 1024)).unwrap();
+        assert!(block_on(client.gate("s1", "true")).is_ok());
+        assert_eq!(
+            String::from("No such gate nosuch"),
+            block_on(client.gate("s1", "nosuch")).unwrap_err()
+        );
+        assert!(block_on(client.gate("nosuch", "true")).is_err());
+        assert!(block_on(client.ungate("s1")).is_ok());
+        assert!(block_on(client.ungate("nosuch")).is_err());
+        drop(client);
+        handle.join().unwrap();
+    }
+    #[test]
+    fn clear_1() {
+        let (client, handle) = start_server();
+        block_on(client.create_1d("s1", "p1", 0.0, 1024.0, 1024)).unwrap();
+        block_on(client.process_events(vec![vec![EventParameter::new(1, 10.0)]])).unwrap();
+        assert_eq!(1, block_on(client.get_contents("s1")).unwrap().len());
+        assert!(block_on(client.clear("s1")).is_ok());
+        assert!(block_on(client.get_contents("s1")).unwrap().is_empty());
+        assert!(block_on(client.clear("nosuch")).is_err());
+        drop(client);
+        handle.join().unwrap();
+    }
+    #[test]
+    fn list_1() {
+        // Sorted list:
+
+        let (client, handle) = start_server();
+        for name in ["c", "a", "b"] {
+            block_on(client.create_1d(name, "p1", 0.0, 1024.0, 1024)).unwrap();
+        }
+        assert_eq!(
+            vec![String::from("a"), String::from("b"), String::from("c")],
+            block_on(client.list()).unwrap()
+        );
+        drop(client);
+        handle.join().unwrap();
+    }
+    #[test]
+    fn contents_1() {
+        let (client, handle) = start_server();
+        block_on(client.create_1d("s1", "p1", 0.0, 1024.0, 1024)).unwrap();
+        block_on(client.create_2d("s2", "p1", "p2", 0.0, 512.0, 512, 0.0, 512.0, 512)).unwrap();
+        let events = vec![
+            vec![EventParameter::new(1, 10.0), EventParameter::new(2, 20.0)],
+            vec![EventParameter::new(1, 10.0), EventParameter::new(2, 20.0)],
+            vec![EventParameter::new(1, 2000.0)], // overflow.
+        ];
+        block_on(client.process_events(events)).unwrap();
+
+        assert_eq!(
+            vec![Channel {
+                x: 10.0,
+                y: 0.0,
+                value: 2.0
+            }],
+            block_on(client.get_contents("s1")).unwrap()
+        );
+        assert_eq!(
+            vec![Channel {
+                x: 10.0,
+                y: 20.0,
+                value: 2.0
+            }],
+            block_on(client.get_contents("s2")).unwrap()
+        );
+        assert!(block_on(client.get_contents("nosuch")).is_err());
+        drop(client);
+        handle.join().unwrap();
+    }
+    #[test]
+    fn generation_1() {
+        // Changes with creation, events and clears only:
+
+        let (client, handle) = start_server();
+        block_on(client.create_1d("s1", "p1", 0.0, 1024.0, 1024)).unwrap();
+        let created = block_on(client.generation("s1")).unwrap();
+        assert!(created > 0);
+        block_on(client.gate("s1", "true")).unwrap();
+        assert_eq!(created, block_on(client.generation("s1")).unwrap());
+
+        block_on(client.process_events(vec![vec![EventParameter::new(1, 10.0)]])).unwrap();
+        let filled = block_on(client.generation("s1")).unwrap();
+        assert!(filled > created);
+        block_on(client.process_events(vec![])).unwrap();
+        assert_eq!(filled, block_on(client.generation("s1")).unwrap());
+
+        block_on(client.clear("s1")).unwrap();
+        let cleared = block_on(client.generation("s1")).unwrap();
+        assert!(cleared > filled);
+
+        // A new spectrum with the same name is a new generation too:
+
+        block_on(client.delete("s1")).unwrap();
+        assert!(block_on(client.generation("s1")).is_err());
+        block_on(client.create_1d("s1", "p1", 0.0, 1024.0, 1024)).unwrap();
+        assert!(block_on(client.generation("s1")).unwrap() > cleared);
+        drop(client);
+        handle.join().unwrap();
+    }
+    #[test]
+    fn closed_1() {
+        // Server gone:
+
+        let (send, recv) = mpsc::channel(1);
+        drop(recv);
+        let client = SpectrumClient::new(send);
+        assert_eq!(
+            String::from("Spectrum server is not running"),
+            block_on(client.list()).unwrap_err()
+        );
+    }
+}
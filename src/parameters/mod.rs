@@ -190,6 +190,86 @@ impl ParameterDictionary {
             Ok(String::from(name))
         }
     }
+    // Is an id already used by a parameter?
+
+    fn id_used(&self, id: u32) -> bool {
+        self.dictionary.values().any(|p| p.id == id)
+    }
+    ///
+    /// Add a parameter with a specific id, e.g. to match the ids in
+    /// a saved analysis.  It is an error if the name or the id is already
+    /// in use.  Parameters added later with add get ids above this one.
+    ///
+    pub fn add_with_id(&mut self, name: &str, id: u32) -> Result<(), String> {
+        if self.dictionary.contains_key(name) {
+            return Err(String::from("Duplicate parameter"));
+        }
+        if self.id_used(id) {
+            return Err(format!("Parameter id {} is already in use", id));
+        }
+        self.dictionary
+            .insert(String::from(name), Parameter::new(name, id));
+        self.next_id = self.next_id.max(id + 1);
+        Ok(())
+    }
+    ///
+    /// Add several (id, name) parameter definitions.  Either all are
+    /// added or, if any name or id is already in use (or repeated in defs),
+    /// none are and Err is returned.
+    ///
+    pub fn add_bulk(&mut self, defs: &[(u32, &str)]) -> Result<(), String> {
+        let mut ids = std::collections::HashSet::new();
+        let mut names = std::collections::HashSet::new();
+        for (id, name) in defs {
+            if self.dictionary.contains_key(*name) || !names.insert(*name) {
+                return Err(format!("Duplicate parameter {}", name));
+            }
+            if self.id_used(*id) || !ids.insert(*id) {
+                return Err(format!("Parameter id {} is already in use", id));
+            }
+        }
+        for (id, name) in defs {
+            self.add_with_id(name, *id)?;
+        }
+        Ok(())
+    }
+    ///
+    /// Merge the parameters of another dictionary (e.g. one built by an
+    /// analysis worker on another node) into this one, keeping their
+    /// ids and metadata.  Parameters that are already here with the
+    /// same id are skipped.  It is an error, and nothing is merged, if a
+    /// parameter is here with a different id or a new parameter's id is
+    /// already used.  On success the number of parameters added is
+    /// returned.
+    ///
+    pub fn merge(&mut self, additional: &ParameterDictionary) -> Result<usize, String> {
+        let mut added = Vec::new();
+        for (name, p) in additional.iter() {
+            match self.lookup(name) {
+                Some(existing) if existing.id == p.id => {}
+                Some(existing) => {
+                    return Err(format!(
+                        "Parameter {} has id {} here but id {} in the merged dictionary",
+                        name, existing.id, p.id
+                    ));
+                }
+                None => {
+                    if self.id_used(p.id) {
+                        return Err(format!(
+                            "Parameter id {} of {} is already in use",
+                            p.id, name
+                        ));
+                    }
+                    added.push(p);
+                }
+            }
+        }
+        for p in added.iter() {
+            self.dictionary.insert(p.get_name(), (*p).clone());
+            self.next_id = self.next_id.max(p.id + 1);
+        }
+        Ok(added.len())
+    }
     ///
     /// Lookup a parameter definition in the dictionary.
     ///
@@ -228,9 +308,12 @@ impl fmt::Display for ParameterDictionary {
 }
 
 ///  In the histogrammer, events are collections of
-/// parameter id/value pairs.
+/// parameter id/value pairs.  Parameters are equal if their ids are
+/// equal and their values are bit for bit identical (so NaN equals
+/// NaN but 0.0 does not equal -0.0).  The Debug output is id=value
+/// which also makes an Event print as e.g. [1=2.5, 3=1.0].
 
-#[derive(PartialEq, Debug, Clone, Copy)]
+#[derive(Clone, Copy)]
 pub struct EventParameter {
     pub id: u32,
     pub value: f64,
@@ -240,6 +323,16 @@ impl EventParameter {
         EventParameter { id, value }
     }
 }
+impl PartialEq for EventParameter {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id && self.value.to_bits() == other.value.to_bits()
+    }
+}
+impl fmt::Debug for EventParameter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}={:?}", self.id, self.value)
+    }
+}
 impl fmt::Display for EventParameter {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "id: {} value: {}", self.id, self.value)
@@ -249,6 +342,20 @@ impl fmt::Display for EventParameter {
 
 pub type Event = Vec<EventParameter>;
 
+///
+/// Events are equal if they have the same parameters regardless of
+/// order.  Since Event is a Vec, == compares in order;  this compares
+/// the sorted parameter lists.
+///
+pub fn events_equal(a: &Event, b: &Event) -> bool {
+    fn sorted(e: &Event) -> Vec<(u32, u64)> {
+        let mut result: Vec<(u32, u64)> = e.iter().map(|p| (p.id, p.value.to_bits())).collect();
+        result.sort_unstable();
+        result
+    }
+    a.len() == b.len() && sorted(a) == sorted(b)
+}
+
 /// ParameterIdMap provides a correspondence between
 /// parameter ids in an Event and parameter ids in some dictionary.
 /// It can be used to take some input event with a different Id space
@@ -373,6 +480,17 @@ impl EventParameterInfo {
     }
 }
 
+/// The representation of a FlatEvent:
+///
+/// *  Dense - slots indexed by parameter id, invalidated by generation.
+/// *  Sparse - only the parameters set in the current event.
+///
+#[derive(Debug, PartialEq)]
+pub enum FlatEventKind {
+    Dense(Vec<EventParameterInfo>),
+    Sparse(HashMap<u32, Option<f64>>),
+}
+
 /// FlatEvent holds an event whose indices are parameter ids and
 /// values.  While the id/value event is good for histograms that
 /// are ordered by a required parameter id, there are cases when a
@@ -387,51 +505,181 @@ impl EventParameterInfo {
 /// vector can hold Option<EventParameterInfo> structs so that entries
 /// are None if they've _never_ been initialized.
 ///
+/// Events with only a few of the parameters of a large dictionary set
+/// so few of the slots that a HashMap of the set parameters (the
+/// Sparse FlatEventKind) is smaller and faster to reset.  The
+/// representation not in use is kept so that events that alternate
+/// between them don't re-allocate.
+///
 #[derive(Debug, PartialEq)]
 pub struct FlatEvent {
     generation: u64, // Supports O(1) invalidation.
-    event: Vec<EventParameterInfo>,
+    event: FlatEventKind,
+    spare: Option<FlatEventKind>, // The other representation, once used.
+    dictionary_size: Option<usize>, // Enables the dense/sparse switch.
 }
 
 impl FlatEvent {
     fn ensure_size(&mut self, required: usize) {
         // Don't allow truncation:
 
-        if required > self.event.len() {
-            self.event.resize(required, EventParameterInfo::new(0, 0.0));
+        if let FlatEventKind::Dense(slots) = &mut self.event {
+            if required > slots.len() {
+                slots.resize(required, EventParameterInfo::new(0, 0.0));
+            }
         }
     }
 
     pub fn new() -> FlatEvent {
         FlatEvent {
             generation: 1, // So anything made by ensure_size is invalid
-            event: Vec::<EventParameterInfo>::new(),
+            event: FlatEventKind::Dense(Vec::<EventParameterInfo>::new()),
+            spare: None,
+            dictionary_size: None,
         }
     }
+    /// Create a flattened event for a dictionary with dictionary_size
+    /// parameters.  load_event will use the Dense representation for
+    /// events that have more than a quarter of the parameters and the
+    /// Sparse representation otherwise.
+    ///
+    pub fn new_for_dictionary(dictionary_size: usize) -> FlatEvent {
+        let mut result = FlatEvent::new();
+        result.dictionary_size = Some(dictionary_size);
+        result
+    }
     /// Given a dope vectored event loads the flattened event
     /// from it.  Note this increments the generation number
     /// this means that you can't load several events into a single
-    /// flattened event.
+    /// flattened event.  If the dictionary size is known, the
+    /// representation is chosen to suit the event.
     ///
     pub fn load_event(&mut self, e: &Event) {
-        self.generation += 1; // New event
-        for p in e {
-            let id = p.id as usize;
-            self.ensure_size(id + 1);
-            self.event[id].set(self.generation, p.value);
+        if let Some(n) = self.dictionary_size {
+            let dense = e.len() > n / 4;
+            if dense != matches!(self.event, FlatEventKind::Dense(_)) {
+                // Stale dense slots are from older generations and
+                // load_event_in_place clears a sparse map:
+
+                let next = self.spare.take().unwrap_or_else(|| {
+                    if dense {
+                        FlatEventKind::Dense(Vec::new())
+                    } else {
+                        FlatEventKind::Sparse(HashMap::new())
+                    }
+                });
+                self.spare = Some(std::mem::replace(&mut self.event, next));
+            }
+        }
+        self.load_event_in_place(e);
+    }
+    /// Create a flattened event with slots for parameter ids up to
+    /// max_param_id already allocated.  Loading events with ids in that
+    /// range never re-allocates.
+    ///
+    pub fn new_with_capacity(max_param_id: u32) -> FlatEvent {
+        let mut result = FlatEvent::new();
+        result.ensure_size(max_param_id as usize + 1);
+        result
+    }
+    /// Number of parameter slots currently allocated (for Sparse
+    /// events, the capacity of the map).
+    pub fn capacity(&self) -> usize {
+        match &self.event {
+            FlatEventKind::Dense(slots) => slots.len(),
+            FlatEventKind::Sparse(map) => map.capacity(),
+        }
+    }
+    /// The current representation.
+    pub fn kind(&self) -> &FlatEventKind {
+        &self.event
+    }
+    /// Load an event, reusing the existing slots.  The slots are only
+    /// extended (once) if the event has a parameter id beyond
+    /// the current capacity.  The representation is not changed.
+    ///
+    pub fn load_event_in_place(&mut self, e: &Event) {
+        self.reset();
+        if let Some(max_id) = e.iter().map(|p| p.id).max() {
+            self.ensure_size(max_id as usize + 1);
+        }
+        let generation = self.generation;
+        match &mut self.event {
+            FlatEventKind::Dense(slots) => {
+                for p in e {
+                    slots[p.id as usize].set(generation, p.value);
+                }
+            }
+            FlatEventKind::Sparse(map) => {
+                for p in e {
+                    map.insert(p.id, Some(p.value));
+                }
+            }
+        }
+    }
+    /// Make all parameters None without giving up the slots.  For
+    /// Dense events, like loading an event, this just starts a new
+    /// generation.
+    pub fn reset(&mut self) {
+        self.generation += 1;
+        if let FlatEventKind::Sparse(map) = &mut self.event {
+            map.clear();
         }
     }
     /// Get the value of a parameter in the event for the current
     /// generation.  None if this parameter does not exist or is not set.
 
     pub fn get_parameter(&self, id: u32) -> &Option<f64> {
-        let id = id as usize; // The better to index with:
-        if id < self.event.len() {
-            &(self.event[id].get(self.generation))
+        match &self.event {
+            FlatEventKind::Dense(slots) => {
+                let id = id as usize; // The better to index with:
+                if id < slots.len() {
+                    slots[id].get(self.generation)
+                } else {
+                    &None
+                }
+            }
+            FlatEventKind::Sparse(map) => map.get(&id).unwrap_or(&None),
+        }
+    }
+    /// The number of parameter ids the event knows about: the
+    /// dictionary size if known, or more if slots beyond it have
+    /// been used.
+    pub fn param_count(&self) -> usize {
+        let used = match &self.event {
+            FlatEventKind::Dense(slots) => slots.len(),
+            FlatEventKind::Sparse(map) => map.keys().max().map_or(0, |id| *id as usize + 1),
+        };
+        used.max(self.dictionary_size.unwrap_or(0))
+    }
+    /// Checked parameter access:
+    ///
+    /// *  None - id is not below param_count.
+    /// *  Some(None) - the parameter is not set in this event.
+    /// *  Some(Some(v)) - the parameter has the value v.
+    pub fn get_param(&self, id: u32) -> Option<Option<f64>> {
+        if (id as usize) < self.param_count() {
+            Some(*self.get_parameter(id))
         } else {
-            &None
+            None
         }
     }
+    /// True if the parameter is set in this event.
+    pub fn is_present(&self, id: u32) -> bool {
+        self.get_parameter(id).is_some()
+    }
+}
+/// Displays the parameters set in the event, one param[id]=value
+/// line each in id order.
+impl fmt::Display for FlatEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for id in 0..self.param_count() as u32 {
+            if let Some(value) = self.get_parameter(id) {
+                writeln!(f, "param[{}]={}", id, value)?;
+            }
+        }
+        Ok(())
+    }
 }
 /// It's reasonable to use just indexing to get the parameter:
 ///  This means that for a FlatEvent e; e[\i] will give None
@@ -727,7 +975,100 @@ mod pdict_tests {
             d.lookup("param2").unwrap().get_limits()
         )
     }
+    #[test]
+    fn add_with_id_1() {
+        let mut d = ParameterDictionary::new();
+        let ids = [5, 100, 7, 3, 42, 1000, 9, 64, 11, 2];
+        for (i, id) in ids.iter().enumerate() {
+            d.add_with_id(&format!("p{}", i), *id).unwrap();
+        }
+        for (i, id) in ids.iter().enumerate() {
+            assert_eq!(*id, d.lookup(&format!("p{}", i)).unwrap().get_id());
+        }
+        // Conflicts:
+
+        assert!(d.add_with_id("p0", 6).is_err());
+        assert!(d.add_with_id("new", 42).is_err());
+        assert!(d.lookup("new").is_none());
+
+        // add doesn't reuse ids:
+
+        d.add("next").unwrap();
+        assert_eq!(1001, d.lookup("next").unwrap().get_id());
+    }
+    #[test]
+    fn add_bulk_1() {
+        let mut d = ParameterDictionary::new();
+        d.add_bulk(&[(10, "a"), (3, "b"), (7, "c")]).unwrap();
+        assert_eq!(10, d.lookup("a").unwrap().get_id());
+        assert_eq!(3, d.lookup("b").unwrap().get_id());
+        assert_eq!(7, d.lookup("c").unwrap().get_id());
+
+        // Any conflict and nothing is added:
+
+        assert!(d.add_bulk(&[(1, "d"), (2, "e"), (3, "f")]).is_err());
+        assert!(d.add_bulk(&[(1, "d"), (2, "a")]).is_err());
+        assert!(d.add_bulk(&[(1, "d"), (1, "e")]).is_err());
+        assert!(d.add_bulk(&[(1, "d"), (2, "d")]).is_err());
+        assert_eq!(3, d.iter().count());
+    }
+    #[test]
+    fn merge_1() {
+        // No conflicts;  common parameters are skipped and the
+        // metadata comes along:
+
+        let mut base = ParameterDictionary::new();
+        base.add_bulk(&[(1, "a"), (2, "b")]).unwrap();
+        let mut additional = ParameterDictionary::new();
+        additional
+            .add_bulk(&[(2, "b"), (5, "c"), (7, "d")])
+            .unwrap();
+        additional.lookup_mut("c").unwrap().set_units("mm");
+
+        assert_eq!(Ok(2), base.merge(&additional));
+        assert_eq!(4, base.iter().count());
+        assert_eq!(5, base.lookup("c").unwrap().get_id());
+        assert_eq!(
+            Some(String::from("mm")),
+            base.lookup("c").unwrap().get_units()
+        );
+        assert_eq!(7, base.lookup("d").unwrap().get_id());
+        assert_eq!(Ok(String::from("e")), base.add("e"));
+        assert_eq!(8, base.lookup("e").unwrap().get_id());
+
+        assert_eq!(Ok(0), base.merge(&additional));
+    }
+    #[test]
+    fn merge_2() {
+        // Same name different id:
+
+        let mut base = ParameterDictionary::new();
+        base.add_bulk(&[(1, "a"), (2, "b")]).unwrap();
+        let mut additional = ParameterDictionary::new();
+        additional.add_bulk(&[(3, "b"), (4, "c")]).unwrap();
+
+        assert!(base.merge(&additional).is_err());
+        assert_eq!(2, base.iter().count());
+        assert!(base.lookup("c").is_none());
+    }
+    #[test]
+    fn merge_3() {
+        // New name with an id that's in use:
+
+        let mut base = ParameterDictionary::new();
+        base.add_bulk(&[(1, "a"), (2, "b")]).unwrap();
+        let mut additional = ParameterDictionary::new();
+        additional.add_bulk(&[(2, "c"), (4, "d")]).unwrap();
+
+        assert_eq!(
+            Err(String::from("Parameter id 2 of c is already in use")),
+            base.merge(&additional)
+        );
+        assert_eq!(2, base.iter().count());
+        assert!(base.lookup("d").is_none());
+    }
 }
+
 #[cfg(test)]
 mod pevent_test {
     use super::*;
@@ -748,6 +1089,37 @@ mod pevent_test {
         let e = EventParameter::new(1, 1.2345);
         assert_eq!(String::from("id: 1 value: 1.2345"), format!("{}", e));
     }
+    #[test]
+    fn debug_1() {
+        let e = EventParameter::new(1, 2.5);
+        assert_eq!("1=2.5", format!("{:?}", e));
+        let event: Event = vec![e, EventParameter::new(3, 1.0)];
+        assert_eq!("[1=2.5, 3=1.0]", format!("{:?}", event));
+    }
+    #[test]
+    fn eq_1() {
+        assert_eq!(EventParameter::new(1, 2.5), EventParameter::new(1, 2.5));
+        assert_ne!(EventParameter::new(1, 2.5), EventParameter::new(2, 2.5));
+        assert_ne!(EventParameter::new(1, 2.5), EventParameter::new(1, 2.6));
+        assert_eq!(
+            EventParameter::new(1, f64::NAN),
+            EventParameter::new(1, f64::NAN)
+        );
+        assert_ne!(EventParameter::new(1, 0.0), EventParameter::new(1, -0.0));
+    }
+    #[test]
+    fn eq_2() {
+        // Events with the same parameters in any order are equal:
+
+        let e1 = vec![EventParameter::new(1, 2.5), EventParameter::new(3, 1.0)];
+        let e2 = vec![EventParameter::new(3, 1.0), EventParameter::new(1, 2.5)];
+        let e3 = vec![EventParameter::new(1, 2.5), EventParameter::new(3, 1.5)];
+        assert!(events_equal(&e1, &e1.clone()));
+        assert!(events_equal(&e1, &e2));
+        assert!(!events_equal(&e1, &e3));
+        assert!(!events_equal(&e1, &e1[0..1].to_vec()));
+        assert!(events_equal(&Event::new(), &Event::new()));
+    }
 }
 #[cfg(test)]
 mod paramap_test {
@@ -972,7 +1344,9 @@ mod parflatevt_test {
         assert_eq!(
             FlatEvent {
                 generation: 1,
-                event: Vec::new()
+                event: FlatEventKind::Dense(Vec::new()),
+                spare: None,
+                dictionary_size: None
             },
             ev
         );
@@ -990,7 +1364,7 @@ mod parflatevt_test {
         ];
         ev.load_event(&e);
         assert_eq!(2, ev.generation);
-        assert_eq!(5, ev.event.len());
+        assert_eq!(5, ev.capacity());
         for i in vec![1, 2, 4] {
             assert!(ev[i].is_some());
             assert_eq!(2.0 * i as f64, ev[i].unwrap());
@@ -1030,4 +1404,188 @@ mod parflatevt_test {
             assert!(ev[i].is_none());
         }
     }
+    fn dense_slots(ev: &FlatEvent) -> *const EventParameterInfo {
+        if let FlatEventKind::Dense(slots) = ev.kind() {
+            slots.as_ptr()
+        } else {
+            panic!("Event is not dense");
+        }
+    }
+    #[test]
+    fn capacity_1() {
+        let ev = FlatEvent::new_with_capacity(511);
+        assert_eq!(512, ev.capacity());
+        for i in 0..512 {
+            assert!(ev[i].is_none());
+        }
+    }
+    #[test]
+    fn load_in_place_1() {
+        // No re-allocation within the capacity:
+
+        let mut ev = FlatEvent::new_with_capacity(10);
+        let slots = dense_slots(&ev);
+        ev.load_event_in_place(&vec![
+            EventParameter::new(10, 1.0),
+            EventParameter::new(3, 2.0),
+        ]);
+        assert_eq!(11, ev.capacity());
+        assert_eq!(slots, dense_slots(&ev));
+        assert_eq!(Some(1.0), ev[10]);
+        assert_eq!(Some(2.0), ev[3]);
+        assert!(ev[4].is_none());
+
+        // Beyond the capacity it grows to fit:
+
+        ev.load_event_in_place(&vec![
+            EventParameter::new(2, 3.0),
+            EventParameter::new(20, 4.0),
+        ]);
+        assert_eq!(21, ev.capacity());
+        assert_eq!(Some(3.0), ev[2]);
+        assert_eq!(Some(4.0), ev[20]);
+        assert!(ev[10].is_none());
+        assert!(ev[3].is_none());
+    }
+    #[test]
+    fn reset_1() {
+        let mut ev = FlatEvent::new_with_capacity(4);
+        ev.load_event_in_place(&vec![
+            EventParameter::new(1, 2.0),
+            EventParameter::new(4, 8.0),
+        ]);
+        ev.reset();
+        assert_eq!(5, ev.capacity());
+        for i in 0..5 {
+            assert!(ev[i].is_none());
+        }
+    }
+    #[test]
+    fn sparse_1() {
+        // The representation follows the event:
+
+        let mut ev = FlatEvent::new_for_dictionary(4096);
+        let few: Event = (0..8)
+            .map(|i| EventParameter::new(i * 500, i as f64))
+            .collect();
+        ev.load_event(&few);
+        assert!(matches!(ev.kind(), FlatEventKind::Sparse(_)));
+        for i in 0..8 {
+            assert_eq!(Some(i as f64), ev[i * 500]);
+        }
+        assert!(ev[1].is_none());
+        assert!(ev[10000].is_none());
+
+        let many: Event = (0..2000)
+            .map(|i| EventParameter::new(i, 2.0 * i as f64))
+            .collect();
+        ev.load_event(&many);
+        assert!(matches!(ev.kind(), FlatEventKind::Dense(_)));
+        assert_eq!(Some(1000.0), ev[500]);
+        assert!(ev[3500].is_none());
+
+        ev.load_event(&few);
+        assert!(matches!(ev.kind(), FlatEventKind::Sparse(_)));
+        assert_eq!(Some(1.0), ev[500]);
+        assert!(ev[499].is_none());
+    }
+    #[test]
+    fn sparse_4() {
+        // Switching representations reuses the earlier buffers:
+
+        let mut ev = FlatEvent::new_for_dictionary(4096);
+        let few: Event = (0..8)
+            .map(|i| EventParameter::new(i * 500, i as f64))
+            .collect();
+        let many: Event = (0..2000)
+            .map(|i| EventParameter::new(i, 2.0 * i as f64))
+            .collect();
+        ev.load_event(&many);
+        let slots = dense_slots(&ev);
+        ev.load_event(&few);
+        let map_capacity = ev.capacity();
+
+        ev.load_event(&many);
+        assert_eq!(slots, dense_slots(&ev));
+        ev.load_event(&few);
+        assert_eq!(map_capacity, ev.capacity());
+        ev.load_event(&vec![EventParameter::new(1500, 1.0); 1025]);
+        assert_eq!(slots, dense_slots(&ev));
+
+        // and nothing from the earlier events leaks through:
+
+        assert_eq!(Some(1.0), ev[1500]);
+        assert!(ev[500].is_none());
+        assert!(ev[1].is_none());
+    }
+    #[test]
+    fn sparse_2() {
+        // Invalidation of sparse events:
+
+        let mut ev = FlatEvent::new_for_dictionary(100);
+        ev.load_event(&vec![
+            EventParameter::new(1, 2.0),
+            EventParameter::new(4, 8.0),
+        ]);
+        ev.load_event(&vec![EventParameter::new(2, 3.0)]);
+        assert!(ev[1].is_none());
+        assert!(ev[4].is_none());
+        assert_eq!(Some(3.0), ev[2]);
+
+        ev.reset();
+        assert!(ev[2].is_none());
+
+        // Without a dictionary size, events are always dense:
+
+        let mut ev = FlatEvent::new();
+        ev.load_event(&vec![EventParameter::new(1, 2.0)]);
+        assert!(matches!(ev.kind(), FlatEventKind::Dense(_)));
+    }
+    #[test]
+    fn get_param_1() {
+        let mut ev = FlatEvent::new_with_capacity(4);
+        ev.load_event(&vec![EventParameter::new(1, 2.0)]);
+        assert_eq!(5, ev.param_count());
+        assert_eq!(Some(Some(2.0)), ev.get_param(1));
+        assert!(ev.is_present(1));
+        assert_eq!(Some(None), ev.get_param(4));
+        assert!(!ev.is_present(4));
+        assert_eq!(None, ev.get_param(5));
+        assert_eq!(None, ev.get_param(u32::MAX));
+        assert!(!ev.is_present(5));
+    }
+    #[test]
+    fn get_param_2() {
+        // Sparse events know the dictionary size:
+
+        let mut ev = FlatEvent::new_for_dictionary(100);
+        ev.load_event(&vec![EventParameter::new(2, 3.0)]);
+        assert!(matches!(ev.kind(), FlatEventKind::Sparse(_)));
+        assert_eq!(100, ev.param_count());
+        assert_eq!(Some(Some(3.0)), ev.get_param(2));
+        assert_eq!(Some(None), ev.get_param(99));
+        assert_eq!(None, ev.get_param(100));
+
+        ev.load_event(&vec![EventParameter::new(200, 1.0)]);
+        assert_eq!(201, ev.param_count());
+        assert_eq!(Some(Some(1.0)), ev.get_param(200));
+    }
+    #[test]
+    fn display_1() {
+        // Only the set parameters, in id order, for either
+        // representation:
+
+        let event = vec![EventParameter::new(5, 1.5), EventParameter::new(2, 3.0)];
+        let mut ev = FlatEvent::new();
+        ev.load_event(&event);
+        assert_eq!("param[2]=3\nparam[5]=1.5\n", ev.to_string());
+
+        let mut ev = FlatEvent::new_for_dictionary(100);
+        ev.load_event(&event);
+        assert!(matches!(ev.kind(), FlatEventKind::Sparse(_)));
+        assert_eq!("param[2]=3\nparam[5]=1.5\n", ev.to_string());
+
+        ev.reset();
+        assert_eq!("", ev.to_string());
+    }
 }
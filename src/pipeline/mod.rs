@@ -0,0 +1,375 @@
+//!  The pipeline module provides a way to compose the processing
+//!  done on each flattened event.  Rather than hard-wiring the
+//!  sequence  gate check -> spectrum increment -> bookkeeping,
+//!  a Pipeline is an ordered list of *stages*.  Each stage is
+//!  an object that implements the EventProcessor trait.
+//!
+//!  For each event, the stages are invoked in order.  A stage
+//!  returns true if the event should continue down the pipeline
+//!  and false if the event is rejected.  Once a stage rejects an
+//!  event, the remaining stages are not invoked for that event.
+//!
+//!  The stages supplied by this module live in the stages
+//!  submodule and are re-exported so that, e.g.
+//!  pipeline::GateStage means pipeline::stages::GateStage.
+//!  The rate submodule provides a RateMeter that can also be used
+//!  as a stage.  The transform submodule provides transforms of
+//!  raw ring items, e.g. before they are turned into events.
+//!
+//!  Objects that must do something before each event (e.g.
+//!  invalidate condition caches) implement EventBoundary and are
+//!  added to the pipeline with add_boundary.  They are notified
+//!  before the first stage sees each event.
+//!
+//!  Event processing is instrumented with tracing spans:
+//!  process_event around each event, gate_stage and fill_stage
+//!  around those stages and handle_event around each spectrum fill.
+//!  Rejections by conditions are DEBUG events and spectrum fills
+//!  are TRACE events.  init_tracing sets up output to stderr.
+//!
+use crate::conditions::{invalidate_cache, ConditionDictionary};
+use crate::parameters::FlatEvent;
+use std::cell::RefCell;
+use std::rc::Rc;
+use tracing::{span, Level};
+
+pub mod stages;
+pub use stages::*;
+pub mod rate;
+pub use rate::*;
+pub mod transform;
+pub use transform::*;
+
+/// The EventProcessor trait defines the interface to a pipeline
+/// stage:
+///
+/// *  process - processes the event.  The return value is true if
+///    the event should be passed on to subsequent stages.
+/// *  reset   - returns the stage to its initial state (e.g. zero
+///    any counters it maintains).
+///
+pub trait EventProcessor {
+    fn process(&mut self, event: &mut FlatEvent) -> bool;
+    fn reset(&mut self);
+}
+
+/// Stages are moved into the pipeline as boxed trait objects.
+/// If the caller needs to keep access to a stage (e.g. to read
+/// a counter after the run), the stage can be wrapped in an
+/// Rc<RefCell<>>; a clone of the Rc is given to the pipeline
+/// and the caller keeps the other one.
+///
+impl<T: EventProcessor> EventProcessor for Rc<RefCell<T>> {
+    fn process(&mut self, event: &mut FlatEvent) -> bool {
+        self.borrow_mut().process(event)
+    }
+    fn reset(&mut self) {
+        self.borrow_mut().reset();
+    }
+}
+
+/// Implemented by objects that are notified at the start of each
+/// event processed by a pipeline.
+///
+pub trait EventBoundary {
+    fn on_event_start(&mut self);
+}
+
+/// Invalidates the cached values of all of the conditions in a
+/// condition dictionary at each event boundary so that the conditions
+/// are re-evaluated for each event.
+///
+pub struct ConditionInvalidator {
+    dict: Rc<RefCell<ConditionDictionary>>,
+}
+impl ConditionInvalidator {
+    pub fn new(dict: &Rc<RefCell<ConditionDictionary>>) -> ConditionInvalidator {
+        ConditionInvalidator {
+            dict: Rc::clone(dict),
+        }
+    }
+    /// Invalidate the cache of every condition in the dictionary.
+    pub fn notify(&self) {
+        invalidate_cache(&mut self.dict.borrow_mut());
+    }
+}
+impl EventBoundary for ConditionInvalidator {
+    fn on_event_start(&mut self) {
+        self.notify();
+    }
+}
+
+/// A Pipeline is the ordered set of stages and the objects
+/// notified at event boundaries.
+///
+pub struct Pipeline {
+    stages: Vec<Box<dyn EventProcessor>>,
+    boundaries: Vec<Box<dyn EventBoundary>>,
+}
+
+impl Pipeline {
+    /// Create an empty pipeline.  An empty pipeline accepts all
+    /// events.
+    pub fn new() -> Pipeline {
+        Pipeline {
+            stages: Vec::new(),
+            boundaries: Vec::new(),
+        }
+    }
+    /// Append a stage to the end of the pipeline.
+    /// Returns self so that stage additions can be chained.
+    ///
+    pub fn add_stage(&mut self, stage: Box<dyn EventProcessor>) -> &mut Pipeline {
+        self.stages.push(stage);
+        self
+    }
+    /// Add an object to notify at the start of each event.
+    pub fn add_boundary(&mut self, boundary: Box<dyn EventBoundary>) -> &mut Pipeline {
+        self.boundaries.push(boundary);
+        self
+    }
+    /// Number of stages in the pipeline.
+    pub fn len(&self) -> usize {
+        self.stages.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+    /// Process an event through the pipeline.  The event boundary
+    /// objects are notified and then the stages are
+    /// called in order until either one of them rejects the event
+    /// or all stages have seen it.  The return value is true
+    /// if all stages accepted the event.
+    ///
+    pub fn process_event(&mut self, event: &mut FlatEvent) -> bool {
+        let _span = span!(Level::DEBUG, "process_event").entered();
+        for boundary in self.boundaries.iter_mut() {
+            boundary.on_event_start();
+        }
+        for stage in self.stages.iter_mut() {
+            if !stage.process(event) {
+                return false;
+            }
+        }
+        true
+    }
+    /// Reset all stages.
+    pub fn reset(&mut self) {
+        for stage in self.stages.iter_mut() {
+            stage.reset();
+        }
+    }
+}
+
+///
+/// Send the pipeline's tracing output at level and above (e.g.
+/// Level::DEBUG to see rejections) to stderr.  Err if a tracing
+/// subscriber has already been set up.
+///
+pub fn init_tracing(level: Level) -> Result<(), String> {
+    tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(std::io::stderr)
+        .try_init()
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod pipeline_tests {
+    use super::*;
+    use crate::conditions::*;
+    use crate::parameters::*;
+    use crate::spectra::*;
+    use ndhistogram::*;
+
+    // Stage that accepts everything and remembers how many times
+    // it was called:
+
+    struct Recorder {
+        calls: usize,
+    }
+    impl EventProcessor for Recorder {
+        fn process(&mut self, _event: &mut FlatEvent) -> bool {
+            self.calls += 1;
+            true
+        }
+        fn reset(&mut self) {
+            self.calls = 0;
+        }
+    }
+
+    #[test]
+    fn new_1() {
+        let mut p = Pipeline::new();
+        assert!(p.is_empty());
+        let mut e = FlatEvent::new();
+        assert!(p.process_event(&mut e));
+    }
+    #[test]
+    fn add_1() {
+        let mut p = Pipeline::new();
+        let r = Rc::new(RefCell::new(Recorder { calls: 0 }));
+        p.add_stage(Box::new(Rc::clone(&r)))
+            .add_stage(Box::new(CountStage::new()));
+        assert_eq!(2, p.len());
+
+        let mut e = FlatEvent::new();
+        assert!(p.process_event(&mut e));
+        assert!(p.process_event(&mut e));
+        assert_eq!(2, r.borrow().calls);
+
+        p.reset();
+        assert_eq!(0, r.borrow().calls);
+    }
+    #[test]
+    fn reject_1() {
+        // A rejecting stage short circuits the stages that follow:
+
+        let mut cdict = ConditionDictionary::new();
+        cdict.insert(String::from("false"), Rc::new(RefCell::new(False {})));
+
+        let mut p = Pipeline::new();
+        let r = Rc::new(RefCell::new(Recorder { calls: 0 }));
+        p.add_stage(Box::new(GateStage::new("false", &cdict).unwrap()));
+        p.add_stage(Box::new(Rc::clone(&r)));
+
+        let mut e = FlatEvent::new();
+        assert!(!p.process_event(&mut e));
+        assert_eq!(0, r.borrow().calls);
+    }
+    #[test]
+    fn chain_1() {
+        // Gate -> Fill -> Count over 100 events where only
+        // half satisfy the gate.
+
+        let mut pdict = ParameterDictionary::new();
+        pdict.add("p").unwrap();
+        let pid = pdict.lookup("p").unwrap().get_id();
+
+        let mut cdict = ConditionDictionary::new();
+        cdict.insert(
+            String::from("cut"),
+            Rc::new(RefCell::new(Cut::new(pid, 0.0, 49.0))),
+        );
+
+        let mut sdict = SpectrumDictionary::new();
+        let spec: SpectrumContainer = Rc::new(RefCell::new(
+            Oned::new("spec", "p", &pdict, Some(0.0), Some(100.0), Some(100)).unwrap(),
+        ));
+        sdict.insert(String::from("spec"), Rc::clone(&spec));
+
+        let counter = Rc::new(RefCell::new(CountStage::new()));
+        let mut p = Pipeline::new();
+        p.add_stage(Box::new(GateStage::new("cut", &cdict).unwrap()))
+            .add_stage(Box::new(FillStage::new(&sdict)))
+            .add_stage(Box::new(Rc::clone(&counter)));
+
+        let mut fe = FlatEvent::new();
+        for i in 0..100 {
+            invalidate_cache(&mut cdict);
+            let e = vec![EventParameter::new(pid, i as f64)];
+            fe.load_event(&e);
+            p.process_event(&mut fe);
+        }
+        assert_eq!(50, counter.borrow().get_count());
+
+        // The spectrum has exactly the 50 accepted events, one per channel:
+
+        let h = spec.borrow().get_histogram_1d().unwrap();
+        let total: f64 = h.borrow().values().map(|v| v.get()).sum();
+        assert_eq!(50.0, total);
+        for i in 0..50 {
+            assert_eq!(1.0, h.borrow().value(&(i as f64)).unwrap().get());
+        }
+        for i in 50..100 {
+            assert_eq!(0.0, h.borrow().value(&(i as f64)).unwrap().get());
+        }
+    }
+    #[test]
+    fn boundary_1() {
+        // Cached cut gating two spectra is re-evaluated for each event:
+
+        let mut pdict = ParameterDictionary::new();
+        pdict.add("p").unwrap();
+        let pid = pdict.lookup("p").unwrap().get_id();
+
+        let cdict = Rc::new(RefCell::new(ConditionDictionary::new()));
+        cdict.borrow_mut().insert(
+            String::from("cut"),
+            Rc::new(RefCell::new(Cut::new(pid, 0.0, 49.0))),
+        );
+
+        let mut sdict = SpectrumDictionary::new();
+        for name in ["spec1", "spec2"] {
+            let mut spec = Oned::new(name, "p", &pdict, Some(0.0), Some(100.0), Some(100)).unwrap();
+            spec.gate("cut", &cdict.borrow()).unwrap();
+            sdict.insert(String::from(name), Rc::new(RefCell::new(spec)));
+        }
+
+        let mut p = Pipeline::new();
+        p.add_boundary(Box::new(ConditionInvalidator::new(&cdict)))
+            .add_stage(Box::new(FillStage::new(&sdict)));
+
+        let mut fe = FlatEvent::new();
+        fe.load_event(&vec![EventParameter::new(pid, 10.0)]);
+        p.process_event(&mut fe);
+        fe.load_event(&vec![EventParameter::new(pid, 60.0)]);
+        p.process_event(&mut fe);
+
+        for spec in sdict.values() {
+            let h = spec.borrow().get_histogram_1d().unwrap();
+            let total: f64 = h.borrow().values().map(|v| v.get()).sum();
+            assert_eq!(1.0, total);
+            assert_eq!(1.0, h.borrow().value(&10.0).unwrap().get());
+            assert_eq!(0.0, h.borrow().value(&60.0).unwrap().get());
+        }
+    }
+    #[test]
+    #[tracing_test::traced_test]
+    fn tracing_1() {
+        // 5 events through a gated pipeline, 2 of them rejected:
+
+        let mut pdict = ParameterDictionary::new();
+        pdict.add("p").unwrap();
+        let pid = pdict.lookup("p").unwrap().get_id();
+
+        let cdict = Rc::new(RefCell::new(ConditionDictionary::new()));
+        cdict.borrow_mut().insert(
+            String::from("cut"),
+            Rc::new(RefCell::new(Cut::new(pid, 0.0, 49.0))),
+        );
+        let mut sdict = SpectrumDictionary::new();
+        sdict.insert(
+            String::from("spec"),
+            Rc::new(RefCell::new(
+                Oned::new("spec", "p", &pdict, Some(0.0), Some(100.0), Some(100)).unwrap(),
+            )),
+        );
+
+        let mut p = Pipeline::new();
+        p.add_boundary(Box::new(ConditionInvalidator::new(&cdict)))
+            .add_stage(Box::new(GateStage::new("cut", &cdict.borrow()).unwrap()))
+            .add_stage(Box::new(FillStage::new(&sdict)));
+
+        let mut fe = FlatEvent::new();
+        for value in [10.0, 60.0, 20.0, 70.0, 30.0] {
+            fe.load_event(&vec![EventParameter::new(pid, value)]);
+            p.process_event(&mut fe);
+        }
+
+        logs_assert(|lines: &[&str]| {
+            let count = |text: &str| lines.iter().filter(|l| l.contains(text)).count();
+            let rejections = count("process_event:gate_stage{condition=cut}");
+            let fills = count("process_event:fill_stage:handle_event{spectrum=spec}");
+            match (rejections, fills) {
+                (2, 3) => Ok(()),
+                _ => Err(format!(
+                    "{} rejections and {} fills logged",
+                    rejections, fills
+                )),
+            }
+        });
+        assert!(logs_contain("Condition rejected the event"));
+        assert!(logs_contain("Spectrum given the event"));
+    }
+}
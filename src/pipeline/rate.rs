@@ -0,0 +1,159 @@
+//! RateMeter measures the rate at which events are processed
+//! over a rolling time window.  Each recorded event is timestamped.
+//! Timestamps older than the window are discarded as new events are
+//! recorded so the rate reflects only recent history.
+//!
+use super::*;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+// Initial timestamp capacity is sized for this many events/second
+// over the window.  The deque will still grow if rates are higher,
+// this just avoids reallocation at typical online rates.
+
+const EXPECTED_MAX_RATE: f64 = 10000.0;
+const MAX_INITIAL_CAPACITY: usize = 1000000;
+
+pub struct RateMeter {
+    window: Duration,
+    timestamps: VecDeque<Instant>,
+}
+
+impl RateMeter {
+    /// Create a rate meter that averages over the window specified.
+    pub fn new(window: Duration) -> RateMeter {
+        let capacity = (window.as_secs_f64() * EXPECTED_MAX_RATE) as usize;
+        RateMeter {
+            window,
+            timestamps: VecDeque::with_capacity(capacity.min(MAX_INITIAL_CAPACITY)),
+        }
+    }
+    /// The averaging window.
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+    /// Record that an event was processed now.  Events that have
+    /// fallen out of the window are pruned.
+    pub fn record_event(&mut self) {
+        let now = Instant::now();
+        while let Some(oldest) = self.timestamps.front() {
+            if now.duration_since(*oldest) > self.window {
+                self.timestamps.pop_front();
+            } else {
+                break;
+            }
+        }
+        self.timestamps.push_back(now);
+    }
+    /// Events/second over the part of the window for which there are
+    /// events.  The rate is computed from the number of intervals between
+    /// the events still in the window and the time spanned by those events.
+    /// If fewer than two events are in the window, the rate is 0.0.
+    ///
+    pub fn rate(&self) -> f64 {
+        let now = Instant::now();
+        let mut in_window = self
+            .timestamps
+            .iter()
+            .filter(|t| now.duration_since(**t) <= self.window);
+
+        if let Some(first) = in_window.next() {
+            let mut last = first;
+            let mut n = 0;
+            for t in in_window {
+                last = t;
+                n += 1;
+            }
+            let span = last.duration_since(*first).as_secs_f64();
+            if n > 0 && span > 0.0 {
+                n as f64 / span
+            } else {
+                0.0
+            }
+        } else {
+            0.0
+        }
+    }
+    /// Record an event and return the resulting rate.
+    pub fn record_and_rate(&mut self) -> f64 {
+        self.record_event();
+        self.rate()
+    }
+}
+
+/// As a pipeline stage, the rate meter just records the time
+/// at which each event passed through it.
+///
+impl EventProcessor for RateMeter {
+    fn process(&mut self, _event: &mut FlatEvent) -> bool {
+        self.record_event();
+        true
+    }
+    fn reset(&mut self) {
+        self.timestamps.clear();
+    }
+}
+
+#[cfg(test)]
+mod rate_tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn new_1() {
+        let r = RateMeter::new(Duration::from_secs(10));
+        assert_eq!(Duration::from_secs(10), r.window());
+        assert_eq!(0.0, r.rate());
+    }
+    #[test]
+    fn rate_1() {
+        // One event isn't enough to compute a rate:
+
+        let mut r = RateMeter::new(Duration::from_secs(10));
+        assert_eq!(0.0, r.record_and_rate());
+    }
+    #[test]
+    fn rate_2() {
+        // 1000 events with a controlled delay.  Sleep overshoots
+        // so the expected frequency comes from the measured elapsed time.
+
+        let mut r = RateMeter::new(Duration::from_secs(60));
+        let start = Instant::now();
+        r.record_event();
+        for _ in 1..1000 {
+            thread::sleep(Duration::from_micros(500));
+            r.record_event();
+        }
+        let expected = 999.0 / start.elapsed().as_secs_f64();
+        let rate = r.rate();
+        assert!(
+            (rate - expected).abs() / expected < 0.05,
+            "rate {} expected {}",
+            rate,
+            expected
+        );
+    }
+    #[test]
+    fn rate_3() {
+        // Events age out of the window:
+
+        let mut r = RateMeter::new(Duration::from_millis(20));
+        for _ in 0..10 {
+            r.record_event();
+        }
+        thread::sleep(Duration::from_millis(40));
+        assert_eq!(0.0, r.rate());
+        r.record_event();
+        assert_eq!(1, r.timestamps.len());
+    }
+    #[test]
+    fn process_1() {
+        let mut r = RateMeter::new(Duration::from_secs(10));
+        let mut e = FlatEvent::new();
+        assert!(r.process(&mut e));
+        assert!(r.process(&mut e));
+        assert_eq!(2, r.timestamps.len());
+        r.reset();
+        assert_eq!(0, r.timestamps.len());
+    }
+}
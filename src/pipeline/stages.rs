@@ -0,0 +1,365 @@
+//! This module contains the pre-built pipeline stages:
+//!
+//! *  GateStage  - passes only events that satisfy a condition.
+//! *  FillStage  - Gives the event to a set of spectra to increment.
+//! *  CountStage - Counts the events that reach it.
+//! *  SamplingStage - Passes only every n'th event.
+//!
+use super::*;
+use crate::conditions::*;
+use crate::spectra::*;
+use std::rc::Rc;
+use tracing::{event, span, Level};
+
+///
+/// GateStage passes events that satisfy a condition from a condition
+/// dictionary.  As with spectrum gates, the stage holds a weak
+/// reference to the condition.  If the condition is deleted from the
+/// dictionary, the stage rejects all events, the same way a compound
+/// condition treats a deleted dependent condition.
+///
+/// Note that the stage uses the condition's cached value if there is
+/// one.  The caller is responsible for invalidating condition caches
+/// between events.
+///
+pub struct GateStage {
+    condition_name: String,
+    condition: ContainerReference,
+}
+
+impl GateStage {
+    /// Create a gate stage for the named condition.
+    /// If the condition is not in cdict, Err is returned.
+    pub fn new(condition_name: &str, cdict: &ConditionDictionary) -> Result<GateStage, String> {
+        if let Some(c) = cdict.get(condition_name) {
+            Ok(GateStage {
+                condition_name: String::from(condition_name),
+                condition: Rc::downgrade(c),
+            })
+        } else {
+            Err(format!("No such gate {}", condition_name))
+        }
+    }
+    pub fn condition_name(&self) -> String {
+        self.condition_name.clone()
+    }
+}
+
+impl EventProcessor for GateStage {
+    fn process(&mut self, event: &mut FlatEvent) -> bool {
+        let _span = span!(Level::DEBUG, "gate_stage", condition = %self.condition_name).entered();
+        let accepted = if let Some(c) = self.condition.upgrade() {
+            c.borrow_mut().check(event)
+        } else {
+            false
+        };
+        if !accepted {
+            event!(
+                Level::DEBUG,
+                condition = %self.condition_name,
+                "Condition rejected the event"
+            );
+        }
+        accepted
+    }
+    fn reset(&mut self) {}
+}
+
+///
+/// FillStage gives each event to a set of spectra.  Each spectrum's
+/// handle_event method is called so any gate applied to the spectrum
+/// is still honored.  Like SpectrumStorage's increment lists, the stage
+/// holds weak references to the spectra.  Spectra that have been removed
+/// from their dictionary are dropped from the stage.
+///
+/// The FillStage always passes events to subsequent stages.
+///
+pub struct FillStage {
+    spectra: SpectrumReferences,
+}
+
+impl FillStage {
+    /// Create a fill stage that increments all of the spectra
+    /// currently in the dictionary.
+    pub fn new(sdict: &SpectrumDictionary) -> FillStage {
+        FillStage {
+            spectra: sdict.values().map(Rc::downgrade).collect(),
+        }
+    }
+    /// Add an additional spectrum to the stage.
+    pub fn add_spectrum(&mut self, spectrum: &SpectrumContainer) -> &mut FillStage {
+        self.spectra.push(Rc::downgrade(spectrum));
+        self
+    }
+    /// Number of spectra the stage is filling.
+    pub fn len(&self) -> usize {
+        self.spectra.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.spectra.is_empty()
+    }
+}
+
+impl EventProcessor for FillStage {
+    fn process(&mut self, event: &mut FlatEvent) -> bool {
+        let _span = span!(Level::DEBUG, "fill_stage").entered();
+        self.spectra.retain(|s| {
+            if let Some(spectrum) = s.upgrade() {
+                let mut spectrum = spectrum.borrow_mut();
+                let name = spectrum.get_name();
+                let _span = span!(Level::TRACE, "handle_event", spectrum = %name).entered();
+                spectrum.handle_event(event);
+                event!(Level::TRACE, spectrum = %name, "Spectrum given the event");
+                true
+            } else {
+                false
+            }
+        });
+        true
+    }
+    fn reset(&mut self) {}
+}
+
+///
+/// CountStage counts the events that reach it and passes
+/// them all on.  reset zeroes the count.
+///
+pub struct CountStage {
+    count: u64,
+}
+impl CountStage {
+    pub fn new() -> CountStage {
+        CountStage { count: 0 }
+    }
+    pub fn get_count(&self) -> u64 {
+        self.count
+    }
+}
+impl EventProcessor for CountStage {
+    fn process(&mut self, _event: &mut FlatEvent) -> bool {
+        self.count += 1;
+        true
+    }
+    fn reset(&mut self) {
+        self.count = 0;
+    }
+}
+
+///
+/// SamplingStage passes the first event and every divisor'th event
+/// after that, rejecting the rest.  This is useful for online
+/// monitoring at rates too high to histogram every event.
+/// A divisor of 0 is treated as 1 (pass all events).
+///
+pub struct SamplingStage {
+    divisor: u64,
+    counter: u64,
+}
+impl SamplingStage {
+    pub fn new(divisor: u64) -> SamplingStage {
+        SamplingStage {
+            divisor: divisor.max(1),
+            counter: 0,
+        }
+    }
+    pub fn get_divisor(&self) -> u64 {
+        self.divisor
+    }
+}
+impl EventProcessor for SamplingStage {
+    fn process(&mut self, _event: &mut FlatEvent) -> bool {
+        let result = self.counter.is_multiple_of(self.divisor);
+        self.counter += 1;
+        result
+    }
+    fn reset(&mut self) {
+        self.counter = 0;
+    }
+}
+
+#[cfg(test)]
+mod gate_stage_tests {
+    use super::*;
+    use crate::parameters::*;
+    use std::cell::RefCell;
+
+    #[test]
+    fn new_1() {
+        let cdict = ConditionDictionary::new();
+        let result = GateStage::new("nosuch", &cdict);
+        assert!(result.is_err());
+        assert_eq!(String::from("No such gate nosuch"), result.err().unwrap());
+    }
+    #[test]
+    fn new_2() {
+        let mut cdict = ConditionDictionary::new();
+        cdict.insert(String::from("true"), Rc::new(RefCell::new(True {})));
+        let stage = GateStage::new("true", &cdict).unwrap();
+        assert_eq!(String::from("true"), stage.condition_name());
+    }
+    #[test]
+    fn process_1() {
+        let mut cdict = ConditionDictionary::new();
+        cdict.insert(
+            String::from("cut"),
+            Rc::new(RefCell::new(Cut::new(1, 10.0, 20.0))),
+        );
+        let mut stage = GateStage::new("cut", &cdict).unwrap();
+
+        let mut fe = FlatEvent::new();
+        fe.load_event(&vec![EventParameter::new(1, 15.0)]);
+        assert!(stage.process(&mut fe));
+
+        invalidate_cache(&mut cdict);
+        fe.load_event(&vec![EventParameter::new(1, 25.0)]);
+        assert!(!stage.process(&mut fe));
+    }
+    #[test]
+    fn process_2() {
+        // Deleted gates reject:
+
+        let mut cdict = ConditionDictionary::new();
+        cdict.insert(String::from("true"), Rc::new(RefCell::new(True {})));
+        let mut stage = GateStage::new("true", &cdict).unwrap();
+        cdict.remove("true");
+
+        let mut fe = FlatEvent::new();
+        assert!(!stage.process(&mut fe));
+    }
+}
+#[cfg(test)]
+mod fill_stage_tests {
+    use super::*;
+    use crate::parameters::*;
+    use ndhistogram::*;
+    use std::cell::RefCell;
+
+    fn make_spectra() -> (ParameterDictionary, SpectrumDictionary) {
+        let mut pdict = ParameterDictionary::new();
+        pdict.add("p").unwrap();
+        let mut sdict = SpectrumDictionary::new();
+        for name in ["s1", "s2"] {
+            let s: SpectrumContainer = Rc::new(RefCell::new(
+                Oned::new(name, "p", &pdict, Some(0.0), Some(10.0), Some(10)).unwrap(),
+            ));
+            sdict.insert(String::from(name), s);
+        }
+        (pdict, sdict)
+    }
+    fn counts(s: &SpectrumContainer) -> f64 {
+        let h = s.borrow().get_histogram_1d().unwrap();
+        let total = h.borrow().values().map(|v| v.get()).sum();
+        total
+    }
+
+    #[test]
+    fn new_1() {
+        let (_pdict, sdict) = make_spectra();
+        let stage = FillStage::new(&sdict);
+        assert_eq!(2, stage.len());
+    }
+    #[test]
+    fn process_1() {
+        let (pdict, sdict) = make_spectra();
+        let mut stage = FillStage::new(&sdict);
+        let pid = pdict.lookup("p").unwrap().get_id();
+
+        let mut fe = FlatEvent::new();
+        fe.load_event(&vec![EventParameter::new(pid, 5.0)]);
+        assert!(stage.process(&mut fe));
+
+        for s in sdict.values() {
+            assert_eq!(1.0, counts(s));
+        }
+    }
+    #[test]
+    fn process_2() {
+        // Removed spectra are pruned:
+
+        let (pdict, mut sdict) = make_spectra();
+        let mut stage = FillStage::new(&sdict);
+        sdict.remove("s1");
+        let pid = pdict.lookup("p").unwrap().get_id();
+
+        let mut fe = FlatEvent::new();
+        fe.load_event(&vec![EventParameter::new(pid, 5.0)]);
+        assert!(stage.process(&mut fe));
+        assert_eq!(1, stage.len());
+        assert_eq!(1.0, counts(sdict.get("s2").unwrap()));
+    }
+    #[test]
+    fn add_1() {
+        let (_pdict, sdict) = make_spectra();
+        let mut stage = FillStage::new(&SpectrumDictionary::new());
+        assert!(stage.is_empty());
+        stage.add_spectrum(sdict.get("s1").unwrap());
+        assert_eq!(1, stage.len());
+    }
+}
+#[cfg(test)]
+mod count_stage_tests {
+    use super::*;
+
+    #[test]
+    fn new_1() {
+        let c = CountStage::new();
+        assert_eq!(0, c.get_count());
+    }
+    #[test]
+    fn process_1() {
+        let mut c = CountStage::new();
+        let mut fe = FlatEvent::new();
+        for _ in 0..10 {
+            assert!(c.process(&mut fe));
+        }
+        assert_eq!(10, c.get_count());
+        c.reset();
+        assert_eq!(0, c.get_count());
+    }
+}
+#[cfg(test)]
+mod sampling_stage_tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[test]
+    fn new_1() {
+        assert_eq!(10, SamplingStage::new(10).get_divisor());
+        assert_eq!(1, SamplingStage::new(0).get_divisor());
+    }
+    #[test]
+    fn process_1() {
+        // Divisor 1 passes everything:
+
+        let mut s = SamplingStage::new(1);
+        let mut e = FlatEvent::new();
+        for _ in 0..100 {
+            assert!(s.process(&mut e));
+        }
+    }
+    #[test]
+    fn process_2() {
+        // First event and every 3rd after that:
+
+        let mut s = SamplingStage::new(3);
+        let mut e = FlatEvent::new();
+        let accepted: Vec<bool> = (0..7).map(|_| s.process(&mut e)).collect();
+        assert_eq!(vec![true, false, false, true, false, false, true], accepted);
+
+        s.reset();
+        assert!(s.process(&mut e));
+    }
+    #[test]
+    fn pipeline_1() {
+        let counter = Rc::new(RefCell::new(CountStage::new()));
+        let mut p = Pipeline::new();
+        p.add_stage(Box::new(SamplingStage::new(10)))
+            .add_stage(Box::new(Rc::clone(&counter)));
+
+        let mut e = FlatEvent::new();
+        for _ in 0..1000 {
+            p.process_event(&mut e);
+        }
+        assert_eq!(100, counter.borrow().get_count());
+    }
+}
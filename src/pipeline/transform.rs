@@ -0,0 +1,179 @@
+//!  Ring item transforms.  A RingItemTransform maps one raw ring item
+//!  to another, e.g. to merge files whose timestamps or source ids
+//!  need adjusting.  A TransformChain applies a sequence of transforms
+//!  in order.
+//!
+//!  Pre-built transforms:
+//!
+//!  *  TimestampOffset - adds a constant to the body header timestamp.
+//!  *  SourceIdRewrite - replaces the body header source id.
+//!  *  TypeFilter - replaces items whose types are not allowed with
+//!     a filtered item (see is_filtered).
+//!
+//!  Items without body headers pass through TimestampOffset and
+//!  SourceIdRewrite unchanged.
+//!
+use crate::ring_items::RingItem;
+use std::collections::HashSet;
+
+/// Type of the sentinel item TypeFilter produces.  No NSCLDAQ ring
+/// item type is zero.
+pub const FILTERED_ITEM_TYPE: u32 = 0;
+
+/// True if an item was rejected by a TypeFilter.
+pub fn is_filtered(item: &RingItem) -> bool {
+    item.type_id() == FILTERED_ITEM_TYPE
+}
+
+pub struct RingItemTransform {
+    f: Box<dyn Fn(RingItem) -> RingItem>,
+}
+impl RingItemTransform {
+    pub fn new<F: Fn(RingItem) -> RingItem + 'static>(f: F) -> RingItemTransform {
+        RingItemTransform { f: Box::new(f) }
+    }
+    pub fn apply(&self, item: RingItem) -> RingItem {
+        (self.f)(item)
+    }
+}
+
+/// Add delta to the body header timestamp.
+pub struct TimestampOffset(pub i64);
+impl From<TimestampOffset> for RingItemTransform {
+    fn from(t: TimestampOffset) -> RingItemTransform {
+        let delta = t.0;
+        RingItemTransform::new(move |mut item| {
+            if let Some(h) = item.get_bodyheader() {
+                let stamp = h.timestamp.wrapping_add_signed(delta);
+                item.set_body_header(stamp, h.source_id, h.barrier_type);
+            }
+            item
+        })
+    }
+}
+/// Replace the body header source id.
+pub struct SourceIdRewrite(pub u32);
+impl From<SourceIdRewrite> for RingItemTransform {
+    fn from(t: SourceIdRewrite) -> RingItemTransform {
+        let new_id = t.0;
+        RingItemTransform::new(move |mut item| {
+            if let Some(h) = item.get_bodyheader() {
+                item.set_body_header(h.timestamp, new_id, h.barrier_type);
+            }
+            item
+        })
+    }
+}
+/// Pass only items whose types are in the set.
+pub struct TypeFilter(pub HashSet<u32>);
+impl From<TypeFilter> for RingItemTransform {
+    fn from(t: TypeFilter) -> RingItemTransform {
+        let allowed = t.0;
+        RingItemTransform::new(move |item| {
+            if allowed.contains(&item.type_id()) {
+                item
+            } else {
+                RingItem::new(FILTERED_ITEM_TYPE)
+            }
+        })
+    }
+}
+
+/// A sequence of transforms applied in the order they were added.
+pub struct TransformChain {
+    transforms: Vec<RingItemTransform>,
+}
+impl TransformChain {
+    /// An empty chain passes items unchanged.
+    pub fn new() -> TransformChain {
+        TransformChain {
+            transforms: Vec::new(),
+        }
+    }
+    /// Append a transform.  Returns self so additions can be chained.
+    pub fn add<T: Into<RingItemTransform>>(&mut self, transform: T) -> &mut TransformChain {
+        self.transforms.push(transform.into());
+        self
+    }
+    pub fn len(&self) -> usize {
+        self.transforms.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.transforms.is_empty()
+    }
+    pub fn apply(&self, item: RingItem) -> RingItem {
+        self.transforms.iter().fold(item, |item, t| t.apply(item))
+    }
+}
+
+#[cfg(test)]
+mod transform_tests {
+    use super::*;
+
+    fn make_item(type_id: u32) -> RingItem {
+        let mut item = RingItem::new_with_body_header(type_id, 5000, 1, 0);
+        item.add(0x12345678_u32);
+        item
+    }
+
+    #[test]
+    fn chain_1() {
+        let mut chain = TransformChain::new();
+        chain.add(TimestampOffset(1000)).add(SourceIdRewrite(99));
+        assert_eq!(2, chain.len());
+
+        let out = chain.apply(make_item(30));
+        let h = out.get_bodyheader().unwrap();
+        assert_eq!(6000, h.timestamp);
+        assert_eq!(99, h.source_id);
+        assert_eq!(0, h.barrier_type);
+        assert_eq!(Some(0x12345678_u32), out.payload_as::<u32>(16));
+        assert_eq!(make_item(30).size(), out.size());
+    }
+    #[test]
+    fn chain_2() {
+        // Negative offsets, no body header and an empty chain:
+
+        let chain = {
+            let mut c = TransformChain::new();
+            c.add(TimestampOffset(-5001));
+            c
+        };
+        let out = chain.apply(make_item(30));
+        assert_eq!(u64::MAX, out.get_bodyheader().unwrap().timestamp);
+
+        let mut item = RingItem::new(30);
+        item.add(1_u32);
+        let bytes = item.to_bytes();
+        assert_eq!(bytes, chain.apply(item).to_bytes());
+
+        let empty = TransformChain::new();
+        assert!(empty.is_empty());
+        assert_eq!(
+            make_item(1).to_bytes(),
+            empty.apply(make_item(1)).to_bytes()
+        );
+    }
+    #[test]
+    fn filter_1() {
+        let mut chain = TransformChain::new();
+        chain
+            .add(TypeFilter(HashSet::from([1, 30])))
+            .add(TimestampOffset(1));
+        assert_eq!(
+            5001,
+            chain
+                .apply(make_item(30))
+                .get_bodyheader()
+                .unwrap()
+                .timestamp
+        );
+        assert!(!is_filtered(&chain.apply(make_item(1))));
+        assert!(is_filtered(&chain.apply(make_item(20))));
+
+        // Custom transforms:
+
+        chain.add(RingItemTransform::new(|_| RingItem::new(2)));
+        assert_eq!(2, chain.apply(make_item(30)).type_id());
+    }
+}
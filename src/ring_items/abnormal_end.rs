@@ -1,18 +1,45 @@
 use crate::ring_items;
 use std::fmt;
 ///
-/// Abnormal ends are empty actually.
+/// Abnormal ends from older (V11) producers are empty.  Others
+/// may carry the exit code of the program that ended abnormally and a
+/// diagnostic message.  The payload is then the i32 exit code
+/// followed by the message as a null terminated string.
 ///
-pub struct AbnormalEnd {}
+#[derive(Default)]
+pub struct AbnormalEnd {
+    exit_code: Option<i32>,
+    message: Option<String>,
+}
 
 impl AbnormalEnd {
-    pub fn new() -> AbnormalEnd {
-        AbnormalEnd {}
+    pub fn new(exit_code: i32, message: &str) -> AbnormalEnd {
+        AbnormalEnd {
+            exit_code: Some(exit_code),
+            message: Some(String::from(message)),
+        }
+    }
+    /// An empty (V11 style) abnormal end.
+    pub fn empty() -> AbnormalEnd {
+        AbnormalEnd::default()
+    }
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
+    pub fn message(&self) -> Option<String> {
+        self.message.clone()
     }
 }
 impl fmt::Display for AbnormalEnd {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "Abnormal end Item")
+        write!(f, "Abnormal end Item")?;
+        if let Some(code) = self.exit_code {
+            write!(f, " exit code: {}", code)?;
+        }
+        if let Some(msg) = &self.message {
+            write!(f, " : {}", msg)?;
+        }
+        Ok(())
     }
 }
 /// ToRaw trait allows the abnormal end items to convert to
@@ -20,19 +47,31 @@ impl fmt::Display for AbnormalEnd {
 
 impl ring_items::ToRaw for AbnormalEnd {
     fn to_raw(&self) -> ring_items::RingItem {
-        ring_items::RingItem::new(ring_items::ABNORMAL_END)
+        let mut result = ring_items::RingItem::new(ring_items::ABNORMAL_END);
+        if let Some(code) = self.exit_code {
+            result
+                .add(code)
+                .add_string(self.message.as_deref().unwrap_or(""));
+        }
+        result
     }
 }
 impl ring_items::FromRaw<AbnormalEnd> for ring_items::RingItem {
     fn to_specific(
         self: &ring_items::RingItem,
         _v: ring_items::RingVersion,
-    ) -> Option<AbnormalEnd> {
-        if self.type_id() == ring_items::ABNORMAL_END {
-            Some(AbnormalEnd::new())
-        } else {
-            None
+    ) -> Result<AbnormalEnd, ring_items::RingItemError> {
+        self.check_type(ring_items::ABNORMAL_END)?;
+        if self.payload().is_empty() {
+            return Ok(AbnormalEnd::empty());
         }
+        let exit_code = self.payload_field::<i32>(0)?;
+        let (message, _) = ring_items::read_string(self.payload(), 4).ok_or_else(|| {
+            ring_items::RingItemError::InvalidPayload(String::from(
+                "Abnormal end message is not a null terminated UTF-8 string",
+            ))
+        })?;
+        Ok(AbnormalEnd::new(exit_code, &message))
     }
 }
 
@@ -47,21 +86,60 @@ mod abend_tests {
     #[test]
     fn fromraw_1() {
         let raw = RingItem::new(crate::ring_items::ABNORMAL_END);
-        let result: Option<AbnormalEnd> = raw.to_specific(RingVersion::V11);
-        assert!(result.is_some());
+        let result: Result<AbnormalEnd, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert!(result.is_ok());
+        let result = result.unwrap();
+        assert!(result.exit_code().is_none());
+        assert!(result.message().is_none());
     }
     #[test]
     fn fromraw_2() {
         let raw = RingItem::new(crate::ring_items::BEGIN_RUN);
-        let result: Option<AbnormalEnd> = raw.to_specific(RingVersion::V11);
-        assert!(result.is_none());
+        let result: Result<AbnormalEnd, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert_eq!(
+            Some(RingItemError::UnexpectedItemType {
+                expected: crate::ring_items::ABNORMAL_END,
+                found: crate::ring_items::BEGIN_RUN
+            }),
+            result.err()
+        );
     }
     #[test]
     fn toraw_1() {
-        let end = abnormal_end::AbnormalEnd::new();
+        let end = abnormal_end::AbnormalEnd::empty();
         let raw = end.to_raw();
         assert_eq!(crate::ring_items::ABNORMAL_END, raw.type_id());
         assert!(!raw.has_body_header());
         assert_eq!(3 * size_of::<u32>() as u32, raw.size());
     }
+    #[test]
+    fn round_trip_1() {
+        let end = AbnormalEnd::new(42, "segfault in pipeline");
+        let raw = end.to_raw();
+        assert_eq!(
+            (3 * size_of::<u32>() + size_of::<i32>() + 21) as u32,
+            raw.size()
+        );
+        let result: AbnormalEnd = raw.to_specific(RingVersion::V12).unwrap();
+        assert_eq!(Some(42), result.exit_code());
+        assert_eq!(Some(String::from("segfault in pipeline")), result.message());
+        assert_eq!(
+            "Abnormal end Item exit code: 42 : segfault in pipeline",
+            format!("{}", result)
+        );
+    }
+    #[test]
+    fn fromraw_3() {
+        // Bad payloads:
+
+        let mut raw = RingItem::new(crate::ring_items::ABNORMAL_END);
+        raw.add(1_u8);
+        let result: Result<AbnormalEnd, RingItemError> = raw.to_specific(RingVersion::V12);
+        assert!(matches!(result, Err(RingItemError::PayloadTooShort { .. })));
+
+        let mut raw = RingItem::new(crate::ring_items::ABNORMAL_END);
+        raw.add(1_i32).add(b'x');
+        let result: Result<AbnormalEnd, RingItemError> = raw.to_specific(RingVersion::V12);
+        assert!(matches!(result, Err(RingItemError::InvalidPayload(_))));
+    }
 }
@@ -1,5 +1,11 @@
+use crate::parameters::{Event, EventParameter, FlatEvent, ParameterDictionary};
 use crate::ring_items;
+use crate::ring_items::FromRaw;
+use std::collections::HashMap;
 use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, Seek, SeekFrom};
+use std::iter::Peekable;
 use std::mem;
 use std::slice::Iter;
 
@@ -60,13 +66,45 @@ impl ParameterDefinitions {
         self
     }
 
+    /// Define the parameters in a parameter dictionary.  Parameters
+    /// that are already defined are left alone.  Note that the dictionary
+    /// assigns its own ids so a ParameterIdMap is needed to map the ids
+    /// in parameter items to dictionary ids if the dictionary was not
+    /// empty.
+    ///
+    pub fn apply_to_dictionary(&self, dict: &mut ParameterDictionary) -> Result<(), String> {
+        for def in &self.defs {
+            if dict.lookup(&def.name).is_none() {
+                dict.add(&def.name)
+                    .map_err(|e| format!("Unable to define {}: {}", def.name, e))?;
+            }
+        }
+        Ok(())
+    }
+    /// Create the definitions for the parameters in a dictionary.
+    /// The definitions are in id order.  Only the names and ids are
+    /// carried by the ring item so parameter metadata (limits, bins,
+    /// units) is not included.
+    ///
+    pub fn from_dictionary(dict: &ParameterDictionary) -> ParameterDefinitions {
+        let mut defs: Vec<ParameterDefinition> = dict
+            .iter()
+            .map(|(name, p)| ParameterDefinition::new(p.get_id(), name))
+            .collect();
+        defs.sort_by_key(|d| d.id);
+        ParameterDefinitions { defs }
+    }
+
     // Private methods.
 
-    fn get_parameter_def(offset: &mut usize, bytes: &[u8]) -> ParameterDefinition {
-        let id = u32::from_ne_bytes(bytes[*offset..*offset + 4].try_into().unwrap());
-        *offset = offset.checked_add(4).unwrap();
-        let name = ring_items::get_c_string(offset, &bytes);
-        ParameterDefinition::new(id, &name)
+    fn get_parameter_def(
+        offset: &mut usize,
+        item: &ring_items::RingItem,
+    ) -> Result<ParameterDefinition, ring_items::RingItemError> {
+        let id = item.payload_field::<u32>(*offset)?;
+        let (name, next) = item.payload_string(*offset + 4)?;
+        *offset = next;
+        Ok(ParameterDefinition::new(id, &name))
     }
 }
 impl fmt::Display for ParameterDefinitions {
@@ -101,24 +139,74 @@ impl ring_items::FromRaw<ParameterDefinitions> for ring_items::RingItem {
     fn to_specific(
         self: &ring_items::RingItem,
         _v: ring_items::RingVersion,
-    ) -> Option<ParameterDefinitions> {
-        if self.type_id() == ring_items::PARAMETER_DEFINITIONS {
-            let mut result = ParameterDefinitions::new();
-            let payload = self.payload().as_slice();
-            let num = u32::from_ne_bytes(payload[0..4].try_into().unwrap());
-
-            let mut offset = 4;
-            for _ in 0..num {
-                result.defs.push(ParameterDefinitions::get_parameter_def(
-                    &mut offset,
-                    &payload,
-                ));
+    ) -> Result<ParameterDefinitions, ring_items::RingItemError> {
+        self.check_type(ring_items::PARAMETER_DEFINITIONS)?;
+        let mut result = ParameterDefinitions::new();
+        let num = self.payload_field::<u32>(0)?;
+
+        let mut offset = 4;
+        for _ in 0..num {
+            result
+                .defs
+                .push(ParameterDefinitions::get_parameter_def(&mut offset, self)?);
+        }
+        Ok(result)
+    }
+}
+
+///
+/// Make the PARAMETER_DEFINITIONS item that describes the parameters in
+/// a dictionary, e.g. for the header of a filtered event file.  The
+/// definitions are in id order.  The NSCLDAQ item only has names and
+/// ids so the parameter limits and bins are not written.
+///
+pub fn to_parameter_definitions_item(dict: &ParameterDictionary) -> ring_items::RingItem {
+    ring_items::ToRaw::to_raw(&ParameterDefinitions::from_dictionary(dict))
+}
+
+/// The differences between two sets of parameter definitions
+/// (see diff_definitions).  Each list is in id order.
+///
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DefinitionsDiff {
+    pub added: Vec<(u32, String)>,
+    pub removed: Vec<(u32, String)>,
+    pub renamed: Vec<(u32, String, String)>, // id, name in a, name in b.
+}
+///
+/// Compare two sets of parameter definitions, e.g. from successive runs.
+/// Definitions are matched by id:
+///
+/// *  added - ids only in b.
+/// *  removed - ids only in a.
+/// *  renamed - ids in both whose names differ.
+///
+pub fn diff_definitions(a: &ParameterDefinitions, b: &ParameterDefinitions) -> DefinitionsDiff {
+    let a_names: HashMap<u32, &String> = a.defs.iter().map(|d| (d.id, &d.name)).collect();
+    let b_names: HashMap<u32, &String> = b.defs.iter().map(|d| (d.id, &d.name)).collect();
+    let mut result = DefinitionsDiff::default();
+
+    for def in a.defs.iter() {
+        match b_names.get(&def.id) {
+            None => result.removed.push((def.id, def.name.clone())),
+            Some(bname) => {
+                if **bname != def.name {
+                    result
+                        .renamed
+                        .push((def.id, def.name.clone(), (*bname).clone()));
+                }
             }
-            Some(result)
-        } else {
-            None
         }
     }
+    for def in b.defs.iter() {
+        if !a_names.contains_key(&def.id) {
+            result.added.push((def.id, def.name.clone()));
+        }
+    }
+    result.added.sort();
+    result.removed.sort();
+    result.renamed.sort();
+    result
 }
 
 //-------------------------------------------------------------
@@ -188,6 +276,32 @@ impl VariableValues {
         self.defs.push(def);
         self
     }
+    /// The variables as (name, value, units) triples.
+    pub fn variables(&self) -> Vec<(String, f64, String)> {
+        self.defs
+            .iter()
+            .map(|d| (d.name.clone(), d.value, d.units.clone()))
+            .collect()
+    }
+    /// Build the item from a map of name -> (value, units).
+    /// The variables are put in the item in name order.
+    ///
+    pub fn from_map(vars: &HashMap<String, (f64, String)>) -> VariableValues {
+        let mut defs: Vec<VariableValue> = vars
+            .iter()
+            .map(|(name, (value, units))| VariableValue::new(*value, name, units))
+            .collect();
+        defs.sort_by(|a, b| a.name.cmp(&b.name));
+        VariableValues { defs }
+    }
+    /// Put the variables in a map of name -> (value, units).
+    /// Variables already in the map are replaced.
+    ///
+    pub fn apply_to_map(&self, out: &mut HashMap<String, (f64, String)>) {
+        for d in &self.defs {
+            out.insert(d.name.clone(), (d.value, d.units.clone()));
+        }
+    }
 }
 impl fmt::Display for VariableValues {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -232,26 +346,28 @@ impl ring_items::FromRaw<VariableValues> for ring_items::RingItem {
     fn to_specific(
         self: &ring_items::RingItem,
         _v: ring_items::RingVersion,
-    ) -> Option<VariableValues> {
-        if self.type_id() == ring_items::VARIABLE_VALUES {
-            let mut result = VariableValues::new();
-            let payload = self.payload().as_slice();
-            let nvars = u32::from_ne_bytes(payload[0..4].try_into().unwrap());
-            let mut offset = 4;
-            for _ in 0..nvars {
-                let value: f64 =
-                    f64::from_ne_bytes(payload[offset..offset + 8].try_into().unwrap());
-                offset += mem::size_of::<f64>();
-                let mut off = offset;
-                let units = ring_items::get_c_string(&mut off, &payload);
-                offset = offset + MAX_UNITS_LENGTH;
-                let name = ring_items::get_c_string(&mut offset, &payload);
-                result.defs.push(VariableValue::new(value, &name, &units));
+    ) -> Result<VariableValues, ring_items::RingItemError> {
+        self.check_type(ring_items::VARIABLE_VALUES)?;
+        let mut result = VariableValues::new();
+        let payload = self.payload().as_slice();
+        let nvars = self.payload_field::<u32>(0)?;
+        let mut offset = 4;
+        for _ in 0..nvars {
+            let value = self.payload_field::<f64>(offset)?;
+            offset += mem::size_of::<f64>();
+            let (units, _) = self.payload_string(offset)?;
+            offset += MAX_UNITS_LENGTH;
+            if offset > payload.len() {
+                return Err(ring_items::RingItemError::PayloadTooShort {
+                    needed: offset,
+                    available: payload.len(),
+                });
             }
-            Some(result)
-        } else {
-            None
+            let (name, next) = self.payload_string(offset)?;
+            offset = next;
+            result.defs.push(VariableValue::new(value, &name, &units));
         }
+        Ok(result)
     }
 }
 //---------------------------------------------------------------
@@ -342,39 +458,102 @@ impl ring_items::FromRaw<ParameterItem> for ring_items::RingItem {
     fn to_specific(
         self: &ring_items::RingItem,
         _v: ring_items::RingVersion,
-    ) -> Option<ParameterItem> {
-        if self.type_id() == ring_items::PARAMETER_DATA {
-            let payload = self.payload().as_slice();
-            let trigger: u64 = u64::from_ne_bytes(payload[0..8].try_into().unwrap());
-            let mut result = ParameterItem::new(trigger);
-            let num = u32::from_ne_bytes(payload[8..12].try_into().unwrap());
-            let mut offset = 12; // First id/value pair.
-            for _ in 0..num {
-                let id = u32::from_ne_bytes(
-                    payload[offset..offset + mem::size_of::<u32>()]
-                        .try_into()
-                        .unwrap(),
-                );
-                offset += mem::size_of::<u32>();
-                let value = f64::from_ne_bytes(
-                    payload[offset..offset + mem::size_of::<f64>()]
-                        .try_into()
-                        .unwrap(),
-                );
-                result.parameters.push(ParameterValue::new(id, value));
-                offset = offset + mem::size_of::<f64>();
-            }
+    ) -> Result<ParameterItem, ring_items::RingItemError> {
+        self.check_type(ring_items::PARAMETER_DATA)?;
+        let trigger = self.payload_field::<u64>(0)?;
+        let mut result = ParameterItem::new(trigger);
+        let num = self.payload_field::<u32>(8)?;
+        let mut offset = 12; // First id/value pair.
+        for _ in 0..num {
+            let id = self.payload_field::<u32>(offset)?;
+            offset += mem::size_of::<u32>();
+            let value = self.payload_field::<f64>(offset)?;
+            result.parameters.push(ParameterValue::new(id, value));
+            offset += mem::size_of::<f64>();
+        }
 
-            Some(result)
-        } else {
-            None
+        Ok(result)
+    }
+}
+
+//---------------------------------------------------------------
+// Support for using parameter items as events:
+//---------------------------------------------------------------
+
+/// Load the parameters from a set of parameter items into a flat
+/// event.  The parameters of all of the items are loaded as a single
+/// event; normally the items all have the same trigger number
+/// (see same_event_group).
+///
+pub fn load_into_flat_event(items: &[ParameterItem], flat: &mut FlatEvent) {
+    let event: Event = items
+        .iter()
+        .flat_map(|item| item.iter())
+        .map(|p| EventParameter::new(p.id(), p.value()))
+        .collect();
+    flat.load_event(&event);
+}
+/// Take the next group of items that have the same trigger number
+/// from an iterator.  The iterator must be peekable so that the first
+/// item of the following group is not consumed.  An empty vector is
+/// returned if the iterator is exhausted.
+///
+pub fn same_event_group<'a, I>(iter: &mut Peekable<I>) -> Vec<&'a ParameterItem>
+where
+    I: Iterator<Item = &'a ParameterItem>,
+{
+    let mut result = Vec::new();
+    if let Some(first) = iter.next() {
+        let trigger = first.trigger();
+        result.push(first);
+        while let Some(item) = iter.next_if(|i| i.trigger() == trigger) {
+            result.push(item);
         }
     }
+    result
+}
+
+//---------------------------------------------------------------
+// Parameter dictionaries from run files:
+//---------------------------------------------------------------
+
+///
+/// Create a parameter dictionary from the first PARAMETER_DEFINITIONS
+/// item in a run file.  The parameters get the ids in the item so
+/// parameter items in the file can be used without an id map.  The file
+/// is then rewound so the caller can process all of its items.  If the
+/// file has no definitions, the dictionary is empty.
+///
+pub fn import_from_run_file(
+    f: &mut File,
+) -> Result<ParameterDictionary, ring_items::RingItemError> {
+    let mut dict = ParameterDictionary::new();
+    {
+        let mut reader = BufReader::new(&mut *f);
+        loop {
+            let item = match ring_items::RingItem::read_item(&mut reader) {
+                Ok(item) => item,
+                Err(ring_items::RingItemError::HeaderReadFailed) => break, // end of file.
+                Err(e) => return Err(e),
+            };
+            if item.type_id() == ring_items::PARAMETER_DEFINITIONS {
+                let defs: ParameterDefinitions = item.to_specific(ring_items::RingVersion::V11)?;
+                let pairs: Vec<(u32, &str)> =
+                    defs.iter().map(|d| (d.id(), d.name.as_str())).collect();
+                dict.add_bulk(&pairs)
+                    .map_err(ring_items::RingItemError::InvalidPayload)?;
+                break;
+            }
+        }
+    }
+    f.seek(SeekFrom::Start(0))
+        .map_err(|e| ring_items::RingItemError::IoFailure(e.to_string()))?;
+    Ok(dict)
 }
 
 #[cfg(test)]
 mod test_paramdef {
-    use crate::analysis_ring_items::ParameterDefinition;
+    use crate::ring_items::analysis_ring_items::ParameterDefinition;
     #[test]
     fn new_1() {
         let def = ParameterDefinition::new(12, "Item");
@@ -394,7 +573,7 @@ mod test_paramdef {
 }
 #[cfg(test)]
 mod test_paramdefs {
-    use crate::analysis_ring_items::*;
+    use crate::ring_items::analysis_ring_items::*;
     use crate::ring_items::*;
     use std::mem::size_of;
     #[test]
@@ -475,8 +654,8 @@ mod test_paramdefs {
     fn from_raw_1() {
         let item = ParameterDefinitions::new();
         let raw = item.to_raw();
-        let recons: Option<ParameterDefinitions> = raw.to_specific(RingVersion::V11);
-        assert!(recons.is_some());
+        let recons: Result<ParameterDefinitions, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert!(recons.is_ok());
         let recons = recons.unwrap();
         assert_eq!(0, recons.defs.len());
     }
@@ -486,8 +665,8 @@ mod test_paramdefs {
         item.add_definition(ParameterDefinition::new(1, "item1"))
             .add_definition(ParameterDefinition::new(2, "item2"));
         let raw = item.to_raw();
-        let recons: Option<ParameterDefinitions> = raw.to_specific(RingVersion::V11);
-        assert!(recons.is_some());
+        let recons: Result<ParameterDefinitions, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert!(recons.is_ok());
         let recons = recons.unwrap();
         assert_eq!(2, recons.defs.len());
         assert_eq!(item.defs[0].id(), recons.defs[0].id());
@@ -497,11 +676,64 @@ mod test_paramdefs {
     }
     #[test]
     fn from_raw_3() {
-        // wrong  type -> None:
+        // wrong  type -> Err:
 
         let raw = ring_items::RingItem::new(PARAMETER_DEFINITIONS - 1);
-        let recons: Option<ParameterDefinitions> = raw.to_specific(RingVersion::V11);
-        assert!(recons.is_none());
+        let recons: Result<ParameterDefinitions, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert_eq!(
+            Some(RingItemError::UnexpectedItemType {
+                expected: PARAMETER_DEFINITIONS,
+                found: PARAMETER_DEFINITIONS - 1
+            }),
+            recons.err()
+        );
+    }
+    #[test]
+    fn from_raw_4() {
+        // Truncated payload -> Err:
+
+        let mut raw = ring_items::RingItem::new(PARAMETER_DEFINITIONS);
+        let recons: Result<ParameterDefinitions, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert_eq!(
+            Some(RingItemError::PayloadTooShort {
+                needed: 4,
+                available: 0
+            }),
+            recons.err()
+        );
+        raw.add(2_u32);
+        let recons: Result<ParameterDefinitions, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert_eq!(
+            Some(RingItemError::PayloadTooShort {
+                needed: 8,
+                available: 4
+            }),
+            recons.err()
+        );
+
+        // Name without a terminator:
+
+        raw.add(1_u32).add_byte_vec(&b"abc".to_vec());
+        let recons: Result<ParameterDefinitions, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert_eq!(
+            Some(RingItemError::PayloadTooShort {
+                needed: 12,
+                available: 11
+            }),
+            recons.err()
+        );
+    }
+    #[test]
+    fn from_raw_5() {
+        // Names that are not UTF-8 -> Err:
+
+        let mut raw = ring_items::RingItem::new(PARAMETER_DEFINITIONS);
+        raw.add(1_u32).add(1_u32).add_byte_vec(&vec![0xff, 0xfe, 0]);
+        let recons: Result<ParameterDefinitions, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert!(matches!(
+            recons.err(),
+            Some(RingItemError::InvalidPayload(_))
+        ));
     }
     #[test]
     fn getdef_1() {
@@ -517,10 +749,45 @@ mod test_paramdefs {
             i += 1;
         }
     }
+    #[test]
+    fn diff_1() {
+        let mut a = ParameterDefinitions::new();
+        for i in 0..9 {
+            a.add_definition(ParameterDefinition::new(i, &format!("p.{}", i)));
+        }
+        // b drops p.4, renames p.2 and adds two:
+
+        let mut b = ParameterDefinitions::new();
+        for i in (0..9).filter(|i| *i != 4) {
+            let name = if i == 2 {
+                String::from("renamed")
+            } else {
+                format!("p.{}", i)
+            };
+            b.add_definition(ParameterDefinition::new(i, &name));
+        }
+        b.add_definition(ParameterDefinition::new(20, "new.2"));
+        b.add_definition(ParameterDefinition::new(10, "new.1"));
+
+        let diff = diff_definitions(&a, &b);
+        assert_eq!(
+            vec![(10, String::from("new.1")), (20, String::from("new.2"))],
+            diff.added
+        );
+        assert_eq!(vec![(4, String::from("p.4"))], diff.removed);
+        assert_eq!(
+            vec![(2, String::from("p.2"), String::from("renamed"))],
+            diff.renamed
+        );
+
+        // No differences:
+
+        assert_eq!(DefinitionsDiff::default(), diff_definitions(&a, &a));
+    }
 }
 #[cfg(test)]
 mod test_vars {
-    use crate::analysis_ring_items::*;
+    use crate::ring_items::analysis_ring_items::*;
     use crate::ring_items::*;
     use std::mem::size_of;
 
@@ -616,8 +883,8 @@ mod test_vars {
 
         let vars = VariableValues::new();
         let raw = vars.to_raw();
-        let recons: Option<VariableValues> = raw.to_specific(RingVersion::V11);
-        assert!(recons.is_some());
+        let recons: Result<VariableValues, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert!(recons.is_ok());
         let recons = recons.unwrap();
         assert_eq!(0, recons.defs.len());
     }
@@ -629,8 +896,8 @@ mod test_vars {
             .add_def(VariableValue::new(1.5, "Measure", "mm"));
 
         let raw = vars.to_raw();
-        let recons: Option<VariableValues> = raw.to_specific(RingVersion::V11);
-        assert!(recons.is_some());
+        let recons: Result<VariableValues, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert!(recons.is_ok());
         let recons = recons.unwrap();
 
         assert_eq!(vars.defs.len(), recons.defs.len());
@@ -645,13 +912,72 @@ mod test_vars {
         // wrong type of raw item.
 
         let raw = RingItem::new(VARIABLE_VALUES + 1); // wrong type.
-        let recons: Option<VariableValues> = raw.to_specific(RingVersion::V11);
-        assert!(recons.is_none());
+        let recons: Result<VariableValues, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert_eq!(
+            Some(RingItemError::UnexpectedItemType {
+                expected: VARIABLE_VALUES,
+                found: VARIABLE_VALUES + 1
+            }),
+            recons.err()
+        );
+    }
+    #[test]
+    fn from_raw_4() {
+        // Truncated payload -> Err:
+
+        let mut raw = RingItem::new(VARIABLE_VALUES);
+        raw.add(1_u32);
+        let recons: Result<VariableValues, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert_eq!(
+            Some(RingItemError::PayloadTooShort {
+                needed: 12,
+                available: 4
+            }),
+            recons.err()
+        );
+
+        // Units that run off the end of the payload:
+
+        raw.add(1.0_f64).add_byte_vec(&b"MeV".to_vec());
+        let recons: Result<VariableValues, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert_eq!(
+            Some(RingItemError::PayloadTooShort {
+                needed: 16,
+                available: 15
+            }),
+            recons.err()
+        );
+    }
+    #[test]
+    fn from_raw_5() {
+        // Units or names that are not UTF-8 -> Err:
+
+        let mut units = vec![0xff_u8, 0xfe];
+        units.resize(MAX_UNITS_LENGTH, 0);
+        let mut raw = RingItem::new(VARIABLE_VALUES);
+        raw.add(1_u32).add(1.0_f64).add_byte_vec(&units);
+        raw.add_byte_vec(&b"name\0".to_vec());
+        let recons: Result<VariableValues, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert!(matches!(
+            recons.err(),
+            Some(RingItemError::InvalidPayload(_))
+        ));
+
+        let mut units = b"MeV".to_vec();
+        units.resize(MAX_UNITS_LENGTH, 0);
+        let mut raw = RingItem::new(VARIABLE_VALUES);
+        raw.add(1_u32).add(1.0_f64).add_byte_vec(&units);
+        raw.add_byte_vec(&vec![b'x', 0x80, 0]);
+        let recons: Result<VariableValues, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert!(matches!(
+            recons.err(),
+            Some(RingItemError::InvalidPayload(_))
+        ));
     }
 }
 #[cfg(test)]
 mod param_tests {
-    use crate::analysis_ring_items::*;
+    use crate::ring_items::analysis_ring_items::*;
     use crate::ring_items::*;
     use std::mem::size_of;
     // Tests for ParameterValue type
@@ -839,15 +1165,15 @@ mod param_tests {
     }
     // from_raw tests can use to_raw to produce the raw item that is the
     // source of the conversion. We'll also need to be sure that wrong
-    // type gives None back.
+    // type gives Err back.
 
     #[test]
     fn from_raw_1() {
         let orig = ParameterItem::new(124);
         let raw = orig.to_raw();
-        let copy: Option<ParameterItem> = raw.to_specific(RingVersion::V11);
+        let copy: Result<ParameterItem, RingItemError> = raw.to_specific(RingVersion::V11);
 
-        assert!(copy.is_some());
+        assert!(copy.is_ok());
         let copy = copy.unwrap();
 
         assert_eq!(orig.trigger(), copy.trigger());
@@ -858,9 +1184,9 @@ mod param_tests {
         let mut orig = ParameterItem::new(12345);
         orig.add(1, 1.2345).add(65, 5.555);
         let raw = orig.to_raw();
-        let copy: Option<ParameterItem> = raw.to_specific(RingVersion::V11);
+        let copy: Result<ParameterItem, RingItemError> = raw.to_specific(RingVersion::V11);
 
-        assert!(copy.is_some());
+        assert!(copy.is_ok());
         let copy = copy.unwrap();
 
         assert_eq!(orig.trigger(), copy.trigger());
@@ -873,11 +1199,315 @@ mod param_tests {
     }
     #[test]
     fn from_raw_3() {
-        // Bad type gives None:
+        // Bad type gives Err:
 
         let raw = RingItem::new(PARAMETER_DATA + 1);
-        let copy: Option<ParameterItem> = raw.to_specific(RingVersion::V11);
+        let copy: Result<ParameterItem, RingItemError> = raw.to_specific(RingVersion::V11);
+
+        assert_eq!(
+            Some(RingItemError::UnexpectedItemType {
+                expected: PARAMETER_DATA,
+                found: PARAMETER_DATA + 1
+            }),
+            copy.err()
+        );
+    }
+    #[test]
+    fn from_raw_4() {
+        // Truncated payload -> Err:
+
+        let mut raw = RingItem::new(PARAMETER_DATA);
+        raw.add(1234_u64);
+        let copy: Result<ParameterItem, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert_eq!(
+            Some(RingItemError::PayloadTooShort {
+                needed: 12,
+                available: 8
+            }),
+            copy.err()
+        );
+
+        raw.add(2_u32).add(1_u32).add(1.5_f64).add(2_u32); // Missing a value.
+        let copy: Result<ParameterItem, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert_eq!(
+            Some(RingItemError::PayloadTooShort {
+                needed: 36,
+                available: 28
+            }),
+            copy.err()
+        );
+    }
+}
+#[cfg(test)]
+mod flat_event_tests {
+    use crate::parameters::FlatEvent;
+    use crate::ring_items::analysis_ring_items::*;
+
+    #[test]
+    fn load_1() {
+        // Five items for the same event:
+
+        let mut items = Vec::new();
+        for i in 0..5 {
+            let mut item = ParameterItem::new(100);
+            item.add(i * 2 + 1, i as f64 * 10.0);
+            items.push(item);
+        }
+        let mut fe = FlatEvent::new();
+        load_into_flat_event(&items, &mut fe);
+
+        for i in 0..5 {
+            assert_eq!(Some(i as f64 * 10.0), fe[i * 2 + 1]);
+            assert!(fe[i * 2].is_none());
+        }
+        assert!(fe[11].is_none());
+    }
+    #[test]
+    fn load_2() {
+        // Loading replaces the previous event:
+
+        let mut item = ParameterItem::new(1);
+        item.add(1, 1.0);
+        let mut fe = FlatEvent::new();
+        load_into_flat_event(&[item], &mut fe);
+
+        let mut item = ParameterItem::new(2);
+        item.add(2, 2.0);
+        load_into_flat_event(&[item], &mut fe);
+        assert!(fe[1].is_none());
+        assert_eq!(Some(2.0), fe[2]);
+    }
+    #[test]
+    fn group_1() {
+        let triggers = [1, 1, 1, 2, 3, 3];
+        let items: Vec<ParameterItem> = triggers.iter().map(|t| ParameterItem::new(*t)).collect();
+        let mut iter = items.iter().peekable();
+
+        let group = same_event_group(&mut iter);
+        assert_eq!(3, group.len());
+        assert!(group.iter().all(|i| i.trigger() == 1));
+        let group = same_event_group(&mut iter);
+        assert_eq!(1, group.len());
+        assert_eq!(2, group[0].trigger());
+        let group = same_event_group(&mut iter);
+        assert_eq!(2, group.len());
+        assert!(group.iter().all(|i| i.trigger() == 3));
+        assert!(same_event_group(&mut iter).is_empty());
+    }
+}
+#[cfg(test)]
+mod dictionary_bridge_tests {
+    use crate::parameters::ParameterDictionary;
+    use crate::ring_items::analysis_ring_items::*;
+    use crate::ring_items::*;
+
+    fn make_dict() -> ParameterDictionary {
+        let mut dict = ParameterDictionary::new();
+        for i in 0..10 {
+            dict.add(&format!("param.{}", i)).unwrap();
+        }
+        dict
+    }
+
+    #[test]
+    fn from_dict_1() {
+        let dict = make_dict();
+        let defs = ParameterDefinitions::from_dictionary(&dict);
+        assert_eq!(10, defs.iter().count());
+        for (i, def) in defs.iter().enumerate() {
+            assert_eq!(format!("param.{}", i), def.name());
+            assert_eq!(dict.lookup(&def.name()).unwrap().get_id(), def.id());
+        }
+    }
+    #[test]
+    fn apply_1() {
+        // Round trip through a ring item into an empty dictionary:
+
+        let dict = make_dict();
+        let raw = ParameterDefinitions::from_dictionary(&dict).to_raw();
+        let defs: ParameterDefinitions = raw.to_specific(RingVersion::V11).unwrap();
+
+        let mut new_dict = ParameterDictionary::new();
+        defs.apply_to_dictionary(&mut new_dict).unwrap();
+        assert_eq!(10, new_dict.iter().count());
+        for (name, p) in dict.iter() {
+            let newp = new_dict.lookup(name).unwrap();
+            assert_eq!(p.get_id(), newp.get_id());
+        }
+    }
+    #[test]
+    fn apply_2() {
+        // Existing parameters are left alone:
 
-        assert!(copy.is_none());
+        let mut dict = ParameterDictionary::new();
+        dict.add("param.5").unwrap();
+        let defs = ParameterDefinitions::from_dictionary(&make_dict());
+        defs.apply_to_dictionary(&mut dict).unwrap();
+        assert_eq!(10, dict.iter().count());
+        assert_eq!(1, dict.lookup("param.5").unwrap().get_id());
+    }
+    #[test]
+    fn to_item_1() {
+        let mut dict = make_dict();
+        dict.lookup_mut("param.3")
+            .unwrap()
+            .set_limits(-1.0, 1.0)
+            .set_bins(200);
+        let item = to_parameter_definitions_item(&dict);
+        assert_eq!(PARAMETER_DEFINITIONS, item.type_id());
+
+        let defs: ParameterDefinitions = item.to_specific(RingVersion::V11).unwrap();
+        assert_eq!(10, defs.iter().count());
+        for (i, def) in defs.iter().enumerate() {
+            assert_eq!(format!("param.{}", i), def.name());
+            assert_eq!(dict.lookup(&def.name()).unwrap().get_id(), def.id());
+        }
+    }
+    #[test]
+    fn to_item_2() {
+        // The NSCLDAQ layout: count then id, nul terminated name pairs:
+
+        let mut dict = ParameterDictionary::new();
+        dict.add("a").unwrap();
+        dict.add("bc").unwrap();
+        let item = to_parameter_definitions_item(&dict);
+        let mut expected = Vec::new();
+        for v in [2u32, 1] {
+            expected.extend_from_slice(&v.to_ne_bytes());
+        }
+        expected.extend_from_slice(b"a\0");
+        expected.extend_from_slice(&2u32.to_ne_bytes());
+        expected.extend_from_slice(b"bc\0");
+        assert_eq!(&expected, item.payload());
+
+        let empty = to_parameter_definitions_item(&ParameterDictionary::new());
+        assert_eq!(&0u32.to_ne_bytes().to_vec(), empty.payload());
+    }
+}
+#[cfg(test)]
+mod variable_map_tests {
+    use crate::ring_items::analysis_ring_items::*;
+    use crate::ring_items::*;
+    use std::collections::HashMap;
+
+    fn make_map() -> HashMap<String, (f64, String)> {
+        let mut vars = HashMap::new();
+        for (i, units) in ["mm", "cm", "MeV", "ns", ""].iter().enumerate() {
+            vars.insert(format!("var.{}", i), (i as f64 * 1.5, String::from(*units)));
+        }
+        vars
+    }
+
+    #[test]
+    fn variables_1() {
+        let mut item = VariableValues::new();
+        item.add_def(VariableValue::new(1.0, "a", "mm"))
+            .add_def(VariableValue::new(2.0, "b", "cm"));
+        assert_eq!(
+            vec![
+                (String::from("a"), 1.0, String::from("mm")),
+                (String::from("b"), 2.0, String::from("cm"))
+            ],
+            item.variables()
+        );
+    }
+    #[test]
+    fn from_map_1() {
+        let item = VariableValues::from_map(&make_map());
+        let vars = item.variables();
+        assert_eq!(5, vars.len());
+        for (i, v) in vars.iter().enumerate() {
+            assert_eq!(format!("var.{}", i), v.0);
+            assert_eq!(i as f64 * 1.5, v.1);
+        }
+    }
+    #[test]
+    fn apply_1() {
+        // Round trip through the ring item:
+
+        let vars = make_map();
+        let raw = VariableValues::from_map(&vars).to_raw();
+        let item: VariableValues = raw.to_specific(RingVersion::V11).unwrap();
+
+        let mut out = HashMap::new();
+        item.apply_to_map(&mut out);
+        assert_eq!(vars, out);
+    }
+}
+#[cfg(test)]
+mod run_file_import_tests {
+    use crate::ring_items::analysis_ring_items::*;
+    use crate::ring_items::*;
+    use std::io::Write;
+
+    fn write_file(items: &[RingItem]) -> File {
+        let mut file = tempfile::tempfile().unwrap();
+        for item in items {
+            item.write_item(&mut file).unwrap();
+        }
+        file.flush().unwrap();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        file
+    }
+
+    #[test]
+    fn import_1() {
+        let mut defs = ParameterDefinitions::new();
+        for (id, name) in [(5, "e"), (2, "t"), (12, "x"), (13, "y")] {
+            defs.add_definition(ParameterDefinition::new(id, name));
+        }
+        let mut items = vec![RingItem::new(BEGIN_RUN), defs.to_raw()];
+        for i in 0..5 {
+            let mut event = ParameterItem::new(i);
+            event.add(5, i as f64).add(12, 2.0 * i as f64);
+            items.push(event.to_raw());
+        }
+        let mut file = write_file(&items);
+
+        let dict = import_from_run_file(&mut file).unwrap();
+        assert_eq!(4, dict.iter().count());
+        assert_eq!(5, dict.lookup("e").unwrap().get_id());
+        assert_eq!(2, dict.lookup("t").unwrap().get_id());
+        assert_eq!(12, dict.lookup("x").unwrap().get_id());
+        assert_eq!(13, dict.lookup("y").unwrap().get_id());
+
+        // Rewound - all the items can be processed:
+
+        let mut count = 0;
+        while let Ok(item) = RingItem::read_item(&mut file) {
+            assert_eq!(items[count].type_id(), item.type_id());
+            count += 1;
+        }
+        assert_eq!(items.len(), count);
+    }
+    #[test]
+    fn import_2() {
+        // No definitions and bad files:
+
+        let mut file = write_file(&[RingItem::new(BEGIN_RUN), RingItem::new(PHYSICS_EVENT)]);
+        let dict = import_from_run_file(&mut file).unwrap();
+        assert_eq!(0, dict.iter().count());
+        assert_eq!(BEGIN_RUN, RingItem::read_item(&mut file).unwrap().type_id());
+
+        let mut defs = ParameterDefinitions::new();
+        defs.add_definition(ParameterDefinition::new(1, "a"));
+        defs.add_definition(ParameterDefinition::new(1, "b"));
+        let mut file = write_file(&[defs.to_raw()]);
+        assert!(matches!(
+            import_from_run_file(&mut file),
+            Err(RingItemError::InvalidPayload(_))
+        ));
+
+        let mut item = RingItem::new(PHYSICS_EVENT);
+        item.add(1234_u32);
+        let mut bytes = item.to_bytes();
+        bytes.pop();
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(&bytes).unwrap();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        assert!(matches!(
+            import_from_run_file(&mut file),
+            Err(RingItemError::FileTooSmall)
+        ));
     }
 }
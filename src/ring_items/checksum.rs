@@ -0,0 +1,119 @@
+//!  CRC32 checksums of ring item payloads.  Custom ring item types
+//!  sometimes carry a checksum of their payload as the last four bytes
+//!  of the item.  append_crc32 adds such a checksum and
+//!  strip_and_verify_crc32 checks and removes it.  The CRC is stored
+//!  in native byte order like the other fields added with
+//!  RingItem::add.
+//!
+//!  The checksummed payload is everything after the header, including
+//!  any body header.
+//!
+use crate::ring_items::{RingItem, RingItemError};
+use std::mem;
+
+/// The CRC32 of the current payload of an item.
+pub fn compute_payload_crc32(item: &RingItem) -> u32 {
+    crc32fast::hash(item.payload())
+}
+
+/// True if stored_crc is the CRC32 of the payload of the item.
+pub fn verify_payload_crc32(item: &RingItem, stored_crc: u32) -> bool {
+    compute_payload_crc32(item) == stored_crc
+}
+
+/// Append the CRC32 of the current payload to the payload.
+pub fn append_crc32(item: &mut RingItem) {
+    let crc = compute_payload_crc32(item);
+    item.add(crc);
+}
+
+///
+/// Check and remove the CRC32 that append_crc32 added to an item.
+/// The item is only modified if the CRC matches the rest of the
+/// payload.  Errors are PayloadTooShort if there's no room for a CRC
+/// and InvalidPayload if the CRC does not match.
+///
+pub fn strip_and_verify_crc32(item: &mut RingItem) -> Result<(), RingItemError> {
+    let crc_size = mem::size_of::<u32>();
+    let available = item.payload.len();
+    if available < crc_size {
+        return Err(RingItemError::PayloadTooShort {
+            needed: crc_size,
+            available,
+        });
+    }
+    let body = available - crc_size;
+    let stored: u32 = item.payload_field(body)?;
+    let computed = crc32fast::hash(&item.payload[0..body]);
+    if stored != computed {
+        return Err(RingItemError::InvalidPayload(format!(
+            "CRC32 mismatch: stored {:#010x} computed {:#010x}",
+            stored, computed
+        )));
+    }
+    item.payload.truncate(body);
+    item.size -= crc_size as u32;
+    Ok(())
+}
+
+#[cfg(test)]
+mod checksum_tests {
+    use super::*;
+
+    fn make_item() -> RingItem {
+        let mut item = RingItem::new_with_body_header(32800, 0x1234, 1, 0);
+        for i in 0..10_u32 {
+            item.add(i);
+        }
+        item
+    }
+
+    #[test]
+    fn compute_1() {
+        let item = make_item();
+        let crc = compute_payload_crc32(&item);
+        assert_eq!(crc32fast::hash(item.payload()), crc);
+        assert!(verify_payload_crc32(&item, crc));
+        assert!(!verify_payload_crc32(&item, crc ^ 1));
+    }
+    #[test]
+    fn strip_1() {
+        // Round trip:
+
+        let original = make_item();
+        let mut item = make_item();
+        append_crc32(&mut item);
+        assert_eq!(original.size() + 4, item.size());
+        assert_eq!(
+            Some(compute_payload_crc32(&original)),
+            item.payload_as::<u32>(original.payload().len())
+        );
+
+        assert_eq!(Ok(()), strip_and_verify_crc32(&mut item));
+        assert_eq!(original.to_bytes(), item.to_bytes());
+    }
+    #[test]
+    fn strip_2() {
+        // Corrupted payloads are errors and are left alone:
+
+        let mut item = make_item();
+        append_crc32(&mut item);
+        item.payload_mut()[20] ^= 0xff;
+        let corrupted = item.to_bytes();
+        assert!(matches!(
+            strip_and_verify_crc32(&mut item),
+            Err(RingItemError::InvalidPayload(_))
+        ));
+        assert_eq!(corrupted, item.to_bytes());
+
+        let mut item = RingItem::new(32800);
+        item.add(1_u8);
+        assert_eq!(
+            Err(RingItemError::PayloadTooShort {
+                needed: 4,
+                available: 1
+            }),
+            strip_and_verify_crc32(&mut item)
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use crate::parameters::{Event, EventParameter, FlatEvent};
 use crate::ring_items;
 use std::fmt;
 use std::mem;
@@ -51,16 +52,14 @@ impl PhysicsEvent {
     pub fn get<T: Copy>(&mut self) -> Option<T> {
         // Make sure there;s enough stuff in the event for item T.
 
-        if self.get_cursor < (self.event_data.len() + 1 - mem::size_of::<T>()) {
-            let mut p = self.event_data.as_ptr();
-            unsafe {
-                p = p.offset(self.get_cursor as isize);
-            }
-            // Need to cast this to a pointer of type T:
+        let end = self.get_cursor + mem::size_of::<T>();
+        if end <= self.event_data.len() {
+            // Need to cast this to a pointer of type T.  The cursor
+            // need not be aligned for T so read_unaligned is required:
 
-            let pt = p.cast::<T>();
-            let result = unsafe { *pt };
-            self.get_cursor = self.get_cursor + mem::size_of::<T>();
+            let pt = self.event_data[self.get_cursor..end].as_ptr().cast::<T>();
+            let result = unsafe { pt.read_unaligned() };
+            self.get_cursor = end;
             Some(result)
         } else {
             None // Out of range.
@@ -83,6 +82,11 @@ impl PhysicsEvent {
     pub fn body_size(&self) -> usize {
         self.event_data.len()
     }
+    /// The bytes of the event body:
+    ///
+    pub fn body(&self) -> &[u8] {
+        &self.event_data
+    }
 }
 
 impl Iterator for PhysicsEvent {
@@ -166,25 +170,84 @@ impl ring_items::ToRaw for PhysicsEvent {
 /// convert a ring item to a specific type:
 
 impl ring_items::FromRaw<PhysicsEvent> for ring_items::RingItem {
-    fn to_specific(&self, _v: ring_items::RingVersion) -> Option<PhysicsEvent> {
-        if self.type_id() == ring_items::PHYSICS_EVENT {
-            let mut payload_offset = 0;
-            let mut result = PhysicsEvent::new(self.get_bodyheader());
-            if self.has_body_header() {
-                payload_offset = ring_items::body_header_size();
-            }
-            result
-                .event_data
-                .extend_from_slice(&self.payload().as_slice()[payload_offset..]);
-            Some(result)
-        } else {
-            None
+    fn to_specific(
+        &self,
+        _v: ring_items::RingVersion,
+    ) -> Result<PhysicsEvent, ring_items::RingItemError> {
+        self.check_type(ring_items::PHYSICS_EVENT)?;
+        let mut payload_offset = 0;
+        let mut result = PhysicsEvent::new(self.get_bodyheader());
+        if self.has_body_header() {
+            payload_offset = ring_items::body_header_size();
+        }
+        result
+            .event_data
+            .extend_from_slice(self.payload().get(payload_offset..).ok_or(
+                ring_items::RingItemError::PayloadTooShort {
+                    needed: payload_offset,
+                    available: self.payload().len(),
+                },
+            )?);
+        Ok(result)
+    }
+}
+///
+/// As in SpecTcl, turning the raw bytes of an event into parameters
+/// is the job of experiment specific decoders.  An EventDecoder
+/// loads a FlatEvent from the body of a physics event.  Err describes
+/// bodies the decoder can't make sense of.
+///
+pub trait EventDecoder {
+    fn decode(&self, raw_bytes: &[u8], flat: &mut FlatEvent) -> Result<(), String>;
+}
+
+///
+/// Decode the parameters of a physics event into a flat event.
+///
+pub fn decode_to_flat_event(
+    raw: &PhysicsEvent,
+    decoder: &dyn EventDecoder,
+    flat: &mut FlatEvent,
+) -> Result<(), String> {
+    decoder.decode(raw.body(), flat)
+}
+
+///
+/// Decodes event bodies that are a sequence of (id: u16, value: f64)
+/// pairs in native byte order with no padding.  The ids are the
+/// parameter ids in the analysis' ParameterDictionary.
+///
+pub struct PackedParameterDecoder {}
+
+const PACKED_PARAMETER_SIZE: usize = mem::size_of::<u16>() + mem::size_of::<f64>();
+
+impl EventDecoder for PackedParameterDecoder {
+    fn decode(&self, raw_bytes: &[u8], flat: &mut FlatEvent) -> Result<(), String> {
+        if !raw_bytes.len().is_multiple_of(PACKED_PARAMETER_SIZE) {
+            return Err(format!(
+                "Event body of {} bytes is not a whole number of {} byte parameters",
+                raw_bytes.len(),
+                PACKED_PARAMETER_SIZE
+            ));
         }
+        let event: Event = raw_bytes
+            .chunks_exact(PACKED_PARAMETER_SIZE)
+            .map(|p| {
+                let (id, value) = p.split_at(mem::size_of::<u16>());
+                EventParameter::new(
+                    u16::from_ne_bytes(id.try_into().unwrap()) as u32,
+                    f64::from_ne_bytes(value.try_into().unwrap()),
+                )
+            })
+            .collect();
+        flat.load_event(&event);
+        Ok(())
     }
 }
+
 #[cfg(test)]
 mod test_event {
-    use crate::event_item::*;
+    use crate::ring_items::event_item::*;
     use crate::ring_items::*;
     use std::mem::size_of;
     #[test]
@@ -488,8 +551,8 @@ mod test_event {
 
         let item = PhysicsEvent::new(None);
         let raw = item.to_raw();
-        let event: Option<PhysicsEvent> = raw.to_specific(RingVersion::V11);
-        assert!(event.is_some());
+        let event: Result<PhysicsEvent, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert!(event.is_ok());
         let event = event.unwrap();
 
         assert!(event.body_header.is_none());
@@ -506,8 +569,8 @@ mod test_event {
             barrier_type: 0,
         }));
         let raw = item.to_raw();
-        let event: Option<PhysicsEvent> = raw.to_specific(RingVersion::V11);
-        assert!(event.is_some());
+        let event: Result<PhysicsEvent, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert!(event.is_ok());
         let event = event.unwrap();
         assert!(event.body_header.is_some());
         let bh = event.body_header.unwrap();
@@ -524,8 +587,8 @@ mod test_event {
             .add(0xa5a5 as u16)
             .add(0xa5a5a5a5 as u32);
         let raw = item.to_raw();
-        let event: Option<PhysicsEvent> = raw.to_specific(RingVersion::V11);
-        assert!(event.is_some());
+        let event: Result<PhysicsEvent, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert!(event.is_ok());
         let mut event = event.unwrap();
 
         assert_eq!(
@@ -548,8 +611,8 @@ mod test_event {
         }));
         item.add(0x1 as u8).add(0x2 as u16).add(0x3 as u32);
         let raw = item.to_raw();
-        let event: Option<PhysicsEvent> = raw.to_specific(RingVersion::V11);
-        assert!(event.is_some());
+        let event: Result<PhysicsEvent, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert!(event.is_ok());
         let mut event = event.unwrap();
         assert!(event.get_bodyheader().is_some());
         assert_eq!(
@@ -566,7 +629,64 @@ mod test_event {
         // failed conversion:
 
         let raw = RingItem::new(PHYSICS_EVENT + 1);
-        let failed: Option<PhysicsEvent> = raw.to_specific(RingVersion::V11);
-        assert!(failed.is_none());
+        let failed: Result<PhysicsEvent, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert_eq!(
+            Some(RingItemError::UnexpectedItemType {
+                expected: PHYSICS_EVENT,
+                found: PHYSICS_EVENT + 1
+            }),
+            failed.err()
+        );
+    }
+    #[test]
+    fn from_raw_6() {
+        // Body header is truncated:
+
+        let mut raw = RingItem::new_with_body_header(PHYSICS_EVENT, 0x1234, 1, 0);
+        raw.payload_mut().truncate(8);
+        let failed: Result<PhysicsEvent, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert_eq!(
+            Some(RingItemError::PayloadTooShort {
+                needed: 16,
+                available: 8
+            }),
+            failed.err()
+        );
+    }
+    #[test]
+    fn decode_1() {
+        // Five packed parameters through a ring item:
+
+        let mut item = PhysicsEvent::new(None);
+        for id in 1..=5_u16 {
+            item.add(id).add(id as f64 * 1.5);
+        }
+        let event: PhysicsEvent = item.to_raw().to_specific(RingVersion::V11).unwrap();
+
+        let mut flat = FlatEvent::new();
+        assert!(decode_to_flat_event(&event, &PackedParameterDecoder {}, &mut flat).is_ok());
+        for id in 1..=5 {
+            assert_eq!(Some(id as f64 * 1.5), flat[id]);
+        }
+        assert!(flat[0].is_none());
+        assert!(flat[6].is_none());
+    }
+    #[test]
+    fn decode_2() {
+        // Empty event and partial parameter:
+
+        let mut flat = FlatEvent::new();
+        let decoder = PackedParameterDecoder {};
+        assert!(decode_to_flat_event(&PhysicsEvent::new(None), &decoder, &mut flat).is_ok());
+        assert!(flat[1].is_none());
+
+        let mut item = PhysicsEvent::new(None);
+        item.add(1_u16).add(2.0_f64).add(2_u16);
+        assert_eq!(
+            Err(String::from(
+                "Event body of 12 bytes is not a whole number of 10 byte parameters"
+            )),
+            decode_to_flat_event(&item, &decoder, &mut flat)
+        );
     }
 }
@@ -0,0 +1,167 @@
+//!  Statistics about a file of ring items: how many items and bytes
+//!  of each type it holds, the range of body header timestamps and
+//!  the run it came from.  The report can be serialized (e.g. to JSON)
+//!  for scripts that catalog run files.
+//!
+use crate::ring_items::{self, FromRaw, RingItemError};
+use crate::sources::RingItemIter;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+///
+/// The statistics about a ring item file.  The maps are keyed by
+/// ring item type id.  The timestamps are from the first and last
+/// items with body headers;  the run number and title are from the
+/// first begin run item.
+///
+#[derive(Serialize, Debug, Clone, PartialEq, Default)]
+pub struct FileReport {
+    pub item_counts: HashMap<u32, u64>,
+    pub byte_totals: HashMap<u32, u64>,
+    pub first_timestamp: Option<u64>,
+    pub last_timestamp: Option<u64>,
+    pub run_number: Option<u32>,
+    pub run_title: Option<String>,
+    pub total_items: u64,
+    pub total_bytes: u64,
+}
+
+///
+/// Read a ring item file in one pass and report on its contents.
+/// Errors opening or reading the file are IoFailure;  a malformed or
+/// truncated ring item gives the error reading it.
+///
+pub fn analyze_file(path: &Path) -> Result<FileReport, RingItemError> {
+    let file = File::open(path).map_err(|e| RingItemError::IoFailure(e.to_string()))?;
+    let mut report = FileReport::default();
+
+    for item in RingItemIter::new(BufReader::new(file)) {
+        let item = item?;
+        let type_id = item.type_id();
+        let size = item.size() as u64;
+        *report.item_counts.entry(type_id).or_insert(0) += 1;
+        *report.byte_totals.entry(type_id).or_insert(0) += size;
+        report.total_items += 1;
+        report.total_bytes += size;
+
+        if let Some(header) = item.get_bodyheader() {
+            if report.first_timestamp.is_none() {
+                report.first_timestamp = Some(header.timestamp);
+            }
+            report.last_timestamp = Some(header.timestamp);
+        }
+        if type_id == ring_items::BEGIN_RUN && report.run_number.is_none() {
+            let begin: ring_items::state_change::StateChange =
+                item.to_specific(ring_items::RingVersion::V11)?;
+            report.run_number = Some(begin.run_number());
+            report.run_title = Some(begin.title());
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod file_report_tests {
+    use super::*;
+    use crate::ring_items::state_change::{StateChange, StateChangeType};
+    use crate::ring_items::{RingItem, ToRaw};
+    use std::io::Write;
+
+    // Write the items to a temporary file:
+
+    fn write_file(items: &[RingItem]) -> tempfile::NamedTempFile {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        for item in items {
+            item.write_item(&mut file).unwrap();
+        }
+        file.flush().unwrap();
+        file
+    }
+
+    #[test]
+    fn analyze_1() {
+        // Empty file:
+
+        let file = write_file(&[]);
+        let report = analyze_file(file.path()).unwrap();
+        assert_eq!(FileReport::default(), report);
+    }
+    #[test]
+    fn analyze_2() {
+        let mut items =
+            vec![
+                StateChange::new(StateChangeType::Begin, None, 88, 0, 1, "A test run", None)
+                    .to_raw(),
+            ];
+        for i in 0..10 {
+            let mut event =
+                RingItem::new_with_body_header(ring_items::PHYSICS_EVENT, 100 + i, 1, 0);
+            event.add(i as u32);
+            items.push(event);
+        }
+        for _ in 0..3 {
+            items.push(RingItem::new(ring_items::PERIODIC_SCALERS));
+        }
+        items.push(
+            StateChange::new(StateChangeType::End, None, 88, 10, 1, "A test run", None).to_raw(),
+        );
+        let file = write_file(&items);
+
+        let report = analyze_file(file.path()).unwrap();
+        assert_eq!(15, report.total_items);
+        assert_eq!(4, report.item_counts.len());
+        assert_eq!(Some(&1), report.item_counts.get(&ring_items::BEGIN_RUN));
+        assert_eq!(
+            Some(&10),
+            report.item_counts.get(&ring_items::PHYSICS_EVENT)
+        );
+        assert_eq!(
+            Some(&3),
+            report.item_counts.get(&ring_items::PERIODIC_SCALERS)
+        );
+        assert_eq!(Some(&1), report.item_counts.get(&ring_items::END_RUN));
+
+        let event_bytes = 10 * items[1].size() as u64;
+        assert_eq!(
+            Some(&event_bytes),
+            report.byte_totals.get(&ring_items::PHYSICS_EVENT)
+        );
+        let total: u64 = items.iter().map(|i| i.size() as u64).sum();
+        assert_eq!(total, report.total_bytes);
+        assert_eq!(total, report.byte_totals.values().sum::<u64>());
+
+        assert_eq!(Some(100), report.first_timestamp);
+        assert_eq!(Some(109), report.last_timestamp);
+        assert_eq!(Some(88), report.run_number);
+        assert_eq!(Some(String::from("A test run")), report.run_title);
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(15, json["total_items"]);
+        assert_eq!(10, json["item_counts"]["30"]);
+        assert_eq!("A test run", json["run_title"]);
+    }
+    #[test]
+    fn analyze_3() {
+        // Errors:
+
+        assert!(matches!(
+            analyze_file(Path::new("/no/such/file.evt")),
+            Err(RingItemError::IoFailure(_))
+        ));
+
+        let mut item = RingItem::new(ring_items::PHYSICS_EVENT);
+        item.add(1234_u32);
+        let mut bytes = item.to_bytes();
+        bytes.pop();
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(&bytes).unwrap();
+        file.flush().unwrap();
+        assert!(matches!(
+            analyze_file(file.path()),
+            Err(RingItemError::FileTooSmall)
+        ));
+    }
+}
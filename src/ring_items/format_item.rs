@@ -23,6 +23,74 @@ impl FormatItem {
             minor: minor,
         }
     }
+    /// The ring item format the item describes.  Ring items that
+    /// follow the format item should be converted using this version.
+    ///
+    pub fn ring_version(&self) -> Result<ring_items::RingVersion, ring_items::RingItemError> {
+        match self.major {
+            11 => Ok(ring_items::RingVersion::V11),
+            12 => Ok(ring_items::RingVersion::V12),
+            major => Err(ring_items::RingItemError::UnsupportedVersion(major)),
+        }
+    }
+    /// True if the format's major version is at least required_major.
+    /// Minor versions don't change the format so required_minor is
+    /// not compared.
+    pub fn is_compatible_with(&self, required_major: u16, _required_minor: u16) -> bool {
+        self.major >= required_major
+    }
+}
+
+///
+/// RingVersionDetector watches the ring items from a source for the
+/// format item that describes the format of the items that follow.
+///
+#[derive(Default)]
+pub struct RingVersionDetector {
+    format: Option<FormatItem>,
+}
+
+impl RingVersionDetector {
+    pub fn new() -> RingVersionDetector {
+        RingVersionDetector::default()
+    }
+    /// Look at an item.  If it's a format item, its version is
+    /// the detected version.  true is returned if the item was a
+    /// format item.
+    pub fn observe(&mut self, item: &ring_items::RingItem) -> bool {
+        if item.type_id() != ring_items::FORMAT_ITEM {
+            return false;
+        }
+        let format: Result<FormatItem, ring_items::RingItemError> =
+            ring_items::FromRaw::to_specific(item, ring_items::RingVersion::V11);
+        if let Ok(format) = format {
+            self.format = Some(format);
+            true
+        } else {
+            false
+        }
+    }
+    /// The (major, minor) version detected if a format item has been seen.
+    pub fn version(&self) -> Option<(u16, u16)> {
+        self.format.as_ref().map(|f| (f.major, f.minor))
+    }
+    /// Err unless a format item has been seen and it is compatible
+    /// with major.minor (see FormatItem::is_compatible_with).
+    pub fn require_minimum(&self, major: u16, minor: u16) -> Result<(), String> {
+        match &self.format {
+            None => Err(String::from("No format item has been seen")),
+            Some(f) => {
+                if f.is_compatible_with(major, minor) {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "Ring format version {}.{} is older than the required {}.{}",
+                        f.major, f.minor, major, minor
+                    ))
+                }
+            }
+        }
+    }
 }
 
 impl fmt::Display for FormatItem {
@@ -44,25 +112,25 @@ impl ring_items::ToRaw for FormatItem {
 /// into a FormatItem:
 
 impl ring_items::FromRaw<FormatItem> for ring_items::RingItem {
-    fn to_specific(&self, _v: ring_items::RingVersion) -> Option<FormatItem> {
-        if self.type_id() != ring_items::FORMAT_ITEM {
-            return None;
-        }
+    fn to_specific(
+        &self,
+        _v: ring_items::RingVersion,
+    ) -> Result<FormatItem, ring_items::RingItemError> {
+        self.check_type(ring_items::FORMAT_ITEM)?;
         let mut result = FormatItem::new(0, 0);
-        let payload = self.payload().as_slice();
 
         // The first u16 is the major, the second u16 is the
         // minor:
 
-        result.major = u16::from_ne_bytes(payload[0..2].try_into().unwrap());
-        result.minor = u16::from_ne_bytes(payload[2..4].try_into().unwrap());
-        Some(result)
+        result.major = self.payload_field::<u16>(0)?;
+        result.minor = self.payload_field::<u16>(2)?;
+        Ok(result)
     }
 }
 
 #[cfg(test)]
 mod fmt_tests {
-    use crate::format_item::*;
+    use crate::ring_items::format_item::*;
     use crate::ring_items::*;
     use std::mem::size_of;
     #[test]
@@ -98,9 +166,9 @@ mod fmt_tests {
     fn from_raw_1() {
         let item = FormatItem::new(11, 26);
         let raw = item.to_raw();
-        let recons: Option<FormatItem> = raw.to_specific(RingVersion::V11);
+        let recons: Result<FormatItem, RingItemError> = raw.to_specific(RingVersion::V11);
 
-        assert!(recons.is_some());
+        assert!(recons.is_ok());
         let recons = recons.unwrap();
         assert_eq!(11, recons.major());
         assert_eq!(26, recons.minor());
@@ -108,7 +176,71 @@ mod fmt_tests {
     #[test]
     fn from_raw_2() {
         let raw = RingItem::new(FORMAT_ITEM + 1); // should fail.
-        let recons: Option<FormatItem> = raw.to_specific(RingVersion::V11);
-        assert!(recons.is_none());
+        let recons: Result<FormatItem, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert_eq!(
+            Some(RingItemError::UnexpectedItemType {
+                expected: FORMAT_ITEM,
+                found: FORMAT_ITEM + 1
+            }),
+            recons.err()
+        );
+    }
+    #[test]
+    fn from_raw_3() {
+        // Truncated payload (no minor version):
+
+        let mut raw = RingItem::new(FORMAT_ITEM);
+        raw.add(11_u16);
+        let recons: Result<FormatItem, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert_eq!(
+            Some(RingItemError::PayloadTooShort {
+                needed: 4,
+                available: 2
+            }),
+            recons.err()
+        );
+    }
+    #[test]
+    fn version_1() {
+        assert!(FormatItem::new(11, 0).ring_version() == Ok(RingVersion::V11));
+        assert!(FormatItem::new(12, 1).ring_version() == Ok(RingVersion::V12));
+        assert!(
+            FormatItem::new(10, 0).ring_version() == Err(RingItemError::UnsupportedVersion(10))
+        );
+    }
+    #[test]
+    fn compatible_1() {
+        let v11 = FormatItem::new(11, 0);
+        let v12 = FormatItem::new(12, 1);
+        assert!(v11.is_compatible_with(11, 0));
+        assert!(v11.is_compatible_with(11, 1)); // Minors aren't compared.
+        assert!(!v11.is_compatible_with(12, 0));
+        assert!(v12.is_compatible_with(11, 5));
+        assert!(v12.is_compatible_with(12, 0));
+        assert!(v12.is_compatible_with(12, 1));
+        assert!(v12.is_compatible_with(12, 2));
+        assert!(!v12.is_compatible_with(13, 0));
+    }
+    #[test]
+    fn detector_1() {
+        let mut detector = RingVersionDetector::new();
+        assert!(detector.version().is_none());
+        assert!(detector.require_minimum(11, 0).is_err());
+
+        assert!(!detector.observe(&RingItem::new(BEGIN_RUN)));
+        assert!(detector.observe(&FormatItem::new(11, 0).to_raw()));
+        assert_eq!(Some((11, 0)), detector.version());
+        assert!(detector.require_minimum(11, 0).is_ok());
+        assert!(detector.require_minimum(12, 0).is_err());
+
+        assert!(detector.observe(&FormatItem::new(12, 0).to_raw()));
+        assert_eq!(Some((12, 0)), detector.version());
+        assert!(detector.require_minimum(12, 0).is_ok());
+        assert!(detector.require_minimum(12, 3).is_ok());
+
+        // A bad format item is ignored:
+
+        assert!(!detector.observe(&RingItem::new(FORMAT_ITEM)));
+        assert_eq!(Some((12, 0)), detector.version());
     }
 }
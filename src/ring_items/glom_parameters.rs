@@ -9,12 +9,36 @@ pub enum TimestampPolicy {
     Last,
     Average,
 }
+impl fmt::Display for TimestampPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TimestampPolicy::First => "First",
+            TimestampPolicy::Last => "Last",
+            TimestampPolicy::Average => "Averaged",
+        };
+        write!(f, "{}", name)
+    }
+}
 /// In raw data these will be represented by:
 
 const GLOM_TIMESTAMP_FIRST: u16 = 0;
 const GLOM_TIMESTAMP_LAST: u16 = 1;
 const GLOM_TIMESTAMP_AVERAGE: u16 = 2;
 
+/// The largest reasonable coincidence interval (10us with the usual
+/// 1ns timestamp ticks).  Larger intervals in data most likely
+/// mean a corrupt item.
+///
+pub const MAX_COINCIDENCE_INTERVAL: u64 = 10_000;
+
+///
+/// Is a coincidence interval (in ticks) within the reasonable range
+/// [0, MAX_COINCIDENCE_INTERVAL]?
+///
+pub fn is_valid_interval(ns: u64) -> bool {
+    ns <= MAX_COINCIDENCE_INTERVAL
+}
+
 ///
 /// The GlomParameters struct documents the settings for the
 /// event bulder.  These are emitted by the glom stage of the event builder
@@ -66,10 +90,18 @@ impl GlomParameters {
         self.timestamp_policy
     }
     pub fn policy_string(&self) -> String {
-        match self.timestamp_policy {
-            TimestampPolicy::First => String::from("First"),
-            TimestampPolicy::Last => String::from("Last"),
-            TimestampPolicy::Average => String::from("Averaged"),
+        self.timestamp_policy.to_string()
+    }
+    /// Checks that the coincidence interval is reasonable
+    /// (see is_valid_interval).
+    pub fn validate(&self) -> Result<(), String> {
+        if is_valid_interval(self.coincidence_ticks) {
+            Ok(())
+        } else {
+            Err(format!(
+                "Coincidence interval {} is larger than the maximum {}",
+                self.coincidence_ticks, MAX_COINCIDENCE_INTERVAL
+            ))
         }
     }
 }
@@ -102,31 +134,33 @@ impl ring_items::ToRaw for GlomParameters {
 }
 /// FromRaw implementations of the generic allow attempts to convert
 /// from a generic RingItem to a specific type (e.g. GlomParameters)
-
+/// Items with unreasonable coincidence intervals are InvalidPayload.
 impl ring_items::FromRaw<GlomParameters> for ring_items::RingItem {
-    fn to_specific(&self, _v: ring_items::RingVersion) -> Option<GlomParameters> {
-        if self.type_id() == ring_items::GLOM_INFO {
-            let mut result = GlomParameters::new(0, true, TimestampPolicy::First);
-            let payload = self.payload().as_slice();
+    fn to_specific(
+        &self,
+        _v: ring_items::RingVersion,
+    ) -> Result<GlomParameters, ring_items::RingItemError> {
+        self.check_type(ring_items::GLOM_INFO)?;
+        let mut result = GlomParameters::new(0, true, TimestampPolicy::First);
 
-            result.coincidence_ticks = u64::from_ne_bytes(payload[0..8].try_into().unwrap());
-            result.is_building = u16::from_ne_bytes(payload[8..10].try_into().unwrap()) != 0;
-            if let Some(policy) = GlomParameters::policy_from_code(u16::from_ne_bytes(
-                payload[10..12].try_into().unwrap(),
-            )) {
-                result.timestamp_policy = policy;
-                Some(result)
-            } else {
-                None
-            }
+        result.coincidence_ticks = self.payload_field::<u64>(0)?;
+        result
+            .validate()
+            .map_err(ring_items::RingItemError::InvalidPayload)?;
+        result.is_building = self.payload_field::<u16>(8)? != 0;
+        if let Some(policy) = GlomParameters::policy_from_code(self.payload_field::<u16>(10)?) {
+            result.timestamp_policy = policy;
+            Ok(result)
         } else {
-            None
+            Err(ring_items::RingItemError::InvalidPayload(String::from(
+                "Unrecognized timestamp policy code",
+            )))
         }
     }
 }
 #[cfg(test)]
 mod glom_tests {
-    use crate::glom_parameters::*;
+    use crate::ring_items::glom_parameters::*;
     use crate::ring_items::*;
     use std::mem::size_of;
     #[test]
@@ -234,8 +268,8 @@ mod glom_tests {
     fn from_raw_1() {
         let item = GlomParameters::new(1000, true, TimestampPolicy::First);
         let raw = item.to_raw();
-        let back: Option<GlomParameters> = raw.to_specific(RingVersion::V11);
-        assert!(back.is_some());
+        let back: Result<GlomParameters, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert!(back.is_ok());
         let back = back.unwrap();
 
         assert_eq!(1000, back.get_coincidence_interval());
@@ -246,8 +280,8 @@ mod glom_tests {
     fn from_raw_2() {
         let item = GlomParameters::new(1000, true, TimestampPolicy::Last);
         let raw = item.to_raw();
-        let back: Option<GlomParameters> = raw.to_specific(RingVersion::V11);
-        assert!(back.is_some());
+        let back: Result<GlomParameters, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert!(back.is_ok());
         let back = back.unwrap();
 
         assert_eq!(1000, back.get_coincidence_interval());
@@ -258,8 +292,8 @@ mod glom_tests {
     fn from_raw_3() {
         let item = GlomParameters::new(1000, true, TimestampPolicy::Average);
         let raw = item.to_raw();
-        let back: Option<GlomParameters> = raw.to_specific(RingVersion::V11);
-        assert!(back.is_some());
+        let back: Result<GlomParameters, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert!(back.is_ok());
         let back = back.unwrap();
 
         assert_eq!(1000, back.get_coincidence_interval());
@@ -268,10 +302,102 @@ mod glom_tests {
     }
     #[test]
     fn from_raw_4() {
-        // invalid type -> None:
+        // invalid type -> Err:
 
         let raw = RingItem::new(GLOM_INFO + 1);
-        let bad: Option<GlomParameters> = raw.to_specific(RingVersion::V11);
-        assert!(bad.is_none());
+        let bad: Result<GlomParameters, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert_eq!(
+            Some(RingItemError::UnexpectedItemType {
+                expected: GLOM_INFO,
+                found: GLOM_INFO + 1
+            }),
+            bad.err()
+        );
+    }
+    #[test]
+    fn from_raw_5() {
+        // Each policy with event building off:
+
+        for policy in [
+            TimestampPolicy::First,
+            TimestampPolicy::Last,
+            TimestampPolicy::Average,
+        ] {
+            let item = GlomParameters::new(250, false, policy);
+            let back: GlomParameters = item.to_raw().to_specific(RingVersion::V12).unwrap();
+            assert_eq!(250, back.get_coincidence_interval());
+            assert!(!back.is_building());
+            assert_eq!(policy, back.get_ts_policy());
+        }
+    }
+    #[test]
+    fn from_raw_6() {
+        // Unrecognized policy codes -> Err:
+
+        let mut raw = RingItem::new(GLOM_INFO);
+        raw.add(1000_u64).add(1_u16).add(3_u16);
+        let bad: Result<GlomParameters, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert_eq!(
+            Some(RingItemError::InvalidPayload(String::from(
+                "Unrecognized timestamp policy code"
+            ))),
+            bad.err()
+        );
+    }
+    #[test]
+    fn from_raw_7() {
+        // Truncated payload (no timestamp policy):
+
+        let mut raw = RingItem::new(GLOM_INFO);
+        raw.add(1000_u64).add(1_u16);
+        let bad: Result<GlomParameters, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert_eq!(
+            Some(RingItemError::PayloadTooShort {
+                needed: 12,
+                available: 10
+            }),
+            bad.err()
+        );
+    }
+    #[test]
+    fn from_raw_8() {
+        // Unreasonable coincidence intervals -> Err:
+
+        let mut raw = RingItem::new(GLOM_INFO);
+        raw.add(u64::MAX).add(1_u16).add(GLOM_TIMESTAMP_FIRST);
+        let bad: Result<GlomParameters, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert!(matches!(bad, Err(RingItemError::InvalidPayload(_))));
+
+        let item = GlomParameters::new(MAX_COINCIDENCE_INTERVAL, true, TimestampPolicy::First);
+        let back: Result<GlomParameters, RingItemError> =
+            item.to_raw().to_specific(RingVersion::V11);
+        assert!(back.is_ok());
+    }
+    #[test]
+    fn validate_1() {
+        assert!(is_valid_interval(0));
+        assert!(is_valid_interval(10_000));
+        assert!(!is_valid_interval(10_001));
+        assert!(!is_valid_interval(u64::MAX));
+
+        assert!(GlomParameters::new(0, true, TimestampPolicy::First)
+            .validate()
+            .is_ok());
+        assert!(GlomParameters::new(10_000, true, TimestampPolicy::Last)
+            .validate()
+            .is_ok());
+        assert_eq!(
+            Err(format!(
+                "Coincidence interval {} is larger than the maximum 10000",
+                u64::MAX
+            )),
+            GlomParameters::new(u64::MAX, false, TimestampPolicy::Average).validate()
+        );
+    }
+    #[test]
+    fn display_1() {
+        assert_eq!("First", TimestampPolicy::First.to_string());
+        assert_eq!("Last", TimestampPolicy::Last.to_string());
+        assert_eq!("Averaged", TimestampPolicy::Average.to_string());
     }
 }
@@ -21,7 +21,9 @@ use std::time;
 
 pub mod abnormal_end;
 pub mod analysis_ring_items;
+pub mod checksum;
 pub mod event_item;
+pub mod file_report;
 pub mod format_item;
 pub mod glom_parameters;
 pub mod scaler_item;
@@ -29,24 +31,69 @@ pub mod state_change;
 pub mod text_item;
 pub mod triggers_item;
 
+// Sealing PayloadPrimitive keeps types for which some bit patterns
+// are invalid (bool, char, references, enums) out of payload_as:
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+///
+/// The primitive types RingItem::payload_as can fetch:  integers and
+/// floats, for which any bit pattern is a valid value.  from_ne_bytes
+/// is given exactly size_of::<Self>() bytes.
+///
+pub trait PayloadPrimitive: sealed::Sealed + Copy {
+    fn from_ne_bytes(bytes: &[u8]) -> Self;
+}
+macro_rules! payload_primitive {
+    ($($t:ty),*) => {
+        $(
+            impl sealed::Sealed for $t {}
+            impl PayloadPrimitive for $t {
+                fn from_ne_bytes(bytes: &[u8]) -> Self {
+                    <$t>::from_ne_bytes(bytes.try_into().expect("Sized by payload_as"))
+                }
+            }
+        )*
+    };
+}
+payload_primitive!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
+
 /// This is an raw ring item.   Raw in the
 /// sense that the payload is just a soup of bytes.
 /// However it wil have methods that allow conversion of this item
 /// to more structured ring items based on the 'type' field.
 ///
 
+#[derive(Clone, Debug)]
 pub struct RingItem {
     size: u32,
     type_id: u32,
     body_header_size: u32,
     payload: Vec<u8>,
 }
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct BodyHeader {
     pub timestamp: u64,
     pub source_id: u32,
     pub barrier_type: u32,
 }
+impl BodyHeader {
+    pub fn new(timestamp: u64, source_id: u32, barrier_type: u32) -> BodyHeader {
+        BodyHeader {
+            timestamp,
+            source_id,
+            barrier_type,
+        }
+    }
+    /// The time from header a to header b, treating the timestamps
+    /// as nanoseconds.  This is negative if b is earlier than a.
+    ///
+    pub fn elapsed_ns(a: BodyHeader, b: BodyHeader) -> i64 {
+        b.timestamp.wrapping_sub(a.timestamp) as i64
+    }
+}
 impl fmt::Display for BodyHeader {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "Body header:\n").unwrap();
@@ -55,21 +102,58 @@ impl fmt::Display for BodyHeader {
         write!(f, "   barrier:   {}\n", self.barrier_type)
     }
 }
-#[derive(Debug)]
+///
+/// Errors reading ring items and converting raw ring items to
+/// specific types:
+///
+/// *  HeaderReadFailed, InvalidHeader and FileTooSmall come from reading
+///    raw items.
+/// *  UnexpectedItemType - a conversion was attempted from a ring item of
+///    the wrong type.  For conversions that accept several types
+///    (e.g. state changes), expected is the first of those types.
+/// *  PayloadTooShort - the payload ended before a field that was needed;
+///    needed is the number of bytes required to hold that field.
+/// *  UnsupportedVersion - a format item described a ring item format
+///    major version that is not supported.
+/// *  InvalidPayload - a payload field has a value that is not legal.
+/// *  IoFailure - a file of ring items could not be opened or read.
+///
+#[derive(Debug, Clone, PartialEq)]
 pub enum RingItemError {
     HeaderReadFailed,
     InvalidHeader,
     FileTooSmall,
+    UnexpectedItemType { expected: u32, found: u32 },
+    PayloadTooShort { needed: usize, available: usize },
+    UnsupportedVersion(u16),
+    InvalidPayload(String),
+    IoFailure(String),
 }
-impl RingItemError {
-    pub fn to_string(&self) -> String {
+impl fmt::Display for RingItemError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::HeaderReadFailed => String::from("Header read failed"),
-            Self::InvalidHeader => String::from("Invalid header"),
-            Self::FileTooSmall => String::from("File not large enough for ring item"),
+            Self::HeaderReadFailed => write!(f, "Header read failed"),
+            Self::InvalidHeader => write!(f, "Invalid header"),
+            Self::FileTooSmall => write!(f, "File not large enough for ring item"),
+            Self::UnexpectedItemType { expected, found } => write!(
+                f,
+                "Unexpected ring item type {} ({} was expected)",
+                found, expected
+            ),
+            Self::PayloadTooShort { needed, available } => write!(
+                f,
+                "Ring item payload too short: {} bytes needed but only {} available",
+                needed, available
+            ),
+            Self::UnsupportedVersion(major) => {
+                write!(f, "Unsupported ring item format version {}", major)
+            }
+            Self::InvalidPayload(msg) => write!(f, "Invalid ring item payload: {}", msg),
+            Self::IoFailure(msg) => write!(f, "Ring item I/O failed: {}", msg),
         }
     }
 }
+impl std::error::Error for RingItemError {}
 pub type RingItemResult = Result<RingItem, RingItemError>;
 
 impl RingItem {
@@ -86,6 +170,16 @@ impl RingItem {
         }
         Err(0)
     }
+    // Number of payload bytes in the body header, including any
+    // extension beyond the standard fields:
+
+    fn body_header_bytes(&self) -> usize {
+        if self.has_body_header() {
+            (self.body_header_size as usize - mem::size_of::<u32>()).min(self.payload.len())
+        } else {
+            0
+        }
+    }
     /// Write a u32:
 
     fn write_long<T: Write>(f: &mut T, l: u32) -> std::io::Result<usize> {
@@ -130,23 +224,93 @@ impl RingItem {
     ///
     pub fn get_bodyheader(&self) -> Option<BodyHeader> {
         if self.has_body_header() {
-            return Some(BodyHeader {
-                timestamp: u64::from_ne_bytes(self.payload.as_slice()[0..8].try_into().unwrap()),
-                source_id: u32::from_ne_bytes(self.payload.as_slice()[8..12].try_into().unwrap()),
-                barrier_type: u32::from_ne_bytes(
-                    self.payload.as_slice()[12..16].try_into().unwrap(),
-                ),
-            });
+            Some(BodyHeader::new(
+                self.payload_as::<u64>(0)?,
+                self.payload_as::<u32>(8)?,
+                self.payload_as::<u32>(12)?,
+            ))
         } else {
-            return None;
+            None
         }
     }
+    /// Set the body header of the item.  If the item already has a
+    /// body header it is replaced (along with any extension bytes beyond
+    /// the standard fields), otherwise one is inserted in front of the
+    /// existing payload.
+    ///
+    pub fn set_body_header(&mut self, timestamp: u64, source_id: u32, barrier_type: u32) {
+        let mut header = Vec::with_capacity(body_header_size());
+        header.extend_from_slice(&timestamp.to_ne_bytes());
+        header.extend_from_slice(&source_id.to_ne_bytes());
+        header.extend_from_slice(&barrier_type.to_ne_bytes());
+
+        let existing = self.body_header_bytes();
+        self.payload.splice(0..existing, header);
+        self.size = self.size - existing as u32 + body_header_size() as u32;
+        self.body_header_size = (body_header_size() + mem::size_of::<u32>()) as u32;
+    }
+    /// Remove the body header from the item, if it has one, including
+    /// any extension bytes.
+    ///
+    pub fn clear_body_header(&mut self) {
+        let existing = self.body_header_bytes();
+        self.payload.drain(0..existing);
+        self.size -= existing as u32;
+        self.body_header_size = mem::size_of::<u32>() as u32;
+    }
     pub fn payload(&self) -> &Vec<u8> {
         &(self.payload)
     }
     pub fn payload_mut(&mut self) -> &mut Vec<u8> {
         &mut (self.payload)
     }
+    /// Fetch a primitive (integer or float) from the payload at offset
+    /// bytes.  None is returned if the payload is too short to hold the
+    /// value.  This is the inverse of add.
+    ///
+    pub fn payload_as<T: PayloadPrimitive>(&self, offset: usize) -> Option<T> {
+        let end = offset.checked_add(mem::size_of::<T>())?;
+        self.payload.get(offset..end).map(T::from_ne_bytes)
+    }
+    /// payload_as for use in conversions to specific types:
+    /// a short payload is a PayloadTooShort error.
+    ///
+    pub fn payload_field<T: PayloadPrimitive>(&self, offset: usize) -> Result<T, RingItemError> {
+        self.payload_as::<T>(offset)
+            .ok_or(RingItemError::PayloadTooShort {
+                needed: offset.saturating_add(mem::size_of::<T>()),
+                available: self.payload.len(),
+            })
+    }
+    /// read_string for use in conversions to specific types:  a string
+    /// that runs off the end of the payload is a PayloadTooShort error
+    /// and one that is not UTF-8 is an InvalidPayload error.
+    ///
+    pub fn payload_string(&self, offset: usize) -> Result<(String, usize), RingItemError> {
+        let bytes = self.payload.get(offset..).unwrap_or(&[]);
+        if !bytes.contains(&0) {
+            return Err(RingItemError::PayloadTooShort {
+                needed: offset.max(self.payload.len()).saturating_add(1),
+                available: self.payload.len(),
+            });
+        }
+        read_string(&self.payload, offset).ok_or_else(|| {
+            RingItemError::InvalidPayload(format!("The string at offset {} is not UTF-8", offset))
+        })
+    }
+    /// Conversions to specific types use this to check the type
+    /// of the raw item.
+    ///
+    pub fn check_type(&self, expected: u32) -> Result<(), RingItemError> {
+        if self.type_id == expected {
+            Ok(())
+        } else {
+            Err(RingItemError::UnexpectedItemType {
+                expected,
+                found: self.type_id,
+            })
+        }
+    }
 
     ///  Add an object of type T to the ring buffer.  Note
     /// That the raw bytes are added therefore the item must
@@ -169,6 +333,13 @@ impl RingItem {
         self.size = self.size + mem::size_of::<T>() as u32;
         self
     }
+    /// Append a string as a C (null terminated) string.
+    pub fn add_string(&mut self, s: &str) -> &mut RingItem {
+        for b in s.bytes() {
+            self.add(b);
+        }
+        self.add(0_u8)
+    }
     pub fn add_byte_vec(&mut self, v: &Vec<u8>) {
         for b in v {
             self.add(*b);
@@ -236,6 +407,56 @@ impl RingItem {
 
         Ok(bytes_written)
     }
+    /// Produce the wire format bytes of the item.  The header
+    /// words are in native byte order followed by the payload.
+    /// The result is exactly size() bytes long.
+    ///
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut result = Vec::with_capacity(self.size as usize);
+        result.extend_from_slice(&u32::to_ne_bytes(self.size));
+        result.extend_from_slice(&u32::to_ne_bytes(self.type_id));
+        result.extend_from_slice(&u32::to_ne_bytes(self.body_header_size));
+        result.extend_from_slice(&self.payload);
+        result
+    }
+    /// Write the item to any writer.  Unlike write_item, the
+    /// entire item is written or an error is returned.
+    ///
+    pub fn write_to<W: Write>(&self, w: &mut W) -> std::io::Result<()> {
+        w.write_all(&self.to_bytes())
+    }
+    /// Construct a ring item from a buffer of bytes in wire format
+    /// (e.g. received from a socket).  The buffer must contain at
+    /// least one complete ring item.  Any bytes following the
+    /// item are ignored.
+    ///
+    pub fn from_slice(data: &[u8]) -> RingItemResult {
+        // A corrupt size field must not make us allocate a payload
+        // bigger than the data we were given:
+
+        if data.len() >= 3 * mem::size_of::<u32>() {
+            if let Some(size) = RingItem::parse_size(data) {
+                if size as usize > data.len() {
+                    return Err(RingItemError::FileTooSmall);
+                }
+            }
+        }
+        let mut source = data;
+        RingItem::read_item(&mut source)
+    }
+    /// Determine the size of the ring item at the start of a buffer
+    /// without parsing it.  If the buffer does not yet contain the
+    /// size field (fewer than four bytes), None is returned.
+    /// Together with from_slice this allows a caller to accumulate bytes
+    /// until a complete item is available.
+    ///
+    pub fn parse_size(data: &[u8]) -> Option<u32> {
+        if data.len() < mem::size_of::<u32>() {
+            None
+        } else {
+            Some(u32::from_ne_bytes(data[0..4].try_into().unwrap()))
+        }
+    }
 }
 
 /// provide for textual formatting of a raw ring item:
@@ -275,10 +496,12 @@ pub trait ToRaw {
 }
 /// This can be implemented for each destination type
 /// e.g. ConvertRaw<StateChange> for RingItem to convert a raw
-/// item to a ring item if possible (correct type_id).
+/// item to a ring item if possible (correct type_id).  The error
+/// distinguishes items of the wrong type (UnexpectedItemType) from
+/// items that can't be parsed (e.g. PayloadTooShort).
 ///
 pub trait FromRaw<T> {
-    fn to_specific(&self, vers: RingVersion) -> Option<T>;
+    fn to_specific(&self, vers: RingVersion) -> Result<T, RingItemError>;
 }
 
 /// convert a u32 into a SystemTime:
@@ -323,6 +546,17 @@ pub fn string_len(d: &[u8]) -> usize {
 
     return result;
 }
+///
+/// Read the C (null terminated) string at offset in a payload.  Returns
+/// the string and the offset just past its null terminator or None if
+/// there's no null terminator or the string is not UTF-8.
+///
+pub fn read_string(payload: &[u8], offset: usize) -> Option<(String, usize)> {
+    let bytes = payload.get(offset..)?;
+    let len = bytes.iter().position(|b| *b == 0)?;
+    let s = std::str::from_utf8(&bytes[..len]).ok()?;
+    Some((String::from(s), offset + len + 1))
+}
 pub fn get_c_string(offset: &mut usize, bytes: &[u8]) -> String {
     let o: usize = *offset;
     let slen = string_len(&bytes[o..]);
@@ -330,7 +564,7 @@ pub fn get_c_string(offset: &mut usize, bytes: &[u8]) -> String {
     String::from_utf8(bytes[o..o + slen].try_into().unwrap()).unwrap()
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum RingVersion {
     V11,
     V12,
@@ -348,6 +582,8 @@ const FORMAT_ITEM: u32 = 12;
 const PERIODIC_SCALERS: u32 = 20;
 const PHYSICS_EVENT: u32 = 30;
 const PHYSICS_EVENT_COUNT: u32 = 31;
+const EVB_FRAGMENT: u32 = 40;
+const EVB_UNKNOWN_PAYLOAD: u32 = 41;
 const GLOM_INFO: u32 = 42;
 const ABNORMAL_END: u32 = 5;
 
@@ -360,12 +596,53 @@ const VARIABLE_VALUES: u32 = 32769;
 /// Contains the actual parameter_id:value pairs for an event.
 const PARAMETER_DATA: u32 = 32770;
 
+// Correspondence between ring item types and their names.
+// The names are those of the NSCLDAQ constants.  Type ids are the same
+// for V11 and V12 items.
+
+const TYPE_NAMES: [(u32, &str); 17] = [
+    (BEGIN_RUN, "BEGIN_RUN"),
+    (END_RUN, "END_RUN"),
+    (PAUSE_RUN, "PAUSE_RUN"),
+    (RESUME_RUN, "RESUME_RUN"),
+    (ABNORMAL_END, "ABNORMAL_ENDRUN"),
+    (PACKET_TYPES, "PACKET_TYPES"),
+    (MONITORED_VARIABLES, "MONITORED_VARIABLES"),
+    (FORMAT_ITEM, "RING_FORMAT"),
+    (PERIODIC_SCALERS, "PERIODIC_SCALERS"),
+    (PHYSICS_EVENT, "PHYSICS_EVENT"),
+    (PHYSICS_EVENT_COUNT, "PHYSICS_EVENT_COUNT"),
+    (EVB_FRAGMENT, "EVB_FRAGMENT"),
+    (EVB_UNKNOWN_PAYLOAD, "EVB_UNKNOWN_PAYLOAD"),
+    (GLOM_INFO, "EVB_GLOM_INFO"),
+    (PARAMETER_DEFINITIONS, "PARAMETER_DEFINITIONS"),
+    (VARIABLE_VALUES, "VARIABLE_VALUES"),
+    (PARAMETER_DATA, "PARAMETER_DATA"),
+];
+
+/// Get the name of a ring item type. None if the type is not known.
+pub fn type_id_to_name(type_id: u32) -> Option<&'static str> {
+    TYPE_NAMES
+        .iter()
+        .find(|(id, _)| *id == type_id)
+        .map(|(_, name)| *name)
+}
+/// Get the ring item type with a name. None if there's no such name.
+pub fn name_to_type_id(name: &str) -> Option<u32> {
+    TYPE_NAMES
+        .iter()
+        .find(|(_, n)| *n == name)
+        .map(|(id, _)| *id)
+}
+
 //---------------------------------------------------------------
 // unit tests
 //
 #[cfg(test)]
 mod tests {
-    use crate::ring_items::RingItem;
+    use crate::ring_items::{
+        body_header_size, read_string, BodyHeader, RingItem, RingItemError, BEGIN_RUN, END_RUN,
+    };
     use humantime;
     use std::io::{Seek, Write};
     use std::mem;
@@ -435,6 +712,99 @@ mod tests {
         assert!(ptr::eq(&mut item.payload, item.payload_mut()));
     }
     #[test]
+    fn payload_as_1() {
+        let mut item = RingItem::new(1234);
+        item.add(0xa5_u8).add(0x12345678_u32).add(3.5_f64);
+        assert_eq!(Some(0xa5_u8), item.payload_as::<u8>(0));
+        assert_eq!(Some(0x12345678_u32), item.payload_as::<u32>(1)); // unaligned.
+        assert_eq!(Some(3.5_f64), item.payload_as::<f64>(5));
+    }
+    #[test]
+    fn payload_as_3() {
+        // Signed and f32 fields:
+
+        let mut item = RingItem::new(1234);
+        item.add(-2_i8)
+            .add(-300_i16)
+            .add(-70000_i32)
+            .add(-1_i64)
+            .add(1.25_f32);
+        assert_eq!(Some(-2_i8), item.payload_as::<i8>(0));
+        assert_eq!(Some(-300_i16), item.payload_as::<i16>(1));
+        assert_eq!(Some(-70000_i32), item.payload_as::<i32>(3));
+        assert_eq!(Some(-1_i64), item.payload_as::<i64>(7));
+        assert_eq!(Some(1.25_f32), item.payload_as::<f32>(15));
+        assert!(item.payload_as::<f32>(16).is_none());
+    }
+    #[test]
+    fn payload_as_2() {
+        // Out of range:
+
+        let mut item = RingItem::new(1234);
+        item.add(0x12345678_u32);
+        assert!(item.payload_as::<u32>(1).is_none());
+        assert!(item.payload_as::<u64>(0).is_none());
+        assert!(item.payload_as::<u8>(4).is_none());
+        assert!(item.payload_as::<u8>(usize::MAX).is_none());
+        assert!(RingItem::new(1).payload_as::<u8>(0).is_none());
+    }
+    #[test]
+    fn payload_field_1() {
+        let mut item = RingItem::new(1234);
+        item.add(0x12345678_u32);
+        assert_eq!(Ok(0x12345678_u32), item.payload_field::<u32>(0));
+        assert_eq!(
+            Err(RingItemError::PayloadTooShort {
+                needed: 10,
+                available: 4
+            }),
+            item.payload_field::<u64>(2)
+        );
+    }
+    #[test]
+    fn check_type_1() {
+        let item = RingItem::new(BEGIN_RUN);
+        assert_eq!(Ok(()), item.check_type(BEGIN_RUN));
+        assert_eq!(
+            Err(RingItemError::UnexpectedItemType {
+                expected: END_RUN,
+                found: BEGIN_RUN
+            }),
+            item.check_type(END_RUN)
+        );
+    }
+    #[test]
+    fn error_1() {
+        // Display and Error:
+
+        assert_eq!(
+            "Header read failed",
+            RingItemError::HeaderReadFailed.to_string()
+        );
+        assert_eq!(
+            "Unexpected ring item type 2 (1 was expected)",
+            RingItemError::UnexpectedItemType {
+                expected: 1,
+                found: 2
+            }
+            .to_string()
+        );
+        assert_eq!(
+            "Ring item payload too short: 8 bytes needed but only 4 available",
+            RingItemError::PayloadTooShort {
+                needed: 8,
+                available: 4
+            }
+            .to_string()
+        );
+        assert_eq!(
+            "Unsupported ring item format version 10",
+            RingItemError::UnsupportedVersion(10).to_string()
+        );
+        let e: Box<dyn std::error::Error> = Box::new(RingItemError::InvalidHeader);
+        assert_eq!("Invalid header", e.to_string());
+    }
+    #[test]
     fn add_1() {
         let mut item = RingItem::new(1234);
         item.add(0xa5 as u8);
@@ -698,6 +1068,278 @@ mod tests {
         assert_eq!(out_item.body_header_size, in_item.body_header_size);
         assert_eq!(out_item.payload, in_item.payload);
     }
+    #[test]
+    fn bodyheader_1() {
+        let bh = BodyHeader::new(0x1122334455667788, 2, 1);
+        assert_eq!(0x1122334455667788, bh.timestamp);
+        assert_eq!(2, bh.source_id);
+        assert_eq!(1, bh.barrier_type);
+
+        let item = RingItem::new_with_body_header(1, 0x1122334455667788, 2, 1);
+        assert_eq!(Some(bh), item.get_bodyheader());
+    }
+    #[test]
+    fn bodyheader_2() {
+        let a = BodyHeader::new(1000, 1, 0);
+        let b = BodyHeader::new(251000, 2, 0);
+        assert_eq!(250000, BodyHeader::elapsed_ns(a, b));
+        assert_eq!(-250000, BodyHeader::elapsed_ns(b, a));
+        assert_eq!(0, BodyHeader::elapsed_ns(a, a));
+    }
+    #[test]
+    fn set_bodyheader_1() {
+        // Add a body header to an item without one:
+
+        let mut item = RingItem::new(1);
+        item.add(0x12345678_u32);
+        item.set_body_header(0x1122334455667788, 2, 1);
+
+        let expected = RingItem::new_with_body_header(1, 0x1122334455667788, 2, 1);
+        assert!(item.has_body_header());
+        assert_eq!(expected.size() + 4, item.size());
+        assert_eq!(expected.body_header_size, item.body_header_size);
+        let bh = item.get_bodyheader().unwrap();
+        assert_eq!(0x1122334455667788, bh.timestamp);
+        assert_eq!(2, bh.source_id);
+        assert_eq!(1, bh.barrier_type);
+        assert_eq!(
+            0x12345678_u32.to_ne_bytes(),
+            item.payload()[body_header_size()..]
+        );
+    }
+    #[test]
+    fn set_bodyheader_2() {
+        // Replace an existing body header:
+
+        let mut item = RingItem::new_with_body_header(1, 0x1234, 1, 0);
+        item.add(0x12345678_u32);
+        let size = item.size();
+        item.set_body_header(0x5678, 5, 2);
+
+        assert_eq!(size, item.size());
+        let bh = item.get_bodyheader().unwrap();
+        assert_eq!(0x5678, bh.timestamp);
+        assert_eq!(5, bh.source_id);
+        assert_eq!(2, bh.barrier_type);
+        assert_eq!(
+            0x12345678_u32.to_ne_bytes(),
+            item.payload()[body_header_size()..]
+        );
+    }
+    // An item whose body header has 4 bytes of extension after the
+    // standard fields:
+
+    fn extended_bodyheader_item() -> RingItem {
+        let mut item = RingItem::new_with_body_header(1, 0x1234, 1, 0);
+        item.add(0xffffffff_u32);
+        item.body_header_size += mem::size_of::<u32>() as u32;
+        item.add(0x12345678_u32);
+        item
+    }
+    #[test]
+    fn set_bodyheader_3() {
+        // Replacing an oversized body header drops the extension:
+
+        let mut item = extended_bodyheader_item();
+        let size = item.size();
+        item.set_body_header(0x5678, 5, 2);
+
+        let mut expected = RingItem::new_with_body_header(1, 0x5678, 5, 2);
+        expected.add(0x12345678_u32);
+        assert_eq!(size - 4, item.size());
+        assert_eq!(expected.to_bytes(), item.to_bytes());
+    }
+    #[test]
+    fn clear_bodyheader_1() {
+        let mut item = RingItem::new_with_body_header(1, 0x1234, 1, 0);
+        item.add(0x12345678_u32);
+        item.clear_body_header();
+
+        let mut expected = RingItem::new(1);
+        expected.add(0x12345678_u32);
+        assert!(!item.has_body_header());
+        assert!(item.get_bodyheader().is_none());
+        assert_eq!(expected.to_bytes(), item.to_bytes());
+
+        // No-op if there's no body header:
+
+        item.clear_body_header();
+        assert_eq!(expected.to_bytes(), item.to_bytes());
+    }
+    #[test]
+    fn clear_bodyheader_2() {
+        // The extension of an oversized body header goes too:
+
+        let mut item = extended_bodyheader_item();
+        item.clear_body_header();
+
+        let mut expected = RingItem::new(1);
+        expected.add(0x12345678_u32);
+        assert!(!item.has_body_header());
+        assert_eq!(expected.to_bytes(), item.to_bytes());
+
+        // 11.x items with no body header have a size of 0:
+
+        item.body_header_size = 0;
+        item.clear_body_header();
+        assert_eq!(expected.to_bytes(), item.to_bytes());
+    }
+    #[test]
+    fn from_slice_1() {
+        // Round trip a minimal item:
+
+        let out_item = RingItem::new(1);
+        let mut bytes: Vec<u8> = Vec::new();
+        out_item.write_item(&mut bytes).unwrap();
+
+        let in_item = RingItem::from_slice(&bytes).unwrap();
+        assert_eq!(out_item.size, in_item.size);
+        assert_eq!(out_item.type_id, in_item.type_id);
+        assert_eq!(out_item.body_header_size, in_item.body_header_size);
+        assert_eq!(out_item.payload, in_item.payload);
+    }
+    #[test]
+    fn from_slice_2() {
+        // Body header and payload with trailing bytes that
+        // are not part of the item:
+
+        let mut out_item = RingItem::new_with_body_header(1, 0x1245123412, 2, 0);
+        let payload: Vec<u8> = vec![5, 4, 3, 2, 1, 0];
+        out_item.add_byte_vec(&payload);
+        let mut bytes: Vec<u8> = Vec::new();
+        out_item.write_item(&mut bytes).unwrap();
+        bytes.extend_from_slice(&[0xff, 0xff]);
+
+        let in_item = RingItem::from_slice(&bytes).unwrap();
+        assert_eq!(out_item.size, in_item.size);
+        assert_eq!(out_item.type_id, in_item.type_id);
+        assert_eq!(out_item.body_header_size, in_item.body_header_size);
+        assert_eq!(out_item.payload, in_item.payload);
+    }
+    #[test]
+    fn from_slice_3() {
+        // Incomplete items are errors:
+
+        let mut out_item = RingItem::new(1);
+        out_item.add_byte_vec(&vec![1, 2, 3, 4]);
+        let mut bytes: Vec<u8> = Vec::new();
+        out_item.write_item(&mut bytes).unwrap();
+
+        assert!(matches!(
+            RingItem::from_slice(&bytes[0..6]),
+            Err(RingItemError::HeaderReadFailed)
+        ));
+        assert!(matches!(
+            RingItem::from_slice(&bytes[0..14]),
+            Err(RingItemError::FileTooSmall)
+        ));
+        assert!(matches!(
+            RingItem::from_slice(&u32::to_ne_bytes(4)),
+            Err(RingItemError::InvalidHeader)
+        ));
+    }
+    #[test]
+    fn from_slice_4() {
+        // A size far bigger than the data is an error, not an
+        // allocation of that size:
+
+        let mut bytes = RingItem::new(1).to_bytes();
+        bytes[0..4].copy_from_slice(&u32::to_ne_bytes(0xffffffff));
+        assert!(matches!(
+            RingItem::from_slice(&bytes),
+            Err(RingItemError::FileTooSmall)
+        ));
+    }
+    #[test]
+    fn to_bytes_1() {
+        let mut out_item = RingItem::new_with_body_header(1, 0x1245123412, 2, 0);
+        out_item.add_byte_vec(&vec![5, 4, 3, 2, 1, 0]);
+        let bytes = out_item.to_bytes();
+        assert_eq!(out_item.size() as usize, bytes.len());
+
+        let mut written: Vec<u8> = Vec::new();
+        out_item.write_item(&mut written).unwrap();
+        assert_eq!(written, bytes);
+    }
+    #[test]
+    fn to_bytes_2() {
+        // Round trip through from_slice:
+
+        let mut out_item = RingItem::new(1);
+        out_item.add_byte_vec(&vec![5, 4, 3, 2, 1, 0]);
+        let in_item = RingItem::from_slice(&out_item.to_bytes()).unwrap();
+        assert_eq!(out_item.size, in_item.size);
+        assert_eq!(out_item.type_id, in_item.type_id);
+        assert_eq!(out_item.body_header_size, in_item.body_header_size);
+        assert_eq!(out_item.payload, in_item.payload);
+    }
+    #[test]
+    fn write_to_1() {
+        // Stream two items and read them back:
+
+        let item1 = RingItem::new(1);
+        let mut item2 = RingItem::new_with_body_header(2, 0x1234, 1, 0);
+        item2.add(0x12345678_u32);
+
+        let mut stream: Vec<u8> = Vec::new();
+        item1.write_to(&mut stream).unwrap();
+        item2.write_to(&mut stream).unwrap();
+        assert_eq!((item1.size() + item2.size()) as usize, stream.len());
+
+        let mut src = stream.as_slice();
+        let in1 = RingItem::read_item(&mut src).unwrap();
+        let in2 = RingItem::read_item(&mut src).unwrap();
+        assert_eq!(item1.type_id, in1.type_id);
+        assert_eq!(item2.type_id, in2.type_id);
+        assert_eq!(item2.payload, in2.payload);
+        assert!(src.is_empty());
+    }
+    #[test]
+    fn parse_size_1() {
+        let mut out_item = RingItem::new(1);
+        out_item.add_byte_vec(&vec![1, 2, 3, 4]);
+        let mut bytes: Vec<u8> = Vec::new();
+        out_item.write_item(&mut bytes).unwrap();
+
+        assert_eq!(None, RingItem::parse_size(&bytes[0..3]));
+        assert_eq!(Some(out_item.size()), RingItem::parse_size(&bytes[0..4]));
+        assert_eq!(Some(out_item.size()), RingItem::parse_size(&bytes));
+    }
+    #[test]
+    fn type_names_1() {
+        use crate::ring_items::*;
+        let ids = [
+            BEGIN_RUN,
+            END_RUN,
+            PAUSE_RUN,
+            RESUME_RUN,
+            ABNORMAL_END,
+            PACKET_TYPES,
+            MONITORED_VARIABLES,
+            FORMAT_ITEM,
+            PERIODIC_SCALERS,
+            PHYSICS_EVENT,
+            PHYSICS_EVENT_COUNT,
+            EVB_FRAGMENT,
+            EVB_UNKNOWN_PAYLOAD,
+            GLOM_INFO,
+            PARAMETER_DEFINITIONS,
+            VARIABLE_VALUES,
+            PARAMETER_DATA,
+        ];
+        for id in ids {
+            let name = type_id_to_name(id).unwrap();
+            assert_eq!(Some(id), name_to_type_id(name));
+        }
+        assert_eq!(Some("PHYSICS_EVENT"), type_id_to_name(PHYSICS_EVENT));
+        assert_eq!(Some(BEGIN_RUN), name_to_type_id("BEGIN_RUN"));
+    }
+    #[test]
+    fn type_names_2() {
+        use crate::ring_items::*;
+        assert!(type_id_to_name(1000).is_none());
+        assert!(name_to_type_id("NO_SUCH_TYPE").is_none());
+    }
     // Unbound functions:
     // Round time time conversion:
 
@@ -736,4 +1378,99 @@ mod tests {
         );
         assert_eq!(test_string.len() + 1, offset);
     }
+    #[test]
+    fn add_string_1() {
+        let mut item = RingItem::new(BEGIN_RUN);
+        item.add(12_u32).add_string("Hello world").add_string("");
+        assert_eq!(12 + 4 + 12 + 1, item.size());
+
+        let (s, next) = read_string(item.payload(), 4).unwrap();
+        assert_eq!("Hello world", s);
+        assert_eq!(4 + 12, next);
+        assert_eq!(Some((String::new(), 17)), read_string(item.payload(), next));
+        assert!(read_string(item.payload(), 17).is_none());
+    }
+    #[test]
+    fn read_string_1() {
+        // no terminator or bad UTF-8:
+
+        assert!(read_string(b"abc", 0).is_none());
+        assert!(read_string(b"abc\0", 10).is_none());
+        assert!(read_string(&[0xff, 0xfe, 0], 0).is_none());
+    }
+}
+
+// Property tests:  arbitrary items must survive writing and reading
+// back unchanged.
+
+#[cfg(test)]
+mod round_trip_proptests {
+    use crate::ring_items::triggers_item::PhysicsEventCountItem;
+    use crate::ring_items::*;
+    use proptest::prelude::*;
+
+    fn arb_body_header() -> impl Strategy<Value = Option<BodyHeader>> {
+        proptest::option::of(
+            (any::<u64>(), any::<u32>(), any::<u32>())
+                .prop_map(|(t, s, b)| BodyHeader::new(t, s, b)),
+        )
+    }
+    fn arb_ring_item() -> impl Strategy<Value = RingItem> {
+        (
+            any::<u32>(),
+            arb_body_header(),
+            proptest::collection::vec(any::<u8>(), 0..512),
+        )
+            .prop_map(|(type_id, bh, payload)| {
+                let mut item = if let Some(bh) = bh {
+                    RingItem::new_with_body_header(
+                        type_id,
+                        bh.timestamp,
+                        bh.source_id,
+                        bh.barrier_type,
+                    )
+                } else {
+                    RingItem::new(type_id)
+                };
+                item.add_byte_vec(&payload);
+                item
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn write_read_1(item in arb_ring_item()) {
+            let mut bytes: Vec<u8> = Vec::new();
+            let n = item.write_item(&mut bytes).unwrap();
+            prop_assert_eq!(item.size() as usize, n);
+            prop_assert_eq!(n, bytes.len());
+
+            let read = RingItem::read_item(&mut bytes.as_slice()).unwrap();
+            prop_assert_eq!(item.size(), read.size());
+            prop_assert_eq!(item.type_id(), read.type_id());
+            prop_assert_eq!(item.has_body_header(), read.has_body_header());
+            prop_assert_eq!(item.get_bodyheader(), read.get_bodyheader());
+            prop_assert_eq!(item.payload(), read.payload());
+        }
+        #[test]
+        fn event_count_1(
+            bh in arb_body_header(),
+            offset in any::<u32>(),
+            divisor in 1..u32::MAX,
+            sid in proptest::option::of(any::<u32>()),
+            count in any::<u64>(),
+        ) {
+            // There's only an original sid in V12 items:
+
+            let version = if sid.is_some() { RingVersion::V12 } else { RingVersion::V11 };
+            let item = PhysicsEventCountItem::new(bh, offset, divisor, sid, count);
+            let raw = item.to_raw();
+            let back: PhysicsEventCountItem = raw.to_specific(version).unwrap();
+            prop_assert_eq!(bh, back.get_bodyheader());
+            prop_assert_eq!(offset, back.get_timeoffset());
+            prop_assert_eq!(divisor, back.get_time_divisor());
+            prop_assert_eq!(sid, back.get_original_sid());
+            prop_assert_eq!(count, back.get_event_count());
+        }
+    }
 }
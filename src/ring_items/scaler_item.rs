@@ -18,6 +18,7 @@ pub struct ScalerItem {
     is_incremental: bool,
     original_sid: Option<u32>,
     scalers: Vec<u32>,
+    channel_names: Vec<String>, // Not in the raw item; "" is unnamed.
 }
 
 impl ScalerItem {
@@ -56,6 +57,40 @@ impl ScalerItem {
         self.scalers.iter()
     }
 
+    // Channel names e.g. "beam" are not part of the raw item; they
+    // label the channels for e.g. Display.
+
+    /// Name channel idx.  Err if there's no such channel.
+    pub fn set_channel_name(&mut self, idx: usize, name: &str) -> Result<(), String> {
+        if idx >= self.len() {
+            return Err(format!(
+                "Scaler item has no channel {} ({} channels)",
+                idx,
+                self.len()
+            ));
+        }
+        if self.channel_names.len() <= idx {
+            self.channel_names.resize(idx + 1, String::new());
+        }
+        self.channel_names[idx] = String::from(name);
+        Ok(())
+    }
+    /// The name of channel idx, if it has one.
+    pub fn channel_name(&self, idx: usize) -> Option<&str> {
+        self.channel_names
+            .get(idx)
+            .filter(|n| !n.is_empty())
+            .map(|n| n.as_str())
+    }
+    /// Name the channels in order.  Names beyond the last channel are
+    /// ignored and channels beyond the last name are unnamed.
+    pub fn apply_names(&mut self, names: &[&str]) {
+        self.channel_names = names
+            .iter()
+            .take(self.len())
+            .map(|n| String::from(*n))
+            .collect();
+    }
     pub fn append_scaler(&mut self, sc: u32) -> &mut Self {
         self.scalers.push(sc);
         self
@@ -82,6 +117,7 @@ impl ScalerItem {
             is_incremental: incremental,
             original_sid: orsid,
             scalers: Vec::<u32>::new(),
+            channel_names: Vec::new(),
         };
         result.scalers.append(scalers);
 
@@ -112,8 +148,12 @@ impl fmt::Display for ScalerItem {
         }
 
         write!(f, " {} scalers:\n", self.len()).unwrap();
-        for s in self.iter() {
-            write!(f, "    {} counts\n", *s).unwrap();
+        for (i, s) in self.iter().enumerate() {
+            if let Some(name) = self.channel_name(i) {
+                writeln!(f, "    {}: {} counts", name, *s).unwrap();
+            } else {
+                writeln!(f, "    {} counts", *s).unwrap();
+            }
         }
         write!(f, "")
     }
@@ -159,61 +199,221 @@ impl ring_items::ToRaw for ScalerItem {
 /// attempt conversion to a specific type (e.g. ScalerItem in this
 /// case)
 impl ring_items::FromRaw<ScalerItem> for ring_items::RingItem {
-    fn to_specific(&self, fmt: ring_items::RingVersion) -> Option<ScalerItem> {
-        if self.type_id() == ring_items::PERIODIC_SCALERS {
-            // Pull parameters from the raw item:
+    fn to_specific(
+        &self,
+        fmt: ring_items::RingVersion,
+    ) -> Result<ScalerItem, ring_items::RingItemError> {
+        self.check_type(ring_items::PERIODIC_SCALERS)?;
+        // Pull parameters from the raw item:
+
+        let body_header = self.get_bodyheader();
+        let offset: usize = if let Some(_b) = body_header {
+            ring_items::body_header_size()
+        } else {
+            0
+        };
+        let start = self.payload_field::<u32>(offset)?;
+        let end = self.payload_field::<u32>(offset + 4)?;
+        let raw_stamp = self.payload_field::<u32>(offset + 8)?;
+        let divisor = self.payload_field::<u32>(offset + 12)?;
+        let nscalers = self.payload_field::<u32>(offset + 16)?;
+        let incr = self.payload_field::<u32>(offset + 20)? != 0;
+        let mut offset = offset + 24; // new offset.
+
+        let mut orsid: Option<u32> = None;
+        if fmt == ring_items::RingVersion::V12 {
+            orsid = Some(self.payload_field::<u32>(offset)?);
+            offset += 4;
+        }
+        // Offset now points at the scalers regardless of the format:
 
-            let body_header = self.get_bodyheader();
-            let offset: usize = if let Some(_b) = body_header {
-                ring_items::body_header_size()
+        let mut scalers: Vec<u32> = Vec::new();
+        for _ in 0..nscalers {
+            scalers.push(self.payload_field::<u32>(offset)?);
+            offset += 4;
+        }
+        Ok(ScalerItem::new(
+            body_header,
+            start,
+            end,
+            ring_items::raw_to_systime(raw_stamp),
+            divisor,
+            incr,
+            orsid,
+            &mut scalers,
+        ))
+    }
+}
+
+///
+/// ScalerItemBuilder builds scaler items by method chaining.  The
+/// interval (start and end offsets) is required.  Items are
+/// incremental with divisor 1 unless set otherwise and the absolute time
+/// is the time at which the item is built.
+///
+pub struct ScalerItemBuilder {
+    body_header: Option<ring_items::BodyHeader>,
+    interval: Option<(u32, u32)>,
+    divisor: u32,
+    is_incremental: bool,
+    original_sid: Option<u32>,
+    scalers: Vec<u32>,
+}
+
+impl Default for ScalerItemBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ScalerItemBuilder {
+    pub fn new() -> ScalerItemBuilder {
+        ScalerItemBuilder {
+            body_header: None,
+            interval: None,
+            divisor: 1,
+            is_incremental: true,
+            original_sid: None,
+            scalers: Vec::new(),
+        }
+    }
+    pub fn body_header(&mut self, timestamp: u64, source_id: u32, barrier: u32) -> &mut Self {
+        self.body_header = Some(ring_items::BodyHeader::new(timestamp, source_id, barrier));
+        self
+    }
+    pub fn interval(&mut self, start: u32, end: u32) -> &mut Self {
+        self.interval = Some((start, end));
+        self
+    }
+    pub fn time_divisor(&mut self, divisor: u32) -> &mut Self {
+        self.divisor = divisor;
+        self
+    }
+    pub fn incremental(&mut self, incremental: bool) -> &mut Self {
+        self.is_incremental = incremental;
+        self
+    }
+    /// The original source id is only put in V12 items.
+    pub fn original_sid(&mut self, sid: u32) -> &mut Self {
+        self.original_sid = Some(sid);
+        self
+    }
+    pub fn add_scaler(&mut self, value: u32) -> &mut Self {
+        self.scalers.push(value);
+        self
+    }
+    pub fn add_scalers(&mut self, values: &[u32]) -> &mut Self {
+        self.scalers.extend_from_slice(values);
+        self
+    }
+    /// Build the item.  Errors if the interval was not set or ends
+    /// before it starts, or if the time divisor is zero.
+    pub fn build(&self) -> Result<ScalerItem, String> {
+        let (start, end) = self
+            .interval
+            .ok_or_else(|| String::from("A scaler item needs an interval"))?;
+        if end < start {
+            return Err(format!(
+                "Scaler interval ends ({}) before it starts ({})",
+                end, start
+            ));
+        }
+        if self.divisor == 0 {
+            return Err(String::from("The time divisor must not be zero"));
+        }
+        Ok(ScalerItem::new(
+            self.body_header,
+            start,
+            end,
+            time::SystemTime::now(),
+            self.divisor,
+            self.is_incremental,
+            self.original_sid,
+            &mut self.scalers.clone(),
+        ))
+    }
+}
+
+///
+/// ScalerAccumulator keeps running totals of the scalers in a run
+/// from the successive scaler items.  Incremental scaler items contain
+/// the counts since the previous item and are summed.  Non incremental
+/// items contain the counts since the start of the run so the totals
+/// are just replaced.
+///
+/// Rates are the totals divided by the end time offset of the most
+/// recent item, i.e. they are averages over the run so far.
+///
+pub struct ScalerAccumulator {
+    totals: Vec<u64>,
+    item_count: u32,
+    last_time_offset: f32,
+}
+
+impl ScalerAccumulator {
+    pub fn new() -> ScalerAccumulator {
+        ScalerAccumulator {
+            totals: Vec::new(),
+            item_count: 0,
+            last_time_offset: 0.0,
+        }
+    }
+    /// Add a scaler item to the totals.  If the item has more scalers
+    /// than have been seen so far, the totals are extended.
+    ///
+    pub fn ingest(&mut self, item: &ScalerItem) {
+        if self.totals.len() < item.len() {
+            self.totals.resize(item.len(), 0);
+        }
+        for (total, value) in self.totals.iter_mut().zip(item.iter()) {
+            if item.is_incremental() {
+                *total += *value as u64;
             } else {
-                0
-            };
-            let p = self.payload().as_slice();
-            let start = u32::from_ne_bytes(p[offset..offset + 4].try_into().unwrap());
-            let end = u32::from_ne_bytes(p[offset + 4..offset + 8].try_into().unwrap());
-            let raw_stamp = u32::from_ne_bytes(p[offset + 8..offset + 12].try_into().unwrap());
-            let divisor = u32::from_ne_bytes(p[offset + 12..offset + 16].try_into().unwrap());
-            let nscalers = u32::from_ne_bytes(p[offset + 16..offset + 20].try_into().unwrap());
-            let incr = u32::from_ne_bytes(p[offset + 20..offset + 24].try_into().unwrap()) != 0;
-            let mut offset = offset + 24; // new offset.
-
-            let mut orsid: Option<u32> = None;
-            if fmt == ring_items::RingVersion::V12 {
-                orsid = Some(u32::from_ne_bytes(
-                    p[offset..offset + 4].try_into().unwrap(),
-                ));
-                offset = offset + 4;
-            }
-            // Offset now points at the scalers regardless of the format:
-
-            let mut scalers: Vec<u32> = Vec::new();
-            for _ in 0..nscalers {
-                scalers.push(u32::from_ne_bytes(
-                    p[offset..offset + 4].try_into().unwrap(),
-                ));
-                offset = offset + 4;
+                *total = *value as u64;
             }
-            Some(ScalerItem::new(
-                body_header,
-                start,
-                end,
-                ring_items::raw_to_systime(raw_stamp),
-                divisor,
-                incr,
-                orsid,
-                &mut scalers,
-            ))
+        }
+        self.item_count += 1;
+        self.last_time_offset = item.get_end_secs();
+    }
+    /// The total for a scaler channel or None if no item has had
+    /// that channel.
+    pub fn get_total(&self, channel: usize) -> Option<u64> {
+        self.totals.get(channel).copied()
+    }
+    /// Average counts/second for a channel.  None if there's no such
+    /// channel or no time has elapsed.
+    pub fn rate(&self, channel: usize) -> Option<f64> {
+        if self.last_time_offset > 0.0 {
+            self.get_total(channel)
+                .map(|t| t as f64 / self.last_time_offset as f64)
         } else {
             None
         }
     }
+    pub fn item_count(&self) -> u32 {
+        self.item_count
+    }
+    pub fn elapsed_secs(&self) -> f32 {
+        self.last_time_offset
+    }
+    pub fn len(&self) -> usize {
+        self.totals.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.totals.is_empty()
+    }
+    /// Forget everything - e.g. at the start of a new run.
+    pub fn reset(&mut self) {
+        self.totals.clear();
+        self.item_count = 0;
+        self.last_time_offset = 0.0;
+    }
 }
 
 #[cfg(test)]
 mod scaler_tests {
+    use crate::ring_items::scaler_item::*;
     use crate::ring_items::*;
-    use crate::scaler_item::*;
     use std::mem::size_of;
     use std::time::*;
     #[test]
@@ -822,8 +1022,8 @@ mod scaler_tests {
         let item = ScalerItem::new(None, 0, 10, t, 1, true, None, &mut scalers);
 
         let raw = item.to_raw();
-        let recons: Option<ScalerItem> = raw.to_specific(RingVersion::V11);
-        assert!(recons.is_some());
+        let recons: Result<ScalerItem, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert!(recons.is_ok());
         let recons = recons.unwrap(); // The scaler item itself:
 
         assert!(recons.get_body_header().is_none());
@@ -851,8 +1051,8 @@ mod scaler_tests {
         let item = ScalerItem::new(Some(bh), 0, 10, t, 1, true, None, &mut scalers);
 
         let raw = item.to_raw();
-        let recons: Option<ScalerItem> = raw.to_specific(RingVersion::V11);
-        assert!(recons.is_some());
+        let recons: Result<ScalerItem, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert!(recons.is_ok());
         let recons = recons.unwrap();
 
         assert!(recons.get_body_header().is_some());
@@ -885,8 +1085,8 @@ mod scaler_tests {
         let item = ScalerItem::new(Some(bh), 0, 10, t, 1, true, Some(5), &mut scalers);
 
         let raw = item.to_raw();
-        let recons: Option<ScalerItem> = raw.to_specific(RingVersion::V12);
-        assert!(recons.is_some());
+        let recons: Result<ScalerItem, RingItemError> = raw.to_specific(RingVersion::V12);
+        assert!(recons.is_ok());
         let recons = recons.unwrap();
 
         assert!(recons.get_body_header().is_some());
@@ -915,9 +1115,9 @@ mod scaler_tests {
         let item = ScalerItem::new(None, 0, 10, t, 1, true, None, &mut scalers.clone());
 
         let raw = item.to_raw();
-        let recons: Option<ScalerItem> = raw.to_specific(RingVersion::V11);
+        let recons: Result<ScalerItem, RingItemError> = raw.to_specific(RingVersion::V11);
 
-        assert!(recons.is_some());
+        assert!(recons.is_ok());
         let recons = recons.unwrap(); // The scaler item itself:
 
         assert!(recons.get_body_header().is_none());
@@ -949,8 +1149,8 @@ mod scaler_tests {
 
         let raw = item.to_raw();
 
-        let recons: Option<ScalerItem> = raw.to_specific(RingVersion::V11);
-        assert!(recons.is_some());
+        let recons: Result<ScalerItem, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert!(recons.is_ok());
         let recons = recons.unwrap(); // The scaler item itself:
 
         assert!(recons.get_body_header().is_some());
@@ -984,9 +1184,9 @@ mod scaler_tests {
         let item = ScalerItem::new(Some(bh), 0, 10, t, 1, true, Some(5), &mut scalers.clone());
 
         let raw = item.to_raw();
-        let recons: Option<ScalerItem> = raw.to_specific(RingVersion::V12);
+        let recons: Result<ScalerItem, RingItemError> = raw.to_specific(RingVersion::V12);
 
-        assert!(recons.is_some());
+        assert!(recons.is_ok());
         let recons = recons.unwrap(); // The scaler item itself:
 
         assert!(recons.get_body_header().is_some());
@@ -1014,9 +1214,203 @@ mod scaler_tests {
         // Give none if the type is wrong:
 
         let raw = RingItem::new(PERIODIC_SCALERS + 1); // bad type.
-        let rcons: Option<ScalerItem> = raw.to_specific(RingVersion::V11);
-        assert!(rcons.is_none());
-        let rcons: Option<ScalerItem> = raw.to_specific(RingVersion::V12);
-        assert!(rcons.is_none());
+        let rcons: Result<ScalerItem, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert_eq!(
+            Some(RingItemError::UnexpectedItemType {
+                expected: PERIODIC_SCALERS,
+                found: PERIODIC_SCALERS + 1
+            }),
+            rcons.err()
+        );
+        let rcons: Result<ScalerItem, RingItemError> = raw.to_specific(RingVersion::V12);
+        assert_eq!(
+            Some(RingItemError::UnexpectedItemType {
+                expected: PERIODIC_SCALERS,
+                found: PERIODIC_SCALERS + 1
+            }),
+            rcons.err()
+        );
+    }
+    #[test]
+    fn from_raw_8() {
+        // Fewer scalers than the item claims:
+
+        let mut raw = RingItem::new(PERIODIC_SCALERS);
+        raw.add(0_u32).add(10_u32).add(0_u32).add(1_u32); // start, end, stamp, divisor
+        raw.add(4_u32).add(1_u32); // 4 incremental scalers
+        raw.add(1_u32).add(2_u32); // ...but only 2 of them.
+        let rcons: Result<ScalerItem, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert_eq!(
+            Some(RingItemError::PayloadTooShort {
+                needed: 36,
+                available: 32
+            }),
+            rcons.err()
+        );
+
+        // Missing fixed fields:
+
+        raw.payload_mut().truncate(12);
+        let rcons: Result<ScalerItem, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert_eq!(
+            Some(RingItemError::PayloadTooShort {
+                needed: 16,
+                available: 12
+            }),
+            rcons.err()
+        );
+    }
+    #[test]
+    fn names_1() {
+        let mut scalers: Vec<u32> = (0..8).collect();
+        let mut item = ScalerItem::new(None, 0, 10, SystemTime::now(), 1, true, None, &mut scalers);
+        assert!(item.channel_name(3).is_none());
+
+        item.apply_names(&["beam", "clock", "trigger", "detector_1"]);
+        assert_eq!(Some("detector_1"), item.channel_name(3));
+        assert_eq!(Some("beam"), item.channel_name(0));
+        assert!(item.channel_name(4).is_none());
+
+        item.set_channel_name(7, "live").unwrap();
+        assert_eq!(Some("live"), item.channel_name(7));
+        assert!(item.channel_name(6).is_none());
+        assert!(item.set_channel_name(8, "nosuch").is_err());
+
+        let text = format!("{}", item);
+        assert!(text.contains("detector_1: 3 counts"));
+        assert!(text.contains("    4 counts"));
+
+        // Extra names are ignored:
+
+        item.apply_names(&["a", "b", "c", "d", "e", "f", "g", "h", "i"]);
+        assert_eq!(Some("h"), item.channel_name(7));
+        assert!(item.channel_name(8).is_none());
+    }
+}
+#[cfg(test)]
+mod accumulator_tests {
+    use crate::ring_items::scaler_item::*;
+    use std::time::*;
+
+    fn make_item(start: u32, end: u32, incremental: bool, scalers: &[u32]) -> ScalerItem {
+        let mut scalers = scalers.to_vec();
+        ScalerItem::new(
+            None,
+            start,
+            end,
+            SystemTime::now(),
+            1,
+            incremental,
+            None,
+            &mut scalers,
+        )
+    }
+
+    #[test]
+    fn new_1() {
+        let acc = ScalerAccumulator::new();
+        assert!(acc.is_empty());
+        assert_eq!(0, acc.item_count());
+        assert!(acc.get_total(0).is_none());
+        assert!(acc.rate(0).is_none());
+    }
+    #[test]
+    fn ingest_1() {
+        // Three incremental items:
+
+        let mut acc = ScalerAccumulator::new();
+        acc.ingest(&make_item(0, 10, true, &[100, 200]));
+        acc.ingest(&make_item(10, 20, true, &[100, 300]));
+        acc.ingest(&make_item(20, 30, true, &[100, 400]));
+
+        assert_eq!(3, acc.item_count());
+        assert_eq!(2, acc.len());
+        assert_eq!(Some(300), acc.get_total(0));
+        assert_eq!(Some(900), acc.get_total(1));
+        assert!(acc.get_total(2).is_none());
+
+        assert_eq!(30.0, acc.elapsed_secs());
+        assert_eq!(Some(10.0), acc.rate(0));
+        assert_eq!(Some(30.0), acc.rate(1));
+        assert!(acc.rate(2).is_none());
+    }
+    #[test]
+    fn ingest_2() {
+        // Three cumulative items:
+
+        let mut acc = ScalerAccumulator::new();
+        acc.ingest(&make_item(0, 10, false, &[100, 200]));
+        acc.ingest(&make_item(0, 20, false, &[200, 500]));
+        acc.ingest(&make_item(0, 30, false, &[300, 900]));
+
+        assert_eq!(Some(300), acc.get_total(0));
+        assert_eq!(Some(900), acc.get_total(1));
+        assert_eq!(Some(10.0), acc.rate(0));
+        assert_eq!(Some(30.0), acc.rate(1));
+    }
+    #[test]
+    fn ingest_3() {
+        // Totals don't overflow u32 and grow with item size:
+
+        let mut acc = ScalerAccumulator::new();
+        acc.ingest(&make_item(0, 10, true, &[u32::MAX]));
+        acc.ingest(&make_item(10, 20, true, &[u32::MAX, 1]));
+        assert_eq!(Some(2 * u32::MAX as u64), acc.get_total(0));
+        assert_eq!(Some(1), acc.get_total(1));
+
+        acc.reset();
+        assert!(acc.is_empty());
+        assert_eq!(0, acc.item_count());
+    }
+}
+#[cfg(test)]
+mod scaler_builder_tests {
+    use crate::ring_items::scaler_item::*;
+    use crate::ring_items::*;
+
+    #[test]
+    fn build_1() {
+        let item = ScalerItemBuilder::new()
+            .body_header(0x123456789, 2, 0)
+            .interval(10, 20)
+            .time_divisor(2)
+            .incremental(false)
+            .original_sid(7)
+            .add_scaler(1)
+            .add_scalers(&[2, 3, 4])
+            .build()
+            .unwrap();
+        let raw = item.to_raw();
+        assert_eq!(PERIODIC_SCALERS, raw.type_id());
+
+        let item: ScalerItem = raw.to_specific(RingVersion::V12).unwrap();
+        let bh = item.get_body_header().unwrap();
+        assert_eq!(0x123456789, bh.timestamp);
+        assert_eq!(2, bh.source_id);
+        assert_eq!(10, item.get_start_offset());
+        assert_eq!(20, item.get_end_offset());
+        assert_eq!(10.0, item.get_end_secs());
+        assert!(!item.is_incremental());
+        assert_eq!(Some(7), item.original_sid());
+        assert_eq!(&vec![1, 2, 3, 4], item.get_scaler_values());
+    }
+    #[test]
+    fn build_2() {
+        // Defaults and validation:
+
+        let item = ScalerItemBuilder::new().interval(0, 10).build().unwrap();
+        let item: ScalerItem = item.to_raw().to_specific(RingVersion::V11).unwrap();
+        assert!(item.get_body_header().is_none());
+        assert!(item.is_incremental());
+        assert_eq!(10.0, item.get_end_secs());
+        assert_eq!(0, item.len());
+
+        assert!(ScalerItemBuilder::new().build().is_err());
+        assert!(ScalerItemBuilder::new().interval(10, 0).build().is_err());
+        assert!(ScalerItemBuilder::new()
+            .interval(0, 10)
+            .time_divisor(0)
+            .build()
+            .is_err());
     }
 }
@@ -5,6 +5,23 @@ use std::time;
 /// provide support for state change items.
 /// these are actually four different item types.
 
+/// Size of the title field in state change items.  This includes
+/// the null terminator so titles can be at most TITLE_MAXSIZE-1
+/// characters long.
+pub const TITLE_MAXSIZE: usize = 80;
+
+// Truncate a title so that it fits in the title field.  The truncation
+// is done at a character boundary so the result may be a bit shorter
+// for titles with multibyte characters.
+
+fn truncate_title(title: &str) -> String {
+    let mut end = title.len().min(TITLE_MAXSIZE - 1);
+    while !title.is_char_boundary(end) {
+        end -= 1;
+    }
+    String::from(&title[0..end])
+}
+
 /// Types of run state transitions in rustly form:
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum StateChangeType {
@@ -152,6 +169,97 @@ impl StateChange {
     pub fn original_sid(&self) -> Option<u32> {
         self.original_sid
     }
+
+    // setters - these support building items to write:
+
+    /// Set the run title.  Titles longer than will fit in a state change
+    /// item (TITLE_MAXSIZE-1 characters) are truncated.
+    pub fn set_title(&mut self, title: &str) {
+        self.run_title = truncate_title(title);
+    }
+    pub fn set_run_number(&mut self, n: u32) {
+        self.run_number = n;
+    }
+}
+
+///
+/// StateChangeBuilder builds state change items by method chaining.
+/// The run number is required.  The title is empty, the time offset
+/// is 0 with divisor 1 unless set and the absolute time is the
+/// time at which the item is built.
+///
+pub struct StateChangeBuilder {
+    change_type: StateChangeType,
+    body_header: Option<ring_items::BodyHeader>,
+    run_number: Option<u32>,
+    time_offset: u32,
+    offset_divisor: u32,
+    run_title: String,
+    original_sid: Option<u32>,
+}
+
+impl StateChangeBuilder {
+    pub fn new(change_type: StateChangeType) -> StateChangeBuilder {
+        StateChangeBuilder {
+            change_type,
+            body_header: None,
+            run_number: None,
+            time_offset: 0,
+            offset_divisor: 1,
+            run_title: String::new(),
+            original_sid: None,
+        }
+    }
+    pub fn body_header(&mut self, timestamp: u64, source_id: u32, barrier: u32) -> &mut Self {
+        self.body_header = Some(ring_items::BodyHeader::new(timestamp, source_id, barrier));
+        self
+    }
+    pub fn run_number(&mut self, run: u32) -> &mut Self {
+        self.run_number = Some(run);
+        self
+    }
+    pub fn time_offset(&mut self, offset: u32) -> &mut Self {
+        self.time_offset = offset;
+        self
+    }
+    pub fn time_divisor(&mut self, divisor: u32) -> &mut Self {
+        self.offset_divisor = divisor;
+        self
+    }
+    pub fn title(&mut self, title: &str) -> &mut Self {
+        self.run_title = String::from(title);
+        self
+    }
+    /// The original source id is only put in V12 items.
+    pub fn original_sid(&mut self, sid: u32) -> &mut Self {
+        self.original_sid = Some(sid);
+        self
+    }
+    /// Build the item.  Errors if the run number was not set, the
+    /// time divisor is zero or the title won't fit in the item.
+    pub fn build(&self) -> Result<StateChange, String> {
+        let run = self
+            .run_number
+            .ok_or_else(|| String::from("A state change item needs a run number"))?;
+        if self.offset_divisor == 0 {
+            return Err(String::from("The time divisor must not be zero"));
+        }
+        if self.run_title.len() >= TITLE_MAXSIZE {
+            return Err(format!(
+                "Titles can be at most {} bytes long",
+                TITLE_MAXSIZE - 1
+            ));
+        }
+        Ok(StateChange::new(
+            self.change_type,
+            self.body_header,
+            run,
+            self.time_offset,
+            self.offset_divisor,
+            &self.run_title,
+            self.original_sid,
+        ))
+    }
 }
 
 impl fmt::Display for StateChange {
@@ -201,24 +309,22 @@ impl ring_items::ToRaw for StateChange {
             item.add(osid);
         }
 
-        // Need the string as bytes -- truncate to 80 and put in as bytes
-        // with null terminator.
+        // The title is truncated to fit and put in with its null
+        // terminator, then padded out with nulls.
 
-        let mut title = self.run_title.clone();
-        title.truncate(79);
-        let title_bytes = title.into_bytes();
-        item.add_byte_vec(&title_bytes);
-
-        // Pad out with nulls and ensure there's a null terminator
-        // which  is not put in title_bytes by into_bytes.
-        for _i in title_bytes.len()..81 {
+        let title = truncate_title(&self.run_title);
+        item.add_string(&title);
+        for _i in title.len() + 1..81 {
             item.add(0 as u8);
         }
         item
     }
 }
 impl ring_items::FromRaw<StateChange> for ring_items::RingItem {
-    fn to_specific(&self, version: ring_items::RingVersion) -> Option<StateChange> {
+    fn to_specific(
+        &self,
+        version: ring_items::RingVersion,
+    ) -> Result<StateChange, ring_items::RingItemError> {
         let body_header = self.get_bodyheader(); // Option of body header.
         if let Some(type_enum) = StateChange::type_from_type_id(self.type_id()) {
             let mut result = StateChange::new(type_enum, body_header, 0, 0, 1, "", None);
@@ -232,36 +338,39 @@ impl ring_items::FromRaw<StateChange> for ring_items::RingItem {
             // Now we can fetch stuff out of the body:
 
             let payload = self.payload().as_slice();
-            result.run_number =
-                u32::from_ne_bytes(payload[body_pos..body_pos + 4].try_into().unwrap());
-            result.time_offset =
-                u32::from_ne_bytes(payload[body_pos + 4..body_pos + 8].try_into().unwrap());
-            let raw_stamp =
-                u32::from_ne_bytes(payload[body_pos + 8..body_pos + 12].try_into().unwrap());
+            result.run_number = self.payload_field::<u32>(body_pos)?;
+            result.time_offset = self.payload_field::<u32>(body_pos + 4)?;
+            let raw_stamp = self.payload_field::<u32>(body_pos + 8)?;
             result.absolute_time = ring_items::raw_to_systime(raw_stamp);
-            result.offset_divisor =
-                u32::from_ne_bytes(payload[body_pos + 12..body_pos + 16].try_into().unwrap());
+            result.offset_divisor = self.payload_field::<u32>(body_pos + 12)?;
             // Might have an original sid:
 
             let mut title_pos = body_pos + 16;
             if version == ring_items::RingVersion::V12 {
-                result.original_sid = Some(u32::from_ne_bytes(
-                    payload[title_pos..title_pos + 4].try_into().unwrap(),
-                ));
+                result.original_sid = Some(self.payload_field::<u32>(title_pos)?);
                 title_pos = title_pos + 4;
             }
 
-            result.run_title = ring_items::get_c_string(&mut title_pos, &payload);
-            return Some(result);
+            result.run_title = ring_items::read_string(payload, title_pos)
+                .ok_or_else(|| {
+                    ring_items::RingItemError::InvalidPayload(String::from(
+                        "Run title is not a null terminated UTF-8 string",
+                    ))
+                })?
+                .0;
+            Ok(result)
         } else {
-            return None;
+            Err(ring_items::RingItemError::UnexpectedItemType {
+                expected: ring_items::BEGIN_RUN,
+                found: self.type_id(),
+            })
         }
     }
 }
 #[cfg(test)]
 mod state_tests {
+    use crate::ring_items::state_change::*;
     use crate::ring_items::*;
-    use crate::state_change::*;
     use std::mem::size_of;
     use std::time::*;
 
@@ -683,8 +792,8 @@ mod state_tests {
         let t = SystemTime::now();
         let item = StateChange::new(StateChangeType::End, None, 13, 100, 2, "Some title", None);
         let raw = item.to_raw();
-        let recons: Option<StateChange> = raw.to_specific(RingVersion::V11);
-        assert!(recons.is_some());
+        let recons: Result<StateChange, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert!(recons.is_ok());
         let recons = recons.unwrap();
 
         assert_eq!(StateChangeType::End, recons.change_type());
@@ -716,8 +825,8 @@ mod state_tests {
             None,
         );
         let raw = item.to_raw();
-        let recons: Option<StateChange> = raw.to_specific(RingVersion::V11);
-        assert!(recons.is_some());
+        let recons: Result<StateChange, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert!(recons.is_ok());
         let recons = recons.unwrap();
 
         assert_eq!(StateChangeType::End, recons.change_type());
@@ -755,8 +864,8 @@ mod state_tests {
         );
 
         let raw = item.to_raw();
-        let recons: Option<StateChange> = raw.to_specific(RingVersion::V12);
-        assert!(recons.is_some());
+        let recons: Result<StateChange, RingItemError> = raw.to_specific(RingVersion::V12);
+        assert!(recons.is_ok());
         let recons = recons.unwrap();
 
         assert_eq!(StateChangeType::End, recons.change_type());
@@ -776,12 +885,174 @@ mod state_tests {
     }
     #[test]
     fn from_raw_4() {
-        // bad type -> None:
+        // bad type -> Err:
 
         let raw = RingItem::new(PHYSICS_EVENT);
-        let recons: Option<StateChange> = raw.to_specific(RingVersion::V11);
-        assert!(recons.is_none());
-        let recons: Option<StateChange> = raw.to_specific(RingVersion::V12);
-        assert!(recons.is_none());
+        let recons: Result<StateChange, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert_eq!(
+            Some(RingItemError::UnexpectedItemType {
+                expected: BEGIN_RUN,
+                found: PHYSICS_EVENT
+            }),
+            recons.err()
+        );
+        let recons: Result<StateChange, RingItemError> = raw.to_specific(RingVersion::V12);
+        assert_eq!(
+            Some(RingItemError::UnexpectedItemType {
+                expected: BEGIN_RUN,
+                found: PHYSICS_EVENT
+            }),
+            recons.err()
+        );
+    }
+    #[test]
+    fn from_raw_5() {
+        // Truncated payloads -> Err:
+
+        let mut raw = RingItem::new(BEGIN_RUN);
+        raw.add(12_u32).add(0_u32);
+        let recons: Result<StateChange, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert_eq!(
+            Some(RingItemError::PayloadTooShort {
+                needed: 12,
+                available: 8
+            }),
+            recons.err()
+        );
+
+        // Enough for V11 but V12 needs the original sid:
+
+        raw.add(0_u32).add(1_u32);
+        let recons: Result<StateChange, RingItemError> = raw.to_specific(RingVersion::V12);
+        assert_eq!(
+            Some(RingItemError::PayloadTooShort {
+                needed: 20,
+                available: 16
+            }),
+            recons.err()
+        );
+    }
+    #[test]
+    fn set_1() {
+        let mut item = StateChange::new(StateChangeType::Begin, None, 12, 0, 1, "", None);
+        item.set_title("A new title");
+        item.set_run_number(100);
+        assert_eq!(String::from("A new title"), item.title());
+        assert_eq!(100, item.run_number());
+    }
+    #[test]
+    fn set_2() {
+        // Titles are truncated:
+
+        let mut item = StateChange::new(StateChangeType::Begin, None, 12, 0, 1, "", None);
+        let long_title = "x".repeat(2 * TITLE_MAXSIZE);
+        item.set_title(&long_title);
+        assert_eq!(TITLE_MAXSIZE - 1, item.title().len());
+        assert_eq!(long_title[0..TITLE_MAXSIZE - 1], item.title());
+
+        // Truncation respects character boundaries:
+
+        let long_title = "\u{e9}".repeat(TITLE_MAXSIZE);
+        item.set_title(&long_title);
+        assert_eq!(TITLE_MAXSIZE - 2, item.title().len());
+    }
+    #[test]
+    fn set_3() {
+        // V11 without body header round trips the set values:
+
+        let mut item = StateChange::new(StateChangeType::Begin, None, 0, 0, 1, "", None);
+        item.set_title(&"y".repeat(100));
+        item.set_run_number(1234);
+
+        let raw = item.to_raw();
+        assert_eq!(BEGIN_RUN, raw.type_id());
+        assert!(!raw.has_body_header());
+        let recons: StateChange = raw.to_specific(RingVersion::V11).unwrap();
+        assert_eq!(1234, recons.run_number());
+        assert_eq!("y".repeat(TITLE_MAXSIZE - 1), recons.title());
+        assert!(recons.original_sid().is_none());
+    }
+    #[test]
+    fn set_4() {
+        // V12 with a body header:
+
+        let bh = BodyHeader {
+            timestamp: 0x123456789abcdef,
+            source_id: 2,
+            barrier_type: 1,
+        };
+        let mut item = StateChange::new(StateChangeType::Begin, Some(bh), 0, 0, 1, "", Some(3));
+        item.set_title("Run title");
+        item.set_run_number(5678);
+
+        let raw = item.to_raw();
+        assert_eq!(BEGIN_RUN, raw.type_id());
+        assert!(raw.has_body_header());
+        let recons: StateChange = raw.to_specific(RingVersion::V12).unwrap();
+        assert_eq!(5678, recons.run_number());
+        assert_eq!(String::from("Run title"), recons.title());
+        assert_eq!(bh.timestamp, recons.body_header().unwrap().timestamp);
+        assert_eq!(Some(3), recons.original_sid());
+    }
+}
+#[cfg(test)]
+mod state_builder_tests {
+    use crate::ring_items::state_change::*;
+    use crate::ring_items::*;
+
+    #[test]
+    fn build_1() {
+        let item = StateChangeBuilder::new(StateChangeType::End)
+            .body_header(0x123456789, 2, 1)
+            .run_number(12)
+            .time_offset(100)
+            .time_divisor(2)
+            .title("The run title")
+            .original_sid(3)
+            .build()
+            .unwrap();
+        let raw = item.to_raw();
+        assert_eq!(END_RUN, raw.type_id());
+
+        let item: StateChange = raw.to_specific(RingVersion::V12).unwrap();
+        assert_eq!(StateChangeType::End, item.change_type());
+        let bh = item.body_header().unwrap();
+        assert_eq!(0x123456789, bh.timestamp);
+        assert_eq!(2, bh.source_id);
+        assert_eq!(1, bh.barrier_type);
+        assert_eq!(12, item.run_number());
+        assert_eq!(100, item.raw_time_offset());
+        assert_eq!(2, item.offset_divisor());
+        assert_eq!("The run title", item.title());
+        assert_eq!(Some(3), item.original_sid());
+    }
+    #[test]
+    fn build_2() {
+        // Defaults and validation:
+
+        let item = StateChangeBuilder::new(StateChangeType::Begin)
+            .run_number(1)
+            .build()
+            .unwrap();
+        let item: StateChange = item.to_raw().to_specific(RingVersion::V11).unwrap();
+        assert_eq!(StateChangeType::Begin, item.change_type());
+        assert!(!item.has_body_header());
+        assert_eq!(0, item.raw_time_offset());
+        assert_eq!(1, item.offset_divisor());
+        assert_eq!("", item.title());
+
+        assert!(StateChangeBuilder::new(StateChangeType::Begin)
+            .build()
+            .is_err());
+        assert!(StateChangeBuilder::new(StateChangeType::Begin)
+            .run_number(1)
+            .time_divisor(0)
+            .build()
+            .is_err());
+        assert!(StateChangeBuilder::new(StateChangeType::Begin)
+            .run_number(1)
+            .title(&"x".repeat(TITLE_MAXSIZE))
+            .build()
+            .is_err());
     }
 }
@@ -103,6 +103,13 @@ impl TextItem {
     pub fn get_strings(&self) -> Vec<String> {
         self.strings.clone()
     }
+    /// The strings without copying them:
+    pub fn strings(&self) -> &[String] {
+        &self.strings
+    }
+    pub fn string_count(&self) -> usize {
+        self.strings.len()
+    }
     pub fn get_string(&self, index: usize) -> Option<String> {
         if index < self.strings.len() {
             Some(self.strings[index].clone())
@@ -125,6 +132,73 @@ impl TextItem {
     }
 }
 
+///
+/// TextItemBuilder accumulates strings for a text item and
+/// then builds the raw ring item.  The time offset is 0 with divisor 1
+/// unless set, and the absolute time is the time at which the
+/// item is built.
+///
+pub struct TextItemBuilder {
+    item_type: TextItemType,
+    body_header: Option<ring_items::BodyHeader>,
+    time_offset: u32,
+    offset_divisor: u32,
+    original_sid: Option<u32>,
+    strings: Vec<String>,
+}
+
+impl TextItemBuilder {
+    pub fn new(text_type: TextItemType) -> TextItemBuilder {
+        TextItemBuilder {
+            item_type: text_type,
+            body_header: None,
+            time_offset: 0,
+            offset_divisor: 1,
+            original_sid: None,
+            strings: Vec::new(),
+        }
+    }
+    pub fn add_string(&mut self, s: &str) -> &mut Self {
+        self.strings.push(String::from(s));
+        self
+    }
+    pub fn add_strings(&mut self, ss: &[&str]) -> &mut Self {
+        for s in ss {
+            self.add_string(s);
+        }
+        self
+    }
+    pub fn body_header(&mut self, header: &ring_items::BodyHeader) -> &mut Self {
+        self.body_header = Some(*header);
+        self
+    }
+    pub fn time_offset(&mut self, offset: u32, divisor: u32) -> &mut Self {
+        self.time_offset = offset;
+        self.offset_divisor = divisor;
+        self
+    }
+    /// The original source id is only put in V12 items.
+    pub fn original_sid(&mut self, sid: u32) -> &mut Self {
+        self.original_sid = Some(sid);
+        self
+    }
+    /// Build the TextItem rather than the raw item.
+    pub fn build_item(&self) -> TextItem {
+        TextItem::new(
+            self.item_type,
+            self.body_header,
+            self.time_offset,
+            time::SystemTime::now(),
+            self.offset_divisor,
+            self.original_sid,
+            &self.strings,
+        )
+    }
+    pub fn build(&self) -> ring_items::RingItem {
+        ring_items::ToRaw::to_raw(&self.build_item())
+    }
+}
+
 impl fmt::Display for TextItem {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "Text Item: \n").unwrap();
@@ -188,7 +262,10 @@ impl ring_items::ToRaw for TextItem {
 }
 
 impl ring_items::FromRaw<TextItem> for ring_items::RingItem {
-    fn to_specific(&self, vers: ring_items::RingVersion) -> Option<TextItem> {
+    fn to_specific(
+        &self,
+        vers: ring_items::RingVersion,
+    ) -> Result<TextItem, ring_items::RingItemError> {
         // figure out the correct value for the
         // type:
 
@@ -212,40 +289,38 @@ impl ring_items::FromRaw<TextItem> for ring_items::RingItem {
             } else {
                 0
             };
-            let p = self.payload().as_slice();
-            result.time_offset = u32::from_ne_bytes(p[offset..offset + 4].try_into().unwrap());
-            result.absolute_time = ring_items::raw_to_systime(u32::from_ne_bytes(
-                p[offset + 4..offset + 8].try_into().unwrap(),
-            ));
-            let num_string = u32::from_ne_bytes(p[offset + 8..offset + 12].try_into().unwrap());
-            result.offset_divisor =
-                u32::from_ne_bytes(p[offset + 12..offset + 16].try_into().unwrap());
+            result.time_offset = self.payload_field::<u32>(offset)?;
+            result.absolute_time =
+                ring_items::raw_to_systime(self.payload_field::<u32>(offset + 4)?);
+            let num_string = self.payload_field::<u32>(offset + 8)?;
+            result.offset_divisor = self.payload_field::<u32>(offset + 12)?;
             let mut offset = offset + 16;
             if vers == ring_items::RingVersion::V12 {
-                result.original_sid = Some(u32::from_ne_bytes(
-                    p[offset..offset + 4].try_into().unwrap(),
-                ));
+                result.original_sid = Some(self.payload_field::<u32>(offset)?);
                 offset = offset + 4;
             }
             // offset is the offset of the first string.
 
             for _ in 0..num_string {
-                result
-                    .strings
-                    .push(ring_items::get_c_string(&mut offset, &p));
+                let (s, next) = self.payload_string(offset)?;
+                result.strings.push(s);
+                offset = next;
             }
 
-            Some(result)
+            Ok(result)
         } else {
-            None
+            Err(ring_items::RingItemError::UnexpectedItemType {
+                expected: ring_items::PACKET_TYPES,
+                found: self.type_id(),
+            })
         }
     }
 }
 
 #[cfg(test)]
 mod text_tests {
+    use crate::ring_items::text_item::*;
     use crate::ring_items::*;
-    use crate::text_item::*;
     use std::mem::size_of;
     use std::time::SystemTime;
 
@@ -1093,8 +1168,8 @@ mod text_tests {
         let t = SystemTime::now();
         let item = TextItem::new(TextItemType::PacketTypes, None, 10, t, 1, None, &strings);
         let raw = item.to_raw();
-        let recons: Option<TextItem> = raw.to_specific(RingVersion::V11);
-        assert!(recons.is_some());
+        let recons: Result<TextItem, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert!(recons.is_ok());
         let recons = recons.unwrap();
 
         assert_eq!(item.get_item_type(), recons.get_item_type());
@@ -1130,8 +1205,8 @@ mod text_tests {
         );
 
         let raw = item.to_raw();
-        let recons: Option<TextItem> = raw.to_specific(RingVersion::V11);
-        assert!(recons.is_some());
+        let recons: Result<TextItem, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert!(recons.is_ok());
         let recons = recons.unwrap();
 
         assert_eq!(item.get_item_type(), recons.get_item_type());
@@ -1171,8 +1246,8 @@ mod text_tests {
             &strings,
         );
         let raw = item.to_raw();
-        let recons: Option<TextItem> = raw.to_specific(RingVersion::V12);
-        assert!(recons.is_some());
+        let recons: Result<TextItem, RingItemError> = raw.to_specific(RingVersion::V12);
+        assert!(recons.is_ok());
         let recons = recons.unwrap();
 
         assert_eq!(item.get_item_type(), recons.get_item_type());
@@ -1214,9 +1289,9 @@ mod text_tests {
             &strings,
         );
         let raw = item.to_raw();
-        let recons: Option<TextItem> = raw.to_specific(RingVersion::V11);
+        let recons: Result<TextItem, RingItemError> = raw.to_specific(RingVersion::V11);
 
-        assert!(recons.is_some());
+        assert!(recons.is_ok());
         let recons = recons.unwrap();
 
         assert_eq!(item.get_item_type(), recons.get_item_type());
@@ -1263,9 +1338,9 @@ mod text_tests {
             &strings,
         );
         let raw = item.to_raw();
-        let recons: Option<TextItem> = raw.to_specific(RingVersion::V11);
+        let recons: Result<TextItem, RingItemError> = raw.to_specific(RingVersion::V11);
 
-        assert!(recons.is_some());
+        assert!(recons.is_ok());
         let recons = recons.unwrap();
 
         assert_eq!(item.get_item_type(), recons.get_item_type());
@@ -1318,9 +1393,9 @@ mod text_tests {
         );
 
         let raw = item.to_raw();
-        let recons: Option<TextItem> = raw.to_specific(RingVersion::V12);
+        let recons: Result<TextItem, RingItemError> = raw.to_specific(RingVersion::V12);
 
-        assert!(recons.is_some());
+        assert!(recons.is_ok());
         let recons = recons.unwrap();
 
         assert_eq!(item.get_item_type(), recons.get_item_type());
@@ -1352,9 +1427,129 @@ mod text_tests {
         // Must be a valid item type:
 
         let raw = RingItem::new(BEGIN_RUN); // not a text item.
-        let recons: Option<TextItem> = raw.to_specific(RingVersion::V11);
-        assert!(recons.is_none());
-        let recons: Option<TextItem> = raw.to_specific(RingVersion::V12);
-        assert!(recons.is_none());
+        let recons: Result<TextItem, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert_eq!(
+            Some(RingItemError::UnexpectedItemType {
+                expected: PACKET_TYPES,
+                found: BEGIN_RUN
+            }),
+            recons.err()
+        );
+        let recons: Result<TextItem, RingItemError> = raw.to_specific(RingVersion::V12);
+        assert_eq!(
+            Some(RingItemError::UnexpectedItemType {
+                expected: PACKET_TYPES,
+                found: BEGIN_RUN
+            }),
+            recons.err()
+        );
+    }
+    #[test]
+    fn from_raw_8() {
+        // Truncated payloads -> Err:
+
+        let mut raw = RingItem::new(PACKET_TYPES);
+        raw.add(0_u32).add(0_u32).add(0_u32);
+        let recons: Result<TextItem, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert_eq!(
+            Some(RingItemError::PayloadTooShort {
+                needed: 16,
+                available: 12
+            }),
+            recons.err()
+        );
+
+        raw.add(1_u32); // Enough for V11 but not V12.
+        let recons: Result<TextItem, RingItemError> = raw.to_specific(RingVersion::V12);
+        assert_eq!(
+            Some(RingItemError::PayloadTooShort {
+                needed: 20,
+                available: 16
+            }),
+            recons.err()
+        );
+    }
+    #[test]
+    fn from_raw_9() {
+        // Strings that are not UTF-8 or are not terminated -> Err:
+
+        let mut raw = RingItem::new(PACKET_TYPES);
+        raw.add(0_u32).add(0_u32).add(2_u32).add(1_u32);
+        raw.add_byte_vec(&b"ADC\0".to_vec());
+        raw.add_byte_vec(&vec![0xc3, 0x28, 0]);
+        let recons: Result<TextItem, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert!(matches!(
+            recons.err(),
+            Some(RingItemError::InvalidPayload(_))
+        ));
+
+        let mut raw = RingItem::new(PACKET_TYPES);
+        raw.add(0_u32).add(0_u32).add(1_u32).add(1_u32);
+        raw.add_byte_vec(&b"ADC".to_vec());
+        let recons: Result<TextItem, RingItemError> = raw.to_specific(RingVersion::V11);
+        assert_eq!(
+            Some(RingItemError::PayloadTooShort {
+                needed: 20,
+                available: 19
+            }),
+            recons.err()
+        );
+    }
+}
+#[cfg(test)]
+mod text_builder_tests {
+    use crate::ring_items::text_item::*;
+    use crate::ring_items::*;
+
+    #[test]
+    fn strings_1() {
+        let item = TextItemBuilder::new(TextItemType::MonitoredVariables)
+            .add_string("one")
+            .add_string("two")
+            .build_item();
+        assert_eq!(2, item.string_count());
+        assert_eq!([String::from("one"), String::from("two")], item.strings());
+    }
+    #[test]
+    fn build_1() {
+        // Five packet type strings round trip through the raw item:
+
+        let strings = ["ADC", "TDC", "QDC", "Scaler", "Trigger"];
+        let raw = TextItemBuilder::new(TextItemType::PacketTypes)
+            .add_string(strings[0])
+            .add_strings(&strings[1..])
+            .build();
+        assert_eq!(PACKET_TYPES, raw.type_id());
+        assert!(!raw.has_body_header());
+
+        let item: TextItem = raw.to_specific(RingVersion::V11).unwrap();
+        assert_eq!(TextItemType::PacketTypes, item.get_item_type());
+        assert_eq!(5, item.string_count());
+        let expected: Vec<String> = strings.iter().map(|s| String::from(*s)).collect();
+        assert_eq!(expected.as_slice(), item.strings());
+    }
+    #[test]
+    fn build_2() {
+        // V12 with body header:
+
+        let bh = BodyHeader {
+            timestamp: 0x123456789abcdef,
+            source_id: 2,
+            barrier_type: 0,
+        };
+        let raw = TextItemBuilder::new(TextItemType::MonitoredVariables)
+            .add_strings(&["set a 1", "set b 2"])
+            .body_header(&bh)
+            .time_offset(10, 2)
+            .original_sid(3)
+            .build();
+        assert_eq!(MONITORED_VARIABLES, raw.type_id());
+
+        let item: TextItem = raw.to_specific(RingVersion::V12).unwrap();
+        assert_eq!(bh.timestamp, item.get_body_header().unwrap().timestamp);
+        assert_eq!(5.0, item.get_offset_secs());
+        assert_eq!(Some(3), item.get_original_sid());
+        assert_eq!(2, item.string_count());
+        assert_eq!("set b 2", item.strings()[1]);
     }
 }
@@ -56,7 +56,92 @@ impl PhysicsEventCountItem {
     pub fn get_absolute_time(&self) -> time::SystemTime {
         self.absolute_time
     }
+    // Offset time computed in double precision:
+    fn offset_seconds(&self) -> f64 {
+        self.time_offset as f64 / self.time_divisor as f64
+    }
+}
+///
+/// The rate, in events per second, between two successive event count
+/// items:  the change in event count over the change in time offset.
+/// NAN is returned if b is not later than a.
+///
+pub fn event_rate(a: &PhysicsEventCountItem, b: &PhysicsEventCountItem) -> f64 {
+    let dt = b.offset_seconds() - a.offset_seconds();
+    if dt > 0.0 {
+        (b.event_count as f64 - a.event_count as f64) / dt
+    } else {
+        f64::NAN
+    }
+}
+///
+/// The trigger rate between two successive event count items.  The
+/// items count triggers so this is computed exactly as event_rate is;
+/// it's provided so code that computes sampling fractions reads naturally.
+///
+pub fn trigger_rate(a: &PhysicsEventCountItem, b: &PhysicsEventCountItem) -> f64 {
+    event_rate(a, b)
 }
+///
+/// PhysicsEventCountItemBuilder builds event count items by method
+/// chaining.  The event count is required;  the time offset is 0 with
+/// divisor 1 unless set and the absolute time is the time at which the
+/// item is built.
+///
+#[derive(Default)]
+pub struct PhysicsEventCountItemBuilder {
+    body_header: Option<ring_items::BodyHeader>,
+    time_offset: u32,
+    time_divisor: Option<u32>,
+    original_sid: Option<u32>,
+    event_count: Option<u64>,
+}
+
+impl PhysicsEventCountItemBuilder {
+    pub fn new() -> PhysicsEventCountItemBuilder {
+        PhysicsEventCountItemBuilder::default()
+    }
+    pub fn body_header(&mut self, timestamp: u64, source_id: u32, barrier: u32) -> &mut Self {
+        self.body_header = Some(ring_items::BodyHeader::new(timestamp, source_id, barrier));
+        self
+    }
+    pub fn time_offset(&mut self, offset: u32) -> &mut Self {
+        self.time_offset = offset;
+        self
+    }
+    pub fn time_divisor(&mut self, divisor: u32) -> &mut Self {
+        self.time_divisor = Some(divisor);
+        self
+    }
+    /// The original source id is only put in V12 items.
+    pub fn original_sid(&mut self, sid: u32) -> &mut Self {
+        self.original_sid = Some(sid);
+        self
+    }
+    pub fn event_count(&mut self, count: u64) -> &mut Self {
+        self.event_count = Some(count);
+        self
+    }
+    /// Build the item.  Errors if the event count was not set or
+    /// the time divisor is zero.
+    pub fn build(&self) -> Result<PhysicsEventCountItem, String> {
+        let event_count = self
+            .event_count
+            .ok_or_else(|| String::from("An event count item needs an event count"))?;
+        let divisor = self.time_divisor.unwrap_or(1);
+        if divisor == 0 {
+            return Err(String::from("The time divisor must not be zero"));
+        }
+        Ok(PhysicsEventCountItem::new(
+            self.body_header,
+            self.time_offset,
+            divisor,
+            self.original_sid,
+            event_count,
+        ))
+    }
+}
+
 impl fmt::Display for PhysicsEventCountItem {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "Trigger count information: \n").unwrap();
@@ -104,45 +189,34 @@ impl ring_items::ToRaw for PhysicsEventCountItem {
 }
 
 impl ring_items::FromRaw<PhysicsEventCountItem> for ring_items::RingItem {
-    fn to_specific(&self, version: ring_items::RingVersion) -> Option<PhysicsEventCountItem> {
-        if self.type_id() == ring_items::PHYSICS_EVENT_COUNT {
-            let mut result = PhysicsEventCountItem::new(None, 0, 1, None, 0);
-            result.body_header = self.get_bodyheader();
-            let offset = if let Some(_) = result.body_header {
-                ring_items::body_header_size()
-            } else {
-                0
-            };
-            let payload = self.payload().as_slice();
-            result.time_offset =
-                u32::from_ne_bytes(payload[offset..offset + 4].try_into().unwrap());
-            result.time_divisor =
-                u32::from_ne_bytes(payload[offset + 4..offset + 8].try_into().unwrap());
-            result.absolute_time = ring_items::raw_to_systime(
-                u32::from_ne_bytes(payload[offset + 8..offset + 12].try_into().unwrap())
-                    .try_into()
-                    .unwrap(),
-            );
-            if version == ring_items::RingVersion::V11 {
-                result.event_count =
-                    u64::from_ne_bytes(payload[offset + 12..offset + 20].try_into().unwrap());
-            } else {
-                result.original_sid = Some(u32::from_ne_bytes(
-                    payload[offset + 12..offset + 16].try_into().unwrap(),
-                ));
-                result.event_count =
-                    u64::from_ne_bytes(payload[offset + 16..offset + 24].try_into().unwrap());
-            }
-            Some(result)
+    fn to_specific(
+        &self,
+        version: ring_items::RingVersion,
+    ) -> Result<PhysicsEventCountItem, ring_items::RingItemError> {
+        self.check_type(ring_items::PHYSICS_EVENT_COUNT)?;
+        let mut result = PhysicsEventCountItem::new(None, 0, 1, None, 0);
+        result.body_header = self.get_bodyheader();
+        let offset = if result.body_header.is_some() {
+            ring_items::body_header_size()
         } else {
-            None
+            0
+        };
+        result.time_offset = self.payload_field::<u32>(offset)?;
+        result.time_divisor = self.payload_field::<u32>(offset + 4)?;
+        result.absolute_time = ring_items::raw_to_systime(self.payload_field::<u32>(offset + 8)?);
+        if version == ring_items::RingVersion::V11 {
+            result.event_count = self.payload_field::<u64>(offset + 12)?;
+        } else {
+            result.original_sid = Some(self.payload_field::<u32>(offset + 12)?);
+            result.event_count = self.payload_field::<u64>(offset + 16)?;
         }
+        Ok(result)
     }
 }
 #[cfg(test)]
 mod triggers_test {
+    use crate::ring_items::triggers_item::*;
     use crate::ring_items::*;
-    use crate::triggers_item::*;
     use std::mem::size_of;
     use std::time::SystemTime;
 
@@ -396,9 +470,10 @@ mod triggers_test {
         let _t = SystemTime::now();
         let item = PhysicsEventCountItem::new(None, 10, 1, None, 100);
         let raw = item.to_raw();
-        let recons: Option<PhysicsEventCountItem> = raw.to_specific(RingVersion::V11);
+        let recons: Result<PhysicsEventCountItem, RingItemError> =
+            raw.to_specific(RingVersion::V11);
 
-        assert!(recons.is_some());
+        assert!(recons.is_ok());
         let recons = recons.unwrap();
         assert!(recons.get_bodyheader().is_none());
         assert_eq!(item.get_timeoffset(), recons.get_timeoffset());
@@ -421,9 +496,10 @@ mod triggers_test {
         let _t = SystemTime::now();
         let item = PhysicsEventCountItem::new(Some(bh), 10, 1, None, 100);
         let raw = item.to_raw();
-        let recons: Option<PhysicsEventCountItem> = raw.to_specific(RingVersion::V11);
+        let recons: Result<PhysicsEventCountItem, RingItemError> =
+            raw.to_specific(RingVersion::V11);
 
-        assert!(recons.is_some());
+        assert!(recons.is_ok());
         let recons = recons.unwrap();
         assert!(recons.get_bodyheader().is_some());
         let ibh = recons.get_bodyheader().unwrap();
@@ -452,9 +528,10 @@ mod triggers_test {
         let _t = SystemTime::now();
         let item = PhysicsEventCountItem::new(Some(bh), 10, 1, Some(5), 100);
         let raw = item.to_raw();
-        let recons: Option<PhysicsEventCountItem> = raw.to_specific(RingVersion::V12);
+        let recons: Result<PhysicsEventCountItem, RingItemError> =
+            raw.to_specific(RingVersion::V12);
 
-        assert!(recons.is_some());
+        assert!(recons.is_ok());
         let recons = recons.unwrap();
         assert!(recons.get_bodyheader().is_some());
         let ibh = recons.get_bodyheader().unwrap();
@@ -480,9 +557,122 @@ mod triggers_test {
         // Invalid conversion:
 
         let raw = RingItem::new(PHYSICS_EVENT_COUNT + 1);
-        let recons: Option<PhysicsEventCountItem> = raw.to_specific(RingVersion::V11);
-        assert!(recons.is_none());
-        let recons: Option<PhysicsEventCountItem> = raw.to_specific(RingVersion::V12);
-        assert!(recons.is_none());
+        let recons: Result<PhysicsEventCountItem, RingItemError> =
+            raw.to_specific(RingVersion::V11);
+        assert_eq!(
+            Some(RingItemError::UnexpectedItemType {
+                expected: PHYSICS_EVENT_COUNT,
+                found: PHYSICS_EVENT_COUNT + 1
+            }),
+            recons.err()
+        );
+        let recons: Result<PhysicsEventCountItem, RingItemError> =
+            raw.to_specific(RingVersion::V12);
+        assert_eq!(
+            Some(RingItemError::UnexpectedItemType {
+                expected: PHYSICS_EVENT_COUNT,
+                found: PHYSICS_EVENT_COUNT + 1
+            }),
+            recons.err()
+        );
+    }
+    #[test]
+    fn from_raw_5() {
+        // Truncated payload (no event count):
+
+        let mut raw = RingItem::new(PHYSICS_EVENT_COUNT);
+        raw.add(0_u32).add(1_u32).add(0_u32).add(0_u32);
+        let recons: Result<PhysicsEventCountItem, RingItemError> =
+            raw.to_specific(RingVersion::V11);
+        assert_eq!(
+            Some(RingItemError::PayloadTooShort {
+                needed: 20,
+                available: 16
+            }),
+            recons.err()
+        );
+        let recons: Result<PhysicsEventCountItem, RingItemError> =
+            raw.to_specific(RingVersion::V12);
+        assert_eq!(
+            Some(RingItemError::PayloadTooShort {
+                needed: 24,
+                available: 16
+            }),
+            recons.err()
+        );
+    }
+    #[test]
+    fn rate_1() {
+        let a = PhysicsEventCountItem::new(None, 10, 1, None, 5000);
+        let b = PhysicsEventCountItem::new(None, 11, 1, None, 6000);
+        assert_eq!(1000.0, event_rate(&a, &b));
+        assert_eq!(1000.0, trigger_rate(&a, &b));
+
+        // Divisors are honored:
+
+        let a = PhysicsEventCountItem::new(None, 20, 2, None, 5000);
+        let b = PhysicsEventCountItem::new(None, 25, 2, None, 6000);
+        assert_eq!(400.0, event_rate(&a, &b));
+    }
+    #[test]
+    fn rate_2() {
+        // No time difference or out of order:
+
+        let a = PhysicsEventCountItem::new(None, 10, 1, None, 5000);
+        let b = PhysicsEventCountItem::new(None, 10, 1, None, 6000);
+        assert!(event_rate(&a, &b).is_nan());
+        assert!(trigger_rate(&a, &b).is_nan());
+        let b = PhysicsEventCountItem::new(None, 9, 1, None, 6000);
+        assert!(event_rate(&a, &b).is_nan());
+    }
+}
+#[cfg(test)]
+mod triggers_builder_tests {
+    use crate::ring_items::triggers_item::*;
+    use crate::ring_items::*;
+
+    #[test]
+    fn build_1() {
+        let item = PhysicsEventCountItemBuilder::new()
+            .body_header(0x123456789, 2, 0)
+            .time_offset(20)
+            .time_divisor(2)
+            .original_sid(5)
+            .event_count(1000)
+            .build()
+            .unwrap();
+        let raw = item.to_raw();
+        assert_eq!(PHYSICS_EVENT_COUNT, raw.type_id());
+
+        let item: PhysicsEventCountItem = raw.to_specific(RingVersion::V12).unwrap();
+        let bh = item.get_bodyheader().unwrap();
+        assert_eq!(0x123456789, bh.timestamp);
+        assert_eq!(2, bh.source_id);
+        assert_eq!(0, bh.barrier_type);
+        assert_eq!(20, item.get_timeoffset());
+        assert_eq!(2, item.get_time_divisor());
+        assert_eq!(Some(5), item.get_original_sid());
+        assert_eq!(1000, item.get_event_count());
+    }
+    #[test]
+    fn build_2() {
+        // Defaults and validation:
+
+        let item = PhysicsEventCountItemBuilder::new()
+            .event_count(10)
+            .build()
+            .unwrap();
+        let item: PhysicsEventCountItem = item.to_raw().to_specific(RingVersion::V11).unwrap();
+        assert!(item.get_bodyheader().is_none());
+        assert_eq!(0, item.get_timeoffset());
+        assert_eq!(1, item.get_time_divisor());
+        assert_eq!(10, item.get_event_count());
+
+        assert!(PhysicsEventCountItemBuilder::new().build().is_err());
+        assert!(PhysicsEventCountItemBuilder::new()
+            .event_count(10)
+            .time_divisor(0)
+            .build()
+            .is_err());
     }
 }
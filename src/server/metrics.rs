@@ -0,0 +1,399 @@
+//!  Prometheus metrics for monitoring the histogrammer.  A
+//!  MetricsStage in the event pipeline fills spectra and records:
+//!
+//!  *  rustogrammer_events_processed_total - events that reached the stage.
+//!  *  rustogrammer_spectra_filled_total{name} - events each spectrum accepted.
+//!  *  rustogrammer_fill_duration_seconds - time taken to fill the spectra
+//!     for an event.
+//!  *  rustogrammer_conditions_evaluated_total{name} - events for which
+//!     each watched condition was evaluated (see ConditionStats).
+//!
+//!  The metrics themselves are thread safe so the pipeline can run
+//!  in one thread while metrics_router serves GET /metrics in the
+//!  Prometheus text format from another.
+//!
+use crate::conditions::{ConditionDictionary, ContainerReference};
+use crate::parameters::FlatEvent;
+use crate::pipeline::EventProcessor;
+use crate::spectra::{SpectrumContainerReference, SpectrumDictionary};
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, IntCounter, IntCounterVec, Opts, Registry, TextEncoder,
+};
+use std::rc::Rc;
+
+///
+/// The metrics and the registry they're exported from.  Clones share
+/// the same metrics.
+///
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    events_processed: IntCounter,
+    spectra_filled: IntCounterVec,
+    fill_duration: Histogram,
+    conditions_evaluated: IntCounterVec,
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        let events_processed = IntCounter::new(
+            "rustogrammer_events_processed_total",
+            "Events processed by the pipeline",
+        )
+        .unwrap();
+        let spectra_filled = IntCounterVec::new(
+            Opts::new(
+                "rustogrammer_spectra_filled_total",
+                "Events accepted by each spectrum",
+            ),
+            &["name"],
+        )
+        .unwrap();
+        let fill_duration = Histogram::with_opts(HistogramOpts::new(
+            "rustogrammer_fill_duration_seconds",
+            "Time to fill the spectra for one event",
+        ))
+        .unwrap();
+        let conditions_evaluated = IntCounterVec::new(
+            Opts::new(
+                "rustogrammer_conditions_evaluated_total",
+                "Events for which each condition was evaluated",
+            ),
+            &["name"],
+        )
+        .unwrap();
+
+        let registry = Registry::new();
+        registry
+            .register(Box::new(events_processed.clone()))
+            .unwrap();
+        registry.register(Box::new(spectra_filled.clone())).unwrap();
+        registry.register(Box::new(fill_duration.clone())).unwrap();
+        registry
+            .register(Box::new(conditions_evaluated.clone()))
+            .unwrap();
+        Metrics {
+            registry,
+            events_processed,
+            spectra_filled,
+            fill_duration,
+            conditions_evaluated,
+        }
+    }
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+    pub fn events_processed(&self) -> u64 {
+        self.events_processed.get()
+    }
+    /// The metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut result = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut result)
+            .expect("Metrics encode");
+        String::from_utf8(result).expect("Metrics are text")
+    }
+}
+
+///
+/// ConditionStats counts the events for which each of a set of
+/// conditions was evaluated.  A condition has a cached value once it
+/// is evaluated so record, called once the event has been processed,
+/// counts the conditions with cached values.  This requires the
+/// caches to be invalidated at each event (e.g. by a
+/// ConditionInvalidator) and conditions that don't cache (True and
+/// False) are never counted.  Deleted conditions are never counted
+/// either.
+///
+pub struct ConditionStats {
+    conditions: Vec<(String, ContainerReference)>,
+    evaluated: Vec<u64>,
+}
+
+impl ConditionStats {
+    pub fn new() -> ConditionStats {
+        ConditionStats {
+            conditions: Vec::new(),
+            evaluated: Vec::new(),
+        }
+    }
+    /// Count the evaluations of a condition.
+    pub fn watch(&mut self, name: &str, cdict: &ConditionDictionary) -> Result<(), String> {
+        let condition = cdict
+            .get(name)
+            .ok_or_else(|| format!("No such gate {}", name))?;
+        self.conditions
+            .push((String::from(name), Rc::downgrade(condition)));
+        self.evaluated.push(0);
+        Ok(())
+    }
+    /// Count the watched conditions that were evaluated for this event.
+    /// on_evaluated is called with the index (in the order the
+    /// conditions were watched) of each of them.
+    pub fn record<F: FnMut(usize)>(&mut self, mut on_evaluated: F) {
+        for (i, (_, condition)) in self.conditions.iter().enumerate() {
+            if let Some(c) = condition.upgrade() {
+                if c.borrow().get_cached_value().is_some() {
+                    self.evaluated[i] += 1;
+                    on_evaluated(i);
+                }
+            }
+        }
+    }
+    /// The names and evaluation counts of the watched conditions.
+    pub fn evaluations(&self) -> Vec<(String, u64)> {
+        self.conditions
+            .iter()
+            .zip(self.evaluated.iter())
+            .map(|((name, _), n)| (name.clone(), *n))
+            .collect()
+    }
+    pub fn reset(&mut self) {
+        self.evaluated.iter_mut().for_each(|n| *n = 0);
+    }
+}
+
+///
+/// MetricsStage fills spectra like a FillStage and updates the
+/// metrics.  The condition evaluation counts include evaluations by
+/// earlier stages.  Prometheus counters never decrease so reset only
+/// resets the ConditionStats.
+///
+/// The stage always passes events to subsequent stages.
+///
+pub struct MetricsStage {
+    metrics: Metrics,
+    spectra: Vec<(SpectrumContainerReference, IntCounter)>,
+    conditions: ConditionStats,
+    condition_counters: Vec<IntCounter>,
+}
+
+impl MetricsStage {
+    /// Create a stage that fills all of the spectra currently in the
+    /// dictionary.
+    pub fn new(metrics: &Metrics, sdict: &SpectrumDictionary) -> MetricsStage {
+        let spectra = sdict
+            .iter()
+            .map(|(name, s)| {
+                (
+                    Rc::downgrade(s),
+                    metrics.spectra_filled.with_label_values(&[name]),
+                )
+            })
+            .collect();
+        MetricsStage {
+            metrics: metrics.clone(),
+            spectra,
+            conditions: ConditionStats::new(),
+            condition_counters: Vec::new(),
+        }
+    }
+    /// Count the evaluations of a condition.
+    pub fn watch_condition(
+        &mut self,
+        name: &str,
+        cdict: &ConditionDictionary,
+    ) -> Result<&mut MetricsStage, String> {
+        self.conditions.watch(name, cdict)?;
+        self.condition_counters
+            .push(self.metrics.conditions_evaluated.with_label_values(&[name]));
+        Ok(self)
+    }
+    pub fn condition_stats(&self) -> &ConditionStats {
+        &self.conditions
+    }
+}
+
+impl EventProcessor for MetricsStage {
+    fn process(&mut self, event: &mut FlatEvent) -> bool {
+        self.metrics.events_processed.inc();
+        let timer = self.metrics.fill_duration.start_timer();
+        self.spectra.retain(|(s, filled)| {
+            if let Some(spectrum) = s.upgrade() {
+                let mut spectrum = spectrum.borrow_mut();
+                if spectrum.check_gate(event) {
+                    spectrum.increment(event);
+                    filled.inc();
+                }
+                true
+            } else {
+                false
+            }
+        });
+        timer.observe_duration();
+
+        let counters = &self.condition_counters;
+        self.conditions.record(|i| counters[i].inc());
+        true
+    }
+    fn reset(&mut self) {
+        self.conditions.reset();
+    }
+}
+
+async fn get_metrics(State(metrics): State<Metrics>) -> impl IntoResponse {
+    (
+        [(
+            header::CONTENT_TYPE,
+            TextEncoder::new().format_type().to_string(),
+        )],
+        metrics.render(),
+    )
+}
+
+///
+/// Build the router for GET /metrics.
+///
+pub fn metrics_router(metrics: Metrics) -> Router {
+    Router::new()
+        .route("/metrics", get(get_metrics))
+        .with_state(metrics)
+}
+
+#[cfg(test)]
+mod metrics_tests {
+    use super::*;
+    use crate::conditions::{Container, Cut};
+    use crate::parameters::{EventParameter, ParameterDictionary};
+    use crate::pipeline::{ConditionInvalidator, GateStage, Pipeline};
+    use crate::spectra::{Oned, SpectrumContainer};
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use http_body_util::BodyExt;
+    use std::cell::RefCell;
+    use tower::ServiceExt;
+
+    // Spectrum s1 on p1 ungated, s2 on p1 gated on cut (p1 in [0, 49])
+    // and a pipeline that fills them:
+
+    struct Setup {
+        _sdict: SpectrumDictionary,
+        cdict: Rc<RefCell<ConditionDictionary>>,
+        pipeline: Pipeline,
+    }
+    fn setup(metrics: &Metrics) -> Setup {
+        let mut pdict = ParameterDictionary::new();
+        pdict.add("p1").unwrap();
+        let cdict = Rc::new(RefCell::new(ConditionDictionary::new()));
+        let cut: Container = Rc::new(RefCell::new(Cut::new(1, 0.0, 49.0)));
+        cdict.borrow_mut().insert(String::from("cut"), cut);
+
+        let mut sdict = SpectrumDictionary::new();
+        let s1: SpectrumContainer = Rc::new(RefCell::new(
+            Oned::new("s1", "p1", &pdict, Some(0.0), Some(100.0), Some(100)).unwrap(),
+        ));
+        let s2: SpectrumContainer = Rc::new(RefCell::new(
+            Oned::new("s2", "p1", &pdict, Some(0.0), Some(100.0), Some(100)).unwrap(),
+        ));
+        s2.borrow_mut().gate("cut", &cdict.borrow()).unwrap();
+        sdict.insert(String::from("s1"), s1);
+        sdict.insert(String::from("s2"), s2);
+
+        let mut stage = MetricsStage::new(metrics, &sdict);
+        stage.watch_condition("cut", &cdict.borrow()).unwrap();
+        let mut pipeline = Pipeline::new();
+        pipeline
+            .add_boundary(Box::new(ConditionInvalidator::new(&cdict)))
+            .add_stage(Box::new(stage));
+        Setup {
+            _sdict: sdict,
+            cdict,
+            pipeline,
+        }
+    }
+    fn run(pipeline: &mut Pipeline, n: usize) {
+        for i in 0..n {
+            let mut e = FlatEvent::new();
+            e.load_event(&vec![EventParameter::new(1, i as f64)]);
+            pipeline.process_event(&mut e);
+        }
+    }
+    async fn scrape(metrics: &Metrics) -> String {
+        let request = Request::builder()
+            .uri("/metrics")
+            .body(Body::empty())
+            .unwrap();
+        let response = metrics_router(metrics.clone())
+            .oneshot(request)
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn endpoint_1() {
+        let metrics = Metrics::new();
+        let mut setup = setup(&metrics);
+        run(&mut setup.pipeline, 100);
+        assert_eq!(100, metrics.events_processed());
+
+        let text = scrape(&metrics).await;
+        assert!(text.contains("rustogrammer_events_processed_total 100\n"));
+        assert!(text.contains("rustogrammer_spectra_filled_total{name=\"s1\"} 100\n"));
+        assert!(text.contains("rustogrammer_spectra_filled_total{name=\"s2\"} 50\n"));
+        assert!(text.contains("rustogrammer_fill_duration_seconds_count 100\n"));
+        assert!(text.contains("rustogrammer_conditions_evaluated_total{name=\"cut\"} 100\n"));
+    }
+    #[test]
+    fn stats_1() {
+        // Only evaluated conditions are counted:
+
+        let metrics = Metrics::new();
+        let mut setup = setup(&metrics);
+        let other: Container = Rc::new(RefCell::new(Cut::new(1, 0.0, 10.0)));
+        setup
+            .cdict
+            .borrow_mut()
+            .insert(String::from("other"), other);
+        let mut stats = ConditionStats::new();
+        stats.watch("cut", &setup.cdict.borrow()).unwrap();
+        stats.watch("other", &setup.cdict.borrow()).unwrap();
+        assert!(stats.watch("nosuch", &setup.cdict.borrow()).is_err());
+
+        run(&mut setup.pipeline, 10);
+        let mut evaluated = Vec::new();
+        stats.record(|i| evaluated.push(i));
+        assert_eq!(vec![0], evaluated);
+        assert_eq!(
+            vec![(String::from("cut"), 1), (String::from("other"), 0)],
+            stats.evaluations()
+        );
+        stats.reset();
+        assert_eq!(0, stats.evaluations()[0].1);
+    }
+    #[tokio::test]
+    async fn gate_1() {
+        // Evaluations by earlier stages count.  Rejected events never
+        // reach the stage:
+
+        let metrics = Metrics::new();
+        let mut setup = setup(&metrics);
+        let mut pipeline = Pipeline::new();
+        let sdict = SpectrumDictionary::new();
+        let mut stage = MetricsStage::new(&metrics, &sdict);
+        stage.watch_condition("cut", &setup.cdict.borrow()).unwrap();
+        assert!(stage
+            .watch_condition("nosuch", &setup.cdict.borrow())
+            .is_err());
+        pipeline
+            .add_boundary(Box::new(ConditionInvalidator::new(&setup.cdict)))
+            .add_stage(Box::new(
+                GateStage::new("cut", &setup.cdict.borrow()).unwrap(),
+            ))
+            .add_stage(Box::new(stage));
+        setup.pipeline = pipeline;
+        run(&mut setup.pipeline, 100);
+
+        let text = scrape(&metrics).await;
+        assert!(text.contains("rustogrammer_events_processed_total 50\n"));
+        assert!(text.contains("rustogrammer_conditions_evaluated_total{name=\"cut\"} 50\n"));
+    }
+}
@@ -0,0 +1,434 @@
+//!  The server module provides an HTTP REST interface to the
+//!  histogram server.  The REST request handlers translate
+//!  requests into calls on the SpectrumClient and ConditionClient
+//!  so the dictionaries themselves remain owned by the histogram
+//!  server thread (they are Rc based and can't be shared across the
+//!  threads that serve HTTP requests).
+//!
+//!  The interface is:
+//!
+//!  *  GET /spectra - JSON list of spectrum names.
+//!  *  GET /spectra/{name} - JSON spectrum contents.
+//!  *  DELETE /spectra/{name} - Delete a spectrum.
+//!  *  POST /spectra  - Create a spectrum (body is a SpectrumCreation).
+//!  *  PUT /spectra/{name}/gate - Gate a spectrum (body is a GateApplication).
+//!  *  GET /conditions - JSON list of condition names.
+//!  *  POST /conditions - Create a condition (body is a ConditionCreation).
+//!
+//!  Successful requests that don't return data return a Status
+//!  with status "OK".  Failed requests return a Status with status
+//!  "ERROR" and the reason for the failure in detail.
+//!
+//!  The ws submodule streams spectrum contents over WebSockets and
+//!  the metrics submodule provides Prometheus metrics.
+//!
+use crate::messaging::{Channel, ConditionClient, SpectrumClient};
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::routing::{get, put};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+
+pub mod ws;
+pub use ws::*;
+pub mod metrics;
+pub use metrics::*;
+
+/// The clients the request handlers use:
+///
+#[derive(Clone)]
+pub struct ServerState {
+    pub spectra: SpectrumClient,
+    pub conditions: ConditionClient,
+}
+
+/// Result status of requests that don't return data:
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Status {
+    pub status: String,
+    pub detail: String,
+}
+
+/// Spectrum contents:
+#[derive(Serialize)]
+pub struct SpectrumContents {
+    pub name: String,
+    pub channels: Vec<Channel>,
+}
+
+/// Body of POST /spectra.  kind is "1d" or "2d".
+/// The y axis fields are only needed for 2d spectra.
+#[derive(Deserialize, Serialize)]
+pub struct SpectrumCreation {
+    pub name: String,
+    pub kind: String,
+    pub xparameter: String,
+    pub xlow: f64,
+    pub xhigh: f64,
+    pub xbins: u32,
+    pub yparameter: Option<String>,
+    pub ylow: Option<f64>,
+    pub yhigh: Option<f64>,
+    pub ybins: Option<u32>,
+}
+
+/// Body of POST /conditions. kind is one of "true", "false",
+/// "cut", "not", "and", "or".  Cuts require parameter_id, low and high.
+/// Compound conditions require dependents.
+#[derive(Deserialize, Serialize)]
+pub struct ConditionCreation {
+    pub name: String,
+    pub kind: String,
+    pub parameter_id: Option<u32>,
+    pub low: Option<f64>,
+    pub high: Option<f64>,
+    pub dependents: Option<Vec<String>>,
+}
+
+/// Body of PUT /spectra/{name}/gate
+#[derive(Deserialize, Serialize)]
+pub struct GateApplication {
+    pub gate: String,
+}
+
+type StatusResponse = (StatusCode, Json<Status>);
+
+// Turn a client result into a status response.  Failures to find
+// objects are NOT_FOUND, other failures are BAD_REQUEST.
+
+fn status_response(result: Result<(), String>) -> StatusResponse {
+    match result {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(Status {
+                status: String::from("OK"),
+                detail: String::new(),
+            }),
+        ),
+        Err(msg) => error_response(msg),
+    }
+}
+fn error_response(msg: String) -> StatusResponse {
+    let code = if msg.starts_with("No such") {
+        StatusCode::NOT_FOUND
+    } else {
+        StatusCode::BAD_REQUEST
+    };
+    (
+        code,
+        Json(Status {
+            status: String::from("ERROR"),
+            detail: msg,
+        }),
+    )
+}
+fn missing(what: &str) -> Result<(), String> {
+    Err(format!("Missing {}", what))
+}
+
+async fn list_spectra(
+    State(state): State<ServerState>,
+) -> Result<Json<Vec<String>>, StatusResponse> {
+    state.spectra.list().await.map(Json).map_err(error_response)
+}
+async fn get_spectrum(
+    State(state): State<ServerState>,
+    Path(name): Path<String>,
+) -> Result<Json<SpectrumContents>, StatusResponse> {
+    let channels = state
+        .spectra
+        .get_contents(&name)
+        .await
+        .map_err(error_response)?;
+    Ok(Json(SpectrumContents { name, channels }))
+}
+async fn delete_spectrum(
+    State(state): State<ServerState>,
+    Path(name): Path<String>,
+) -> StatusResponse {
+    status_response(state.spectra.delete(&name).await)
+}
+async fn create_spectrum(
+    State(state): State<ServerState>,
+    Json(def): Json<SpectrumCreation>,
+) -> StatusResponse {
+    let result = match def.kind.as_str() {
+        "1d" => {
+            state
+                .spectra
+                .create_1d(&def.name, &def.xparameter, def.xlow, def.xhigh, def.xbins)
+                .await
+        }
+        "2d" => {
+            if let (Some(yparam), Some(ylow), Some(yhigh), Some(ybins)) =
+                (&def.yparameter, def.ylow, def.yhigh, def.ybins)
+            {
+                state
+                    .spectra
+                    .create_2d(
+                        &def.name,
+                        &def.xparameter,
+                        yparam,
+                        def.xlow,
+                        def.xhigh,
+                        def.xbins,
+                        ylow,
+                        yhigh,
+                        ybins,
+                    )
+                    .await
+            } else {
+                missing("y axis definition")
+            }
+        }
+        _ => Err(format!("Unsupported spectrum type {}", def.kind)),
+    };
+    status_response(result)
+}
+async fn gate_spectrum(
+    State(state): State<ServerState>,
+    Path(name): Path<String>,
+    Json(gate): Json<GateApplication>,
+) -> StatusResponse {
+    status_response(state.spectra.gate(&name, &gate.gate).await)
+}
+async fn list_conditions(
+    State(state): State<ServerState>,
+) -> Result<Json<Vec<String>>, StatusResponse> {
+    state
+        .conditions
+        .list()
+        .await
+        .map(Json)
+        .map_err(error_response)
+}
+async fn create_condition(
+    State(state): State<ServerState>,
+    Json(def): Json<ConditionCreation>,
+) -> StatusResponse {
+    let conditions = &state.conditions;
+    let dependents: Vec<&str> = def
+        .dependents
+        .iter()
+        .flatten()
+        .map(|s| s.as_str())
+        .collect();
+    let result = match def.kind.as_str() {
+        "true" => conditions.create_true(&def.name).await,
+        "false" => conditions.create_false(&def.name).await,
+        "cut" => {
+            if let (Some(id), Some(low), Some(high)) = (def.parameter_id, def.low, def.high) {
+                conditions.create_cut(&def.name, id, low, high).await
+            } else {
+                missing("cut parameter_id, low or high")
+            }
+        }
+        "not" => {
+            if dependents.len() == 1 {
+                conditions.create_not(&def.name, dependents[0]).await
+            } else {
+                Err(String::from("Not conditions need exactly one dependent"))
+            }
+        }
+        "and" => conditions.create_and(&def.name, &dependents).await,
+        "or" => conditions.create_or(&def.name, &dependents).await,
+        _ => Err(format!("Unsupported condition type {}", def.kind)),
+    };
+    status_response(result)
+}
+
+///
+/// Build the router for the REST interface.
+///
+pub fn router(spectra: SpectrumClient, conditions: ConditionClient) -> Router {
+    Router::new()
+        .route("/spectra", get(list_spectra).post(create_spectrum))
+        .route("/spectra/{name}", get(get_spectrum).delete(delete_spectrum))
+        .route("/spectra/{name}/gate", put(gate_spectrum))
+        .route("/conditions", get(list_conditions).post(create_condition))
+        .with_state(ServerState {
+            spectra,
+            conditions,
+        })
+}
+
+///
+/// Serve the REST interface on the address given (e.g. "127.0.0.1:8000").
+/// This runs until the server fails.
+///
+pub async fn serve(address: &str, app: Router) -> std::io::Result<()> {
+    let listener = tokio::net::TcpListener::bind(address).await?;
+    axum::serve(listener, app).await
+}
+
+#[cfg(test)]
+mod rest_tests {
+    use super::*;
+    use crate::messaging::start_histogram_server;
+    use crate::parameters::{EventParameter, ParameterDictionary};
+    use axum::body::Body;
+    use axum::http::Request;
+    use http_body_util::BodyExt;
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    fn make_router() -> (Router, SpectrumClient) {
+        let mut pdict = ParameterDictionary::new();
+        pdict.add("p1").unwrap();
+        pdict.add("p2").unwrap();
+        let (spectra, conditions, _handle) = start_histogram_server(pdict);
+        (router(spectra.clone(), conditions), spectra)
+    }
+    async fn request(
+        app: &Router,
+        method: &str,
+        uri: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let builder = Request::builder().method(method).uri(uri);
+        let req = if let Some(b) = body {
+            builder
+                .header("content-type", "application/json")
+                .body(Body::from(b.to_string()))
+                .unwrap()
+        } else {
+            builder.body(Body::empty()).unwrap()
+        };
+        let response = app.clone().oneshot(req).await.unwrap();
+        let status = response.status();
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+    fn make_1d(name: &str) -> Value {
+        serde_json::json!({
+            "name": name, "kind": "1d", "xparameter": "p1",
+            "xlow": 0.0, "xhigh": 100.0, "xbins": 100
+        })
+    }
+
+    #[tokio::test]
+    async fn spectra_1() {
+        // Initially empty list:
+
+        let (app, _) = make_router();
+        let (status, body) = request(&app, "GET", "/spectra", None).await;
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!(serde_json::json!([]), body);
+    }
+    #[tokio::test]
+    async fn spectra_2() {
+        // Create 1d and 2d spectra:
+
+        let (app, _) = make_router();
+        let (status, body) = request(&app, "POST", "/spectra", Some(make_1d("s1"))).await;
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!("OK", body["status"]);
+
+        let twod = serde_json::json!({
+            "name": "s2", "kind": "2d", "xparameter": "p1",
+            "xlow": 0.0, "xhigh": 100.0, "xbins": 100,
+            "yparameter": "p2", "ylow": 0.0, "yhigh": 100.0, "ybins": 100
+        });
+        let (status, _) = request(&app, "POST", "/spectra", Some(twod)).await;
+        assert_eq!(StatusCode::OK, status);
+
+        let (_, body) = request(&app, "GET", "/spectra", None).await;
+        assert_eq!(serde_json::json!(["s1", "s2"]), body);
+    }
+    #[tokio::test]
+    async fn spectra_3() {
+        // Creation errors:
+
+        let (app, _) = make_router();
+        let mut bad = make_1d("s1");
+        bad["xparameter"] = Value::from("nosuch");
+        let (status, body) = request(&app, "POST", "/spectra", Some(bad)).await;
+        assert_eq!(StatusCode::NOT_FOUND, status);
+        assert_eq!("ERROR", body["status"]);
+        assert_eq!("No such parameter: nosuch", body["detail"]);
+
+        let mut bad = make_1d("s1");
+        bad["kind"] = Value::from("2d");
+        let (status, _) = request(&app, "POST", "/spectra", Some(bad)).await;
+        assert_eq!(StatusCode::BAD_REQUEST, status);
+    }
+    #[tokio::test]
+    async fn spectrum_1() {
+        // Get contents:
+
+        let (app, client) = make_router();
+        request(&app, "POST", "/spectra", Some(make_1d("s1"))).await;
+        client
+            .process_events(vec![vec![EventParameter::new(1, 5.0)]])
+            .await
+            .unwrap();
+        let (status, body) = request(&app, "GET", "/spectra/s1", None).await;
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!("s1", body["name"]);
+        assert_eq!(
+            serde_json::json!([{"x": 5.0, "y": 0.0, "value": 1.0}]),
+            body["channels"]
+        );
+
+        let (status, _) = request(&app, "GET", "/spectra/nosuch", None).await;
+        assert_eq!(StatusCode::NOT_FOUND, status);
+    }
+    #[tokio::test]
+    async fn delete_1() {
+        let (app, _) = make_router();
+        request(&app, "POST", "/spectra", Some(make_1d("s1"))).await;
+        let (status, _) = request(&app, "DELETE", "/spectra/s1", None).await;
+        assert_eq!(StatusCode::OK, status);
+        let (_, body) = request(&app, "GET", "/spectra", None).await;
+        assert_eq!(serde_json::json!([]), body);
+        let (status, _) = request(&app, "DELETE", "/spectra/s1", None).await;
+        assert_eq!(StatusCode::NOT_FOUND, status);
+    }
+    #[tokio::test]
+    async fn conditions_1() {
+        let (app, _) = make_router();
+        let cut = serde_json::json!({
+            "name": "c", "kind": "cut", "parameter_id": 1, "low": 10.0, "high": 20.0
+        });
+        let (status, _) = request(&app, "POST", "/conditions", Some(cut)).await;
+        assert_eq!(StatusCode::OK, status);
+        let not = serde_json::json!({"name": "n", "kind": "not", "dependents": ["c"]});
+        let (status, _) = request(&app, "POST", "/conditions", Some(not)).await;
+        assert_eq!(StatusCode::OK, status);
+        let and = serde_json::json!({"name": "a", "kind": "and", "dependents": ["c", "n"]});
+        let (status, _) = request(&app, "POST", "/conditions", Some(and)).await;
+        assert_eq!(StatusCode::OK, status);
+
+        let (status, body) = request(&app, "GET", "/conditions", None).await;
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!(serde_json::json!(["a", "c", "n"]), body);
+    }
+    #[tokio::test]
+    async fn conditions_2() {
+        let (app, _) = make_router();
+        let cut = serde_json::json!({"name": "c", "kind": "cut", "low": 10.0});
+        let (status, body) = request(&app, "POST", "/conditions", Some(cut)).await;
+        assert_eq!(StatusCode::BAD_REQUEST, status);
+        assert_eq!("Missing cut parameter_id, low or high", body["detail"]);
+
+        let bad = serde_json::json!({"name": "c", "kind": "banana"});
+        let (status, _) = request(&app, "POST", "/conditions", Some(bad)).await;
+        assert_eq!(StatusCode::BAD_REQUEST, status);
+    }
+    #[tokio::test]
+    async fn gate_1() {
+        let (app, _) = make_router();
+        request(&app, "POST", "/spectra", Some(make_1d("s1"))).await;
+        let t = serde_json::json!({"name": "t", "kind": "true"});
+        request(&app, "POST", "/conditions", Some(t)).await;
+
+        let gate = serde_json::json!({"gate": "t"});
+        let (status, _) = request(&app, "PUT", "/spectra/s1/gate", Some(gate)).await;
+        assert_eq!(StatusCode::OK, status);
+
+        let gate = serde_json::json!({"gate": "nosuch"});
+        let (status, body) = request(&app, "PUT", "/spectra/s1/gate", Some(gate)).await;
+        assert_eq!(StatusCode::NOT_FOUND, status);
+        assert_eq!("No such gate nosuch", body["detail"]);
+    }
+}
@@ -0,0 +1,332 @@
+//!  WebSocket streaming of spectrum contents for browser based
+//!  displays.  A client connects to /ws and sends subscriptions:
+//!
+//!  `{"subscribe": ["spec1", "spec2"]}`
+//!
+//!  Each subscription replaces the previous one.  Periodically, the
+//!  server checks the generation of each subscribed spectrum and,
+//!  if it changed since the last push (or the spectrum was never
+//!  pushed), sends its contents:
+//!
+//!  `{"name": "spec1", "bins": [{"x":..., "y":..., "value":...}...], "generation": 42}`
+//!
+//!  bins are the non-empty channels as in GET /spectra/{name}.
+//!  Bad subscriptions and subscribed spectra that don't exist get an
+//!  ERROR Status and the spectra are dropped from the subscription.
+//!
+//!  Like the REST interface, the spectra are reached through a
+//!  SpectrumClient since the dictionary belongs to the histogram
+//!  server thread.
+//!
+use super::Status;
+use crate::messaging::{Channel, SpectrumClient};
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::State;
+use axum::response::Response;
+use axum::routing::get;
+use axum::Router;
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// The shortest period at which spectra are checked for changes.
+pub const MIN_PERIOD: Duration = Duration::from_millis(1);
+
+/// The messages clients send.
+#[derive(Deserialize, Serialize)]
+pub struct Subscription {
+    pub subscribe: Vec<String>,
+}
+
+/// The updates the server sends.
+#[derive(Serialize)]
+pub struct SpectrumUpdate {
+    pub name: String,
+    pub bins: Vec<Channel>,
+    pub generation: u64,
+}
+
+#[derive(Clone)]
+struct WsState {
+    spectra: SpectrumClient,
+    period: Duration,
+}
+
+// A subscribed spectrum and the generation last pushed:
+
+struct Subscribed {
+    name: String,
+    pushed: Option<u64>,
+}
+
+async fn send_json<T: Serialize>(socket: &mut WebSocket, value: &T) -> Result<(), axum::Error> {
+    let text = serde_json::to_string(value).expect("Updates serialize");
+    socket.send(Message::Text(text.into())).await
+}
+async fn send_error(socket: &mut WebSocket, detail: String) -> Result<(), axum::Error> {
+    send_json(
+        socket,
+        &Status {
+            status: String::from("ERROR"),
+            detail,
+        },
+    )
+    .await
+}
+
+// The new subscription list, keeping what was pushed for spectra that
+// stay subscribed:
+
+fn subscribe(old: Vec<Subscribed>, names: Vec<String>) -> Vec<Subscribed> {
+    names
+        .into_iter()
+        .map(|name| {
+            let pushed = old.iter().find(|s| s.name == name).and_then(|s| s.pushed);
+            Subscribed { name, pushed }
+        })
+        .collect()
+}
+
+// Push the subscribed spectra that changed.  Errors are failures to
+// send;  spectra that could not be looked up are unsubscribed.
+
+async fn push_updates(
+    socket: &mut WebSocket,
+    spectra: &SpectrumClient,
+    subscriptions: &mut Vec<Subscribed>,
+) -> Result<(), axum::Error> {
+    let mut failed = Vec::new();
+    for (i, s) in subscriptions.iter_mut().enumerate() {
+        let update = match spectra.generation(&s.name).await {
+            Ok(generation) if Some(generation) == s.pushed => continue,
+            Ok(generation) => spectra
+                .get_contents(&s.name)
+                .await
+                .map(|bins| (generation, bins)),
+            Err(msg) => Err(msg),
+        };
+        match update {
+            Ok((generation, bins)) => {
+                send_json(
+                    socket,
+                    &SpectrumUpdate {
+                        name: s.name.clone(),
+                        bins,
+                        generation,
+                    },
+                )
+                .await?;
+                s.pushed = Some(generation);
+            }
+            Err(msg) => {
+                send_error(socket, msg).await?;
+                failed.push(i);
+            }
+        }
+    }
+    for i in failed.into_iter().rev() {
+        subscriptions.remove(i);
+    }
+    Ok(())
+}
+
+// Serve one connection until the client goes away:
+
+async fn stream_updates(mut socket: WebSocket, state: WsState) {
+    let mut subscriptions = Vec::new();
+    let mut ticks = tokio::time::interval(state.period);
+    loop {
+        tokio::select! {
+            message = socket.recv() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    match serde_json::from_str::<Subscription>(&text) {
+                        Ok(s) => subscriptions = subscribe(subscriptions, s.subscribe),
+                        Err(e) => {
+                            if send_error(&mut socket, e.to_string()).await.is_err() {
+                                break;
+                            }
+                        }
+                    }
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {} // axum answers pings.
+            },
+            _ = ticks.tick() => {
+                if push_updates(&mut socket, &state.spectra, &mut subscriptions).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+async fn upgrade(ws: WebSocketUpgrade, State(state): State<WsState>) -> Response {
+    ws.on_upgrade(move |socket| stream_updates(socket, state))
+}
+
+///
+/// Build the router for the WebSocket interface.  Subscribed
+/// spectra are checked for changes every period;  periods shorter
+/// than MIN_PERIOD are MIN_PERIOD.
+///
+pub fn ws_router(spectra: SpectrumClient, period: Duration) -> Router {
+    Router::new()
+        .route("/ws", get(upgrade))
+        .with_state(WsState {
+            spectra,
+            period: period.max(MIN_PERIOD),
+        })
+}
+
+///
+/// Start serving the WebSocket interface in its own task on the
+/// address given (e.g. "127.0.0.1:8001", port 0 picks a free port).
+/// Returns the address actually bound and the task handle.
+///
+pub async fn start_ws_server(
+    address: &str,
+    spectra: SpectrumClient,
+    period: Duration,
+) -> std::io::Result<(SocketAddr, JoinHandle<std::io::Result<()>>)> {
+    let listener = tokio::net::TcpListener::bind(address).await?;
+    let bound = listener.local_addr()?;
+    let app = ws_router(spectra, period);
+    Ok((
+        bound,
+        tokio::spawn(async move { axum::serve(listener, app).await }),
+    ))
+}
+
+#[cfg(test)]
+mod ws_tests {
+    use super::*;
+    use crate::messaging::start_histogram_server;
+    use crate::parameters::{EventParameter, ParameterDictionary};
+    use futures_util::{SinkExt, StreamExt};
+    use serde_json::Value;
+    use tokio::net::TcpStream;
+    use tokio_tungstenite::tungstenite;
+    use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+    type Client = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+    const PERIOD: Duration = Duration::from_millis(20);
+    const WAIT: Duration = Duration::from_secs(5);
+
+    async fn start() -> (Client, SpectrumClient) {
+        start_with_period(PERIOD).await
+    }
+    async fn start_with_period(period: Duration) -> (Client, SpectrumClient) {
+        let mut pdict = ParameterDictionary::new();
+        pdict.add("p1").unwrap();
+        let (spectra, _conditions, _handle) = start_histogram_server(pdict);
+        spectra
+            .create_1d("s1", "p1", 0.0, 100.0, 100)
+            .await
+            .unwrap();
+        spectra
+            .create_1d("s2", "p1", 0.0, 100.0, 100)
+            .await
+            .unwrap();
+
+        let (address, _task) = start_ws_server("127.0.0.1:0", spectra.clone(), period)
+            .await
+            .unwrap();
+        let (client, _) = connect_async(format!("ws://{}/ws", address)).await.unwrap();
+        (client, spectra)
+    }
+    async fn send(client: &mut Client, text: &str) {
+        client
+            .send(tungstenite::Message::Text(text.into()))
+            .await
+            .unwrap();
+    }
+    async fn receive(client: &mut Client) -> Value {
+        loop {
+            let message = tokio::time::timeout(WAIT, client.next())
+                .await
+                .expect("No message from server")
+                .unwrap()
+                .unwrap();
+            if let tungstenite::Message::Text(text) = message {
+                return serde_json::from_str(&text).unwrap();
+            }
+        }
+    }
+    async fn nothing_sent(client: &mut Client) -> bool {
+        tokio::time::timeout(PERIOD * 10, client.next())
+            .await
+            .is_err()
+    }
+
+    #[tokio::test]
+    async fn subscribe_1() {
+        // Initial contents then the update after events:
+
+        let (mut client, spectra) = start().await;
+        send(&mut client, r#"{"subscribe": ["s1"]}"#).await;
+        let initial = receive(&mut client).await;
+        assert_eq!("s1", initial["name"]);
+        assert_eq!(serde_json::json!([]), initial["bins"]);
+
+        spectra
+            .process_events(vec![vec![EventParameter::new(1, 5.0)]])
+            .await
+            .unwrap();
+        let update = receive(&mut client).await;
+        assert_eq!("s1", update["name"]);
+        assert_eq!(
+            serde_json::json!([{"x": 5.0, "y": 0.0, "value": 1.0}]),
+            update["bins"]
+        );
+        assert!(update["generation"].as_u64().unwrap() > initial["generation"].as_u64().unwrap());
+    }
+    #[tokio::test]
+    async fn subscribe_2() {
+        // Nothing is sent for spectra that didn't change or aren't
+        // subscribed:
+
+        let (mut client, spectra) = start().await;
+        send(&mut client, r#"{"subscribe": ["s1"]}"#).await;
+        receive(&mut client).await;
+        assert!(nothing_sent(&mut client).await);
+        spectra.clear("s2").await.unwrap();
+        assert!(nothing_sent(&mut client).await);
+
+        // Resubscribing doesn't resend what's been sent:
+
+        send(&mut client, r#"{"subscribe": ["s2", "s1"]}"#).await;
+        let update = receive(&mut client).await;
+        assert_eq!("s2", update["name"]);
+        assert!(nothing_sent(&mut client).await);
+    }
+    #[tokio::test]
+    async fn subscribe_3() {
+        // Errors:
+
+        let (mut client, _spectra) = start().await;
+        send(&mut client, r#"{"subscribe": "s1"}"#).await;
+        assert_eq!("ERROR", receive(&mut client).await["status"]);
+
+        send(&mut client, r#"{"subscribe": ["nosuch"]}"#).await;
+        let error = receive(&mut client).await;
+        assert_eq!("ERROR", error["status"]);
+        assert_eq!("No such spectrum nosuch", error["detail"]);
+        assert!(nothing_sent(&mut client).await);
+    }
+    #[tokio::test]
+    async fn period_1() {
+        // A zero period is treated as MIN_PERIOD rather than
+        // panicking the connection's task:
+
+        let (mut client, spectra) = start_with_period(Duration::ZERO).await;
+        send(&mut client, r#"{"subscribe": ["s1"]}"#).await;
+        assert_eq!("s1", receive(&mut client).await["name"]);
+        spectra
+            .process_events(vec![vec![EventParameter::new(1, 5.0)]])
+            .await
+            .unwrap();
+        assert_eq!(1.0, receive(&mut client).await["bins"][0]["value"]);
+    }
+}
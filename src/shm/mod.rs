@@ -0,0 +1,371 @@
+//!  The shm module provides shared memory histogram output.
+//!  Display programs running in the same host as the histogrammer
+//!  can map the shared memory region and read the histogram
+//!  channels directly rather than fetching them over the network.
+//!
+//!  On Linux the region is a file in /dev/shm (which is how POSIX
+//!  shared memory is implemented there).  On other systems, or if
+//!  /dev/shm does not exist, the region is a file in the temporary
+//!  directory.  Either way the file is mapped with memmap2.  Region
+//!  names are therefore file names in that directory:  they can't be
+//!  empty, "." or ".." or contain a /.
+//!
+//!  The region has a fixed layout:
+//!
+//!  *  A header (see Header below).
+//!  *  bins f64 channel values.  These include the underflow and
+//!     overflow channels in the order ndhistogram stores them.
+//!
+//!  The header contains a generation counter that is used as a
+//!  sequence lock.  The writer makes the generation odd while
+//!  it is modifying the channels and even when it's done.
+//!  A reader that sees an odd generation, or a generation that
+//!  changed while it copied the channels has a stale read and
+//!  must retry.
+//!
+use crate::spectra::H1D;
+use memmap2::{Mmap, MmapMut};
+use ndhistogram::{axis::Axis, Histogram};
+use std::fs::{self, File, OpenOptions};
+use std::mem;
+use std::path::PathBuf;
+use std::sync::atomic::{fence, AtomicU64, Ordering};
+
+/// Marks a region as a histogram region ("RSTGSHM1").
+pub const SHM_MAGIC: u64 = 0x5253_5447_5348_4d31;
+
+// Number of times a reader will retry a read that overlapped a write.
+
+const MAX_READ_RETRIES: usize = 1000;
+
+/// Fixed layout header at the start of the region.
+///
+#[repr(C)]
+struct Header {
+    magic: u64,
+    generation: AtomicU64,
+    bins: u64,
+    low: f64,
+    high: f64,
+}
+
+/// Where the region for a name lives.  Err if the name would be
+/// outside the region directory:
+///
+fn region_path(name: &str) -> Result<PathBuf, String> {
+    if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+        return Err(format!("Invalid shared memory region name '{}'", name));
+    }
+    let shm = PathBuf::from("/dev/shm");
+    let mut path = if shm.is_dir() {
+        shm
+    } else {
+        std::env::temp_dir()
+    };
+    path.push(name);
+    Ok(path)
+}
+fn region_size(bins: usize) -> usize {
+    mem::size_of::<Header>() + bins * mem::size_of::<f64>()
+}
+
+///
+/// The writer side of a shared memory histogram.  Creating the writer
+/// creates the region.  The region is removed when the writer is dropped,
+/// though readers that have already attached continue to see it.
+///
+pub struct SharedMemoryHistogram {
+    path: PathBuf,
+    map: MmapMut,
+    bins: usize,
+}
+
+impl SharedMemoryHistogram {
+    /// Create the shared memory region for a histogram with the
+    /// number of bins (including underflow and overflow) and axis limits
+    /// given.  Any existing region with the same name is replaced.
+    ///
+    pub fn new(
+        name: &str,
+        bins: usize,
+        low: f64,
+        high: f64,
+    ) -> Result<SharedMemoryHistogram, String> {
+        let path = region_path(name)?;
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)
+            .map_err(|e| format!("Unable to create {}: {}", path.display(), e))?;
+        file.set_len(region_size(bins) as u64)
+            .map_err(|e| format!("Unable to size {}: {}", path.display(), e))?;
+        let map = unsafe { MmapMut::map_mut(&file) }
+            .map_err(|e| format!("Unable to map {}: {}", path.display(), e))?;
+
+        let mut result = SharedMemoryHistogram { path, map, bins };
+        let header = result.header_mut();
+        header.bins = bins as u64;
+        header.low = low;
+        header.high = high;
+        header.generation.store(0, Ordering::Relaxed);
+        fence(Ordering::Release);
+        header.magic = SHM_MAGIC;
+        Ok(result)
+    }
+    /// Create a region sized and described by a 1-d histogram's axis.
+    ///
+    pub fn for_1d(name: &str, h: &H1D) -> Result<SharedMemoryHistogram, String> {
+        let axis = h.axes().as_tuple().0.clone();
+        Self::new(name, axis.num_bins(), *axis.low(), *axis.high())
+    }
+    /// Number of channels in the region.
+    pub fn bins(&self) -> usize {
+        self.bins
+    }
+    /// Current generation.  This increases by two for each write.
+    pub fn generation(&self) -> u64 {
+        self.header().generation.load(Ordering::Acquire)
+    }
+    /// Write a set of channel values into the region.  The number of
+    /// values must match the number of bins in the region.
+    ///
+    pub fn write(&mut self, values: &[f64]) -> Result<(), String> {
+        if values.len() != self.bins {
+            return Err(format!(
+                "Value count {} does not match region size {}",
+                values.len(),
+                self.bins
+            ));
+        }
+        let generation = self.header().generation.load(Ordering::Relaxed);
+        self.header()
+            .generation
+            .store(generation + 1, Ordering::Relaxed);
+        fence(Ordering::Release);
+        self.channels_mut().copy_from_slice(values);
+        fence(Ordering::Release);
+        self.header()
+            .generation
+            .store(generation + 2, Ordering::Release);
+        Ok(())
+    }
+    /// Write the channels of a 1-d histogram into the region.
+    ///
+    pub fn write_1d(&mut self, h: &H1D) -> Result<(), String> {
+        let values: Vec<f64> = h.values().map(|v| v.get()).collect();
+        self.write(&values)
+    }
+
+    fn header(&self) -> &Header {
+        unsafe { &*(self.map.as_ptr() as *const Header) }
+    }
+    fn header_mut(&mut self) -> &mut Header {
+        unsafe { &mut *(self.map.as_mut_ptr() as *mut Header) }
+    }
+    fn channels_mut(&mut self) -> &mut [f64] {
+        let bins = self.bins;
+        unsafe {
+            let p = self.map.as_mut_ptr().add(mem::size_of::<Header>()) as *mut f64;
+            std::slice::from_raw_parts_mut(p, bins)
+        }
+    }
+}
+impl Drop for SharedMemoryHistogram {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// A consistent copy of the contents of a region.
+///
+#[derive(Debug, PartialEq, Clone)]
+pub struct HistogramSnapshot {
+    pub generation: u64,
+    pub low: f64,
+    pub high: f64,
+    pub values: Vec<f64>,
+}
+
+///
+/// Read-only access to a shared memory histogram.
+///
+pub struct SharedMemoryHistogramReader {
+    map: Mmap,
+    bins: usize,
+}
+
+/// Attach to an existing shared memory histogram by name.
+///
+pub fn attach(name: &str) -> Result<SharedMemoryHistogramReader, String> {
+    let path = region_path(name)?;
+    let file =
+        File::open(&path).map_err(|e| format!("Unable to open {}: {}", path.display(), e))?;
+    let map = unsafe { Mmap::map(&file) }
+        .map_err(|e| format!("Unable to map {}: {}", path.display(), e))?;
+    if map.len() < mem::size_of::<Header>() {
+        return Err(format!("{} is too small to be a histogram", path.display()));
+    }
+    let header = unsafe { &*(map.as_ptr() as *const Header) };
+    if header.magic != SHM_MAGIC {
+        return Err(format!("{} is not a histogram region", path.display()));
+    }
+    let bins = header.bins as usize;
+    if map.len() < region_size(bins) {
+        return Err(format!("{} is truncated", path.display()));
+    }
+    Ok(SharedMemoryHistogramReader { map, bins })
+}
+
+impl SharedMemoryHistogramReader {
+    pub fn bins(&self) -> usize {
+        self.bins
+    }
+    /// Current generation.  Readers can compare this with the
+    /// generation of a prior snapshot to see if the snapshot is stale.
+    pub fn generation(&self) -> u64 {
+        self.header().generation.load(Ordering::Acquire)
+    }
+    /// Get a consistent copy of the region.  If the read overlaps a write
+    /// it is retried.  Err is returned if a consistent copy could not be
+    /// gotten.
+    ///
+    pub fn read(&self) -> Result<HistogramSnapshot, String> {
+        for _ in 0..MAX_READ_RETRIES {
+            let before = self.generation();
+            if before % 2 == 1 {
+                std::hint::spin_loop();
+                continue;
+            }
+            let values = self.channels().to_vec();
+            fence(Ordering::Acquire);
+            if self.header().generation.load(Ordering::Relaxed) == before {
+                let header = self.header();
+                return Ok(HistogramSnapshot {
+                    generation: before,
+                    low: header.low,
+                    high: header.high,
+                    values,
+                });
+            }
+        }
+        Err(String::from(
+            "Unable to get a consistent read of the histogram",
+        ))
+    }
+
+    fn header(&self) -> &Header {
+        unsafe { &*(self.map.as_ptr() as *const Header) }
+    }
+    fn channels(&self) -> &[f64] {
+        unsafe {
+            let p = self.map.as_ptr().add(mem::size_of::<Header>()) as *const f64;
+            std::slice::from_raw_parts(p, self.bins)
+        }
+    }
+}
+
+#[cfg(test)]
+mod shm_tests {
+    use super::*;
+    use crate::parameters::*;
+    use crate::spectra::*;
+
+    // Region names must be unique across concurrently running tests.
+
+    fn region_name(test: &str) -> String {
+        format!("histogrammer-test-{}-{}", std::process::id(), test)
+    }
+
+    #[test]
+    fn new_1() {
+        let name = region_name("new_1");
+        let shm = SharedMemoryHistogram::new(&name, 12, 0.0, 10.0).unwrap();
+        assert_eq!(12, shm.bins());
+        assert_eq!(0, shm.generation());
+
+        let reader = attach(&name).unwrap();
+        assert_eq!(12, reader.bins());
+        let snap = reader.read().unwrap();
+        assert_eq!(0, snap.generation);
+        assert_eq!(0.0, snap.low);
+        assert_eq!(10.0, snap.high);
+        assert_eq!(vec![0.0; 12], snap.values);
+    }
+    #[test]
+    fn attach_1() {
+        assert!(attach(&region_name("attach_1")).is_err());
+    }
+    #[test]
+    fn names_1() {
+        // Names that would be outside the region directory:
+
+        for name in ["", ".", "..", "../tmp/x", "a/b", "/tmp/x"] {
+            assert!(SharedMemoryHistogram::new(name, 12, 0.0, 10.0).is_err());
+            assert_eq!(
+                Some(format!("Invalid shared memory region name '{}'", name)),
+                attach(name).err()
+            );
+        }
+    }
+    #[test]
+    fn attach_2() {
+        // Removed when the writer is dropped:
+
+        let name = region_name("attach_2");
+        {
+            let _shm = SharedMemoryHistogram::new(&name, 12, 0.0, 10.0).unwrap();
+        }
+        assert!(attach(&name).is_err());
+    }
+    #[test]
+    fn write_1() {
+        let name = region_name("write_1");
+        let mut shm = SharedMemoryHistogram::new(&name, 4, 0.0, 2.0).unwrap();
+        assert!(shm.write(&[1.0, 2.0]).is_err());
+        shm.write(&[1.0, 2.0, 3.0, 4.0]).unwrap();
+        assert_eq!(2, shm.generation());
+
+        let reader = attach(&name).unwrap();
+        let snap = reader.read().unwrap();
+        assert_eq!(2, snap.generation);
+        assert_eq!(vec![1.0, 2.0, 3.0, 4.0], snap.values);
+
+        // Stale snapshot detection:
+
+        shm.write(&[0.0; 4]).unwrap();
+        assert_ne!(snap.generation, reader.generation());
+    }
+    #[test]
+    fn write_2() {
+        // Write a Oned spectrum's histogram:
+
+        let mut pdict = ParameterDictionary::new();
+        pdict.add("p").unwrap();
+        let pid = pdict.lookup("p").unwrap().get_id();
+        let mut spec = Oned::new("s", "p", &pdict, Some(0.0), Some(10.0), Some(10)).unwrap();
+        let mut fe = FlatEvent::new();
+        for i in 0..10 {
+            for _ in 0..=i {
+                fe.load_event(&vec![EventParameter::new(pid, i as f64 + 0.5)]);
+                spec.handle_event(&fe);
+            }
+        }
+        let h = spec.get_histogram_1d().unwrap();
+
+        let name = region_name("write_2");
+        let mut shm = SharedMemoryHistogram::for_1d(&name, &h.borrow()).unwrap();
+        shm.write_1d(&h.borrow()).unwrap();
+
+        let snap = attach(&name).unwrap().read().unwrap();
+        assert_eq!(0.0, snap.low);
+        assert_eq!(10.0, snap.high);
+        let expected: Vec<f64> = h.borrow().values().map(|v| v.get()).collect();
+        assert_eq!(expected, snap.values);
+        for i in 0..10 {
+            let value = h.borrow().value(&(i as f64 + 0.5)).unwrap().get();
+            assert_eq!((i + 1) as f64, value);
+        }
+    }
+}
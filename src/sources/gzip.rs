@@ -0,0 +1,135 @@
+//! GzipRingItemSource reads ring items from gzip compressed run
+//! files, decompressing them on the fly.  Since archived runs may
+//! or may not be compressed, open looks for the gzip magic number
+//! at the start of the file and reads the file uncompressed if it's
+//! not there.
+//!
+//! write_gzip writes a set of ring items to a gzip compressed file.
+//!
+use super::*;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// First two bytes of gzip files.
+pub const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+pub struct GzipRingItemSource {
+    inner: RingItemIter<Box<dyn Read>>,
+    compressed: bool,
+}
+
+impl GzipRingItemSource {
+    /// Open a run file that may or may not be compressed.
+    pub fn open(path: &Path) -> std::io::Result<GzipRingItemSource> {
+        let mut file = File::open(path)?;
+        let mut magic = [0_u8; 2];
+        let compressed = file.read_exact(&mut magic).is_ok() && magic == GZIP_MAGIC;
+        file.seek(SeekFrom::Start(0))?;
+
+        let reader: Box<dyn Read> = if compressed {
+            Box::new(GzDecoder::new(BufReader::new(file)))
+        } else {
+            Box::new(BufReader::new(file))
+        };
+        Ok(GzipRingItemSource {
+            inner: RingItemIter::new(reader),
+            compressed,
+        })
+    }
+    /// True if the file being read is compressed.
+    pub fn is_compressed(&self) -> bool {
+        self.compressed
+    }
+}
+
+impl Iterator for GzipRingItemSource {
+    type Item = RingItemResult;
+
+    fn next(&mut self) -> Option<RingItemResult> {
+        self.inner.next()
+    }
+}
+
+/// Write ring items to a gzip compressed file.  The file is created
+/// or replaced.
+///
+pub fn write_gzip(items: impl Iterator<Item = RingItem>, path: &Path) -> std::io::Result<()> {
+    let file = File::create(path)?;
+    let mut encoder = GzEncoder::new(BufWriter::new(file), Compression::default());
+    for item in items {
+        item.write_to(&mut encoder)?;
+    }
+    encoder.finish()?.flush()
+}
+
+#[cfg(test)]
+mod gzip_source_tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn make_items() -> Vec<RingItem> {
+        let mut result = Vec::new();
+        for i in 0..100_u32 {
+            let mut item = RingItem::new_with_body_header(30, i as u64 * 10, 1, 0);
+            for j in 0..i {
+                item.add(j);
+            }
+            result.push(item);
+        }
+        result
+    }
+
+    #[test]
+    fn gzip_1() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("run.evt.gz");
+        let items = make_items();
+        write_gzip(make_items().into_iter(), &path).unwrap();
+
+        let mut magic = [0_u8; 2];
+        File::open(&path).unwrap().read_exact(&mut magic).unwrap();
+        assert_eq!(GZIP_MAGIC, magic);
+
+        let source = GzipRingItemSource::open(&path).unwrap();
+        assert!(source.is_compressed());
+        let read: Vec<RingItem> = source.map(|i| i.unwrap()).collect();
+        assert_eq!(items.len(), read.len());
+        for (o, i) in items.iter().zip(read.iter()) {
+            assert_eq!(o.to_bytes(), i.to_bytes());
+        }
+    }
+    #[test]
+    fn plain_1() {
+        // Uncompressed files are read as is:
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("run.evt");
+        let items = make_items();
+        let mut file = File::create(&path).unwrap();
+        for item in items.iter() {
+            item.write_to(&mut file).unwrap();
+        }
+        drop(file);
+
+        let source = GzipRingItemSource::open(&path).unwrap();
+        assert!(!source.is_compressed());
+        let read: Vec<RingItem> = source.map(|i| i.unwrap()).collect();
+        assert_eq!(items.len(), read.len());
+        for (o, i) in items.iter().zip(read.iter()) {
+            assert_eq!(o.to_bytes(), i.to_bytes());
+        }
+    }
+    #[test]
+    fn empty_1() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("empty.evt");
+        File::create(&path).unwrap();
+        let mut source = GzipRingItemSource::open(&path).unwrap();
+        assert!(!source.is_compressed());
+        assert!(source.next().is_none());
+    }
+}
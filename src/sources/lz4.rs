@@ -0,0 +1,254 @@
+//! Lz4RingItemSource reads ring items from LZ4 compressed run files
+//! and write_lz4 writes them.  LZ4 is much cheaper to compress than gzip,
+//! which makes it suited to compressing data as it's recorded online.
+//!
+//! Each ring item is compressed independently.  The file is a sequence
+//! of frames where each frame is:
+//!
+//! *  A u32 (native byte order, like ring item headers) containing
+//!    the number of compressed bytes that follow.
+//! *  The LZ4 block compressed wire format of the ring item
+//!    (with the uncompressed size prepended as lz4_flex does).
+//!
+//! Since frames can be skipped without decompressing them,
+//! the source can seek to a ring item by its index.
+//!
+//! Neither size is trusted:  frames and ring items larger than
+//! MAX_LZ4_ITEM_SIZE are reported as InvalidHeader rather than
+//! allocated.
+//!
+use super::*;
+use lz4_flex::block::{compress_prepend_size, decompress, uncompressed_size};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// The largest compressed frame and uncompressed ring item
+/// Lz4RingItemSource will read (16MiB).
+pub const MAX_LZ4_ITEM_SIZE: usize = 16 * 1024 * 1024;
+
+pub struct Lz4RingItemSource {
+    reader: BufReader<File>,
+}
+
+impl Lz4RingItemSource {
+    pub fn open(path: &Path) -> std::io::Result<Lz4RingItemSource> {
+        Ok(Lz4RingItemSource {
+            reader: BufReader::new(File::open(path)?),
+        })
+    }
+    /// Position the source so that the next item returned is the
+    /// index'th item in the file (counting from 0).  Err is returned
+    /// if the file does not have that many items.
+    ///
+    pub fn seek_item(&mut self, index: usize) -> std::io::Result<()> {
+        self.reader.rewind()?;
+        for _ in 0..index {
+            let size = self.read_frame_size()?;
+            self.reader.seek(SeekFrom::Current(size as i64))?;
+        }
+        // Seeking past the end succeeds so be sure there's a frame:
+
+        let position = self.reader.stream_position()?;
+        self.read_frame_size()?;
+        self.reader.seek(SeekFrom::Start(position))?;
+        Ok(())
+    }
+
+    fn read_frame_size(&mut self) -> std::io::Result<u32> {
+        let mut size = [0_u8; 4];
+        self.reader.read_exact(&mut size)?;
+        Ok(u32::from_ne_bytes(size))
+    }
+}
+
+impl Iterator for Lz4RingItemSource {
+    type Item = RingItemResult;
+
+    fn next(&mut self) -> Option<RingItemResult> {
+        // The end of file is only clean between frames:
+
+        let mut size = [0_u8; 4];
+        let mut read = 0;
+        while read < size.len() {
+            match self.reader.read(&mut size[read..]) {
+                Ok(0) => break,
+                Ok(n) => read += n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(_) => return Some(Err(RingItemError::HeaderReadFailed)),
+            }
+        }
+        if read == 0 {
+            return None;
+        } else if read < size.len() {
+            return Some(Err(RingItemError::FileTooSmall));
+        }
+        let size = u32::from_ne_bytes(size) as usize;
+        if size > MAX_LZ4_ITEM_SIZE {
+            return Some(Err(RingItemError::InvalidHeader));
+        }
+
+        let mut frame = vec![0_u8; size];
+        if self.reader.read_exact(&mut frame).is_err() {
+            return Some(Err(RingItemError::FileTooSmall));
+        }
+        match uncompressed_size(&frame) {
+            Ok((item_size, compressed)) if item_size <= MAX_LZ4_ITEM_SIZE => {
+                match decompress(compressed, item_size) {
+                    Ok(bytes) => Some(RingItem::from_slice(&bytes)),
+                    Err(_) => Some(Err(RingItemError::InvalidHeader)),
+                }
+            }
+            _ => Some(Err(RingItemError::InvalidHeader)),
+        }
+    }
+}
+
+/// Write ring items to an LZ4 framed file.  The file is created
+/// or replaced.
+///
+pub fn write_lz4(items: impl Iterator<Item = RingItem>, path: &Path) -> std::io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    for item in items {
+        let frame = compress_prepend_size(&item.to_bytes());
+        file.write_all(&u32::to_ne_bytes(frame.len() as u32))?;
+        file.write_all(&frame)?;
+    }
+    file.flush()
+}
+
+#[cfg(test)]
+mod lz4_source_tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn make_items() -> Vec<RingItem> {
+        let mut result = Vec::new();
+        for i in 0..100_u32 {
+            let mut item = RingItem::new_with_body_header(30, i as u64 * 10, 1, 0);
+            for j in 0..i {
+                item.add(j % 4);
+            }
+            result.push(item);
+        }
+        result
+    }
+
+    #[test]
+    fn lz4_1() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("run.evt.lz4");
+        let items = make_items();
+        write_lz4(make_items().into_iter(), &path).unwrap();
+
+        let read: Vec<RingItem> = Lz4RingItemSource::open(&path)
+            .unwrap()
+            .map(|i| i.unwrap())
+            .collect();
+        assert_eq!(items.len(), read.len());
+        for (o, i) in items.iter().zip(read.iter()) {
+            assert_eq!(o.to_bytes(), i.to_bytes());
+        }
+    }
+    #[test]
+    fn seek_1() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("run.evt.lz4");
+        let items = make_items();
+        write_lz4(make_items().into_iter(), &path).unwrap();
+
+        let mut source = Lz4RingItemSource::open(&path).unwrap();
+        source.seek_item(50).unwrap();
+        assert_eq!(
+            items[50].to_bytes(),
+            source.next().unwrap().unwrap().to_bytes()
+        );
+        assert_eq!(
+            items[51].to_bytes(),
+            source.next().unwrap().unwrap().to_bytes()
+        );
+
+        source.seek_item(0).unwrap();
+        assert_eq!(
+            items[0].to_bytes(),
+            source.next().unwrap().unwrap().to_bytes()
+        );
+
+        source.seek_item(99).unwrap();
+        assert_eq!(
+            items[99].to_bytes(),
+            source.next().unwrap().unwrap().to_bytes()
+        );
+        assert!(source.next().is_none());
+
+        assert!(source.seek_item(100).is_err());
+    }
+    #[test]
+    fn truncated_1() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("run.evt.lz4");
+        write_lz4(make_items().into_iter().take(2), &path).unwrap();
+        let len = std::fs::metadata(&path).unwrap().len();
+        let file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(len - 1).unwrap();
+
+        let mut source = Lz4RingItemSource::open(&path).unwrap();
+        assert!(source.next().unwrap().is_ok());
+        assert!(matches!(
+            source.next().unwrap(),
+            Err(RingItemError::FileTooSmall)
+        ));
+    }
+    #[test]
+    fn truncated_2() {
+        // The file ends part way through a frame size:
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("run.evt.lz4");
+        write_lz4(make_items().into_iter().take(1), &path).unwrap();
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes.extend_from_slice(&[1, 0]);
+        std::fs::write(&path, &bytes).unwrap();
+
+        let mut source = Lz4RingItemSource::open(&path).unwrap();
+        assert!(source.next().unwrap().is_ok());
+        assert!(matches!(
+            source.next(),
+            Some(Err(RingItemError::FileTooSmall))
+        ));
+    }
+    #[test]
+    fn oversized_1() {
+        // A frame size larger than the maximum isn't allocated:
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("run.evt.lz4");
+        let mut bytes = u32::to_ne_bytes(MAX_LZ4_ITEM_SIZE as u32 + 1).to_vec();
+        bytes.extend_from_slice(&[0; 16]);
+        std::fs::write(&path, &bytes).unwrap();
+
+        let mut source = Lz4RingItemSource::open(&path).unwrap();
+        assert!(matches!(
+            source.next(),
+            Some(Err(RingItemError::InvalidHeader))
+        ));
+    }
+    #[test]
+    fn oversized_2() {
+        // A small frame claiming to decompress to more than the maximum:
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("run.evt.lz4");
+        let mut frame = u32::to_le_bytes(u32::MAX).to_vec();
+        frame.extend_from_slice(&[0; 8]);
+        let mut bytes = u32::to_ne_bytes(frame.len() as u32).to_vec();
+        bytes.extend_from_slice(&frame);
+        std::fs::write(&path, &bytes).unwrap();
+
+        let mut source = Lz4RingItemSource::open(&path).unwrap();
+        assert!(matches!(
+            source.next(),
+            Some(Err(RingItemError::InvalidHeader))
+        ));
+    }
+}
@@ -0,0 +1,185 @@
+//! MockRingItemSource supplies a canned sequence of ring items (and
+//! errors) so that code that consumes ring item sources can be tested
+//! without run files.
+//!
+use super::*;
+use std::collections::VecDeque;
+
+///
+/// Returns its items in order.  A repeating source starts over
+/// after its last item, forever.
+///
+pub struct MockRingItemSource {
+    items: VecDeque<RingItemResult>,
+    repeat: bool,
+}
+
+impl MockRingItemSource {
+    pub fn new(items: Vec<RingItem>) -> MockRingItemSource {
+        MockRingItemSource {
+            items: items.into_iter().map(Ok).collect(),
+            repeat: false,
+        }
+    }
+    pub fn new_repeating(items: Vec<RingItem>) -> MockRingItemSource {
+        MockRingItemSource {
+            items: items.into_iter().map(Ok).collect(),
+            repeat: true,
+        }
+    }
+    /// Return err instead of the item at pos (counting from the next
+    /// item the source will return).  Positions beyond the last item
+    /// are ignored.
+    pub fn inject_error(&mut self, pos: usize, err: RingItemError) -> &mut Self {
+        if let Some(item) = self.items.get_mut(pos) {
+            *item = Err(err);
+        }
+        self
+    }
+    /// Number of items (and errors) left to return before the end
+    /// (or before starting over).
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+impl Iterator for MockRingItemSource {
+    type Item = RingItemResult;
+
+    fn next(&mut self) -> Option<RingItemResult> {
+        let result = self.items.pop_front()?;
+        if self.repeat {
+            self.items.push_back(result.clone());
+        }
+        Some(result)
+    }
+}
+
+///
+/// Builds a MockRingItemSource an item or error at a time e.g.
+///
+/// ```ignore
+/// let source = MockRingItemSourceBuilder::new()
+///     .item(begin)
+///     .items(events)
+///     .error(RingItemError::InvalidHeader)
+///     .build();
+/// ```
+///
+#[derive(Default)]
+pub struct MockRingItemSourceBuilder {
+    items: Vec<RingItemResult>,
+    repeat: bool,
+}
+
+impl MockRingItemSourceBuilder {
+    pub fn new() -> MockRingItemSourceBuilder {
+        MockRingItemSourceBuilder::default()
+    }
+    pub fn item(&mut self, item: RingItem) -> &mut Self {
+        self.items.push(Ok(item));
+        self
+    }
+    pub fn items(&mut self, items: Vec<RingItem>) -> &mut Self {
+        self.items.extend(items.into_iter().map(Ok));
+        self
+    }
+    pub fn error(&mut self, err: RingItemError) -> &mut Self {
+        self.items.push(Err(err));
+        self
+    }
+    pub fn repeat(&mut self, repeat: bool) -> &mut Self {
+        self.repeat = repeat;
+        self
+    }
+    pub fn build(&self) -> MockRingItemSource {
+        MockRingItemSource {
+            items: self.items.iter().cloned().collect(),
+            repeat: self.repeat,
+        }
+    }
+}
+
+#[cfg(test)]
+mod mock_tests {
+    use super::*;
+
+    // Type ids of the items used:
+
+    const PHYSICS_EVENT: u32 = 30;
+    const END_RUN: u32 = 2;
+
+    fn make_items(n: u32) -> Vec<RingItem> {
+        (0..n)
+            .map(|i| {
+                let mut item = RingItem::new(PHYSICS_EVENT);
+                item.add(i);
+                item
+            })
+            .collect()
+    }
+    fn value(item: &RingItemResult) -> u32 {
+        item.as_ref().unwrap().payload_field::<u32>(0).unwrap()
+    }
+
+    #[test]
+    fn new_1() {
+        let mut source = MockRingItemSource::new(make_items(3));
+        assert_eq!(3, source.len());
+        for i in 0..3 {
+            assert_eq!(i, value(&source.next().unwrap()));
+        }
+        assert!(source.next().is_none());
+        assert!(source.next().is_none());
+        assert!(source.is_empty());
+    }
+    #[test]
+    fn error_1() {
+        let mut source = MockRingItemSource::new(make_items(4));
+        source
+            .inject_error(2, RingItemError::InvalidHeader)
+            .inject_error(10, RingItemError::FileTooSmall);
+        let results: Vec<RingItemResult> = source.collect();
+        assert_eq!(4, results.len());
+        assert_eq!(0, value(&results[0]));
+        assert_eq!(1, value(&results[1]));
+        assert_eq!(Some(RingItemError::InvalidHeader), results[2].clone().err());
+        assert_eq!(3, value(&results[3]));
+    }
+    #[test]
+    fn repeat_1() {
+        let source = MockRingItemSource::new_repeating(make_items(2));
+        let values: Vec<u32> = source.take(5).map(|r| value(&r)).collect();
+        assert_eq!(vec![0, 1, 0, 1, 0], values);
+
+        let mut empty = MockRingItemSource::new_repeating(vec![]);
+        assert!(empty.next().is_none());
+    }
+    #[test]
+    fn builder_1() {
+        let items = make_items(3);
+        let mut source = MockRingItemSourceBuilder::new()
+            .items(items)
+            .error(RingItemError::HeaderReadFailed)
+            .item(RingItem::new(END_RUN))
+            .build();
+        for i in 0..3 {
+            assert_eq!(i, value(&source.next().unwrap()));
+        }
+        assert_eq!(
+            Some(RingItemError::HeaderReadFailed),
+            source.next().unwrap().err()
+        );
+        assert_eq!(END_RUN, source.next().unwrap().unwrap().type_id());
+        assert!(source.next().is_none());
+
+        let source = MockRingItemSourceBuilder::new()
+            .item(RingItem::new(END_RUN))
+            .repeat(true)
+            .build();
+        assert_eq!(3, source.take(3).count());
+    }
+}
@@ -0,0 +1,265 @@
+//!  The sources module provides ring item sources.  A source is
+//!  an iterator over RingItemResult values.  The source ends (returns
+//!  None) when there are no more ring items.  If a source
+//!  encounters a malformed or truncated ring item it returns the
+//!  error.
+//!
+//!  *  RingItemIter reads ring items from anything that implements
+//!     Read, e.g. a File, and can report its progress.
+//!  *  TcpRingItemSource (tcp submodule) reads ring items from a live
+//!     NSCLDAQ data server.
+//!  *  GzipRingItemSource (gzip submodule) reads ring items from
+//!     run files that may be gzip compressed.
+//!  *  Lz4RingItemSource (lz4 submodule) reads ring items from
+//!     LZ4 compressed run files.
+//!  *  MergedRingItemIter (segments submodule) reads the segment
+//!     files of a run as a single stream.
+//!  *  MockRingItemSource (mock submodule) returns a canned sequence
+//!     of ring items and errors for tests.
+//!
+use crate::ring_items::{name_to_type_id, RingItem, RingItemError, RingItemResult};
+use std::io::{Read, Seek, SeekFrom};
+
+pub mod tcp;
+pub use tcp::*;
+pub mod gzip;
+pub use gzip::*;
+pub mod lz4;
+pub use lz4::*;
+pub mod segments;
+pub use segments::*;
+pub mod mock;
+pub use mock::*;
+
+/// The default number of items between progress callbacks.
+pub const DEFAULT_PROGRESS_INTERVAL: u64 = 1000;
+
+///
+/// Iterates over the ring items in a reader.  The end of
+/// the data is detected when a ring item header can't be read.
+///
+/// The iterator counts the items and bytes it has read.  For long
+/// runs, a progress callback can be given that's called with
+/// (items_read, bytes_read) every progress interval items.
+///
+pub struct RingItemIter<R: Read> {
+    reader: R,
+    items_read: u64,
+    bytes_read: u64,
+    progress: Option<Box<dyn Fn(u64, u64)>>,
+    progress_interval: u64,
+    length: Option<u64>, // Of seekable readers, once known.
+}
+
+impl<R: Read> RingItemIter<R> {
+    pub fn new(reader: R) -> RingItemIter<R> {
+        RingItemIter {
+            reader,
+            items_read: 0,
+            bytes_read: 0,
+            progress: None,
+            progress_interval: DEFAULT_PROGRESS_INTERVAL,
+            length: None,
+        }
+    }
+    /// Call cb with (items_read, bytes_read) every progress
+    /// interval items.
+    pub fn with_progress_callback(mut self, cb: impl Fn(u64, u64) + 'static) -> Self {
+        self.progress = Some(Box::new(cb));
+        self
+    }
+    /// Set the number of items between progress callbacks (at least 1).
+    pub fn with_progress_interval(mut self, n: u64) -> Self {
+        self.progress_interval = n.max(1);
+        self
+    }
+    /// The number of ring items read so far.
+    pub fn items_read(&self) -> u64 {
+        self.items_read
+    }
+    /// The number of bytes of ring items read so far.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+    /// Give back the reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+    /// Only pass ring items with the type named (e.g. "PHYSICS_EVENT").
+    /// Errors are still passed through.  If the name is not a known
+    /// ring item type, no items are passed.
+    ///
+    pub fn filter_by_name(self, name: &str) -> impl Iterator<Item = RingItemResult> {
+        let type_id = name_to_type_id(name);
+        self.filter(move |item| match item {
+            Ok(i) => Some(i.type_id()) == type_id,
+            Err(_) => true,
+        })
+    }
+}
+
+impl<R: Read + Seek> RingItemIter<R> {
+    ///
+    /// How far through its reader (e.g. a File) the iterator is: the
+    /// current position over the length.  None if the reader can't
+    /// report those or is empty.
+    ///
+    pub fn progress_fraction(&mut self) -> Option<f64> {
+        let position = self.reader.stream_position().ok()?;
+        if self.length.is_none() {
+            let length = self.reader.seek(SeekFrom::End(0)).ok()?;
+            self.reader.seek(SeekFrom::Start(position)).ok()?;
+            self.length = Some(length);
+        }
+        match self.length {
+            Some(0) | None => None,
+            Some(length) => Some(position as f64 / length as f64),
+        }
+    }
+}
+
+impl<R: Read> Iterator for RingItemIter<R> {
+    type Item = RingItemResult;
+
+    fn next(&mut self) -> Option<RingItemResult> {
+        match RingItem::read_item(&mut self.reader) {
+            Ok(item) => {
+                self.items_read += 1;
+                self.bytes_read += item.size() as u64;
+                if let Some(cb) = &self.progress {
+                    if self.items_read.is_multiple_of(self.progress_interval) {
+                        cb(self.items_read, self.bytes_read);
+                    }
+                }
+                Some(Ok(item))
+            }
+            Err(RingItemError::HeaderReadFailed) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod ring_item_iter_tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn iter_1() {
+        // Empty reader:
+
+        let data: Vec<u8> = Vec::new();
+        let mut iter = RingItemIter::new(data.as_slice());
+        assert!(iter.next().is_none());
+    }
+    #[test]
+    fn iter_2() {
+        let mut data: Vec<u8> = Vec::new();
+        for i in 0..10 {
+            let mut item = RingItem::new(i);
+            item.add(i);
+            item.write_to(&mut data).unwrap();
+        }
+        let items: Vec<RingItem> = RingItemIter::new(data.as_slice())
+            .map(|i| i.unwrap())
+            .collect();
+        assert_eq!(10, items.len());
+        for (i, item) in items.iter().enumerate() {
+            assert_eq!(i as u32, item.type_id());
+            assert_eq!(i.to_ne_bytes()[0..4], item.payload()[0..4]);
+        }
+    }
+    #[test]
+    fn filter_1() {
+        let mut data: Vec<u8> = Vec::new();
+        for i in 0..10 {
+            let type_id = if i % 2 == 0 { 30 } else { 1 };
+            RingItem::new(type_id).write_to(&mut data).unwrap();
+        }
+        let events: Vec<RingItem> = RingItemIter::new(data.as_slice())
+            .filter_by_name("PHYSICS_EVENT")
+            .map(|i| i.unwrap())
+            .collect();
+        assert_eq!(5, events.len());
+        assert!(events.iter().all(|e| e.type_id() == 30));
+
+        let begins = RingItemIter::new(data.as_slice()).filter_by_name("BEGIN_RUN");
+        assert_eq!(5, begins.count());
+
+        let none = RingItemIter::new(data.as_slice()).filter_by_name("NO_SUCH_TYPE");
+        assert_eq!(0, none.count());
+    }
+    #[test]
+    fn iter_3() {
+        // Truncated final item:
+
+        let mut data: Vec<u8> = Vec::new();
+        RingItem::new(1).write_to(&mut data).unwrap();
+        let mut item = RingItem::new(2);
+        item.add(1234_u32);
+        item.write_to(&mut data).unwrap();
+        data.pop();
+
+        let mut iter = RingItemIter::new(data.as_slice());
+        assert!(iter.next().unwrap().is_ok());
+        assert!(matches!(
+            iter.next().unwrap(),
+            Err(RingItemError::FileTooSmall)
+        ));
+        assert!(iter.next().is_none());
+    }
+    #[test]
+    fn progress_1() {
+        // Callbacks every 100 of 1000 items:
+
+        let mut data: Vec<u8> = Vec::new();
+        let mut total_size = 0;
+        for i in 0..1000_u32 {
+            let mut item = RingItem::new(30);
+            for j in 0..i % 7 {
+                item.add(j);
+            }
+            total_size += item.size() as u64;
+            item.write_to(&mut data).unwrap();
+        }
+        let calls = Rc::new(RefCell::new(Vec::new()));
+        let recorder = Rc::clone(&calls);
+        let mut iter = RingItemIter::new(data.as_slice())
+            .with_progress_interval(100)
+            .with_progress_callback(move |items, bytes| recorder.borrow_mut().push((items, bytes)));
+        assert_eq!(1000, iter.by_ref().count());
+
+        let calls = calls.borrow();
+        assert_eq!(10, calls.len());
+        for (i, (items, _)) in calls.iter().enumerate() {
+            assert_eq!(100 * (i as u64 + 1), *items);
+        }
+        assert_eq!(Some(&(1000, total_size)), calls.last());
+        assert_eq!(1000, iter.items_read());
+        assert_eq!(total_size, iter.bytes_read());
+    }
+    #[test]
+    fn progress_2() {
+        // Progress through a seekable reader:
+
+        let mut data: Vec<u8> = Vec::new();
+        for _ in 0..4 {
+            let mut item = RingItem::new(30);
+            item.add(0_u32);
+            item.write_to(&mut data).unwrap();
+        }
+        let mut iter = RingItemIter::new(std::io::Cursor::new(data));
+        assert_eq!(Some(0.0), iter.progress_fraction());
+        iter.next().unwrap().unwrap();
+        assert_eq!(Some(0.25), iter.progress_fraction());
+        iter.next().unwrap().unwrap();
+        iter.next().unwrap().unwrap();
+        iter.next().unwrap().unwrap();
+        assert_eq!(Some(1.0), iter.progress_fraction());
+        assert!(iter.next().is_none());
+
+        let mut empty = RingItemIter::new(std::io::Cursor::new(Vec::<u8>::new()));
+        assert_eq!(None, empty.progress_fraction());
+    }
+}
@@ -0,0 +1,490 @@
+//! Runs that are too large for a single file are written as a set of
+//! segment files, e.g. run-0001-00.evt, run-0001-01.evt...  This
+//! submodule reads those as a single stream of ring items:
+//!
+//! *  open_run_files opens the files in a directory that match a glob
+//!    pattern, in name order.
+//! *  MergedRingItemIter reads the ring items of a sequence of readers
+//!    one reader after the other.
+//! *  scan_run_directory describes the run in a directory of segment
+//!    files (RunManifest).
+//! *  split_file writes a single run file as a set of segment files.
+//!
+use super::*;
+use crate::ring_items::state_change::StateChange;
+use crate::ring_items::{FromRaw, RingVersion};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+// Paths in dir that match pattern, sorted by name:
+
+fn matching_paths(dir: &Path, pattern: &str) -> std::io::Result<Vec<PathBuf>> {
+    let full = dir.join(pattern);
+    let full = full.to_str().ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, "Path is not valid UTF-8")
+    })?;
+    let paths = glob::glob(full)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e.to_string()))?;
+    let mut result = paths
+        .collect::<Result<Vec<PathBuf>, glob::GlobError>>()
+        .map_err(std::io::Error::from)?;
+    result.sort();
+    Ok(result)
+}
+
+///
+/// Open the files in dir whose names match a glob pattern
+/// (e.g. "run-0001-*.evt").  The files are in name order which,
+/// for NSCLDAQ segment names, is the order they were written.
+///
+pub fn open_run_files(dir: &Path, pattern: &str) -> std::io::Result<Vec<File>> {
+    matching_paths(dir, pattern)?
+        .iter()
+        .map(File::open)
+        .collect()
+}
+
+///
+/// Iterates over the ring items of several readers as if they were
+/// one: all items from the first reader, then all from the second and
+/// so on.  As with RingItemIter, an error reading an item is passed
+/// on;  iteration then continues with the next reader.
+///
+pub struct MergedRingItemIter<R: Read> {
+    readers: std::vec::IntoIter<R>,
+    current: Option<RingItemIter<R>>,
+}
+
+impl<R: Read> MergedRingItemIter<R> {
+    pub fn new(readers: Vec<R>) -> MergedRingItemIter<R> {
+        let mut readers = readers.into_iter();
+        let current = readers.next().map(RingItemIter::new);
+        MergedRingItemIter { readers, current }
+    }
+}
+impl MergedRingItemIter<BufReader<File>> {
+    /// Read the files returned by open_run_files.
+    pub fn from_files(files: Vec<File>) -> MergedRingItemIter<BufReader<File>> {
+        MergedRingItemIter::new(files.into_iter().map(BufReader::new).collect())
+    }
+}
+
+impl<R: Read> Iterator for MergedRingItemIter<R> {
+    type Item = RingItemResult;
+
+    fn next(&mut self) -> Option<RingItemResult> {
+        loop {
+            let current = self.current.as_mut()?;
+            match current.next() {
+                Some(Err(e)) => {
+                    // The rest of this reader can't be trusted.
+
+                    self.current = self.readers.next().map(RingItemIter::new);
+                    return Some(Err(e));
+                }
+                Some(item) => return Some(item),
+                None => self.current = self.readers.next().map(RingItemIter::new),
+            }
+        }
+    }
+}
+
+/// Describes the run in a directory of segment files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunManifest {
+    pub run_number: u32,
+    pub segment_files: Vec<PathBuf>,
+    pub total_items: u64,
+}
+
+///
+/// Scan the *.evt segment files in a directory and describe the run
+/// they hold.  The run number comes from the BEGIN_RUN state change
+/// items (normally only the first segment has one).  Errors:
+///
+/// *  The directory can't be read or a segment file is malformed.
+/// *  There are no segment files or no BEGIN_RUN item.
+/// *  Segments have BEGIN_RUN items for different runs.
+///
+pub fn scan_run_directory(dir: &Path) -> Result<RunManifest, String> {
+    let segment_files = matching_paths(dir, "*.evt")
+        .map_err(|e| format!("Unable to list {}: {}", dir.display(), e))?;
+    if segment_files.is_empty() {
+        return Err(format!("No segment files in {}", dir.display()));
+    }
+    let begin_run = name_to_type_id("BEGIN_RUN").unwrap();
+    let mut run_number: Option<u32> = None;
+    let mut total_items = 0;
+
+    for path in segment_files.iter() {
+        let file =
+            File::open(path).map_err(|e| format!("Unable to open {}: {}", path.display(), e))?;
+        for item in RingItemIter::new(BufReader::new(file)) {
+            let item = item.map_err(|e| format!("{}: {}", path.display(), e))?;
+            total_items += 1;
+            if item.type_id() == begin_run {
+                let begin: StateChange = item
+                    .to_specific(RingVersion::V11)
+                    .map_err(|e| format!("{}: {}", path.display(), e))?;
+                match run_number {
+                    Some(n) if n != begin.run_number() => {
+                        return Err(format!(
+                            "{} has run {} but earlier segments have run {}",
+                            path.display(),
+                            begin.run_number(),
+                            n
+                        ));
+                    }
+                    _ => run_number = Some(begin.run_number()),
+                }
+            }
+        }
+    }
+    let run_number = run_number
+        .ok_or_else(|| format!("No BEGIN_RUN item in the segments in {}", dir.display()))?;
+    Ok(RunManifest {
+        run_number,
+        segment_files,
+        total_items,
+    })
+}
+
+fn invalid_data(detail: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, detail)
+}
+
+// The run number of the first BEGIN_RUN item in a file:
+
+fn first_run_number(path: &Path) -> std::io::Result<u32> {
+    let begin_run = name_to_type_id("BEGIN_RUN").unwrap();
+    for item in RingItemIter::new(BufReader::new(File::open(path)?)) {
+        let item = item.map_err(|e| invalid_data(format!("{}: {}", path.display(), e)))?;
+        if item.type_id() == begin_run {
+            let begin: StateChange = item
+                .to_specific(RingVersion::V11)
+                .map_err(|e| invalid_data(format!("{}: {}", path.display(), e)))?;
+            return Ok(begin.run_number());
+        }
+    }
+    Err(invalid_data(format!(
+        "No BEGIN_RUN item in {}",
+        path.display()
+    )))
+}
+
+///
+/// Split a run file into segment files in output_dir named
+/// run-NNNN-00.evt, run-NNNN-01.evt... where NNNN is the run number of
+/// the file's BEGIN_RUN item.  Each segment is at most max_bytes long.
+/// Items are never split across segments;  an item too big to fit in
+/// an empty segment starts a new segment and nothing else is added to it.
+///
+/// Each segment after the first starts with a copy of the BEGIN_RUN
+/// item and, if the run is paused at that point, the PAUSE_RUN item
+/// that paused it so that every segment is self-contained.  These
+/// copies count against max_bytes.  Readers that merge the segments
+/// (MergedRingItemIter) therefore see those items more than once.
+///
+/// Returns the segment files written, in order.  A malformed input
+/// item or an input with no BEGIN_RUN item is InvalidData.
+///
+pub fn split_file(
+    input: &Path,
+    output_dir: &Path,
+    max_bytes: u64,
+) -> std::io::Result<Vec<PathBuf>> {
+    let run_number = first_run_number(input)?;
+    let begin_run = name_to_type_id("BEGIN_RUN").unwrap();
+    let pause_run = name_to_type_id("PAUSE_RUN").unwrap();
+    let resume_run = name_to_type_id("RESUME_RUN").unwrap();
+    let end_run = name_to_type_id("END_RUN").unwrap();
+
+    let mut segments = Vec::new();
+    let mut begin: Option<RingItem> = None;
+    let mut pause: Option<RingItem> = None;
+    let mut output: Option<BufWriter<File>> = None;
+    let mut segment_bytes: u64 = 0;
+    let mut segment_items = 0; // Not counting repeated state changes.
+
+    for item in RingItemIter::new(BufReader::new(File::open(input)?)) {
+        let item = item.map_err(|e| invalid_data(format!("{}: {}", input.display(), e)))?;
+        let size = item.size() as u64;
+
+        if output.is_none() || (segment_items > 0 && segment_bytes + size > max_bytes) {
+            if let Some(mut previous) = output.take() {
+                previous.flush()?;
+            }
+            let path = output_dir.join(format!("run-{:04}-{:02}.evt", run_number, segments.len()));
+            let mut file = BufWriter::new(File::create(&path)?);
+            segment_bytes = 0;
+            segment_items = 0;
+            for state_change in begin.iter().chain(pause.iter()) {
+                state_change.write_to(&mut file)?;
+                segment_bytes += state_change.size() as u64;
+            }
+            segments.push(path);
+            output = Some(file);
+        }
+        let file = output.as_mut().unwrap();
+        item.write_to(file)?;
+        segment_bytes += size;
+        segment_items += 1;
+        let type_id = item.type_id();
+        if type_id == begin_run {
+            begin = Some(item);
+            pause = None;
+        } else if type_id == pause_run {
+            pause = Some(item);
+        } else if type_id == resume_run || type_id == end_run {
+            pause = None;
+        }
+    }
+    if let Some(mut last) = output {
+        last.flush()?;
+    }
+    Ok(segments)
+}
+
+#[cfg(test)]
+mod segments_tests {
+    use super::*;
+    use crate::ring_items::state_change::StateChangeType;
+    use crate::ring_items::ToRaw;
+
+    // Write a segment file with optional begin run and n events
+    // numbered from first:
+
+    fn write_segment(path: &Path, begin: Option<u32>, first: u32, n: u32) {
+        let mut file = File::create(path).unwrap();
+        if let Some(run) = begin {
+            StateChange::new(StateChangeType::Begin, None, run, 0, 1, "title", None)
+                .to_raw()
+                .write_item(&mut file)
+                .unwrap();
+        }
+        for i in first..first + n {
+            let mut item = RingItem::new(name_to_type_id("PHYSICS_EVENT").unwrap());
+            item.add(i);
+            item.write_item(&mut file).unwrap();
+        }
+    }
+
+    #[test]
+    fn open_1() {
+        let dir = tempfile::tempdir().unwrap();
+        write_segment(&dir.path().join("run-0001-01.evt"), None, 10, 5);
+        write_segment(&dir.path().join("run-0001-00.evt"), Some(1), 0, 10);
+        write_segment(&dir.path().join("run-0002-00.evt"), Some(2), 0, 3);
+
+        let files = open_run_files(dir.path(), "run-0001-*.evt").unwrap();
+        assert_eq!(2, files.len());
+
+        // One stream with the segments in order:
+
+        let items: Vec<RingItem> = MergedRingItemIter::from_files(files)
+            .map(|i| i.unwrap())
+            .collect();
+        assert_eq!(16, items.len());
+        assert_eq!(name_to_type_id("BEGIN_RUN"), Some(items[0].type_id()));
+        for (i, item) in items[1..].iter().enumerate() {
+            assert_eq!(Some(i as u32), item.payload_as::<u32>(0));
+        }
+
+        assert!(open_run_files(dir.path(), "none-*.evt").unwrap().is_empty());
+        assert!(open_run_files(dir.path(), "[").is_err());
+    }
+    #[test]
+    fn scan_1() {
+        let dir = tempfile::tempdir().unwrap();
+        write_segment(&dir.path().join("run-0007-00.evt"), Some(7), 0, 10);
+        write_segment(&dir.path().join("run-0007-01.evt"), None, 10, 5);
+
+        let manifest = scan_run_directory(dir.path()).unwrap();
+        assert_eq!(7, manifest.run_number);
+        assert_eq!(
+            vec![
+                dir.path().join("run-0007-00.evt"),
+                dir.path().join("run-0007-01.evt")
+            ],
+            manifest.segment_files
+        );
+        assert_eq!(16, manifest.total_items);
+    }
+    #[test]
+    fn scan_2() {
+        // Errors:
+
+        let dir = tempfile::tempdir().unwrap();
+        assert!(scan_run_directory(dir.path()).is_err());
+
+        write_segment(&dir.path().join("run-0007-01.evt"), None, 10, 5);
+        assert!(scan_run_directory(dir.path()).is_err());
+
+        write_segment(&dir.path().join("run-0007-00.evt"), Some(7), 0, 10);
+        assert!(scan_run_directory(dir.path()).is_ok());
+        write_segment(&dir.path().join("run-0008-00.evt"), Some(8), 0, 10);
+        assert!(scan_run_directory(dir.path()).is_err());
+    }
+    // A run file with a begin run and n events of event_bytes each:
+
+    fn write_run(path: &Path, run: u32, n: u32, event_bytes: usize) {
+        let mut file = File::create(path).unwrap();
+        StateChange::new(StateChangeType::Begin, None, run, 0, 1, "title", None)
+            .to_raw()
+            .write_item(&mut file)
+            .unwrap();
+        for i in 0..n {
+            let mut item = RingItem::new(name_to_type_id("PHYSICS_EVENT").unwrap());
+            item.add(i);
+            item.add_byte_vec(&vec![0; event_bytes - 16]);
+            item.write_item(&mut file).unwrap();
+        }
+    }
+    fn read_items(path: &Path) -> Vec<RingItem> {
+        RingItemIter::new(BufReader::new(File::open(path).unwrap()))
+            .map(|i| i.unwrap())
+            .collect()
+    }
+    fn event_numbers(items: &[RingItem]) -> Vec<u32> {
+        let physics_event = name_to_type_id("PHYSICS_EVENT").unwrap();
+        items
+            .iter()
+            .filter(|i| i.type_id() == physics_event)
+            .map(|i| i.payload_as::<u32>(0).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn split_1() {
+        // 10 items, 1MB in all, fit in one 3MB segment:
+
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("run.evt");
+        write_run(&input, 12, 10, 100_000);
+        let out = tempfile::tempdir().unwrap();
+
+        let segments = split_file(&input, out.path(), 3_000_000).unwrap();
+        assert_eq!(vec![out.path().join("run-0012-00.evt")], segments);
+        assert_eq!(
+            std::fs::read(&input).unwrap(),
+            std::fs::read(&segments[0]).unwrap()
+        );
+    }
+    #[test]
+    fn split_2() {
+        // at 300KB segments each hold the begin run and two events:
+
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("run.evt");
+        write_run(&input, 12, 10, 100_000);
+        let out = tempfile::tempdir().unwrap();
+
+        let segments = split_file(&input, out.path(), 300_000).unwrap();
+        assert_eq!(5, segments.len());
+        for (i, path) in segments.iter().enumerate() {
+            assert_eq!(out.path().join(format!("run-0012-{:02}.evt", i)), *path);
+            assert!(std::fs::metadata(path).unwrap().len() <= 300_000);
+
+            let items = read_items(path);
+            assert_eq!(3, items.len());
+            assert_eq!(name_to_type_id("BEGIN_RUN"), Some(items[0].type_id()));
+            let first = 2 * i as u32;
+            assert_eq!(vec![first, first + 1], event_numbers(&items));
+        }
+
+        let manifest = scan_run_directory(out.path()).unwrap();
+        assert_eq!(12, manifest.run_number);
+        assert_eq!(15, manifest.total_items);
+    }
+    #[test]
+    fn split_3() {
+        // Items bigger than max_bytes get their own segments (after
+        // the repeated begin run):
+
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("run.evt");
+        write_run(&input, 3, 3, 100_000);
+        let out = tempfile::tempdir().unwrap();
+
+        let segments = split_file(&input, out.path(), 50_000).unwrap();
+        assert_eq!(4, segments.len());
+        assert_eq!(1, read_items(&segments[0]).len());
+        for (i, path) in segments[1..].iter().enumerate() {
+            let items = read_items(path);
+            assert_eq!(2, items.len());
+            assert_eq!(vec![i as u32], event_numbers(&items));
+        }
+    }
+    #[test]
+    fn split_4() {
+        // Errors:
+
+        let dir = tempfile::tempdir().unwrap();
+        let out = tempfile::tempdir().unwrap();
+        let input = dir.path().join("run.evt");
+        assert!(split_file(&input, out.path(), 1000).is_err());
+
+        write_segment(&input, None, 0, 10);
+        let e = split_file(&input, out.path(), 1000).unwrap_err();
+        assert_eq!(std::io::ErrorKind::InvalidData, e.kind());
+        assert!(std::fs::read_dir(out.path()).unwrap().next().is_none());
+    }
+    #[test]
+    fn split_5() {
+        // Only the begin run and, while paused, the pause are repeated:
+
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("run.evt");
+        let mut file = File::create(&input).unwrap();
+        let state_change = |kind: StateChangeType, file: &mut File| {
+            StateChange::new(kind, None, 5, 0, 1, "title", None)
+                .to_raw()
+                .write_item(file)
+                .unwrap();
+        };
+        let event = |i: u32, file: &mut File| {
+            let mut item = RingItem::new(name_to_type_id("PHYSICS_EVENT").unwrap());
+            item.add(i);
+            item.add_byte_vec(&vec![0; 100_000 - 16]);
+            item.write_item(file).unwrap();
+        };
+        state_change(StateChangeType::Begin, &mut file);
+        event(0, &mut file);
+        event(1, &mut file);
+        event(2, &mut file);
+        state_change(StateChangeType::Pause, &mut file);
+        event(3, &mut file);
+        event(4, &mut file);
+        state_change(StateChangeType::Resume, &mut file);
+        event(5, &mut file);
+        event(6, &mut file);
+        state_change(StateChangeType::End, &mut file);
+        drop(file);
+        let out = tempfile::tempdir().unwrap();
+
+        let segments = split_file(&input, out.path(), 250_000).unwrap();
+        let names = |path: &Path| -> Vec<String> {
+            read_items(path)
+                .iter()
+                .map(|i| match event_numbers(std::slice::from_ref(i)).first() {
+                    Some(n) => n.to_string(),
+                    None => crate::ring_items::type_id_to_name(i.type_id())
+                        .unwrap()
+                        .to_string(),
+                })
+                .collect()
+        };
+        let expected: Vec<Vec<&str>> = vec![
+            vec!["BEGIN_RUN", "0", "1"],
+            vec!["BEGIN_RUN", "2", "PAUSE_RUN", "3"],
+            vec!["BEGIN_RUN", "PAUSE_RUN", "4", "RESUME_RUN", "5"],
+            vec!["BEGIN_RUN", "6", "END_RUN"],
+        ];
+        assert_eq!(expected.len(), segments.len());
+        for (path, expected) in segments.iter().zip(expected.iter()) {
+            assert_eq!(*expected, names(path));
+            assert!(std::fs::metadata(path).unwrap().len() <= 250_000);
+        }
+    }
+}
@@ -0,0 +1,180 @@
+//! TcpRingItemSource reads ring items from an NSCLDAQ data server.
+//! Since a read from a socket can return any number of bytes,
+//! bytes are accumulated until a complete ring item is available.
+//!
+use super::*;
+use std::io::ErrorKind;
+use std::net::TcpStream;
+
+/// The port on which NSCLDAQ normally serves ring buffer data.
+pub const NSCLDAQ_PORT: u16 = 11999;
+
+const READ_SIZE: usize = 64 * 1024;
+
+pub struct TcpRingItemSource {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+    chunk: Vec<u8>, // Reused for each read from the stream.
+    done: bool,
+}
+
+impl TcpRingItemSource {
+    /// Connect to the data server on host:port.
+    pub fn connect(host: &str, port: u16) -> std::io::Result<TcpRingItemSource> {
+        Ok(Self::from_stream(TcpStream::connect((host, port))?))
+    }
+    /// Use an already connected stream.
+    pub fn from_stream(stream: TcpStream) -> TcpRingItemSource {
+        TcpRingItemSource {
+            stream,
+            buffer: Vec::new(),
+            chunk: vec![0_u8; READ_SIZE],
+            done: false,
+        }
+    }
+    // If the buffer has a complete ring item, remove and return it.
+
+    fn complete_item(&mut self) -> Option<RingItemResult> {
+        let size = RingItem::parse_size(&self.buffer)? as usize;
+        if size < 3 * std::mem::size_of::<u32>() {
+            // Can't resynchronize with the stream after this:
+
+            self.done = true;
+            return Some(Err(RingItemError::InvalidHeader));
+        }
+        if self.buffer.len() >= size {
+            let result = RingItem::from_slice(&self.buffer[0..size]);
+            self.buffer.drain(0..size);
+            Some(result)
+        } else {
+            None
+        }
+    }
+}
+
+impl Iterator for TcpRingItemSource {
+    type Item = RingItemResult;
+
+    /// The source ends when the server closes the connection or the
+    /// connection fails.  If the connection closes in the middle of a
+    /// ring item, FileTooSmall is returned.
+    ///
+    fn next(&mut self) -> Option<RingItemResult> {
+        while !self.done {
+            if let Some(result) = self.complete_item() {
+                return Some(result);
+            }
+            match self.stream.read(&mut self.chunk) {
+                Ok(0) => {
+                    self.done = true;
+                    if !self.buffer.is_empty() {
+                        self.buffer.clear();
+                        return Some(Err(RingItemError::FileTooSmall));
+                    }
+                }
+                Ok(n) => self.buffer.extend_from_slice(&self.chunk[0..n]),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => self.done = true,
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tcp_source_tests {
+    use super::*;
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::Duration;
+
+    // Start a server that writes the bytes in the chunks given with
+    // a small delay between chunks so that the client sees partial items.
+    // Returns the port.
+
+    fn serve(chunks: Vec<Vec<u8>>) -> (u16, thread::JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            for chunk in chunks {
+                stream.write_all(&chunk).unwrap();
+                stream.flush().unwrap();
+                thread::sleep(Duration::from_millis(10));
+            }
+        });
+        (port, handle)
+    }
+    fn make_items() -> (RingItem, RingItem) {
+        let mut item1 = RingItem::new(1);
+        item1.add(0x12345678_u32);
+        let mut item2 = RingItem::new_with_body_header(30, 0x1234, 2, 0);
+        item2.add_byte_vec(&vec![1, 2, 3, 4, 5, 6, 7, 8]);
+        (item1, item2)
+    }
+
+    #[test]
+    fn connect_1() {
+        // Two items in one write:
+
+        let (item1, item2) = make_items();
+        let mut bytes = item1.to_bytes();
+        bytes.extend(item2.to_bytes());
+        let (port, handle) = serve(vec![bytes]);
+
+        let items: Vec<RingItem> = TcpRingItemSource::connect("127.0.0.1", port)
+            .unwrap()
+            .map(|i| i.unwrap())
+            .collect();
+        handle.join().unwrap();
+
+        assert_eq!(2, items.len());
+        assert_eq!(item1.to_bytes(), items[0].to_bytes());
+        assert_eq!(item2.to_bytes(), items[1].to_bytes());
+    }
+    #[test]
+    fn partial_1() {
+        // Items split across writes, including the size field:
+
+        let (item1, item2) = make_items();
+        let mut bytes = item1.to_bytes();
+        bytes.extend(item2.to_bytes());
+        let chunks: Vec<Vec<u8>> = vec![
+            bytes[0..2].to_vec(),
+            bytes[2..10].to_vec(),
+            bytes[10..20].to_vec(),
+            bytes[20..].to_vec(),
+        ];
+        let (port, handle) = serve(chunks);
+
+        let items: Vec<RingItem> = TcpRingItemSource::connect("127.0.0.1", port)
+            .unwrap()
+            .map(|i| i.unwrap())
+            .collect();
+        handle.join().unwrap();
+
+        assert_eq!(2, items.len());
+        assert_eq!(item1.to_bytes(), items[0].to_bytes());
+        assert_eq!(item2.to_bytes(), items[1].to_bytes());
+    }
+    #[test]
+    fn truncated_1() {
+        // Connection closes part way through an item:
+
+        let (item1, item2) = make_items();
+        let mut bytes = item1.to_bytes();
+        let bytes2 = item2.to_bytes();
+        bytes.extend_from_slice(&bytes2[0..bytes2.len() - 1]);
+        let (port, handle) = serve(vec![bytes]);
+
+        let mut source = TcpRingItemSource::connect("127.0.0.1", port).unwrap();
+        assert!(source.next().unwrap().is_ok());
+        assert!(matches!(
+            source.next().unwrap(),
+            Err(RingItemError::FileTooSmall)
+        ));
+        assert!(source.next().is_none());
+        handle.join().unwrap();
+    }
+}
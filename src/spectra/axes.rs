@@ -0,0 +1,185 @@
+//!  Axis types that ndhistogram does not supply.
+//!
+//!  *  LogUniform - bins that are uniform in the log of the
+//!     coordinate.  Energy spectra often span orders of magnitude
+//!     and are better viewed this way.
+//!
+//!  Spectra keep H1D/H2D histograms (uniform axes) so that they can all
+//!  be handled the same way.  Spectra with a log axis therefore
+//!  histogram ln(value) on a uniform axis from ln(low) to ln(high);
+//!  since ln is monotonic, the bins are the same as the LogUniform bins.
+//!  LogUniform::log_coordinate does that mapping.
+//!
+use ndhistogram::axis::{Axis, BinInterval, Uniform};
+
+///
+/// An axis with bins geometrically spaced between low and high.
+/// Bin k (counting from 0, not including the underflow bin) covers
+/// low * (high/low)^(k/bins) to low * (high/low)^((k+1)/bins).
+/// As with axis::Uniform there are underflow and overflow bins so
+/// num_bins is bins + 2.  Values that are not positive are in the
+/// underflow bin.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogUniform {
+    bins: usize,
+    low: f64,
+    high: f64,
+}
+
+impl LogUniform {
+    /// Create the axis.  low and high must both be positive and
+    /// high must be larger than low;  there must be at least one bin.
+    pub fn new(bins: usize, low: f64, high: f64) -> Result<LogUniform, String> {
+        if bins == 0 {
+            return Err(String::from("A log axis needs at least one bin"));
+        }
+        if low <= 0.0 || high <= 0.0 {
+            return Err(String::from("Log axis limits must be positive"));
+        }
+        if high <= low {
+            return Err(String::from("Log axis high limit must be above low limit"));
+        }
+        Ok(LogUniform { bins, low, high })
+    }
+    pub fn low(&self) -> f64 {
+        self.low
+    }
+    pub fn high(&self) -> f64 {
+        self.high
+    }
+    pub fn bins(&self) -> usize {
+        self.bins
+    }
+    /// The uniform axis on ln(coordinate) that has the same bins as
+    /// this axis.
+    pub fn to_uniform(&self) -> Uniform {
+        Uniform::new(self.bins, self.low.ln(), self.high.ln())
+    }
+    /// Map a coordinate to the coordinate on the to_uniform axis.
+    /// Values that are not positive map to negative infinity
+    /// (underflow) rather than NaN.
+    pub fn log_coordinate(value: f64) -> f64 {
+        if value > 0.0 {
+            value.ln()
+        } else {
+            f64::NEG_INFINITY
+        }
+    }
+    // Boundary k of the bins:
+    fn boundary(&self, k: usize) -> f64 {
+        self.low * (self.high / self.low).powf(k as f64 / self.bins as f64)
+    }
+}
+
+impl Axis for LogUniform {
+    type Coordinate = f64;
+    type BinInterval = BinInterval<f64>;
+
+    fn index(&self, coordinate: &f64) -> Option<usize> {
+        let v = *coordinate;
+        if v.is_nan() {
+            return None;
+        }
+        if v < self.low {
+            return Some(0);
+        }
+        if v >= self.high {
+            return Some(self.bins + 1);
+        }
+        let bin = ((v / self.low).ln() / (self.high / self.low).ln() * self.bins as f64).floor();
+
+        // Rounding can put values just below high into bin 'bins'.
+
+        Some((bin as usize).min(self.bins - 1) + 1)
+    }
+    fn num_bins(&self) -> usize {
+        self.bins + 2
+    }
+    fn bin(&self, index: usize) -> Option<BinInterval<f64>> {
+        if index == 0 {
+            Some(BinInterval::underflow(self.low))
+        } else if index == self.bins + 1 {
+            Some(BinInterval::overflow(self.high))
+        } else if index > self.bins + 1 {
+            None
+        } else {
+            Some(BinInterval::new(
+                self.boundary(index - 1),
+                self.boundary(index),
+            ))
+        }
+    }
+    fn indices(&self) -> Box<dyn Iterator<Item = usize>> {
+        Box::new(0..self.num_bins())
+    }
+}
+
+#[cfg(test)]
+mod log_uniform_tests {
+    use super::*;
+    use ndhistogram::value::Sum;
+    use ndhistogram::*;
+
+    #[test]
+    fn new_1() {
+        assert!(LogUniform::new(0, 1.0, 10.0).is_err());
+        assert!(LogUniform::new(10, 0.0, 10.0).is_err());
+        assert!(LogUniform::new(10, -1.0, 10.0).is_err());
+        assert!(LogUniform::new(10, 10.0, 1.0).is_err());
+        let a = LogUniform::new(10, 1.0, 1000.0).unwrap();
+        assert_eq!(1.0, a.low());
+        assert_eq!(1000.0, a.high());
+        assert_eq!(10, a.bins());
+        assert_eq!(12, a.num_bins());
+    }
+    #[test]
+    fn index_1() {
+        let a = LogUniform::new(3, 1.0, 1000.0).unwrap();
+        assert_eq!(Some(0), a.index(&0.5));
+        assert_eq!(Some(0), a.index(&0.0));
+        assert_eq!(Some(0), a.index(&-5.0));
+        assert_eq!(Some(1), a.index(&1.0));
+        assert_eq!(Some(1), a.index(&9.0));
+        assert_eq!(Some(2), a.index(&11.0));
+        assert_eq!(Some(3), a.index(&999.0));
+        assert_eq!(Some(4), a.index(&1000.0));
+        assert_eq!(None, a.index(&f64::NAN));
+
+        // Same bins as the uniform axis on ln:
+
+        let u = a.to_uniform();
+        for v in [0.5, 1.0, 5.0, 15.0, 150.0, 999.0, 2000.0] {
+            assert_eq!(a.index(&v), u.index(&LogUniform::log_coordinate(v)));
+        }
+        assert_eq!(Some(0), u.index(&LogUniform::log_coordinate(-1.0)));
+    }
+    #[test]
+    fn bin_1() {
+        let a = LogUniform::new(2, 1.0, 100.0).unwrap();
+        assert_eq!(Some(BinInterval::underflow(1.0)), a.bin(0));
+        let b = a.bin(1).unwrap();
+        assert_eq!(Some(1.0), b.start());
+        assert!((b.end().unwrap() - 10.0).abs() < 1.0e-9);
+        let b = a.bin(2).unwrap();
+        assert!((b.start().unwrap() - 10.0).abs() < 1.0e-9);
+        assert!((b.end().unwrap() - 100.0).abs() < 1.0e-9);
+        assert_eq!(Some(BinInterval::overflow(100.0)), a.bin(3));
+        assert!(a.bin(4).is_none());
+    }
+    #[test]
+    fn fill_1() {
+        // Log uniformly distributed values give equal occupancy:
+
+        let mut h = ndhistogram!(LogUniform::new(10, 1.0, 1.0e6).unwrap(); Sum);
+        for i in 0..1000 {
+            let v = 1.0e6_f64.powf((i as f64 + 0.5) / 1000.0);
+            h.fill(&v);
+        }
+        for i in 1..=10 {
+            assert_eq!(100.0, h.value_at_index(i).unwrap().get());
+        }
+        assert_eq!(0.0, h.value_at_index(0).unwrap().get());
+        assert_eq!(0.0, h.value_at_index(11).unwrap().get());
+    }
+}
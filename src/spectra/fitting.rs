@@ -0,0 +1,499 @@
+//!  Simple fitting and statistics for 1-d spectra:
+//!
+//!  *  centroid_1d - the count weighted mean of a region.
+//!  *  fwhm_1d - the full width at half maximum of the highest peak
+//!     in a region.
+//!  *  fit_gaussian_1d - a Levenberg-Marquardt fit of a Gaussian to a
+//!     region.
+//!  *  chi2_test_1d - Pearson chi square test of the compatibility of
+//!     two spectra, with p_value to turn the result into a probability.
+//!
+//!  Regions are given in axis coordinates; a bin is in the region if
+//!  its center is in [xlow, xhigh).  Regions with channels that are not
+//!  finite (e.g. NaNs from apply_function_1d) are errors.
+//!
+use super::*;
+use libm::lgamma;
+use ndhistogram::axis::Axis;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GaussianFitResult {
+    pub amplitude: f64,
+    pub mean: f64,
+    pub sigma: f64,
+    pub chi2: f64,
+}
+
+const MAX_ITERATIONS: usize = 100;
+const FWHM_PER_SIGMA: f64 = 2.354820045; // 2*sqrt(2 ln 2)
+
+// The (bin center, counts) pairs of the bins in a region of a 1-d
+// spectrum.  Err if any of the counts are not finite.
+
+fn region(spec: &dyn Spectrum, xlow: f64, xhigh: f64) -> Result<Vec<(f64, f64)>, String> {
+    let histogram = spec
+        .get_histogram_1d()
+        .ok_or(format!("{} is not a 1-d spectrum", spec.get_name()))?;
+    let histogram = histogram.borrow();
+    let axis = histogram.axes().as_tuple().0.clone();
+    let points: Vec<(f64, f64)> = (1..axis.num_bins() - 1)
+        .filter_map(|i| match axis.bin(i) {
+            Some(BinInterval::Bin { start, end }) => Some(((start + end) / 2.0, i)),
+            _ => None,
+        })
+        .filter(|(x, _)| *x >= xlow && *x < xhigh)
+        .map(|(x, i)| (x, histogram.value_at_index(i).unwrap().get()))
+        .collect();
+    if let Some((x, _)) = points.iter().find(|(_, n)| !n.is_finite()) {
+        return Err(format!("The channel at {} is not finite", x));
+    }
+    Ok(points)
+}
+
+///
+/// The centroid of the counts in a region of a 1-d spectrum.  Err is
+/// returned if the spectrum is not 1-d or the region has no counts.
+///
+pub fn centroid_1d(spec: &dyn Spectrum, xlow: f64, xhigh: f64) -> Result<f64, String> {
+    let points = region(spec, xlow, xhigh)?;
+    let sum: f64 = points.iter().map(|(_, n)| n).sum();
+    if sum <= 0.0 {
+        return Err(String::from("There are no counts in the region"));
+    }
+    Ok(points.iter().map(|(x, n)| x * n).sum::<f64>() / sum)
+}
+
+///
+/// The full width at half maximum of the highest peak in a region of
+/// a 1-d spectrum.  The half maximum points are linearly interpolated
+/// between bin centers.  Err is returned if the spectrum is not 1-d,
+/// the region has no counts or the peak does not fall to half its
+/// height on both sides within the region.
+///
+pub fn fwhm_1d(spec: &dyn Spectrum, xlow: f64, xhigh: f64) -> Result<f64, String> {
+    let points = region(spec, xlow, xhigh)?;
+    let (peak, &(_, height)) = points
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1 .1.total_cmp(&b.1 .1))
+        .ok_or("The region is empty")?;
+    if height <= 0.0 {
+        return Err(String::from("There are no counts in the region"));
+    }
+    let half = height / 2.0;
+    // Interpolate the half height point between points i and j:
+    let crossing = |i: usize, j: usize| {
+        let (x1, n1) = points[i];
+        let (x2, n2) = points[j];
+        x1 + (half - n1) * (x2 - x1) / (n2 - n1)
+    };
+    let left = (0..peak)
+        .rev()
+        .find(|&i| points[i].1 < half)
+        .ok_or("The peak does not fall to half height below the maximum")?;
+    let right = (peak + 1..points.len())
+        .find(|&i| points[i].1 < half)
+        .ok_or("The peak does not fall to half height above the maximum")?;
+    Ok(crossing(right - 1, right) - crossing(left, left + 1))
+}
+
+// The Gaussian and its derivatives with respect to amplitude,
+// mean and sigma:
+
+fn gaussian(p: &[f64; 3], x: f64) -> (f64, [f64; 3]) {
+    let (a, m, s) = (p[0], p[1], p[2]);
+    let d = x - m;
+    let e = (-d * d / (2.0 * s * s)).exp();
+    let f = a * e;
+    (f, [e, f * d / (s * s), f * d * d / (s * s * s)])
+}
+// Chi square with Poisson errors; the points all have counts.
+fn chi_square(p: &[f64; 3], points: &[(f64, f64)]) -> f64 {
+    points
+        .iter()
+        .map(|(x, n)| {
+            let r = n - gaussian(p, *x).0;
+            r * r / n
+        })
+        .sum()
+}
+// Solve the 3x3 system a x = b by Gaussian elimination with partial
+// pivoting.  None if the system is singular.
+fn solve3(mut a: [[f64; 3]; 3], mut b: [f64; 3]) -> Option<[f64; 3]> {
+    for col in 0..3 {
+        let pivot = (col..3).max_by(|i, j| a[*i][col].abs().total_cmp(&a[*j][col].abs()))?;
+        if a[pivot][col].is_nan() || a[pivot][col].abs() < f64::MIN_POSITIVE {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        for row in col + 1..3 {
+            let factor = a[row][col] / a[col][col];
+            let pivot_row = a[col];
+            for (v, p) in a[row].iter_mut().zip(pivot_row.iter()).skip(col) {
+                *v -= factor * p;
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+    let mut x = [0.0; 3];
+    for row in (0..3).rev() {
+        let sum: f64 = (row + 1..3).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - sum) / a[row][row];
+    }
+    Some(x)
+}
+
+///
+/// Fit a Gaussian to the region [xlow, xhigh) of a 1-d spectrum.
+/// The initial parameters come from the maximum, centroid_1d and
+/// fwhm_1d of the region.  Chi square is computed assuming Poisson
+/// errors, so empty bins are not used.  Err is returned if the initial
+/// estimates can't be made, there are too few non-empty bins or the fit
+/// does not converge in 100 iterations.
+///
+pub fn fit_gaussian_1d(
+    spec: &dyn Spectrum,
+    xlow: f64,
+    xhigh: f64,
+) -> Result<GaussianFitResult, String> {
+    let points: Vec<(f64, f64)> = region(spec, xlow, xhigh)?
+        .into_iter()
+        .filter(|(_, n)| *n > 0.0)
+        .collect();
+    if points.len() <= 3 {
+        return Err(String::from("Too few non-empty bins to fit a Gaussian"));
+    }
+    let amplitude = points.iter().map(|(_, n)| *n).fold(0.0, f64::max);
+    let mut p = [
+        amplitude,
+        centroid_1d(spec, xlow, xhigh)?,
+        fwhm_1d(spec, xlow, xhigh)? / FWHM_PER_SIGMA,
+    ];
+    let mut chi2 = chi_square(&p, &points);
+    let mut lambda = 1.0e-3;
+
+    for _ in 0..MAX_ITERATIONS {
+        // Normal equations (J^T W J) and J^T W r:
+
+        let mut alpha = [[0.0; 3]; 3];
+        let mut beta = [0.0; 3];
+        for (x, n) in points.iter() {
+            let (f, d) = gaussian(&p, *x);
+            let w = 1.0 / n;
+            for i in 0..3 {
+                beta[i] += w * (n - f) * d[i];
+                for j in 0..3 {
+                    alpha[i][j] += w * d[i] * d[j];
+                }
+            }
+        }
+        for (i, row) in alpha.iter_mut().enumerate() {
+            row[i] *= 1.0 + lambda;
+        }
+        let step = solve3(alpha, beta).ok_or("Singular fit matrix")?;
+        let trial = [p[0] + step[0], p[1] + step[1], p[2] + step[2]];
+        let trial_chi2 = chi_square(&trial, &points);
+        if trial_chi2 <= chi2 {
+            let converged = chi2 - trial_chi2 <= 1.0e-8 * chi2.max(1.0);
+            p = trial;
+            chi2 = trial_chi2;
+            if converged {
+                return Ok(GaussianFitResult {
+                    amplitude: p[0],
+                    mean: p[1],
+                    sigma: p[2].abs(),
+                    chi2,
+                });
+            }
+            lambda /= 10.0;
+        } else {
+            lambda *= 10.0;
+        }
+    }
+    Err(format!(
+        "Gaussian fit did not converge in {} iterations",
+        MAX_ITERATIONS
+    ))
+}
+
+///
+/// Pearson chi square test of the compatibility of two 1-d spectra:
+/// the sum of (a_i - b_i)^2 / (a_i + b_i) over the bins where at least
+/// one of the spectra has counts.  Under/overflow bins are not used.
+/// The result is the chi square and the number of degrees of freedom,
+/// the number of bins summed.  Err is returned if either spectrum is
+/// not 1-d or their axes differ.
+///
+pub fn chi2_test_1d(a: &dyn Spectrum, b: &dyn Spectrum) -> Result<(f64, usize), String> {
+    let ha = a
+        .get_histogram_1d()
+        .ok_or(format!("{} is not a 1-d spectrum", a.get_name()))?;
+    let hb = b
+        .get_histogram_1d()
+        .ok_or(format!("{} is not a 1-d spectrum", b.get_name()))?;
+    let ha = ha.borrow();
+    let hb = hb.borrow();
+    let axis = ha.axes().as_tuple().0.clone();
+    if axis != hb.axes().as_tuple().0 {
+        return Err(format!(
+            "{} and {} have different axes",
+            a.get_name(),
+            b.get_name()
+        ));
+    }
+    let mut chi2 = 0.0;
+    let mut dof = 0;
+    for i in 1..axis.num_bins() - 1 {
+        let na = ha.value_at_index(i).unwrap().get();
+        let nb = hb.value_at_index(i).unwrap().get();
+        if na + nb > 0.0 {
+            chi2 += (na - nb) * (na - nb) / (na + nb);
+            dof += 1;
+        }
+    }
+    Ok((chi2, dof))
+}
+
+///
+/// The probability of a chi square at least as large as chi2 with dof
+/// degrees of freedom, i.e. 1 - CDF(chi2).  With no degrees of freedom
+/// this is 1.
+///
+pub fn p_value(chi2: f64, dof: usize) -> f64 {
+    if dof == 0 || chi2 <= 0.0 {
+        return 1.0;
+    }
+    let a = dof as f64 / 2.0;
+    let x = chi2 / 2.0;
+    if x < a + 1.0 {
+        1.0 - lower_gamma_series(a, x)
+    } else {
+        upper_gamma_fraction(a, x)
+    }
+}
+
+// The regularized incomplete gamma functions (Numerical Recipes 6.2);
+// the series for P(a, x) converges quickly for x < a + 1, the continued
+// fraction for Q(a, x) otherwise.
+
+const GAMMA_EPSILON: f64 = 1.0e-14;
+const GAMMA_ITERATIONS: usize = 1000;
+
+fn lower_gamma_series(a: f64, x: f64) -> f64 {
+    let mut term = 1.0 / a;
+    let mut sum = term;
+    let mut n = a;
+    for _ in 0..GAMMA_ITERATIONS {
+        n += 1.0;
+        term *= x / n;
+        sum += term;
+        if term.abs() < sum.abs() * GAMMA_EPSILON {
+            break;
+        }
+    }
+    sum * (-x + a * x.ln() - lgamma(a)).exp()
+}
+fn upper_gamma_fraction(a: f64, x: f64) -> f64 {
+    let tiny = f64::MIN_POSITIVE / GAMMA_EPSILON;
+    let mut b = x + 1.0 - a;
+    let mut c = 1.0 / tiny;
+    let mut d = 1.0 / b;
+    let mut h = d;
+    for i in 1..GAMMA_ITERATIONS {
+        let an = -(i as f64) * (i as f64 - a);
+        b += 2.0;
+        d = an * d + b;
+        if d.abs() < tiny {
+            d = tiny;
+        }
+        c = b + an / c;
+        if c.abs() < tiny {
+            c = tiny;
+        }
+        d = 1.0 / d;
+        let delta = d * c;
+        h *= delta;
+        if (delta - 1.0).abs() < GAMMA_EPSILON {
+            break;
+        }
+    }
+    (-x + a * x.ln() - lgamma(a)).exp() * h
+}
+
+#[cfg(test)]
+mod fitting_tests {
+    use super::*;
+
+    // 1024 channels on [0, 1024) with a Gaussian with integer counts:
+
+    fn make_spectrum(amplitude: f64, mean: f64, sigma: f64) -> Oned {
+        let mut pd = ParameterDictionary::new();
+        pd.add("p").unwrap();
+        let mut spec = Oned::new("fit", "p", &pd, Some(0.0), Some(1024.0), Some(1024)).unwrap();
+        for i in 0..1024 {
+            let x = i as f64 + 0.5;
+            let d = x - mean;
+            let n = amplitude * (-d * d / (2.0 * sigma * sigma)).exp();
+            spec.fill_channel_1d(x, n.round());
+        }
+        spec
+    }
+
+    #[test]
+    fn centroid_1() {
+        let spec = make_spectrum(1000.0, 500.5, 10.0);
+        assert!((centroid_1d(&spec, 400.0, 600.0).unwrap() - 500.5).abs() < 0.01);
+        assert!(centroid_1d(&spec, 0.0, 100.0).is_err());
+    }
+    #[test]
+    fn fwhm_1() {
+        let spec = make_spectrum(1000.0, 500.5, 10.0);
+        let fwhm = fwhm_1d(&spec, 400.0, 600.0).unwrap();
+        assert!((fwhm - 10.0 * FWHM_PER_SIGMA).abs() < 0.5);
+
+        // Peak doesn't fall to half height in the region:
+
+        assert!(fwhm_1d(&spec, 495.0, 600.0).is_err());
+        assert!(fwhm_1d(&spec, 400.0, 505.0).is_err());
+    }
+    #[test]
+    fn fit_1() {
+        let spec = make_spectrum(1000.0, 500.3, 12.0);
+        let fit = fit_gaussian_1d(&spec, 400.0, 600.0).unwrap();
+        assert!((fit.mean - 500.3).abs() < 0.5, "mean {}", fit.mean);
+        assert!((fit.sigma - 12.0).abs() < 1.2, "sigma {}", fit.sigma);
+        assert!(
+            (fit.amplitude - 1000.0).abs() < 10.0,
+            "amp {}",
+            fit.amplitude
+        );
+        assert!(fit.chi2 >= 0.0);
+    }
+    #[test]
+    fn fit_2() {
+        // Failures:
+
+        let spec = make_spectrum(1000.0, 500.3, 12.0);
+        assert!(fit_gaussian_1d(&spec, 0.0, 100.0).is_err()); // empty.
+        assert!(fit_gaussian_1d(&spec, 500.0, 502.0).is_err()); // too few bins.
+
+        let mut pd = ParameterDictionary::new();
+        pd.add("x").unwrap();
+        pd.add("y").unwrap();
+        let twod = Twod::new(
+            "2d",
+            "x",
+            "y",
+            &pd,
+            Some(0.0),
+            Some(10.0),
+            Some(10),
+            Some(0.0),
+            Some(10.0),
+            Some(10),
+        )
+        .unwrap();
+        assert!(fit_gaussian_1d(&twod, 0.0, 10.0).is_err());
+    }
+    #[test]
+    fn nan_1() {
+        // NaN channels are errors, not panics:
+
+        let mut spec = make_spectrum(1000.0, 500.5, 10.0);
+        spec.apply_function_1d(&|x, n| if x == 490.5 { f64::NAN } else { n });
+        assert!(centroid_1d(&spec, 400.0, 600.0).is_err());
+        assert!(fwhm_1d(&spec, 400.0, 600.0).is_err());
+        assert!(fit_gaussian_1d(&spec, 400.0, 600.0).is_err());
+        assert!(fwhm_1d(&spec, 495.0, 600.0).is_err());
+        assert!((centroid_1d(&spec, 491.0, 510.0).unwrap() - 500.5).abs() < 0.1);
+
+        // A NaN matrix is singular:
+
+        assert!(solve3([[f64::NAN; 3]; 3], [1.0; 3]).is_none());
+        let mut a = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+        a[1][1] = f64::NAN;
+        assert!(solve3(a, [1.0; 3]).is_none());
+    }
+    #[test]
+    fn chi2_1() {
+        // Compared with itself:
+
+        let spec = make_spectrum(1000.0, 500.3, 12.0);
+        let (chi2, dof) = chi2_test_1d(&spec, &spec).unwrap();
+        assert_eq!(0.0, chi2);
+        assert!(dof > 0);
+        assert_eq!(1.0, p_value(chi2, dof));
+    }
+    #[test]
+    fn chi2_2() {
+        // Different distributions:
+
+        let a = make_spectrum(1000.0, 500.0, 12.0);
+        let b = make_spectrum(1000.0, 520.0, 12.0);
+        let (chi2, dof) = chi2_test_1d(&a, &b).unwrap();
+        assert!(chi2 > 10.0 * dof as f64, "chi2 {} dof {}", chi2, dof);
+        assert!(p_value(chi2, dof) < 1.0e-6);
+
+        // Only bins with counts count:
+
+        let c = make_spectrum(0.0, 500.0, 12.0);
+        c.get_histogram_1d().unwrap().borrow_mut().fill(&10.0);
+        let d = make_spectrum(0.0, 500.0, 12.0);
+        assert_eq!(Ok((1.0, 1)), chi2_test_1d(&c, &d));
+    }
+    #[test]
+    fn chi2_3() {
+        // Incompatible spectra:
+
+        let a = make_spectrum(1000.0, 500.0, 12.0);
+        let mut pd = ParameterDictionary::new();
+        pd.add("p").unwrap();
+        let b = Oned::new("b", "p", &pd, Some(0.0), Some(1024.0), Some(512)).unwrap();
+        assert!(chi2_test_1d(&a, &b).is_err());
+        let c = Oned::new("c", "p", &pd, Some(1.0), Some(1025.0), Some(1024)).unwrap();
+        assert!(chi2_test_1d(&a, &c).is_err());
+        pd.add("y").unwrap();
+        let twod = Twod::new(
+            "2d",
+            "p",
+            "y",
+            &pd,
+            Some(0.0),
+            Some(10.0),
+            Some(10),
+            Some(0.0),
+            Some(10.0),
+            Some(10),
+        )
+        .unwrap();
+        assert!(chi2_test_1d(&a, &twod).is_err());
+        assert!(chi2_test_1d(&twod, &a).is_err());
+    }
+    #[test]
+    fn p_value_1() {
+        // Known values of the chi square distribution:
+
+        let cases = [
+            (1.0, 1, 0.3173105),
+            (3.841459, 1, 0.05),
+            (2.0, 2, 0.3678794),
+            (10.0, 5, 0.07523525),
+            (124.3421, 100, 0.05),
+            (50.0, 100, 0.9999934),
+        ];
+        for (chi2, dof, p) in cases {
+            let v = p_value(chi2, dof);
+            assert!(
+                (v - p).abs() < 1.0e-6,
+                "p({}, {}) = {} not {}",
+                chi2,
+                dof,
+                v,
+                p
+            );
+        }
+        assert_eq!(1.0, p_value(0.0, 10));
+        assert_eq!(1.0, p_value(5.0, 0));
+    }
+}
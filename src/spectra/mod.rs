@@ -43,12 +43,17 @@ use super::conditions::*;
 use super::parameters::*;
 use ndhistogram::axis::*;
 use ndhistogram::*;
-use std::cell::RefCell;
+use rayon::prelude::*;
+use std::any::Any;
+use std::cell::{Ref, RefCell};
 use std::collections::HashMap;
 use std::rc::{Rc, Weak};
 
 // Re-exports
 
+pub mod axes;
+pub use axes::*;
+
 pub mod oned;
 pub use oned::*;
 
@@ -70,11 +75,18 @@ pub use twodsum::*;
 pub mod pgamma;
 pub use pgamma::*;
 
+pub mod peaks;
+pub use peaks::*;
+
+pub mod fitting;
+pub use fitting::*;
+
 ///
 /// Gated spectra have this.  The condition_name just documents
 /// which condition is applied to the spectrum.
 /// The gate is the weakened Rc::RefCell that 'points' to the gate.
 ///
+#[derive(Clone)]
 pub struct Gate {
     condition_name: String,
     gate: ContainerReference,
@@ -83,6 +95,7 @@ pub struct Gate {
 /// have a 'special' True gate, we'll put one of these into the
 /// spectrum and a None value for the gate field means the spetrum is
 /// ungated.
+#[derive(Clone)]
 pub struct SpectrumGate {
     gate: Option<Gate>,
 }
@@ -109,10 +122,30 @@ impl SpectrumGate {
             Err(format!("No such gate {}", name))
         }
     }
+    /// Evaluate the gate as check does, but without ungating if
+    /// the underlying gate was deleted:
+    pub fn would_accept(&self, e: &FlatEvent) -> bool {
+        match self.gate.as_ref().and_then(|g| g.gate.upgrade()) {
+            Some(g) => g.borrow_mut().check(e),
+            None => true,
+        }
+    }
     /// Remove the gate:
     pub fn ungate(&mut self) {
         self.gate = None
     }
+    /// The name of the applied condition, if any:
+    pub fn gate_name(&self) -> Option<String> {
+        self.gate.as_ref().map(|g| g.condition_name.clone())
+    }
+    /// False if the applied condition has been deleted (until check
+    /// or ungate notice that).  An ungated SpectrumGate is alive.
+    pub fn is_alive(&self) -> bool {
+        match &self.gate {
+            Some(g) => g.gate.upgrade().is_some(),
+            None => true,
+        }
+    }
     /// Evaluate the gate for an event  The following cases and results
     /// are considered
     /// *   self.gate.is_none() - the spectrum is ungated, true is returned.
@@ -146,15 +179,186 @@ pub type H1DContainer = Rc<RefCell<H1D>>;
 pub type H2D = Hist2D<axis::Uniform, axis::Uniform, ndhistogram::value::Sum>;
 pub type H2DContainer = Rc<RefCell<H2D>>;
 
+/// A description of what a spectrum's increment does that can be
+/// sent between threads.  fill_batch_parallel uses it to fill copies
+/// of the histograms off the thread that owns the spectra.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FillPlan {
+    /// Fill with each of the parameters present (Oned, Multi1d).  log
+    /// is true if the axis is on ln(value).
+    OneD { ids: Vec<u32>, log: bool },
+    /// Fill with each x/y pair whose parameters are both present
+    /// (Twod, Multi2d, PGamma, TwodSum).
+    TwoD {
+        pairs: Vec<(u32, u32)>,
+        x_log: bool,
+        y_log: bool,
+    },
+    /// Fill (i, value) for each parameter i present (Summary).
+    Summary { ids: Vec<u32> },
+}
+impl FillPlan {
+    fn coordinate(value: f64, log: bool) -> f64 {
+        if log {
+            LogUniform::log_coordinate(value)
+        } else {
+            value
+        }
+    }
+    /// Fill a 1-d histogram from an event.  Does nothing unless
+    /// this is a OneD plan.
+    pub fn fill_1d(&self, histogram: &mut H1D, e: &FlatEvent) {
+        if let FillPlan::OneD { ids, log } = self {
+            for id in ids {
+                if let Some(x) = e[*id] {
+                    histogram.fill(&Self::coordinate(x, *log));
+                }
+            }
+        }
+    }
+    /// Fill a 2-d histogram from an event.  Does nothing for a
+    /// OneD plan.
+    pub fn fill_2d(&self, histogram: &mut H2D, e: &FlatEvent) {
+        match self {
+            FillPlan::OneD { .. } => {}
+            FillPlan::TwoD {
+                pairs,
+                x_log,
+                y_log,
+            } => {
+                for (x_id, y_id) in pairs {
+                    if let (Some(x), Some(y)) = (e[*x_id], e[*y_id]) {
+                        histogram.fill(&(Self::coordinate(x, *x_log), Self::coordinate(y, *y_log)));
+                    }
+                }
+            }
+            FillPlan::Summary { ids } => {
+                for (x, id) in ids.iter().enumerate() {
+                    if let Some(y) = e[*id] {
+                        histogram.fill(&(x as f64, y));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The types of spectra.  Used in SpectrumDescription to let
+/// clients know what they have without downcasting.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpectrumKind {
+    Oned,
+    Twod,
+    Summary,
+    PGamma,
+    Multi1D,
+    Multi2D,
+    TwodSum,
+}
+
+/// Describes one axis of a spectrum.  bins is the number of bins
+/// requested when the spectrum was made; it does not include the
+/// underflow and overflow bins ndhistogram adds.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AxisDescription {
+    pub low: f64,
+    pub high: f64,
+    pub bins: u32,
+}
+impl AxisDescription {
+    pub fn from_axis(axis: &axis::Uniform) -> AxisDescription {
+        AxisDescription {
+            low: *axis.low(),
+            high: *axis.high(),
+            bins: (axis.num_bins() - 2) as u32,
+        }
+    }
+    pub fn from_log_axis(axis: &LogUniform) -> AxisDescription {
+        AxisDescription {
+            low: axis.low(),
+            high: axis.high(),
+            bins: axis.bins() as u32,
+        }
+    }
+}
+
+/// What Spectrum::description returns.  The parameter lists
+/// are the names of the parameters on each axis.  Spectra whose
+/// parameters are not split by axis (Summary, Multi1D and Multi2D),
+/// put all of their parameters in x_params and y_params is empty.
+/// gate is the name of the applied condition, if any.  x_log and
+/// y_log are the real axes of logarithmic axes;  x_axis and y_axis
+/// always describe the histogram's axes, which are on ln(value) for
+/// those.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpectrumDescription {
+    pub name: String,
+    pub kind: SpectrumKind,
+    pub x_params: Vec<String>,
+    pub y_params: Vec<String>,
+    pub x_axis: Option<AxisDescription>,
+    pub y_axis: Option<AxisDescription>,
+    pub x_log: Option<AxisDescription>,
+    pub y_log: Option<AxisDescription>,
+    pub gate: Option<String>,
+}
+impl SpectrumDescription {
+    // Fill in the axis descriptions from the histogram of a 1-d spectrum:
+    fn from_1d(
+        name: &str,
+        kind: SpectrumKind,
+        x_params: Vec<String>,
+        histogram: &H1DContainer,
+        gate: &SpectrumGate,
+    ) -> SpectrumDescription {
+        SpectrumDescription {
+            name: String::from(name),
+            kind,
+            x_params,
+            y_params: vec![],
+            x_axis: Some(AxisDescription::from_axis(
+                &histogram.borrow().axes().as_tuple().0,
+            )),
+            y_axis: None,
+            x_log: None,
+            y_log: None,
+            gate: gate.gate_name(),
+        }
+    }
+    // Fill in the axis descriptions from the histogram of a 2-d spectrum:
+    fn from_2d(
+        name: &str,
+        kind: SpectrumKind,
+        x_params: Vec<String>,
+        y_params: Vec<String>,
+        histogram: &H2DContainer,
+        gate: &SpectrumGate,
+    ) -> SpectrumDescription {
+        let h = histogram.borrow();
+        let axes = h.axes().as_tuple();
+        SpectrumDescription {
+            name: String::from(name),
+            kind,
+            x_params,
+            y_params,
+            x_axis: Some(AxisDescription::from_axis(&axes.0)),
+            y_axis: Some(AxisDescription::from_axis(&axes.1)),
+            x_log: None,
+            y_log: None,
+            gate: gate.gate_name(),
+        }
+    }
+}
+
 /// In order to support dynamic dispatch, we need to define a Spectrum trait which combines the
 /// Capabilities of ndhistogram objects to supply the interfaces of Axes, Fill and Histogram;
 /// Along with the interfaces we need:
 /// Normally clients of spectra use:
 ///
-/// *     handle_event to process an event.  This will
-///       check any applied gate before attempting to call increment
-/// *     gate to gate a spectrum on a condition or replace the gate.
-/// *     ungate to remove the gate condition of a spectrum, if any.
+/// *  handle_event to process an event.  This will
+///    check any applied gate before attempting to call increment
+/// *  gate to gate a spectrum on a condition or replace the gate.
+/// *  ungate to remove the gate condition of a spectrum, if any.
 pub trait Spectrum {
     // Method that handle incrementing/gating
     fn check_gate(&mut self, e: &FlatEvent) -> bool;
@@ -174,6 +378,11 @@ pub trait Spectrum {
     fn required_parameter(&self) -> Option<u32> {
         None
     }
+    /// How increment fills the histogram, for fill_batch_parallel.
+    /// Spectra that return None are filled sequentially.
+    fn fill_plan(&self) -> Option<FillPlan> {
+        None
+    }
     /// Return the spectrum name:
     ///
     fn get_name(&self) -> String;
@@ -182,6 +391,13 @@ pub trait Spectrum {
 
     fn gate(&mut self, name: &str, dict: &ConditionDictionary) -> Result<(), String>;
     fn ungate(&mut self);
+    fn get_gate(&self) -> &SpectrumGate;
+
+    /// Allows downcasting to the concrete spectrum type:
+    fn as_any(&self) -> &dyn Any;
+    /// A copy of the spectrum with its own copy of the histogram.
+    /// The copy has the same gate.
+    fn deep_copy(&self) -> Box<dyn Spectrum>;
 
     // manipulate the underlying histogram:
 
@@ -194,9 +410,99 @@ pub trait Spectrum {
         self.get_histogram_1d().is_some()
     }
 
+    /// The x axis of the histogram (for log axes, the axis on
+    /// ln(value)).  Every spectrum has an x axis.
+    fn x_axis(&self) -> AxisDescription {
+        if let Some(h) = self.get_histogram_1d() {
+            AxisDescription::from_axis(&h.borrow().axes().as_tuple().0)
+        } else {
+            let h = self
+                .get_histogram_2d()
+                .expect("Spectra have a 1-d or a 2-d histogram");
+            let h = h.borrow();
+            AxisDescription::from_axis(&h.axes().as_tuple().0)
+        }
+    }
+    /// The y axis of 2-d spectra.
+    fn y_axis(&self) -> Option<AxisDescription> {
+        self.get_histogram_2d()
+            .map(|h| AxisDescription::from_axis(&h.borrow().axes().as_tuple().1))
+    }
+
     /// Clear the histogram counts.:
 
     fn clear(&mut self);
+
+    /// Describe the spectrum for e.g. GUI tools:
+    fn description(&self) -> SpectrumDescription;
+
+    // Direct channel manipulation, e.g. to mask a dead detector strip.
+    // These bypass the gate and *replace* the value of the channel
+    // that contains the coordinates.  Coordinates outside the axes
+    // set the underflow/overflow channels; NaN coordinates are ignored.
+    // Spectra with the wrong number of axes ignore these.
+
+    /// Set the value of the channel containing x in a 1-d spectrum:
+    fn fill_channel_1d(&mut self, x: f64, value: f64) {
+        if let (Some(h), false) = (self.get_histogram_1d(), x.is_nan()) {
+            set_channel(&mut *h.borrow_mut(), &x, value);
+        }
+    }
+    /// Set the value of the channel containing (x, y) in a 2-d spectrum:
+    fn fill_channel_2d(&mut self, x: f64, y: f64, value: f64) {
+        if let (Some(h), false) = (self.get_histogram_2d(), x.is_nan() || y.is_nan()) {
+            set_channel(&mut *h.borrow_mut(), &(x, y), value);
+        }
+    }
+    // Channel-wise transforms e.g. efficiency corrections.  The
+    // function gets the bin center (histogram coordinates, that's
+    // ln(value) for log axes) and the channel value and returns the
+    // new channel value.  Underflow/overflow channels are not touched.
+
+    /// Transform the channels of a 1-d spectrum:
+    fn apply_function_1d(&mut self, f: &dyn Fn(f64, f64) -> f64) {
+        if let Some(h) = self.get_histogram_1d() {
+            let mut h = h.borrow_mut();
+            let channels: Vec<(f64, f64)> = h
+                .iter()
+                .filter_map(|c| bin_center(&c.bin).map(|x| (x, c.value.get())))
+                .collect();
+            for (x, value) in channels {
+                set_channel(&mut *h, &x, f(x, value));
+            }
+        }
+    }
+    /// Transform the channels of a 2-d spectrum:
+    fn apply_function_2d(&mut self, f: &dyn Fn(f64, f64, f64) -> f64) {
+        if let Some(h) = self.get_histogram_2d() {
+            let mut h = h.borrow_mut();
+            let channels: Vec<(f64, f64, f64)> = h
+                .iter()
+                .filter_map(|c| match (bin_center(&c.bin.0), bin_center(&c.bin.1)) {
+                    (Some(x), Some(y)) => Some((x, y, c.value.get())),
+                    _ => None,
+                })
+                .collect();
+            for (x, y, value) in channels {
+                set_channel(&mut *h, &(x, y), f(x, y, value));
+            }
+        }
+    }
+    /// Divide the channels by an event count e.g. to compare spectra
+    /// accumulated over different numbers of events.  As with the
+    /// apply_function methods, underflow/overflow channels are not
+    /// touched.  The event count must be positive.
+    fn normalize(&mut self, event_count: f64) -> Result<(), String> {
+        if event_count <= 0.0 || event_count.is_nan() {
+            return Err(format!(
+                "Normalization event count must be positive: {}",
+                event_count
+            ));
+        }
+        self.apply_function_1d(&|_, v| v / event_count);
+        self.apply_function_2d(&|_, _, v| v / event_count);
+        Ok(())
+    }
 }
 
 // We also need some sort of repository in which spectra can be stored and looked up by name.
@@ -207,6 +513,351 @@ pub type SpectrumContainerReference = Weak<RefCell<dyn Spectrum>>;
 pub type SpectrumReferences = Vec<SpectrumContainerReference>;
 pub type SpectrumDictionary = HashMap<String, SpectrumContainer>;
 
+// Bulk operations on the spectra in a SpectrumDictionary:
+
+/// Clear the counts in all spectra.
+pub fn clear_all(dict: &mut SpectrumDictionary) {
+    for spec in dict.values() {
+        spec.borrow_mut().clear();
+    }
+}
+///
+/// Gate all spectra on the same condition.  Err is returned if there
+/// is no such condition; otherwise the number of spectra gated.
+///
+pub fn gate_all(
+    dict: &mut SpectrumDictionary,
+    gate_name: &str,
+    cdict: &ConditionDictionary,
+) -> Result<usize, String> {
+    if !cdict.contains_key(gate_name) {
+        return Err(format!("No such gate {}", gate_name));
+    }
+    let mut count = 0;
+    for spec in dict.values() {
+        if spec.borrow_mut().gate(gate_name, cdict).is_ok() {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+///
+/// Restore saved gates (e.g. from an analysis configuration);  gates
+/// maps spectrum names to gate names.  All gates that can be applied
+/// are, even if others fail.  Err has a message for each spectrum
+/// that doesn't exist or could not be gated, in spectrum name order.
+///
+pub fn apply_saved_gates(
+    gates: &HashMap<String, String>,
+    sdict: &mut SpectrumDictionary,
+    cdict: &ConditionDictionary,
+) -> Result<(), Vec<String>> {
+    let mut failures = Vec::new();
+    let mut names: Vec<&String> = gates.keys().collect();
+    names.sort();
+    for name in names {
+        let gate_name = &gates[name];
+        match sdict.get(name) {
+            Some(spec) => {
+                if let Err(msg) = spec.borrow_mut().gate(gate_name, cdict) {
+                    failures.push(format!("{}: {}", name, msg));
+                }
+            }
+            None => failures.push(format!("No such spectrum {}", name)),
+        }
+    }
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(failures)
+    }
+}
+/// Remove the gates from all spectra.
+pub fn ungate_all(dict: &mut SpectrumDictionary) {
+    for spec in dict.values() {
+        spec.borrow_mut().ungate();
+    }
+}
+/// Ungate the spectra whose gate conditions have been deleted.
+pub fn remove_dead_gates(dict: &mut SpectrumDictionary) {
+    for spec in dict.values() {
+        let dead = !spec.borrow().get_gate().is_alive();
+        if dead {
+            spec.borrow_mut().ungate();
+        }
+    }
+}
+///
+/// Fill all spectra from a batch of events.  The condition caches are
+/// invalidated before each event is processed.
+///
+/// See fill_batch_parallel to fill the spectra on several threads.
+///
+pub fn fill_batch(
+    events: &[FlatEvent],
+    dict: &mut SpectrumDictionary,
+    cdict: &mut ConditionDictionary,
+) {
+    for e in events {
+        invalidate_cache(cdict);
+        for spec in dict.values() {
+            spec.borrow_mut().handle_event(e);
+        }
+    }
+}
+
+// Add the channels of a histogram into those of one with the same
+// axes:
+
+fn add_channels<'a>(
+    target: impl Iterator<Item = &'a mut ndhistogram::value::Sum>,
+    source: impl Iterator<Item = &'a ndhistogram::value::Sum>,
+) {
+    for (t, s) in target.zip(source) {
+        if s.get() != 0.0 {
+            *t = sum_of(t.get() + s.get());
+        }
+    }
+}
+// An empty copy of a spectrum's histogram:
+
+enum HistogramSnapshot {
+    OneD(H1D),
+    TwoD(H2D),
+}
+// What fill_batch_parallel sends to a worker thread for one spectrum:
+// its fill plan, whether its gate accepted each event (None if it is
+// ungated) and the histogram to fill.
+
+struct ParallelFill {
+    plan: FillPlan,
+    accepted: Option<Vec<bool>>,
+    histogram: HistogramSnapshot,
+}
+impl ParallelFill {
+    fn new(spec: &dyn Spectrum) -> Option<ParallelFill> {
+        let plan = spec.fill_plan()?;
+        let histogram = match plan {
+            FillPlan::OneD { .. } => {
+                HistogramSnapshot::OneD(H1D::new(spec.get_histogram_1d()?.borrow().axes().clone()))
+            }
+            _ => {
+                HistogramSnapshot::TwoD(H2D::new(spec.get_histogram_2d()?.borrow().axes().clone()))
+            }
+        };
+        Some(ParallelFill {
+            plan,
+            accepted: spec.get_gate().gate_name().map(|_| Vec::new()),
+            histogram,
+        })
+    }
+    fn fill(&mut self, events: &[FlatEvent]) {
+        for (i, e) in events.iter().enumerate() {
+            if self.accepted.as_ref().is_none_or(|a| a[i]) {
+                match &mut self.histogram {
+                    HistogramSnapshot::OneD(h) => self.plan.fill_1d(h, e),
+                    HistogramSnapshot::TwoD(h) => self.plan.fill_2d(h, e),
+                }
+            }
+        }
+    }
+    fn merge_into(&self, spec: &dyn Spectrum) {
+        match &self.histogram {
+            HistogramSnapshot::OneD(h) => {
+                if let Some(target) = spec.get_histogram_1d() {
+                    add_channels(target.borrow_mut().values_mut(), h.values());
+                }
+            }
+            HistogramSnapshot::TwoD(h) => {
+                if let Some(target) = spec.get_histogram_2d() {
+                    add_channels(target.borrow_mut().values_mut(), h.values());
+                }
+            }
+        }
+    }
+}
+///
+/// Fill all spectra from a batch of events using rayon, with the same
+/// result as fill_batch.  Spectra and conditions are Rc/RefCell and
+/// can't leave this thread so:
+///
+/// *  Gates are evaluated for all the events first, on this thread,
+///    invalidating the condition caches before each event.  Nothing is
+///    filled in this pass and it is skipped if no spectrum is gated.
+/// *  Each spectrum with a fill_plan gets an empty copy of its
+///    histogram.  These are filled in parallel, one task per spectrum,
+///    so there are no locks.
+/// *  The copies are then added into the spectra's histograms.
+///
+/// Spectra without a fill_plan are filled in the gate pass.  The gate
+/// pass, the copies and the merge are the overhead; it pays off for
+/// large batches with several spectra.
+///
+pub fn fill_batch_parallel(
+    events: &[FlatEvent],
+    dict: &mut SpectrumDictionary,
+    cdict: &mut ConditionDictionary,
+) {
+    let mut parallel = Vec::<(SpectrumContainer, ParallelFill)>::new();
+    let mut sequential = Vec::<SpectrumContainer>::new();
+    for spec in dict.values() {
+        let fill = ParallelFill::new(&*spec.borrow());
+        match fill {
+            Some(fill) => parallel.push((spec.clone(), fill)),
+            None => sequential.push(spec.clone()),
+        }
+    }
+    if !sequential.is_empty() || parallel.iter().any(|(_, f)| f.accepted.is_some()) {
+        for e in events {
+            invalidate_cache(cdict);
+            for (spec, fill) in parallel.iter_mut() {
+                if let Some(accepted) = fill.accepted.as_mut() {
+                    accepted.push(spec.borrow_mut().check_gate(e));
+                }
+            }
+            for spec in sequential.iter() {
+                spec.borrow_mut().handle_event(e);
+            }
+        }
+    }
+    let (spectra, mut fills): (Vec<SpectrumContainer>, Vec<ParallelFill>) =
+        parallel.into_iter().unzip();
+    fills.par_iter_mut().for_each(|fill| fill.fill(events));
+    for (spec, fill) in spectra.iter().zip(fills.iter()) {
+        fill.merge_into(&*spec.borrow());
+    }
+}
+///
+/// A normalized (see Spectrum::normalize) copy of a spectrum.
+///
+pub fn normalized_copy(src: &dyn Spectrum, event_count: f64) -> Result<Box<dyn Spectrum>, String> {
+    let mut result = src.deep_copy();
+    result.normalize(event_count)?;
+    Ok(result)
+}
+
+// Iterating over the spectra of a single type:
+
+fn iter_type<'a, T: 'static>(
+    dict: &'a SpectrumDictionary,
+) -> impl Iterator<Item = (&'a str, Ref<'a, T>)> + 'a {
+    dict.iter().filter_map(|(name, spec)| {
+        Ref::filter_map(spec.borrow(), |s| s.as_any().downcast_ref::<T>())
+            .ok()
+            .map(|s| (name.as_str(), s))
+    })
+}
+///
+/// Iterate over the 1-d spectra in a dictionary.  The spectra are
+/// in RefCells so the items hold a borrow of each spectrum;  they
+/// must not be held while the spectrum is mutably borrowed.
+///
+pub fn iter_oned(dict: &SpectrumDictionary) -> impl Iterator<Item = (&str, Ref<'_, Oned>)> {
+    iter_type(dict)
+}
+/// Iterate over the 2-d spectra in a dictionary.
+pub fn iter_twod(dict: &SpectrumDictionary) -> impl Iterator<Item = (&str, Ref<'_, Twod>)> {
+    iter_type(dict)
+}
+/// Iterate over the summary spectra in a dictionary.
+pub fn iter_summary(dict: &SpectrumDictionary) -> impl Iterator<Item = (&str, Ref<'_, Summary>)> {
+    iter_type(dict)
+}
+/// Iterate over the particle gamma spectra in a dictionary.
+pub fn iter_pgamma(dict: &SpectrumDictionary) -> impl Iterator<Item = (&str, Ref<'_, PGamma>)> {
+    iter_type(dict)
+}
+/// Iterate over the multi 1-d (gamma 1d) spectra in a dictionary.
+pub fn iter_multi1d(dict: &SpectrumDictionary) -> impl Iterator<Item = (&str, Ref<'_, Multi1d>)> {
+    iter_type(dict)
+}
+/// Iterate over the multi 2-d (gamma 2d) spectra in a dictionary.
+pub fn iter_multi2d(dict: &SpectrumDictionary) -> impl Iterator<Item = (&str, Ref<'_, Multi2d>)> {
+    iter_type(dict)
+}
+/// Iterate over the 2-d sum spectra in a dictionary.
+pub fn iter_twodsum(dict: &SpectrumDictionary) -> impl Iterator<Item = (&str, Ref<'_, TwodSum>)> {
+    iter_type(dict)
+}
+///
+/// Clear the spectra whose names match a glob pattern (e.g. "strip.*").
+/// An invalid pattern matches nothing.  The number of spectra cleared
+/// is returned.
+///
+pub fn clear_matching(dict: &mut SpectrumDictionary, pattern: &str) -> usize {
+    let pattern = if let Ok(p) = glob::Pattern::new(pattern) {
+        p
+    } else {
+        return 0;
+    };
+    let mut count = 0;
+    for (name, spec) in dict.iter() {
+        if pattern.matches(name) {
+            spec.borrow_mut().clear();
+            count += 1;
+        }
+    }
+    count
+}
+
+// Namespaces:  multi-detector experiments can organize spectra by
+// detector subsystem by storing them as "namespace/name".  Only the
+// dictionary key carries the namespace; the spectrum itself keeps the
+// name it was created with.  So get_name() and anything that writes it
+// (e.g. save_spe) report the short name while dictionary lookups need
+// the full name.
+
+const NAMESPACE_SEPARATOR: char = '/';
+
+///
+/// Add a spectrum to a dictionary as namespace/name.  Err if the
+/// namespace or name is empty or contains a '/' or if there's
+/// already a spectrum with that full name.  The spectrum is not
+/// renamed: its get_name() is unchanged and only the key is namespaced.
+///
+pub fn add_namespaced(
+    dict: &mut SpectrumDictionary,
+    namespace: &str,
+    name: &str,
+    spec: SpectrumContainer,
+) -> Result<(), String> {
+    for part in [namespace, name] {
+        if part.is_empty() || part.contains(NAMESPACE_SEPARATOR) {
+            return Err(format!("Invalid namespace or spectrum name '{}'", part));
+        }
+    }
+    let full_name = format!("{}{}{}", namespace, NAMESPACE_SEPARATOR, name);
+    if dict.contains_key(&full_name) {
+        return Err(format!("Spectrum {} already exists", full_name));
+    }
+    dict.insert(full_name, spec);
+    Ok(())
+}
+// The short name of a spectrum if its full name is in namespace:
+
+fn short_name<'a>(full_name: &'a str, namespace: &str) -> Option<&'a str> {
+    full_name
+        .strip_prefix(namespace)?
+        .strip_prefix(NAMESPACE_SEPARATOR)
+}
+///
+/// The names (without the namespace) of the spectra in a namespace,
+/// sorted.
+///
+pub fn list_namespace(dict: &SpectrumDictionary, namespace: &str) -> Vec<String> {
+    let mut result: Vec<String> = dict
+        .keys()
+        .filter_map(|k| short_name(k, namespace))
+        .map(String::from)
+        .collect();
+    result.sort();
+    result
+}
+/// Remove all spectra in a namespace from the dictionary.
+pub fn clear_namespace(dict: &mut SpectrumDictionary, namespace: &str) {
+    dict.retain(|k, _| short_name(k, namespace).is_none());
+}
+
 /// The SpectrumStorage type supports several things:
 /// -   Spectrum storage by name through a contained SpectrumDictionary.
 /// -   Rapid spectrum increment by holding a set of spectra that are
@@ -324,6 +975,11 @@ impl SpectrumStorage {
     pub fn get(&self, name: &str) -> Option<&SpectrumContainer> {
         self.dict.get(name)
     }
+    /// Iterate over the spectra in the storage.
+    ///
+    pub fn iter(&self) -> std::collections::hash_map::Iter<'_, String, SpectrumContainer> {
+        self.dict.iter()
+    }
     /// Clear all the spectra
     ///
     pub fn clear_all(&self) {
@@ -374,6 +1030,51 @@ impl SpectrumStorage {
     }
 }
 
+// The center of a bin, None for underflow/overflow:
+
+fn bin_center(bin: &BinInterval<f64>) -> Option<f64> {
+    match bin {
+        BinInterval::Bin { start, end } => Some((start + end) / 2.0),
+        _ => None,
+    }
+}
+
+// Replace the value of the channel at coordinate in a histogram.
+// ndhistogram's Sum can only be incremented by one, there's no way to
+// set it, so the new value is built by deserializing a Sum.
+
+fn set_channel<A, H>(histogram: &mut H, coordinate: &A::Coordinate, value: f64)
+where
+    A: axis::Axis,
+    H: Histogram<A, ndhistogram::value::Sum>,
+{
+    if let Some(index) = histogram.axes().index(coordinate) {
+        if let Some(channel) = histogram.value_at_index_mut(index) {
+            *channel = sum_of(value);
+        }
+    }
+}
+// Sum only has a setter through Deserialize:
+
+fn sum_of(value: f64) -> ndhistogram::value::Sum {
+    use serde::de::value::{Error, MapDeserializer};
+    use serde::Deserialize;
+    ndhistogram::value::Sum::deserialize(MapDeserializer::<_, Error>::new(std::iter::once((
+        "sum", value,
+    ))))
+    .expect("Sum deserializes from its only field")
+}
+
+// A coordinate inside a bin of an axis:
+
+fn bin_coordinate(bin: &BinInterval<f64>) -> f64 {
+    match bin {
+        BinInterval::Underflow { .. } => f64::NEG_INFINITY,
+        BinInterval::Overflow { start } => *start,
+        BinInterval::Bin { start, end } => (start + end) / 2.0,
+    }
+}
+
 // Utility function to figure out the axis limits given
 // a parameter definition for the axis and options for each
 // of the values
@@ -603,7 +1304,537 @@ mod gate_tests {
         assert!(g.check(&e));
         assert!(g.gate.is_none());
     }
+    #[test]
+    fn spgate_alive_1() {
+        let mut dict = ConditionDictionary::new();
+        let mut g = SpectrumGate::new();
+        assert!(g.is_alive());
+
+        dict.insert(String::from("true"), Rc::new(RefCell::new(True {})));
+        g.set_gate("true", &dict).unwrap();
+        assert!(g.is_alive());
+
+        dict.remove("true").unwrap();
+        assert!(!g.is_alive());
+        assert_eq!(Some(String::from("true")), g.gate_name());
+    }
+}
+#[cfg(test)]
+mod spec_dict_tests {
+    use super::*;
+
+    // Ten 1-d spectra, spec.0..spec.9 each with one count:
+
+    fn make_dict() -> (SpectrumDictionary, ConditionDictionary) {
+        let mut pdict = ParameterDictionary::new();
+        pdict.add("p").unwrap();
+        let mut dict = SpectrumDictionary::new();
+        for i in 0..10 {
+            let name = format!("spec.{}", i);
+            let spec = Oned::new(&name, "p", &pdict, Some(0.0), Some(10.0), Some(10)).unwrap();
+            spec.get_histogram_1d().unwrap().borrow_mut().fill(&5.0);
+            dict.insert(name, Rc::new(RefCell::new(spec)));
+        }
+        let mut cdict = ConditionDictionary::new();
+        cdict.insert(String::from("true"), Rc::new(RefCell::new(True {})));
+        (dict, cdict)
+    }
+    fn total(spec: &SpectrumContainer) -> f64 {
+        let h = spec.borrow().get_histogram_1d().unwrap();
+        let h = h.borrow();
+        h.values().map(|v| v.get()).sum()
+    }
+
+    #[test]
+    fn gate_1() {
+        let (mut dict, cdict) = make_dict();
+        assert_eq!(Ok(10), gate_all(&mut dict, "true", &cdict));
+        for spec in dict.values() {
+            assert_eq!(Some(String::from("true")), spec.borrow().description().gate);
+        }
+        ungate_all(&mut dict);
+        for spec in dict.values() {
+            assert!(spec.borrow().description().gate.is_none());
+        }
+        assert!(gate_all(&mut dict, "nosuch", &cdict).is_err());
+    }
+    #[test]
+    fn clear_1() {
+        let (mut dict, _) = make_dict();
+        clear_all(&mut dict);
+        for spec in dict.values() {
+            assert_eq!(0.0, total(spec));
+        }
+    }
+    #[test]
+    fn clear_2() {
+        let (mut dict, _) = make_dict();
+        assert_eq!(3, clear_matching(&mut dict, "spec.[135]"));
+        for (name, spec) in dict.iter() {
+            let expected = if ["spec.1", "spec.3", "spec.5"].contains(&name.as_str()) {
+                0.0
+            } else {
+                1.0
+            };
+            assert_eq!(expected, total(spec), "{}", name);
+        }
+        assert_eq!(0, clear_matching(&mut dict, "[bad"));
+        assert_eq!(10, clear_matching(&mut dict, "*"));
+    }
+    #[test]
+    fn fill_batch_1() {
+        let (mut dict, mut cdict) = make_dict();
+        cdict.insert(
+            String::from("low"),
+            Rc::new(RefCell::new(Cut::new(1, 0.0, 2.5))),
+        );
+        dict.get("spec.0")
+            .unwrap()
+            .borrow_mut()
+            .gate("low", &cdict)
+            .unwrap();
+
+        let events: Vec<FlatEvent> = (1..=5)
+            .map(|i| {
+                let mut fe = FlatEvent::new();
+                fe.load_event(&vec![EventParameter::new(1, i as f64)]);
+                fe
+            })
+            .collect();
+        fill_batch(&events, &mut dict, &mut cdict);
+
+        // Same as handling the events one at a time:
+
+        for (name, spec) in dict.iter() {
+            let expected = if name == "spec.0" { 3.0 } else { 6.0 };
+            assert_eq!(expected, total(spec), "{}", name);
+        }
+        let h = dict
+            .get("spec.3")
+            .unwrap()
+            .borrow()
+            .get_histogram_1d()
+            .unwrap();
+        for i in 1..=5 {
+            let bin = h.borrow().axes().as_tuple().0.index(&(i as f64)).unwrap();
+            let expected = if i == 5 { 2.0 } else { 1.0 };
+            assert_eq!(expected, h.borrow().value_at_index(bin).unwrap().get());
+        }
+    }
+
+    // One spectrum of each kind on parameters p.1..p.4 (ids 1..4),
+    // half of them gated on p.1 < 5:
+
+    fn make_mixed_dict() -> (SpectrumDictionary, ConditionDictionary) {
+        let mut pdict = ParameterDictionary::new();
+        for i in 1..=4 {
+            let name = format!("p.{}", i);
+            pdict.add(&name).unwrap();
+            pdict
+                .lookup_mut(&name)
+                .unwrap()
+                .set_limits(0.0, 10.0)
+                .set_bins(10);
+        }
+        let p = |ids: &[u32]| -> Vec<String> { ids.iter().map(|i| format!("p.{}", i)).collect() };
+        let spectra: Vec<SpectrumContainer> = vec![
+            Rc::new(RefCell::new(
+                Oned::new("oned", "p.1", &pdict, None, None, None).unwrap(),
+            )),
+            Rc::new(RefCell::new(
+                Oned::new_log("log", "p.2", &pdict, Some(1.0), Some(10.0), None).unwrap(),
+            )),
+            Rc::new(RefCell::new(
+                Twod::new(
+                    "twod", "p.1", "p.2", &pdict, None, None, None, None, None, None,
+                )
+                .unwrap(),
+            )),
+            Rc::new(RefCell::new(
+                Summary::new("summary", p(&[1, 2, 3, 4]), &pdict, None, None, None).unwrap(),
+            )),
+            Rc::new(RefCell::new(
+                Multi1d::new("multi1d", p(&[1, 2, 3]), &pdict, None, None, None).unwrap(),
+            )),
+            Rc::new(RefCell::new(
+                Multi2d::new(
+                    "multi2d",
+                    p(&[1, 2, 3]),
+                    &pdict,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .unwrap(),
+            )),
+            Rc::new(RefCell::new(
+                PGamma::new(
+                    "pgamma",
+                    &p(&[1, 2]),
+                    &p(&[3, 4]),
+                    &pdict,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .unwrap(),
+            )),
+            Rc::new(RefCell::new(
+                TwodSum::new(
+                    "twodsum",
+                    vec![
+                        (String::from("p.1"), String::from("p.2")),
+                        (String::from("p.3"), String::from("p.4")),
+                    ],
+                    &pdict,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .unwrap(),
+            )),
+        ];
+        let mut cdict = ConditionDictionary::new();
+        cdict.insert(
+            String::from("low"),
+            Rc::new(RefCell::new(Cut::new(1, 0.0, 5.0))),
+        );
+        let mut dict = SpectrumDictionary::new();
+        for (i, spec) in spectra.into_iter().enumerate() {
+            if i % 2 == 1 {
+                spec.borrow_mut().gate("low", &cdict).unwrap();
+            }
+            let name = spec.borrow().get_name();
+            dict.insert(name, spec);
+        }
+        (dict, cdict)
+    }
+    fn channels(spec: &SpectrumContainer) -> Vec<f64> {
+        let spec = spec.borrow();
+        if let Some(h) = spec.get_histogram_1d() {
+            let values = h.borrow().values().map(|v| v.get()).collect();
+            values
+        } else {
+            let h = spec.get_histogram_2d().unwrap();
+            let values = h.borrow().values().map(|v| v.get()).collect();
+            values
+        }
+    }
+    fn mixed_events() -> Vec<FlatEvent> {
+        (0..200)
+            .map(|i| {
+                let mut fe = FlatEvent::new();
+                let params: Vec<EventParameter> = (1..=4)
+                    .filter(|id| (i + id) % 3 != 0)
+                    .map(|id| EventParameter::new(id, ((i * id) % 11) as f64))
+                    .collect();
+                fe.load_event(&params);
+                fe
+            })
+            .collect()
+    }
+    #[test]
+    fn fill_batch_parallel_1() {
+        // Every kind of spectrum, gated and ungated, gets the same
+        // counts as fill_batch gives:
+
+        let events = mixed_events();
+        let (mut sequential, mut scdict) = make_mixed_dict();
+        let (mut parallel, mut pcdict) = make_mixed_dict();
+        fill_batch(&events, &mut sequential, &mut scdict);
+        fill_batch_parallel(&events, &mut parallel, &mut pcdict);
+        fill_batch_parallel(&events, &mut parallel, &mut pcdict);
+        fill_batch(&events, &mut sequential, &mut scdict);
+
+        assert_eq!(8, parallel.len());
+        for (name, spec) in sequential.iter() {
+            let expected = channels(spec);
+            assert!(expected.iter().sum::<f64>() > 0.0, "{}", name);
+            assert_eq!(expected, channels(&parallel[name]), "{}", name);
+        }
+    }
+    #[test]
+    fn fill_batch_parallel_2() {
+        // The spectra fill_batch_parallel fills are the ones in
+        // make_dict (10 1-d spectra on p);   one is gated:
+
+        let (mut dict, mut cdict) = make_dict();
+        cdict.insert(
+            String::from("low"),
+            Rc::new(RefCell::new(Cut::new(1, 0.0, 2.5))),
+        );
+        dict.get("spec.0")
+            .unwrap()
+            .borrow_mut()
+            .gate("low", &cdict)
+            .unwrap();
+        let events: Vec<FlatEvent> = (1..=5)
+            .map(|i| {
+                let mut fe = FlatEvent::new();
+                fe.load_event(&vec![EventParameter::new(1, i as f64)]);
+                fe
+            })
+            .collect();
+        fill_batch_parallel(&events, &mut dict, &mut cdict);
+        for (name, spec) in dict.iter() {
+            let expected = if name == "spec.0" { 3.0 } else { 6.0 };
+            assert_eq!(expected, total(spec), "{}", name);
+        }
+        fill_batch_parallel(&[], &mut dict, &mut cdict);
+        assert_eq!(6.0, total(&dict["spec.1"]));
+    }
+    #[test]
+    fn remove_dead_gates_1() {
+        let (mut dict, mut cdict) = make_dict();
+        cdict.insert(String::from("false"), Rc::new(RefCell::new(False {})));
+        for (i, spec) in dict.values().enumerate() {
+            let gate = if i % 2 == 0 { "true" } else { "false" };
+            spec.borrow_mut().gate(gate, &cdict).unwrap();
+        }
+        cdict.remove("false").unwrap();
+        for spec in dict.values() {
+            let spec = spec.borrow();
+            let alive = spec.get_gate().gate_name() == Some(String::from("true"));
+            assert_eq!(alive, spec.get_gate().is_alive());
+        }
+
+        remove_dead_gates(&mut dict);
+        for spec in dict.values() {
+            let spec = spec.borrow();
+            assert!(spec.get_gate().is_alive());
+            let name = spec.get_gate().gate_name();
+            assert!(name.is_none() || name == Some(String::from("true")));
+        }
+        assert_eq!(
+            5,
+            dict.values()
+                .filter(|s| s.borrow().get_gate().gate_name().is_none())
+                .count()
+        );
+    }
+    #[test]
+    fn iter_type_1() {
+        // One spectrum of each type:
+
+        let mut pdict = ParameterDictionary::new();
+        pdict.add("x").unwrap();
+        pdict.add("y").unwrap();
+        let names = vec![String::from("x"), String::from("y")];
+        let lim = (Some(0.0), Some(10.0), Some(10));
+        let mut dict = SpectrumDictionary::new();
+        let specs: Vec<(&str, SpectrumContainer)> = vec![
+            (
+                "1d",
+                Rc::new(RefCell::new(
+                    Oned::new("1d", "x", &pdict, lim.0, lim.1, lim.2).unwrap(),
+                )),
+            ),
+            (
+                "2d",
+                Rc::new(RefCell::new(
+                    Twod::new(
+                        "2d", "x", "y", &pdict, lim.0, lim.1, lim.2, lim.0, lim.1, lim.2,
+                    )
+                    .unwrap(),
+                )),
+            ),
+            (
+                "summary",
+                Rc::new(RefCell::new(
+                    Summary::new("summary", names.clone(), &pdict, lim.0, lim.1, lim.2).unwrap(),
+                )),
+            ),
+            (
+                "m1",
+                Rc::new(RefCell::new(
+                    Multi1d::new("m1", names.clone(), &pdict, lim.0, lim.1, lim.2).unwrap(),
+                )),
+            ),
+            (
+                "m2",
+                Rc::new(RefCell::new(
+                    Multi2d::new(
+                        "m2",
+                        names.clone(),
+                        &pdict,
+                        lim.0,
+                        lim.1,
+                        lim.2,
+                        lim.0,
+                        lim.1,
+                        lim.2,
+                    )
+                    .unwrap(),
+                )),
+            ),
+            (
+                "sum",
+                Rc::new(RefCell::new(
+                    TwodSum::new(
+                        "sum",
+                        vec![(String::from("x"), String::from("y"))],
+                        &pdict,
+                        lim.0,
+                        lim.1,
+                        lim.2,
+                        lim.0,
+                        lim.1,
+                        lim.2,
+                    )
+                    .unwrap(),
+                )),
+            ),
+            (
+                "pgamma",
+                Rc::new(RefCell::new(
+                    PGamma::new(
+                        "pgamma", &names, &names, &pdict, lim.0, lim.1, lim.2, lim.0, lim.1, lim.2,
+                    )
+                    .unwrap(),
+                )),
+            ),
+        ];
+        for (name, spec) in specs {
+            dict.insert(String::from(name), spec);
+        }
+
+        let oned: Vec<&str> = iter_oned(&dict).map(|(n, _)| n).collect();
+        assert_eq!(vec!["1d"], oned);
+        let twod: Vec<&str> = iter_twod(&dict).map(|(n, _)| n).collect();
+        assert_eq!(vec!["2d"], twod);
+        let summary: Vec<&str> = iter_summary(&dict).map(|(n, _)| n).collect();
+        assert_eq!(vec!["summary"], summary);
+        let m1: Vec<&str> = iter_multi1d(&dict).map(|(n, _)| n).collect();
+        assert_eq!(vec!["m1"], m1);
+        let m2: Vec<&str> = iter_multi2d(&dict).map(|(n, _)| n).collect();
+        assert_eq!(vec!["m2"], m2);
+        let sum: Vec<&str> = iter_twodsum(&dict).map(|(n, _)| n).collect();
+        assert_eq!(vec!["sum"], sum);
+        let pgamma: Vec<&str> = iter_pgamma(&dict).map(|(n, _)| n).collect();
+        assert_eq!(vec!["pgamma"], pgamma);
+
+        // The items are the spectra:
+
+        let (_, spec) = iter_oned(&dict).next().unwrap();
+        assert_eq!("1d", spec.get_name());
+    }
+    #[test]
+    fn namespace_1() {
+        let (dict, _cdict) = make_dict();
+        let spectra: Vec<SpectrumContainer> = dict.values().take(5).cloned().collect();
+        let mut dict = SpectrumDictionary::new();
+        for (i, spec) in spectra.iter().enumerate() {
+            let (namespace, name) = if i < 3 {
+                ("si", format!("strip.{}", i))
+            } else {
+                ("ge", format!("crystal.{}", i))
+            };
+            add_namespaced(&mut dict, namespace, &name, Rc::clone(spec)).unwrap();
+        }
+        assert_eq!(5, dict.len());
+        assert!(dict.contains_key("si/strip.0"));
+        assert_eq!(
+            vec![
+                String::from("strip.0"),
+                String::from("strip.1"),
+                String::from("strip.2")
+            ],
+            list_namespace(&dict, "si")
+        );
+        assert_eq!(
+            vec![String::from("crystal.3"), String::from("crystal.4")],
+            list_namespace(&dict, "ge")
+        );
+        assert!(list_namespace(&dict, "s").is_empty());
+        assert!(list_namespace(&dict, "nosuch").is_empty());
+
+        clear_namespace(&mut dict, "si");
+        assert_eq!(2, dict.len());
+        assert!(list_namespace(&dict, "si").is_empty());
+        assert_eq!(2, list_namespace(&dict, "ge").len());
+    }
+    #[test]
+    fn namespace_2() {
+        // Errors:
+
+        let (dict, _cdict) = make_dict();
+        let spec = Rc::clone(&dict["spec.0"]);
+        let mut dict = SpectrumDictionary::new();
+        assert!(add_namespaced(&mut dict, "si", "strip", Rc::clone(&spec)).is_ok());
+        assert!(add_namespaced(&mut dict, "si", "strip", Rc::clone(&spec)).is_err());
+        assert!(add_namespaced(&mut dict, "", "strip", Rc::clone(&spec)).is_err());
+        assert!(add_namespaced(&mut dict, "si", "", Rc::clone(&spec)).is_err());
+        assert!(add_namespaced(&mut dict, "si/a", "strip", Rc::clone(&spec)).is_err());
+        assert!(add_namespaced(&mut dict, "si", "a/strip", spec).is_err());
+        assert_eq!(1, dict.len());
+    }
+    #[test]
+    fn namespace_3() {
+        // The key is namespaced but the spectrum's own name is not:
+
+        let (dict, _cdict) = make_dict();
+        let spec = Rc::clone(&dict["spec.0"]);
+        let mut dict = SpectrumDictionary::new();
+        add_namespaced(&mut dict, "si", "strip", Rc::clone(&spec)).unwrap();
+
+        assert!(dict.contains_key("si/strip"));
+        assert!(!dict.contains_key("strip"));
+        assert_eq!("spec.0", dict["si/strip"].borrow().get_name());
+        assert_eq!(vec![String::from("strip")], list_namespace(&dict, "si"));
+    }
+    #[test]
+    fn saved_gates_1() {
+        // Five good mappings and one with a nonexistent gate:
+
+        let (mut dict, cdict) = make_dict();
+        let mut gates = HashMap::new();
+        for i in 0..5 {
+            gates.insert(format!("spec.{}", i), String::from("true"));
+        }
+        gates.insert(String::from("spec.5"), String::from("nosuch"));
+
+        let failures = apply_saved_gates(&gates, &mut dict, &cdict).unwrap_err();
+        assert_eq!(1, failures.len());
+        assert!(failures[0].starts_with("spec.5"));
+        for i in 0..5 {
+            assert_eq!(
+                Some(String::from("true")),
+                dict[&format!("spec.{}", i)].borrow().description().gate
+            );
+        }
+        for i in 5..10 {
+            assert!(dict[&format!("spec.{}", i)]
+                .borrow()
+                .description()
+                .gate
+                .is_none());
+        }
+    }
+    #[test]
+    fn saved_gates_2() {
+        let (mut dict, cdict) = make_dict();
+        let mut gates = HashMap::new();
+        gates.insert(String::from("spec.1"), String::from("true"));
+        assert_eq!(Ok(()), apply_saved_gates(&gates, &mut dict, &cdict));
+        assert!(apply_saved_gates(&HashMap::new(), &mut dict, &cdict).is_ok());
+
+        gates.insert(String::from("nosuch"), String::from("true"));
+        assert_eq!(
+            Err(vec![String::from("No such spectrum nosuch")]),
+            apply_saved_gates(&gates, &mut dict, &cdict)
+        );
+    }
 }
+
 #[cfg(test)]
 mod spec_storage_tests {
     use super::*;
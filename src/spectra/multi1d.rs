@@ -41,6 +41,12 @@ impl Spectrum for Multi1d {
             }
         }
     }
+    fn fill_plan(&self) -> Option<FillPlan> {
+        Some(FillPlan::OneD {
+            ids: self.param_ids.clone(),
+            log: false,
+        })
+    }
     fn get_name(&self) -> String {
         self.name.clone()
     }
@@ -50,6 +56,21 @@ impl Spectrum for Multi1d {
     fn ungate(&mut self) {
         self.applied_gate.ungate()
     }
+    fn get_gate(&self) -> &SpectrumGate {
+        &self.applied_gate
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn deep_copy(&self) -> Box<dyn Spectrum> {
+        Box::new(Multi1d {
+            applied_gate: self.applied_gate.clone(),
+            name: self.name.clone(),
+            histogram: Rc::new(RefCell::new(self.histogram.borrow().clone())),
+            param_names: self.param_names.clone(),
+            param_ids: self.param_ids.clone(),
+        })
+    }
     fn get_histogram_1d(&self) -> Option<H1DContainer> {
         Some(Rc::clone(&self.histogram))
     }
@@ -62,6 +83,15 @@ impl Spectrum for Multi1d {
             *c.value = Sum::new();
         }
     }
+    fn description(&self) -> SpectrumDescription {
+        SpectrumDescription::from_1d(
+            &self.name,
+            SpectrumKind::Multi1D,
+            self.param_names.clone(),
+            &self.histogram,
+            &self.applied_gate,
+        )
+    }
 }
 
 impl Multi1d {
@@ -484,4 +514,45 @@ mod multi1d_tests {
         assert_eq!(5.0, spec.histogram.borrow().value(&-1.0).unwrap().get());
         assert_eq!(5.0, spec.histogram.borrow().value(&1025.0).unwrap().get());
     }
+    #[test]
+    fn description_1() {
+        let mut pd = ParameterDictionary::new();
+        for i in 0..4 {
+            let name = format!("param.{}", i);
+            pd.add(&name).unwrap();
+            let p = pd.lookup_mut(&name).unwrap();
+            p.set_limits(0.0, 1024.0);
+            p.set_bins(512);
+        }
+        let names: Vec<String> = (0..4).map(|i| format!("param.{}", i)).collect();
+        let mut spec = Multi1d::new(
+            "test",
+            names.clone(),
+            &pd,
+            Some(-10.0),
+            Some(10.0),
+            Some(20),
+        )
+        .unwrap();
+        let d = spec.description();
+        assert_eq!(String::from("test"), d.name);
+        assert_eq!(SpectrumKind::Multi1D, d.kind);
+        assert_eq!(names, d.x_params);
+        assert!(d.y_params.is_empty());
+        assert_eq!(
+            Some(AxisDescription {
+                low: -10.0,
+                high: 10.0,
+                bins: 20
+            }),
+            d.x_axis
+        );
+        assert!(d.y_axis.is_none());
+        assert!(d.gate.is_none());
+
+        let mut cd = ConditionDictionary::new();
+        cd.insert(String::from("true"), Rc::new(RefCell::new(True {})));
+        spec.gate("true", &cd).unwrap();
+        assert_eq!(Some(String::from("true")), spec.description().gate);
+    }
 }
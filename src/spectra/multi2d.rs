@@ -50,6 +50,19 @@ impl Spectrum for Multi2d {
             }
         }
     }
+    fn fill_plan(&self) -> Option<FillPlan> {
+        let mut pairs = Vec::new();
+        for a in 0..self.param_ids.len() {
+            for b in (a + 1)..self.param_ids.len() {
+                pairs.push((self.param_ids[a], self.param_ids[b]));
+            }
+        }
+        Some(FillPlan::TwoD {
+            pairs,
+            x_log: false,
+            y_log: false,
+        })
+    }
     fn get_name(&self) -> String {
         self.name.clone()
     }
@@ -59,6 +72,21 @@ impl Spectrum for Multi2d {
     fn ungate(&mut self) {
         self.applied_gate.ungate()
     }
+    fn get_gate(&self) -> &SpectrumGate {
+        &self.applied_gate
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn deep_copy(&self) -> Box<dyn Spectrum> {
+        Box::new(Multi2d {
+            applied_gate: self.applied_gate.clone(),
+            name: self.name.clone(),
+            histogram: Rc::new(RefCell::new(self.histogram.borrow().clone())),
+            param_names: self.param_names.clone(),
+            param_ids: self.param_ids.clone(),
+        })
+    }
     fn get_histogram_1d(&self) -> Option<H1DContainer> {
         None
     }
@@ -71,6 +99,16 @@ impl Spectrum for Multi2d {
             *c.value = Sum::new();
         }
     }
+    fn description(&self) -> SpectrumDescription {
+        SpectrumDescription::from_2d(
+            &self.name,
+            SpectrumKind::Multi2D,
+            self.param_names.clone(),
+            vec![],
+            &self.histogram,
+            &self.applied_gate,
+        )
+    }
 }
 impl Multi2d {
     /// Create a multi2d spectrum.
@@ -599,4 +637,55 @@ mod multi2d_tests {
             assert_eq!(0.0, chan.value.get());
         }
     }
+    #[test]
+    fn description_1() {
+        let mut pd = ParameterDictionary::new();
+        for i in 0..4 {
+            let name = format!("param.{}", i);
+            pd.add(&name).unwrap();
+            let p = pd.lookup_mut(&name).unwrap();
+            p.set_limits(0.0, 1024.0);
+            p.set_bins(512);
+        }
+        let names: Vec<String> = (0..4).map(|i| format!("param.{}", i)).collect();
+        let mut spec = Multi2d::new(
+            "test",
+            names.clone(),
+            &pd,
+            Some(-10.0),
+            Some(10.0),
+            Some(20),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        let d = spec.description();
+        assert_eq!(String::from("test"), d.name);
+        assert_eq!(SpectrumKind::Multi2D, d.kind);
+        assert_eq!(names, d.x_params);
+        assert!(d.y_params.is_empty());
+        assert_eq!(
+            Some(AxisDescription {
+                low: -10.0,
+                high: 10.0,
+                bins: 20
+            }),
+            d.x_axis
+        );
+        assert_eq!(
+            Some(AxisDescription {
+                low: 0.0,
+                high: 1024.0,
+                bins: 512
+            }),
+            d.y_axis
+        );
+        assert!(d.gate.is_none());
+
+        let mut cd = ConditionDictionary::new();
+        cd.insert(String::from("true"), Rc::new(RefCell::new(True {})));
+        spec.gate("true", &cd).unwrap();
+        assert_eq!(Some(String::from("true")), spec.description().gate);
+    }
 }
@@ -9,6 +9,7 @@ use ndhistogram::value::Sum;
 /// *   parameter_name is the name of the parameter used to increment the
 ///     spectrum and
 /// *   parameter_id is its id in the flattened event.
+/// *   log_axis is the real axis of spectra made with new_log.
 ///
 pub struct Oned {
     applied_gate: SpectrumGate,
@@ -16,6 +17,7 @@ pub struct Oned {
     histogram: H1DContainer,
     parameter_name: String,
     parameter_id: u32,
+    log_axis: Option<LogUniform>,
 }
 impl Spectrum for Oned {
     fn check_gate(&mut self, e: &FlatEvent) -> bool {
@@ -23,12 +25,18 @@ impl Spectrum for Oned {
     }
     fn increment(&mut self, e: &FlatEvent) {
         if let Some(p) = e[self.parameter_id] {
-            self.histogram.borrow_mut().fill(&p);
+            self.fill_values(&[p]);
         }
     }
     fn required_parameter(&self) -> Option<u32> {
         Some(self.parameter_id)
     }
+    fn fill_plan(&self) -> Option<FillPlan> {
+        Some(FillPlan::OneD {
+            ids: vec![self.parameter_id],
+            log: self.log_axis.is_some(),
+        })
+    }
     fn get_name(&self) -> String {
         self.name.clone()
     }
@@ -38,6 +46,22 @@ impl Spectrum for Oned {
     fn ungate(&mut self) {
         self.applied_gate.ungate()
     }
+    fn get_gate(&self) -> &SpectrumGate {
+        &self.applied_gate
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn deep_copy(&self) -> Box<dyn Spectrum> {
+        Box::new(Oned {
+            applied_gate: self.applied_gate.clone(),
+            name: self.name.clone(),
+            histogram: Rc::new(RefCell::new(self.histogram.borrow().clone())),
+            parameter_name: self.parameter_name.clone(),
+            parameter_id: self.parameter_id,
+            log_axis: self.log_axis.clone(),
+        })
+    }
     fn get_histogram_1d(&self) -> Option<H1DContainer> {
         Some(Rc::clone(&self.histogram))
     }
@@ -50,9 +74,44 @@ impl Spectrum for Oned {
             *c.value = Sum::new();
         }
     }
+    fn fill_channel_1d(&mut self, x: f64, value: f64) {
+        let x = if self.log_axis.is_some() {
+            LogUniform::log_coordinate(x)
+        } else if x.is_nan() {
+            return;
+        } else {
+            x
+        };
+        set_channel(&mut *self.histogram.borrow_mut(), &x, value);
+    }
+    fn description(&self) -> SpectrumDescription {
+        let mut result = SpectrumDescription::from_1d(
+            &self.name,
+            SpectrumKind::Oned,
+            vec![self.parameter_name.clone()],
+            &self.histogram,
+            &self.applied_gate,
+        );
+        result.x_log = self.log_axis.as_ref().map(AxisDescription::from_log_axis);
+        result
+    }
 }
 
 impl Oned {
+    // Fill the histogram with parameter values (converted for log axes):
+
+    fn fill_values(&self, values: &[f64]) {
+        let mut histogram = self.histogram.borrow_mut();
+        if self.log_axis.is_some() {
+            for v in values {
+                histogram.fill(&LogUniform::log_coordinate(*v));
+            }
+        } else {
+            for v in values {
+                histogram.fill(v);
+            }
+        }
+    }
     ///
     /// Create a new 1d spectrum.   The spectrum is initially ungated.
     /// the parameters of creation are:
@@ -87,11 +146,55 @@ impl Oned {
                 ))),
                 parameter_name: String::from(param_name),
                 parameter_id: param.get_id(),
+                log_axis: None,
             })
         } else {
             Err(format!("No such parameter: {}", param_name))
         }
     }
+    ///
+    /// Create a 1d spectrum with logarithmic binning.  The parameters
+    /// are the same as for new, but the resulting limits must be
+    /// positive.  The underlying histogram has a uniform axis on
+    /// ln(parameter) from ln(low) to ln(high).  get_log_axis gives
+    /// the axis in parameter coordinates.
+    ///
+    pub fn new_log(
+        spectrum_name: &str,
+        param_name: &str,
+        pdict: &ParameterDictionary,
+        low: Option<f64>,
+        high: Option<f64>,
+        bins: Option<u32>,
+    ) -> Result<Oned, String> {
+        let param = pdict
+            .lookup(param_name)
+            .ok_or(format!("No such parameter: {}", param_name))?;
+        let (low_lim, high_lim, bin_count) = axis_limits(param, low, high, bins)?;
+        let log_axis = LogUniform::new(bin_count as usize, low_lim, high_lim)?;
+        let mut result = Self::new(
+            spectrum_name,
+            param_name,
+            pdict,
+            Some(low_lim.ln()),
+            Some(high_lim.ln()),
+            Some(bin_count),
+        )?;
+        result.log_axis = Some(log_axis);
+        Ok(result)
+    }
+    /// The log axis of a spectrum made with new_log:
+    pub fn get_log_axis(&self) -> Option<LogUniform> {
+        self.log_axis.clone()
+    }
+}
+///
+/// Fill a 1-d spectrum with each of a slice of parameter values, e.g.
+/// when replaying stored data for calibrations.  No events are built
+/// so the spectrum's gate is not checked.
+///
+pub fn fill_1d_bulk(spec: &mut Oned, values: &[f64]) {
+    spec.fill_values(values);
 }
 #[cfg(test)]
 mod oned_tests {
@@ -428,6 +531,27 @@ mod oned_tests {
         assert_eq!(100.0, bin_value(512, &s));
     }
     #[test]
+    fn incr_8() {
+        // Sparse events increment the same as dense ones:
+
+        let mut s = make_1d();
+        let pid = s.parameter_id;
+        let e = vec![
+            EventParameter::new(pid + 100, 1.0),
+            EventParameter::new(pid, 511.0),
+        ];
+        let mut sparse = FlatEvent::new_for_dictionary(4096);
+        sparse.load_event(&e);
+        assert!(matches!(sparse.kind(), FlatEventKind::Sparse(_)));
+        let mut dense = FlatEvent::new();
+        dense.load_event(&e);
+
+        s.handle_event(&sparse);
+        assert_eq!(1.0, bin_value(512, &s));
+        s.handle_event(&dense);
+        assert_eq!(2.0, bin_value(512, &s));
+    }
+    #[test]
     fn clear_1() {
         let mut s = make_1d();
         let pid = s.parameter_id; // so we know how to fill in flat event:
@@ -445,4 +569,257 @@ mod oned_tests {
         s.clear();
         assert_eq!(0.0, bin_value(512, &s));
     }
+    #[test]
+    fn description_1() {
+        let mut pd = ParameterDictionary::new();
+        for i in 0..4 {
+            let name = format!("param.{}", i);
+            pd.add(&name).unwrap();
+            let p = pd.lookup_mut(&name).unwrap();
+            p.set_limits(0.0, 1024.0);
+            p.set_bins(512);
+        }
+        let mut spec =
+            Oned::new("test", "param.0", &pd, Some(-10.0), Some(10.0), Some(20)).unwrap();
+        let d = spec.description();
+        assert_eq!(String::from("test"), d.name);
+        assert_eq!(SpectrumKind::Oned, d.kind);
+        assert_eq!(vec![String::from("param.0")], d.x_params);
+        assert!(d.y_params.is_empty());
+        assert_eq!(
+            Some(AxisDescription {
+                low: -10.0,
+                high: 10.0,
+                bins: 20
+            }),
+            d.x_axis
+        );
+        assert!(d.y_axis.is_none());
+        assert!(d.gate.is_none());
+
+        let mut cd = ConditionDictionary::new();
+        cd.insert(String::from("true"), Rc::new(RefCell::new(True {})));
+        spec.gate("true", &cd).unwrap();
+        assert_eq!(Some(String::from("true")), spec.description().gate);
+    }
+    #[test]
+    fn log_1() {
+        // Limits must be positive:
+
+        let mut d = ParameterDictionary::new();
+        d.add("test").unwrap();
+        d.lookup_mut("test").unwrap().set_limits(0.0, 1000.0);
+        d.lookup_mut("test").unwrap().set_bins(10);
+        assert!(Oned::new_log("test_spec", "test", &d, None, None, None).is_err());
+        assert!(Oned::new_log("test_spec", "nosuch", &d, Some(1.0), None, None).is_err());
+
+        let spec = Oned::new_log("test_spec", "test", &d, Some(1.0), None, None).unwrap();
+        assert_eq!(
+            Some(LogUniform::new(10, 1.0, 1000.0).unwrap()),
+            spec.get_log_axis()
+        );
+        let x = spec.histogram.borrow().axes().as_tuple().0.clone();
+        assert_eq!(0.0, *x.low());
+        assert_eq!(1000.0_f64.ln(), *x.high());
+        assert_eq!(12, x.num_bins());
+        assert!(Oned::new("test_spec", "test", &d, None, None, None)
+            .unwrap()
+            .get_log_axis()
+            .is_none());
+    }
+    #[test]
+    fn log_2() {
+        // Log uniformly distributed values give (about) equal occupancy:
+
+        let mut d = ParameterDictionary::new();
+        d.add("test").unwrap();
+        let id = d.lookup("test").unwrap().get_id();
+        let mut spec =
+            Oned::new_log("test_spec", "test", &d, Some(1.0), Some(1.0e4), Some(10)).unwrap();
+        let mut fe = FlatEvent::new();
+        for i in 0..1000 {
+            let v = 1.0e4_f64.powf((i as f64 + 0.5) / 1000.0);
+            fe.load_event(&vec![EventParameter::new(id, v)]);
+            spec.handle_event(&fe);
+        }
+        // Non positive values underflow:
+
+        fe.load_event(&vec![EventParameter::new(id, 0.0)]);
+        spec.handle_event(&fe);
+        fe.load_event(&vec![EventParameter::new(id, -1.0)]);
+        spec.handle_event(&fe);
+
+        let h = spec.histogram.borrow();
+        for i in 1..=10 {
+            let counts = h.value_at_index(i).unwrap().get();
+            assert!((counts - 100.0).abs() <= 1.0, "bin {} has {}", i, counts);
+        }
+        assert_eq!(2.0, h.value_at_index(0).unwrap().get());
+        assert_eq!(0.0, h.value_at_index(11).unwrap().get());
+    }
+    #[test]
+    fn fill_channel_1() {
+        // Values replace the channel contents:
+
+        let mut s = make_1d();
+        let pid = s.parameter_id;
+        let mut fe = FlatEvent::new();
+        fe.load_event(&vec![EventParameter::new(pid, 512.0)]);
+        s.handle_event(&fe);
+        s.handle_event(&fe);
+        assert_eq!(2.0, s.histogram.borrow().value(&512.0).unwrap().get());
+
+        s.fill_channel_1d(512.2, 0.0);
+        assert_eq!(0.0, s.histogram.borrow().value(&512.0).unwrap().get());
+        s.fill_channel_1d(100.0, 3.5);
+        assert_eq!(3.5, s.histogram.borrow().value(&100.0).unwrap().get());
+        s.fill_channel_1d(-10.0, 7.0);
+        assert_eq!(7.0, s.histogram.borrow().value_at_index(0).unwrap().get());
+
+        // NaN and 2d fills are ignored:
+
+        s.fill_channel_1d(f64::NAN, 1.0);
+        s.fill_channel_2d(100.0, 100.0, 1.0);
+        let total: f64 = s.histogram.borrow().values().map(|v| v.get()).sum();
+        assert_eq!(10.5, total);
+    }
+    #[test]
+    fn fill_channel_2() {
+        // Gates don't matter:
+
+        let mut s = make_1d();
+        let mut gdict = ConditionDictionary::new();
+        gdict.insert(String::from("false"), Rc::new(RefCell::new(False {})));
+        s.gate("false", &gdict).unwrap();
+        s.fill_channel_1d(10.0, 5.0);
+        assert_eq!(5.0, s.histogram.borrow().value(&10.0).unwrap().get());
+
+        // Log spectra use parameter coordinates:
+
+        let mut d = ParameterDictionary::new();
+        d.add("test").unwrap();
+        let mut log = Oned::new_log("log", "test", &d, Some(1.0), Some(1000.0), Some(3)).unwrap();
+        log.fill_channel_1d(50.0, 2.0);
+        assert_eq!(2.0, log.histogram.borrow().value_at_index(2).unwrap().get());
+        log.fill_channel_1d(0.0, 1.0);
+        assert_eq!(1.0, log.histogram.borrow().value_at_index(0).unwrap().get());
+    }
+    #[test]
+    fn apply_function_1() {
+        let mut s = make_1d();
+        for x in [10.0, 10.0, 500.0, 900.0] {
+            s.histogram.borrow_mut().fill(&x);
+        }
+        s.histogram.borrow_mut().fill(&-5.0); // underflow is left alone.
+        let total = |s: &Oned| -> f64 { s.histogram.borrow().values().map(|v| v.get()).sum() };
+        assert_eq!(5.0, total(&s));
+
+        s.apply_function_1d(&|_, v| v * 2.0);
+        assert_eq!(9.0, total(&s));
+        assert_eq!(4.0, s.histogram.borrow().value(&10.0).unwrap().get());
+        assert_eq!(1.0, s.histogram.borrow().value(&-5.0).unwrap().get());
+
+        // The function gets the bin center:
+
+        s.apply_function_1d(&|x, v| if x < 100.0 { 0.0 } else { v });
+        assert_eq!(5.0, total(&s));
+    }
+    #[test]
+    fn normalize_1() {
+        // 100 events uniformly spread, one per channel:
+
+        let mut s = make_1d();
+        for i in 0..100 {
+            s.histogram.borrow_mut().fill(&(i as f64 * 10.0 + 0.5));
+        }
+        assert!(s.normalize(0.0).is_err());
+        assert!(s.normalize(-1.0).is_err());
+        s.normalize(100.0).unwrap();
+        let h = s.histogram.borrow();
+        let nonzero: Vec<f64> = h.values().map(|v| v.get()).filter(|v| *v != 0.0).collect();
+        assert_eq!(100, nonzero.len());
+        for v in nonzero {
+            assert!((v - 0.01).abs() < 1.0e-12);
+        }
+    }
+    #[test]
+    fn normalized_copy_1() {
+        let s = make_1d();
+        s.histogram.borrow_mut().fill(&10.0);
+        s.histogram.borrow_mut().fill(&10.0);
+        let copy = normalized_copy(&s, 4.0).unwrap();
+        assert_eq!("test_spec", copy.get_name());
+        let h = copy.get_histogram_1d().unwrap();
+        assert_eq!(0.5, h.borrow().value(&10.0).unwrap().get());
+
+        // The source is unchanged:
+
+        assert_eq!(2.0, s.histogram.borrow().value(&10.0).unwrap().get());
+        assert!(normalized_copy(&s, 0.0).is_err());
+    }
+    #[test]
+    fn axes_1() {
+        let mut d = ParameterDictionary::new();
+        d.add("test").unwrap();
+        let s = Oned::new("s", "test", &d, Some(0.0), Some(1024.0), Some(512)).unwrap();
+        assert_eq!(
+            AxisDescription {
+                low: 0.0,
+                high: 1024.0,
+                bins: 512
+            },
+            s.x_axis()
+        );
+        assert!(s.y_axis().is_none());
+    }
+    #[test]
+    fn bulk_1() {
+        // Same result as event by event filling:
+
+        let mut bulk = make_1d();
+        let mut by_event = make_1d();
+        let id = by_event.parameter_id;
+        let values: Vec<f64> = (0..2000).map(|i| (i % 1100) as f64 - 10.0).collect();
+
+        fill_1d_bulk(&mut bulk, &values);
+        let mut fe = FlatEvent::new();
+        for v in values.iter() {
+            fe.load_event(&vec![EventParameter::new(id, *v)]);
+            by_event.handle_event(&fe);
+        }
+        assert_eq!(
+            by_event
+                .histogram
+                .borrow()
+                .values()
+                .map(|v| v.get())
+                .collect::<Vec<f64>>(),
+            bulk.histogram
+                .borrow()
+                .values()
+                .map(|v| v.get())
+                .collect::<Vec<f64>>()
+        );
+        assert_eq!(2.0, bulk.histogram.borrow().value(&5.0).unwrap().get());
+    }
+    #[test]
+    fn bulk_2() {
+        // Ungated even if the spectrum has a gate and log axes are
+        // honored:
+
+        let mut d = ParameterDictionary::new();
+        d.add("test").unwrap();
+        let mut cdict = ConditionDictionary::new();
+        cdict.insert(String::from("false"), Rc::new(RefCell::new(False {})));
+        let mut spec =
+            Oned::new_log("test_spec", "test", &d, Some(1.0), Some(1.0e4), Some(4)).unwrap();
+        spec.gate("false", &cdict).unwrap();
+
+        fill_1d_bulk(&mut spec, &[2.0, 20.0, 200.0, 2000.0, 0.0]);
+        let h = spec.histogram.borrow();
+        assert_eq!(1.0, h.value_at_index(0).unwrap().get());
+        for i in 1..=4 {
+            assert_eq!(1.0, h.value_at_index(i).unwrap().get());
+        }
+    }
 }
@@ -0,0 +1,194 @@
+//!  A simple peak finder for 1-d spectra.  It is meant to supply
+//!  starting points for fits or to label peaks in displays, not
+//!  to replace a real peak search.
+//!
+//!  The algorithm is:
+//!
+//!  *  Find the local maxima whose counts are at least min_height.
+//!  *  Starting with the highest, drop any maximum that is within
+//!     min_separation_bins of a higher one.
+//!  *  For each remaining maximum, walk left and right until the
+//!     counts drop below half the peak height.  Those bins are the
+//!     bases of the peak.
+//!
+//!  Coordinates are in histogram (axis) coordinates, the center of
+//!  the relevant bin.
+//!
+use super::*;
+use ndhistogram::axis::Axis;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PeakCandidate {
+    pub center: f64,
+    pub height: f64,
+    pub left_base: f64,
+    pub right_base: f64,
+}
+
+// Center of user bin i (0 is the first bin above the underflow):
+fn bin_center(axis: &axis::Uniform, i: usize) -> f64 {
+    match axis.bin(i + 1) {
+        Some(BinInterval::Bin { start, end }) => (start + end) / 2.0,
+        _ => panic!("bin_center called for a bin that's not in the axis"),
+    }
+}
+
+///
+/// Find the peaks in a 1-d spectrum.  The peaks are returned in
+/// order of decreasing height.  If the spectrum is not 1-d, no peaks
+/// are returned.
+///
+pub fn find_peaks_1d(
+    spec: &dyn Spectrum,
+    min_height: f64,
+    min_separation_bins: usize,
+) -> Vec<PeakCandidate> {
+    let histogram = if let Some(h) = spec.get_histogram_1d() {
+        h
+    } else {
+        return vec![];
+    };
+    let histogram = histogram.borrow();
+    let axis = histogram.axes().as_tuple().0.clone();
+    let bins = axis.num_bins() - 2;
+    let counts: Vec<f64> = (1..=bins)
+        .map(|i| histogram.value_at_index(i).unwrap().get())
+        .collect();
+
+    // Local maxima: the first bin of a plateau counts as the maximum.
+
+    let mut maxima: Vec<usize> = (0..bins)
+        .filter(|&i| {
+            counts[i] >= min_height
+                && (i == 0 || counts[i] > counts[i - 1])
+                && (i == bins - 1 || counts[i] >= counts[i + 1])
+        })
+        .collect();
+    maxima.sort_by(|a, b| counts[*b].partial_cmp(&counts[*a]).unwrap());
+
+    let mut peaks: Vec<usize> = Vec::new();
+    for m in maxima {
+        if peaks.iter().all(|p| p.abs_diff(m) > min_separation_bins) {
+            peaks.push(m);
+        }
+    }
+
+    peaks
+        .iter()
+        .map(|&p| {
+            let half = counts[p] / 2.0;
+            let left = (0..p).rev().find(|&i| counts[i] < half);
+            let right = (p + 1..bins).find(|&i| counts[i] < half);
+            PeakCandidate {
+                center: bin_center(&axis, p),
+                height: counts[p],
+                left_base: left.map_or(*axis.low(), |i| bin_center(&axis, i)),
+                right_base: right.map_or(*axis.high(), |i| bin_center(&axis, i)),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod peaks_tests {
+    use super::*;
+
+    // 1024 channels on [0, 1024) with Gaussian peaks given as
+    // (center, height, sigma):
+
+    fn make_spectrum(peaks: &[(f64, f64, f64)]) -> Oned {
+        let mut pd = ParameterDictionary::new();
+        pd.add("p").unwrap();
+        let mut spec = Oned::new("peaks", "p", &pd, Some(0.0), Some(1024.0), Some(1024)).unwrap();
+        for i in 0..1024 {
+            let x = i as f64 + 0.5;
+            let value: f64 = peaks
+                .iter()
+                .map(|(c, h, s)| h * (-(x - c) * (x - c) / (2.0 * s * s)).exp())
+                .sum();
+            spec.fill_channel_1d(x, value);
+        }
+        spec
+    }
+
+    #[test]
+    fn find_1() {
+        let spec = make_spectrum(&[(300.5, 50.0, 15.0), (200.5, 100.0, 10.0)]);
+        let peaks = find_peaks_1d(&spec, 10.0, 20);
+        assert_eq!(2, peaks.len());
+
+        // Highest first:
+
+        assert_eq!(200.5, peaks[0].center);
+        assert!((peaks[0].height - 100.0).abs() < 1.0e-6);
+        assert_eq!(300.5, peaks[1].center);
+        assert!((peaks[1].height - 50.0).abs() < 1.0e-6);
+
+        // Bases are at about the half width (1.177 sigma):
+
+        assert!((peaks[0].left_base - (200.5 - 12.0)).abs() <= 1.0);
+        assert!((peaks[0].right_base - (200.5 + 12.0)).abs() <= 1.0);
+        assert!((peaks[1].left_base - (300.5 - 18.0)).abs() <= 1.0);
+        assert!((peaks[1].right_base - (300.5 + 18.0)).abs() <= 1.0);
+    }
+    #[test]
+    fn find_2() {
+        // min_height and min_separation_bins:
+
+        let spec = make_spectrum(&[(200.5, 100.0, 10.0), (300.5, 50.0, 15.0)]);
+        let peaks = find_peaks_1d(&spec, 60.0, 20);
+        assert_eq!(1, peaks.len());
+        assert_eq!(200.5, peaks[0].center);
+
+        let peaks = find_peaks_1d(&spec, 10.0, 100);
+        assert_eq!(1, peaks.len());
+        assert_eq!(200.5, peaks[0].center);
+    }
+    #[test]
+    fn find_3() {
+        // Empty spectrum, edge peaks and 2-d spectra:
+
+        let spec = make_spectrum(&[]);
+        assert!(find_peaks_1d(&spec, 1.0, 1).is_empty());
+
+        let mut spec = make_spectrum(&[]);
+        spec.fill_channel_1d(0.5, 10.0);
+        spec.fill_channel_1d(1023.5, 5.0);
+        let peaks = find_peaks_1d(&spec, 1.0, 1);
+        assert_eq!(
+            vec![
+                PeakCandidate {
+                    center: 0.5,
+                    height: 10.0,
+                    left_base: 0.0,
+                    right_base: 1.5
+                },
+                PeakCandidate {
+                    center: 1023.5,
+                    height: 5.0,
+                    left_base: 1022.5,
+                    right_base: 1024.0
+                }
+            ],
+            peaks
+        );
+
+        let mut pd = ParameterDictionary::new();
+        pd.add("x").unwrap();
+        pd.add("y").unwrap();
+        let twod = Twod::new(
+            "2d",
+            "x",
+            "y",
+            &pd,
+            Some(0.0),
+            Some(10.0),
+            Some(10),
+            Some(0.0),
+            Some(10.0),
+            Some(10),
+        )
+        .unwrap();
+        assert!(find_peaks_1d(&twod, 0.0, 1).is_empty());
+    }
+}
@@ -24,6 +24,7 @@ use ndhistogram::value::Sum;
 
 // This struct defines a parameter for the spectrum:
 
+#[derive(Clone)]
 struct SpectrumParameter {
     name: String,
     id: u32,
@@ -55,19 +56,22 @@ impl Spectrum for PGamma {
     //
     fn increment(&mut self, e: &FlatEvent) {
         let mut histogram = self.histogram.borrow_mut();
-        for xp in self.x_params.iter() {
-            for yp in self.y_params.iter() {
-                let xid = xp.id;
-                let yid = yp.id;
-
-                let x = e[xid];
-                let y = e[yid];
-                if x.is_some() && y.is_some() {
-                    histogram.fill(&(x.unwrap(), y.unwrap()));
-                }
-            }
+        for xy in self.pairs(e) {
+            histogram.fill(&xy);
         }
     }
+    fn fill_plan(&self) -> Option<FillPlan> {
+        let pairs = self
+            .x_params
+            .iter()
+            .flat_map(|xp| self.y_params.iter().map(move |yp| (xp.id, yp.id)))
+            .collect();
+        Some(FillPlan::TwoD {
+            pairs,
+            x_log: false,
+            y_log: false,
+        })
+    }
     fn get_name(&self) -> String {
         self.name.clone()
     }
@@ -77,6 +81,21 @@ impl Spectrum for PGamma {
     fn ungate(&mut self) {
         self.applied_gate.ungate()
     }
+    fn get_gate(&self) -> &SpectrumGate {
+        &self.applied_gate
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn deep_copy(&self) -> Box<dyn Spectrum> {
+        Box::new(PGamma {
+            applied_gate: self.applied_gate.clone(),
+            name: self.name.clone(),
+            histogram: Rc::new(RefCell::new(self.histogram.borrow().clone())),
+            x_params: self.x_params.clone(),
+            y_params: self.y_params.clone(),
+        })
+    }
     fn get_histogram_1d(&self) -> Option<H1DContainer> {
         None
     }
@@ -89,8 +108,27 @@ impl Spectrum for PGamma {
             *c.value = Sum::new();
         }
     }
+    fn description(&self) -> SpectrumDescription {
+        SpectrumDescription::from_2d(
+            &self.name,
+            SpectrumKind::PGamma,
+            self.x_params.iter().map(|p| p.name.clone()).collect(),
+            self.y_params.iter().map(|p| p.name.clone()).collect(),
+            &self.histogram,
+            &self.applied_gate,
+        )
+    }
 }
 impl PGamma {
+    // The (x, y) values of the pairs an event increments: all
+    // combinations of x and y parameters present in the event.
+    fn pairs<'a>(&'a self, e: &'a FlatEvent) -> impl Iterator<Item = (f64, f64)> + 'a {
+        self.x_params.iter().flat_map(move |xp| {
+            self.y_params
+                .iter()
+                .filter_map(move |yp| Some((e[xp.id]?, e[yp.id]?)))
+        })
+    }
     fn make_axis_def(
         params: &Vec<String>,
         pdict: &ParameterDictionary,
@@ -217,6 +255,19 @@ impl PGamma {
         })
     }
 }
+///
+/// Dry run of handling an event: the (x, y) values that handling the
+/// event would increment, in increment order.  Nothing is filled.  If
+/// the spectrum's gate does not accept the event the result is empty.
+///
+pub fn simulate_fill(spec: &PGamma, event: &FlatEvent) -> Vec<(f64, f64)> {
+    if spec.applied_gate.would_accept(event) {
+        spec.pairs(event).collect()
+    } else {
+        vec![]
+    }
+}
+
 #[cfg(test)]
 mod pgamma_tests {
     use super::*;
@@ -723,4 +774,128 @@ mod pgamma_tests {
             assert_eq!(0.0, c.value.get());
         }
     }
+    #[test]
+    fn description_1() {
+        let mut pd = ParameterDictionary::new();
+        for i in 0..4 {
+            let name = format!("param.{}", i);
+            pd.add(&name).unwrap();
+            let p = pd.lookup_mut(&name).unwrap();
+            p.set_limits(0.0, 1024.0);
+            p.set_bins(512);
+        }
+        let names: Vec<String> = (0..4).map(|i| format!("param.{}", i)).collect();
+        let xp = vec![names[0].clone()];
+        let yp = vec![names[1].clone(), names[2].clone(), names[3].clone()];
+        let mut spec = PGamma::new(
+            "test",
+            &xp,
+            &yp,
+            &pd,
+            Some(-10.0),
+            Some(10.0),
+            Some(20),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        let d = spec.description();
+        assert_eq!(String::from("test"), d.name);
+        assert_eq!(SpectrumKind::PGamma, d.kind);
+        assert_eq!(xp, d.x_params);
+        assert_eq!(yp, d.y_params);
+        assert_eq!(
+            Some(AxisDescription {
+                low: -10.0,
+                high: 10.0,
+                bins: 20
+            }),
+            d.x_axis
+        );
+        assert_eq!(
+            Some(AxisDescription {
+                low: 0.0,
+                high: 1024.0,
+                bins: 512
+            }),
+            d.y_axis
+        );
+        assert!(d.gate.is_none());
+
+        let mut cd = ConditionDictionary::new();
+        cd.insert(String::from("true"), Rc::new(RefCell::new(True {})));
+        spec.gate("true", &cd).unwrap();
+        assert_eq!(Some(String::from("true")), spec.description().gate);
+    }
+    #[test]
+    fn simulate_1() {
+        let dict = make_params(10, Some((0.0, 1024.0)), Some(1024));
+        let xp: Vec<String> = (0..4).map(|i| format!("param.{}", i)).collect();
+        let yp: Vec<String> = (4..7).map(|i| format!("param.{}", i)).collect();
+        let mut spec =
+            PGamma::new("test", &xp, &yp, &dict, None, None, None, None, None, None).unwrap();
+
+        // Three of the x and two of the y parameters are present:
+
+        let mut fe = FlatEvent::new();
+        let ids: Vec<u32> = ["param.0", "param.1", "param.3", "param.4", "param.6"]
+            .iter()
+            .map(|n| dict.lookup(n).unwrap().get_id())
+            .collect();
+        fe.load_event(&vec![
+            EventParameter::new(ids[0], 1.0),
+            EventParameter::new(ids[1], 2.0),
+            EventParameter::new(ids[2], 3.0),
+            EventParameter::new(ids[3], 10.0),
+            EventParameter::new(ids[4], 20.0),
+        ]);
+        let pairs = simulate_fill(&spec, &fe);
+        assert_eq!(
+            vec![
+                (1.0, 10.0),
+                (1.0, 20.0),
+                (2.0, 10.0),
+                (2.0, 20.0),
+                (3.0, 10.0),
+                (3.0, 20.0)
+            ],
+            pairs
+        );
+
+        // Nothing was filled; the real thing fills the same pairs:
+
+        let total = |s: &PGamma| -> f64 { s.histogram.borrow().values().map(|v| v.get()).sum() };
+        assert_eq!(0.0, total(&spec));
+        spec.handle_event(&fe);
+        assert_eq!(6.0, total(&spec));
+        for (x, y) in pairs {
+            assert_eq!(1.0, spec.histogram.borrow().value(&(x, y)).unwrap().get());
+        }
+    }
+    #[test]
+    fn simulate_2() {
+        // Gates are respected:
+
+        let dict = make_params(4, Some((0.0, 1024.0)), Some(1024));
+        let xp = vec![String::from("param.0")];
+        let yp = vec![String::from("param.1")];
+        let mut spec =
+            PGamma::new("test", &xp, &yp, &dict, None, None, None, None, None, None).unwrap();
+        let mut fe = FlatEvent::new();
+        fe.load_event(&vec![
+            EventParameter::new(1, 1.0),
+            EventParameter::new(2, 2.0),
+        ]);
+
+        let mut gdict = ConditionDictionary::new();
+        gdict.insert(String::from("false"), Rc::new(RefCell::new(False {})));
+        spec.gate("false", &gdict).unwrap();
+        assert!(simulate_fill(&spec, &fe).is_empty());
+
+        // A deleted gate accepts:
+
+        gdict.remove("false");
+        assert_eq!(vec![(1.0, 2.0)], simulate_fill(&spec, &fe));
+    }
 }
@@ -53,6 +53,11 @@ impl Spectrum for Summary {
             }
         }
     }
+    fn fill_plan(&self) -> Option<FillPlan> {
+        Some(FillPlan::Summary {
+            ids: self.param_ids.clone(),
+        })
+    }
     fn get_name(&self) -> String {
         self.name.clone()
     }
@@ -62,6 +67,21 @@ impl Spectrum for Summary {
     fn ungate(&mut self) {
         self.applied_gate.ungate()
     }
+    fn get_gate(&self) -> &SpectrumGate {
+        &self.applied_gate
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn deep_copy(&self) -> Box<dyn Spectrum> {
+        Box::new(Summary {
+            applied_gate: self.applied_gate.clone(),
+            name: self.name.clone(),
+            histogram: Rc::new(RefCell::new(self.histogram.borrow().clone())),
+            param_names: self.param_names.clone(),
+            param_ids: self.param_ids.clone(),
+        })
+    }
     fn get_histogram_1d(&self) -> Option<H1DContainer> {
         None
     }
@@ -73,6 +93,16 @@ impl Spectrum for Summary {
             *c.value = Sum::new();
         }
     }
+    fn description(&self) -> SpectrumDescription {
+        SpectrumDescription::from_2d(
+            &self.name,
+            SpectrumKind::Summary,
+            self.param_names.clone(),
+            vec![],
+            &self.histogram,
+            &self.applied_gate,
+        )
+    }
 }
 impl Summary {
     /// This local function takes the minimum of two values which
@@ -168,6 +198,119 @@ impl Summary {
             param_ids: param_ids.clone(),
         })
     }
+    ///
+    /// Reorder the strips of the spectrum.  new_order gives, for each
+    /// new x position, the current position of the parameter that will be
+    /// there;  e.g. [2, 0, 1] moves the last parameter to the front.
+    /// The counts move with their parameters.  new_order must be a
+    /// permutation of 0..number of parameters.
+    ///
+    pub fn reorder_parameters(&mut self, new_order: &[usize]) -> Result<(), String> {
+        let n = self.param_names.len();
+        let mut seen = vec![false; n];
+        if new_order.len() != n {
+            return Err(format!("The new order must have {} elements", n));
+        }
+        for &i in new_order {
+            if i >= n || seen[i] {
+                return Err(format!("{:?} is not a permutation of 0..{}", new_order, n));
+            }
+            seen[i] = true;
+        }
+        // position[old position] = new position:
+
+        let mut position = vec![0; n];
+        for (new, &old) in new_order.iter().enumerate() {
+            position[old] = new;
+        }
+
+        self.param_names = new_order
+            .iter()
+            .map(|&i| self.param_names[i].clone())
+            .collect();
+        self.param_ids = new_order.iter().map(|&i| self.param_ids[i]).collect();
+
+        let old = self.histogram.borrow();
+        let (xaxis, yaxis) = old.axes().as_tuple();
+        let mut reordered = ndhistogram!(xaxis.clone(), yaxis.clone(); Sum);
+        for item in old.iter() {
+            let counts = item.value.get();
+            if counts == 0.0 {
+                continue;
+            }
+            let x = match item.bin.0 {
+                BinInterval::Bin { start, .. } => position[start as usize] as f64 + 0.5,
+                other => bin_coordinate(&other),
+            };
+            set_channel(&mut reordered, &(x, bin_coordinate(&item.bin.1)), counts);
+        }
+        drop(old);
+        *self.histogram.borrow_mut() = reordered;
+        Ok(())
+    }
+}
+
+///
+/// The mean and RMS of the y values in each column of a Summary
+/// spectrum.  The result has an entry (parameter name, mean, rms) for
+/// each parameter, in x axis order.  Bin centers are used as the y
+/// values and the under/overflow bins are ignored.  Empty columns
+/// have NaN for the mean and rms.
+///
+pub fn column_stats(spec: &Summary) -> Vec<(String, f64, f64)> {
+    let histogram = spec.histogram.borrow();
+    let yaxis = histogram.axes().as_tuple().1.clone();
+    let ycenters: Vec<f64> = yaxis
+        .iter()
+        .filter_map(|(_, bin)| match bin {
+            BinInterval::Bin { start, end } => Some((start + end) / 2.0),
+            _ => None,
+        })
+        .collect();
+
+    spec.param_names
+        .iter()
+        .enumerate()
+        .map(|(column, name)| {
+            let x = column as f64 + 0.5;
+            let (mut sum, mut sum_y, mut sum_y2) = (0.0, 0.0, 0.0);
+            for y in ycenters.iter() {
+                let n = histogram.value(&(x, *y)).map_or(0.0, |v| v.get());
+                sum += n;
+                sum_y += n * y;
+                sum_y2 += n * y * y;
+            }
+            if sum > 0.0 {
+                let mean = sum_y / sum;
+                let rms = (sum_y2 / sum - mean * mean).max(0.0).sqrt();
+                (name.clone(), mean, rms)
+            } else {
+                (name.clone(), f64::NAN, f64::NAN)
+            }
+        })
+        .collect()
+}
+
+///
+/// Fill one column (x channel col, the col'th parameter) of a summary
+/// spectrum with each of a slice of values.  Like fill_1d_bulk, this
+/// does not check the spectrum's gate.  Err if there's no such column.
+///
+pub fn fill_column_bulk(spec: &mut Summary, col: usize, values: &[f64]) -> Result<(), String> {
+    if col >= spec.param_ids.len() {
+        return Err(format!(
+            "{} has no column {} (it has {})",
+            spec.name,
+            col,
+            spec.param_ids.len()
+        ));
+    }
+    let x = col as f64;
+    let mut histogram = spec.histogram.borrow_mut();
+    for y in values {
+        histogram.fill(&(x, *y));
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -614,4 +757,224 @@ mod summary_tests {
             }
         }
     }
+    #[test]
+    fn description_1() {
+        let mut pd = ParameterDictionary::new();
+        for i in 0..4 {
+            let name = format!("param.{}", i);
+            pd.add(&name).unwrap();
+            let p = pd.lookup_mut(&name).unwrap();
+            p.set_limits(0.0, 1024.0);
+            p.set_bins(512);
+        }
+        let names: Vec<String> = (0..4).map(|i| format!("param.{}", i)).collect();
+        let mut spec = Summary::new("test", names.clone(), &pd, None, None, None).unwrap();
+        let d = spec.description();
+        assert_eq!(String::from("test"), d.name);
+        assert_eq!(SpectrumKind::Summary, d.kind);
+        assert_eq!(names, d.x_params);
+        assert!(d.y_params.is_empty());
+        assert_eq!(
+            Some(AxisDescription {
+                low: 0.0,
+                high: 4.0,
+                bins: 4
+            }),
+            d.x_axis
+        );
+        assert_eq!(
+            Some(AxisDescription {
+                low: 0.0,
+                high: 1024.0,
+                bins: 512
+            }),
+            d.y_axis
+        );
+        assert!(d.gate.is_none());
+
+        let mut cd = ConditionDictionary::new();
+        cd.insert(String::from("true"), Rc::new(RefCell::new(True {})));
+        spec.gate("true", &cd).unwrap();
+        assert_eq!(Some(String::from("true")), spec.description().gate);
+    }
+    #[test]
+    fn fill_channel_1() {
+        // The default implementation works on x/y coordinates:
+
+        let mut pd = ParameterDictionary::new();
+        for i in 0..4 {
+            pd.add(&format!("param.{}", i)).unwrap();
+        }
+        let names: Vec<String> = (0..4).map(|i| format!("param.{}", i)).collect();
+        let mut spec = Summary::new("test", names, &pd, Some(0.0), Some(100.0), Some(100)).unwrap();
+        spec.fill_channel_2d(2.0, 50.0, 4.0);
+        assert_eq!(
+            4.0,
+            spec.histogram.borrow().value(&(2.0, 50.5)).unwrap().get()
+        );
+        spec.fill_channel_1d(2.0, 1.0);
+        let total: f64 = spec.histogram.borrow().values().map(|v| v.get()).sum();
+        assert_eq!(4.0, total);
+    }
+    #[test]
+    fn column_stats_1() {
+        let mut pd = ParameterDictionary::new();
+        let mut names = Vec::<String>::new();
+        for i in 0..4 {
+            let name = format!("param{}", i);
+            pd.add(&name).unwrap();
+            names.push(name);
+        }
+        let mut spec = Summary::new(
+            "summary",
+            names.clone(),
+            &pd,
+            Some(0.0),
+            Some(1024.0),
+            Some(1024),
+        )
+        .unwrap();
+
+        // param0: flat from 100-199, param1: 10 at 500, 10 at 510
+        // param2: 3 at 700.  param3 is empty.
+
+        let mut fe = FlatEvent::new();
+        for i in 0..100 {
+            fe.load_event(&vec![EventParameter::new(1, 100.0 + i as f64)]);
+            spec.handle_event(&fe);
+        }
+        for _ in 0..10 {
+            fe.load_event(&vec![
+                EventParameter::new(2, 500.0),
+                EventParameter::new(3, 700.0),
+            ]);
+            spec.handle_event(&fe);
+            fe.load_event(&vec![EventParameter::new(2, 510.0)]);
+            spec.handle_event(&fe);
+        }
+
+        let stats = column_stats(&spec);
+        assert_eq!(4, stats.len());
+        for (i, s) in stats.iter().enumerate() {
+            assert_eq!(names[i], s.0);
+        }
+        assert!((stats[0].1 - 150.0).abs() <= 0.5);
+        assert!((stats[0].2 - 28.866).abs() < 0.01); // sqrt((100^2 - 1)/12)
+        assert!((stats[1].1 - 505.0).abs() <= 0.5);
+        assert!((stats[1].2 - 5.0).abs() < 1.0e-6);
+        assert!((stats[2].1 - 700.0).abs() <= 0.5);
+        assert!(stats[2].2.abs() < 1.0e-6);
+        assert!(stats[3].1.is_nan());
+        assert!(stats[3].2.is_nan());
+    }
+    #[test]
+    fn reorder_1() {
+        let mut pd = ParameterDictionary::new();
+        let mut names = Vec::<String>::new();
+        for i in 0..3 {
+            let name = format!("param{}", i);
+            pd.add(&name).unwrap();
+            names.push(name);
+        }
+        let mut spec = Summary::new(
+            "summary",
+            names.clone(),
+            &pd,
+            Some(0.0),
+            Some(100.0),
+            Some(100),
+        )
+        .unwrap();
+
+        // Column i has i+1 counts at y = 10*(i+1); there's also an
+        // underflow in column 0:
+
+        let mut fe = FlatEvent::new();
+        for i in 0..3_u32 {
+            fe.load_event(&vec![EventParameter::new(i + 1, 10.0 * (i + 1) as f64)]);
+            for _ in 0..=i {
+                spec.handle_event(&fe);
+            }
+        }
+        fe.load_event(&vec![EventParameter::new(1, -1.0)]);
+        spec.handle_event(&fe);
+
+        spec.reorder_parameters(&[2, 0, 1]).unwrap();
+        assert_eq!(
+            vec![names[2].clone(), names[0].clone(), names[1].clone()],
+            spec.param_names
+        );
+        assert_eq!(vec![3, 1, 2], spec.param_ids);
+        {
+            let h = spec.histogram.borrow();
+            assert_eq!(3.0, h.value(&(0.5, 30.5)).unwrap().get());
+            assert_eq!(1.0, h.value(&(1.5, 10.5)).unwrap().get());
+            assert_eq!(2.0, h.value(&(2.5, 20.5)).unwrap().get());
+            assert_eq!(1.0, h.value(&(1.5, -1.0)).unwrap().get());
+            let total: f64 = h.values().map(|v| v.get()).sum();
+            assert_eq!(7.0, total);
+        }
+
+        // Increments follow the new order:
+
+        fe.load_event(&vec![EventParameter::new(3, 50.0)]);
+        spec.handle_event(&fe);
+        assert_eq!(
+            1.0,
+            spec.histogram.borrow().value(&(0.5, 50.5)).unwrap().get()
+        );
+    }
+    #[test]
+    fn reorder_2() {
+        // Not permutations:
+
+        let mut pd = ParameterDictionary::new();
+        let mut names = Vec::<String>::new();
+        for i in 0..3 {
+            let name = format!("param{}", i);
+            pd.add(&name).unwrap();
+            names.push(name);
+        }
+        let mut spec = Summary::new(
+            "summary",
+            names.clone(),
+            &pd,
+            Some(0.0),
+            Some(100.0),
+            Some(100),
+        )
+        .unwrap();
+        assert!(spec.reorder_parameters(&[0, 1]).is_err());
+        assert!(spec.reorder_parameters(&[0, 1, 2, 3]).is_err());
+        assert!(spec.reorder_parameters(&[0, 1, 1]).is_err());
+        assert!(spec.reorder_parameters(&[0, 1, 3]).is_err());
+        assert_eq!(names, spec.param_names);
+        assert!(spec.reorder_parameters(&[0, 1, 2]).is_ok());
+        assert_eq!(names, spec.param_names);
+    }
+    #[test]
+    fn bulk_1() {
+        let mut pd = ParameterDictionary::new();
+        let mut names = Vec::<String>::new();
+        for i in 0..3 {
+            let name = format!("param{}", i);
+            pd.add(&name).unwrap();
+            names.push(name);
+        }
+        let mut spec =
+            Summary::new("summary", names, &pd, Some(0.0), Some(100.0), Some(100)).unwrap();
+
+        assert!(fill_column_bulk(&mut spec, 1, &[10.0, 10.5, 20.0]).is_ok());
+        let h = spec.histogram.borrow();
+        assert_eq!(2.0, h.value(&(1.5, 10.5)).unwrap().get());
+        assert_eq!(1.0, h.value(&(1.5, 20.5)).unwrap().get());
+        assert_eq!(0.0, h.value(&(0.5, 10.5)).unwrap().get());
+        assert_eq!(0.0, h.value(&(2.5, 10.5)).unwrap().get());
+        drop(h);
+
+        assert_eq!(
+            Err(String::from("summary has no column 3 (it has 3)")),
+            fill_column_bulk(&mut spec, 3, &[1.0])
+        );
+    }
 }
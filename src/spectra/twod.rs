@@ -13,6 +13,7 @@ use ndhistogram::value::Sum;
 /// *    histogram -The underlying histogram.
 /// *    x_name, x_id - the name and Id of the X axis parameter.
 /// *    y_name, y_id - the name and Id of the Y axis parameter.
+/// *    x_log, y_log - the real axes of axes made with new_log_x/new_log_y.
 ///
 pub struct Twod {
     applied_gate: SpectrumGate,
@@ -24,6 +25,8 @@ pub struct Twod {
     x_id: u32,
     y_name: String,
     y_id: u32,
+    x_log: Option<LogUniform>,
+    y_log: Option<LogUniform>,
 }
 impl Spectrum for Twod {
     fn check_gate(&mut self, e: &FlatEvent) -> bool {
@@ -35,13 +38,26 @@ impl Spectrum for Twod {
 
         // We need both parameters in the event:
 
-        if x.is_some() && y.is_some() {
-            self.histogram.borrow_mut().fill(&(x.unwrap(), y.unwrap()));
+        if let (Some(mut x), Some(mut y)) = (x, y) {
+            if self.x_log.is_some() {
+                x = LogUniform::log_coordinate(x);
+            }
+            if self.y_log.is_some() {
+                y = LogUniform::log_coordinate(y);
+            }
+            self.histogram.borrow_mut().fill(&(x, y));
         }
     }
     fn required_parameter(&self) -> Option<u32> {
         Some(self.x_id)
     }
+    fn fill_plan(&self) -> Option<FillPlan> {
+        Some(FillPlan::TwoD {
+            pairs: vec![(self.x_id, self.y_id)],
+            x_log: self.x_log.is_some(),
+            y_log: self.y_log.is_some(),
+        })
+    }
     fn get_name(&self) -> String {
         self.name.clone()
     }
@@ -51,6 +67,25 @@ impl Spectrum for Twod {
     fn ungate(&mut self) {
         self.applied_gate.ungate()
     }
+    fn get_gate(&self) -> &SpectrumGate {
+        &self.applied_gate
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn deep_copy(&self) -> Box<dyn Spectrum> {
+        Box::new(Twod {
+            applied_gate: self.applied_gate.clone(),
+            name: self.name.clone(),
+            histogram: Rc::new(RefCell::new(self.histogram.borrow().clone())),
+            x_name: self.x_name.clone(),
+            x_id: self.x_id,
+            y_name: self.y_name.clone(),
+            y_id: self.y_id,
+            x_log: self.x_log.clone(),
+            y_log: self.y_log.clone(),
+        })
+    }
     fn get_histogram_1d(&self) -> Option<H1DContainer> {
         None
     }
@@ -62,6 +97,30 @@ impl Spectrum for Twod {
             *c.value = Sum::new();
         }
     }
+    fn fill_channel_2d(&mut self, mut x: f64, mut y: f64, value: f64) {
+        if self.x_log.is_some() {
+            x = LogUniform::log_coordinate(x);
+        }
+        if self.y_log.is_some() {
+            y = LogUniform::log_coordinate(y);
+        }
+        if !x.is_nan() && !y.is_nan() {
+            set_channel(&mut *self.histogram.borrow_mut(), &(x, y), value);
+        }
+    }
+    fn description(&self) -> SpectrumDescription {
+        let mut result = SpectrumDescription::from_2d(
+            &self.name,
+            SpectrumKind::Twod,
+            vec![self.x_name.clone()],
+            vec![self.y_name.clone()],
+            &self.histogram,
+            &self.applied_gate,
+        );
+        result.x_log = self.x_log.as_ref().map(AxisDescription::from_log_axis);
+        result.y_log = self.y_log.as_ref().map(AxisDescription::from_log_axis);
+        result
+    }
 }
 impl Twod {
     pub fn new(
@@ -98,6 +157,8 @@ impl Twod {
                 x_id: xpar.get_id(),
                 y_name: String::from(yname),
                 y_id: ypar.get_id(),
+                x_log: None,
+                y_log: None,
             })
         } else {
             Err(format!(
@@ -106,7 +167,146 @@ impl Twod {
             ))
         }
     }
+    ///
+    /// Create a 2d spectrum with logarithmic binning on the x axis.
+    /// The x axis of the underlying histogram is uniform on ln(x) over
+    /// x_axis.  y_axis None means the y axis comes from the y
+    /// parameter's default limits and bins as it does for new.
+    ///
+    pub fn new_log_x(
+        spectrum_name: &str,
+        xname: &str,
+        yname: &str,
+        pdict: &ParameterDictionary,
+        x_axis: LogUniform,
+        y_axis: Option<AxisDescription>,
+    ) -> Result<Twod, String> {
+        let (ylow, yhigh, ybins) = Self::axis_options(y_axis);
+        let mut result = Self::new(
+            spectrum_name,
+            xname,
+            yname,
+            pdict,
+            Some(x_axis.low().ln()),
+            Some(x_axis.high().ln()),
+            Some(x_axis.bins() as u32),
+            ylow,
+            yhigh,
+            ybins,
+        )?;
+        result.x_log = Some(x_axis);
+        Ok(result)
+    }
+    ///
+    /// Same as new_log_x but it's the y axis that is logarithmic.
+    ///
+    pub fn new_log_y(
+        spectrum_name: &str,
+        xname: &str,
+        yname: &str,
+        pdict: &ParameterDictionary,
+        x_axis: Option<AxisDescription>,
+        y_axis: LogUniform,
+    ) -> Result<Twod, String> {
+        let (xlow, xhigh, xbins) = Self::axis_options(x_axis);
+        let mut result = Self::new(
+            spectrum_name,
+            xname,
+            yname,
+            pdict,
+            xlow,
+            xhigh,
+            xbins,
+            Some(y_axis.low().ln()),
+            Some(y_axis.high().ln()),
+            Some(y_axis.bins() as u32),
+        )?;
+        result.y_log = Some(y_axis);
+        Ok(result)
+    }
+    /// The log axes of spectra made with new_log_x/new_log_y:
+    pub fn get_log_axes(&self) -> (Option<LogUniform>, Option<LogUniform>) {
+        (self.x_log.clone(), self.y_log.clone())
+    }
+    // The optional limits new wants for a linear axis:
+    fn axis_options(axis: Option<AxisDescription>) -> (Option<f64>, Option<f64>, Option<u32>) {
+        axis.map_or((None, None, None), |a| {
+            (Some(a.low), Some(a.high), Some(a.bins))
+        })
+    }
+}
+// Folding 2-d spectra.  Folding about x = 0 (y = 0) adds the counts in
+// each bin with negative x (y) to the mirror image bin with positive
+// x (y).  The result is a new, ungated Twod named <name>_xfold
+// (<name>_yfold) whose folded axis covers only the positive half of
+// the original axis.  The folded axis must be symmetric about zero
+// and have an even number of bins and must not be logarithmic.
+// Underflows on the folded axis become overflows.
+//
+// The result holds a snapshot of the folded counts;  events
+// later histogrammed into it are not folded.
+
+/// Fold a Twod about x = 0.
+pub fn fold_x(spec: &Twod) -> Result<Twod, String> {
+    fold(spec, true)
+}
+/// Fold a Twod about y = 0.
+pub fn fold_y(spec: &Twod) -> Result<Twod, String> {
+    fold(spec, false)
+}
+
+// The positive half of an axis that is symmetric about 0:
+fn half_axis(axis: &axis::Uniform) -> Result<axis::Uniform, String> {
+    let bins = axis.num_bins() - 2;
+    if *axis.low() != -*axis.high() || bins % 2 != 0 {
+        return Err(String::from(
+            "A folded axis must be symmetric about 0 with an even number of bins",
+        ));
+    }
+    Ok(axis::Uniform::new(bins / 2, 0.0, *axis.high()))
+}
+
+fn fold(spec: &Twod, about_x: bool) -> Result<Twod, String> {
+    if (about_x && spec.x_log.is_some()) || (!about_x && spec.y_log.is_some()) {
+        return Err(String::from("Log axes can't be folded"));
+    }
+    let source = spec.histogram.borrow();
+    let (xaxis, yaxis) = source.axes().as_tuple();
+    let (xaxis, yaxis) = if about_x {
+        (half_axis(xaxis)?, yaxis.clone())
+    } else {
+        (xaxis.clone(), half_axis(yaxis)?)
+    };
+    let mut folded = ndhistogram!(xaxis, yaxis; Sum);
+
+    for item in source.iter() {
+        let counts = item.value.get();
+        if counts == 0.0 {
+            continue;
+        }
+        let mut x = bin_coordinate(&item.bin.0);
+        let mut y = bin_coordinate(&item.bin.1);
+        if about_x {
+            x = x.abs();
+        } else {
+            y = y.abs();
+        }
+        let current = folded.value(&(x, y)).map(|v| v.get()).unwrap_or(0.0);
+        set_channel(&mut folded, &(x, y), current + counts);
+    }
+    Ok(Twod {
+        applied_gate: SpectrumGate::new(),
+        name: format!("{}_{}fold", spec.name, if about_x { "x" } else { "y" }),
+        histogram: Rc::new(RefCell::new(folded)),
+        x_name: spec.x_name.clone(),
+        x_id: spec.x_id,
+        y_name: spec.y_name.clone(),
+        y_id: spec.y_id,
+        x_log: spec.x_log.clone(),
+        y_log: spec.y_log.clone(),
+    })
 }
+
 #[cfg(test)]
 mod twod_tests {
     use super::*;
@@ -716,4 +916,384 @@ mod twod_tests {
 
         assert_eq!(0.0, v.get());
     }
+    #[test]
+    fn description_1() {
+        let mut pd = ParameterDictionary::new();
+        for i in 0..4 {
+            let name = format!("param.{}", i);
+            pd.add(&name).unwrap();
+            let p = pd.lookup_mut(&name).unwrap();
+            p.set_limits(0.0, 1024.0);
+            p.set_bins(512);
+        }
+        let mut spec = Twod::new(
+            "test",
+            "param.0",
+            "param.1",
+            &pd,
+            Some(-10.0),
+            Some(10.0),
+            Some(20),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        let d = spec.description();
+        assert_eq!(String::from("test"), d.name);
+        assert_eq!(SpectrumKind::Twod, d.kind);
+        assert_eq!(vec![String::from("param.0")], d.x_params);
+        assert_eq!(vec![String::from("param.1")], d.y_params);
+        assert_eq!(
+            Some(AxisDescription {
+                low: -10.0,
+                high: 10.0,
+                bins: 20
+            }),
+            d.x_axis
+        );
+        assert_eq!(
+            Some(AxisDescription {
+                low: 0.0,
+                high: 1024.0,
+                bins: 512
+            }),
+            d.y_axis
+        );
+        assert!(d.gate.is_none());
+
+        let mut cd = ConditionDictionary::new();
+        cd.insert(String::from("true"), Rc::new(RefCell::new(True {})));
+        spec.gate("true", &cd).unwrap();
+        assert_eq!(Some(String::from("true")), spec.description().gate);
+    }
+    #[test]
+    fn log_1() {
+        let mut pdict = ParameterDictionary::new();
+        pdict.add("x").unwrap();
+        pdict.add("y").unwrap();
+
+        // Undefined parameters:
+
+        let log_axis = LogUniform::new(10, 1.0, 100.0).unwrap();
+        assert!(Twod::new_log_x("2d", "nosuch", "y", &pdict, log_axis.clone(), None).is_err());
+        assert!(Twod::new_log_y("2d", "x", "nosuch", &pdict, None, log_axis.clone()).is_err());
+
+        let logx = Twod::new_log_x(
+            "2d",
+            "x",
+            "y",
+            &pdict,
+            log_axis.clone(),
+            Some(AxisDescription {
+                low: 0.0,
+                high: 1.0,
+                bins: 10,
+            }),
+        )
+        .unwrap();
+        assert_eq!(
+            (Some(LogUniform::new(10, 1.0, 100.0).unwrap()), None),
+            logx.get_log_axes()
+        );
+        let logy = Twod::new_log_y(
+            "2d",
+            "x",
+            "y",
+            &pdict,
+            Some(AxisDescription {
+                low: 0.0,
+                high: 1.0,
+                bins: 10,
+            }),
+            log_axis,
+        )
+        .unwrap();
+        assert_eq!(
+            (None, Some(LogUniform::new(10, 1.0, 100.0).unwrap())),
+            logy.get_log_axes()
+        );
+        let h = logy.histogram.borrow();
+        let (x, y) = h.axes().as_tuple();
+        assert_eq!(1.0, *x.high());
+        assert_eq!(100.0_f64.ln(), *y.high());
+    }
+    #[test]
+    fn log_2() {
+        // Log uniformly distributed x values give equal occupancy
+        // in each x bin:
+
+        let mut pdict = ParameterDictionary::new();
+        pdict.add("x").unwrap();
+        pdict.add("y").unwrap();
+        let mut spec = Twod::new_log_x(
+            "2d",
+            "x",
+            "y",
+            &pdict,
+            LogUniform::new(10, 1.0, 1.0e4).unwrap(),
+            Some(AxisDescription {
+                low: 0.0,
+                high: 1.0,
+                bins: 1,
+            }),
+        )
+        .unwrap();
+        let mut fe = FlatEvent::new();
+        for i in 0..1000 {
+            let v = 1.0e4_f64.powf((i as f64 + 0.5) / 1000.0);
+            fe.load_event(&vec![
+                EventParameter::new(1, v),
+                EventParameter::new(2, 0.5),
+            ]);
+            spec.handle_event(&fe);
+        }
+        let h = spec.histogram.borrow();
+        for x in 0..10 {
+            let counts = h
+                .value(&((x as f64 + 0.5) * 0.4 * 10.0_f64.ln(), 0.5))
+                .unwrap()
+                .get();
+            assert!((counts - 100.0).abs() <= 1.0, "bin {} has {}", x, counts);
+        }
+    }
+    #[test]
+    fn fill_channel_1() {
+        let mut spec = make_test_2d();
+        let event = vec![
+            EventParameter::new(spec.x_id, 0.0),
+            EventParameter::new(spec.y_id, 0.0),
+        ];
+        let mut fe = FlatEvent::new();
+        fe.load_event(&event);
+        spec.handle_event(&fe);
+        assert_eq!(
+            1.0,
+            spec.histogram.borrow().value(&(0.0, 0.0)).unwrap().get()
+        );
+
+        spec.fill_channel_2d(1.0, 0.01, 12.25);
+        assert_eq!(
+            12.25,
+            spec.histogram.borrow().value(&(0.0, 0.0)).unwrap().get()
+        );
+        spec.fill_channel_2d(100.0, -1.0, 2.0);
+        assert_eq!(
+            2.0,
+            spec.histogram.borrow().value(&(100.0, -1.0)).unwrap().get()
+        );
+
+        // Ignored:
+
+        spec.fill_channel_2d(f64::NAN, 0.0, 1.0);
+        spec.fill_channel_1d(0.0, 1.0);
+        let total: f64 = spec.histogram.borrow().values().map(|v| v.get()).sum();
+        assert_eq!(14.25, total);
+    }
+    fn make_symmetric_2d() -> Twod {
+        // 20x10 bins on [-10, 10) x [-5, 5) filled symmetrically:
+        // (+/-x, +/-y) all get i+1 counts where x is the center of
+        // positive x bin i.
+
+        let mut pdict = ParameterDictionary::new();
+        pdict.add("x").unwrap();
+        pdict.add("y").unwrap();
+        let mut spec = Twod::new(
+            "sym",
+            "x",
+            "y",
+            &pdict,
+            Some(-10.0),
+            Some(10.0),
+            Some(20),
+            Some(-5.0),
+            Some(5.0),
+            Some(10),
+        )
+        .unwrap();
+        let mut fe = FlatEvent::new();
+        for i in 0..10 {
+            let x = i as f64 + 0.5;
+            for (x, y) in [(x, 2.5), (-x, 2.5), (x, -2.5), (-x, -2.5)] {
+                fe.load_event(&vec![EventParameter::new(1, x), EventParameter::new(2, y)]);
+                for _ in 0..=i {
+                    spec.handle_event(&fe);
+                }
+            }
+        }
+        spec
+    }
+    #[test]
+    fn fold_1() {
+        let spec = make_symmetric_2d();
+        let folded = fold_x(&spec).unwrap();
+        assert_eq!("sym_xfold", folded.get_name());
+        assert!(folded.applied_gate.gate.is_none());
+        let h = folded.histogram.borrow();
+        let (x, y) = h.axes().as_tuple();
+        assert_eq!(0.0, *x.low());
+        assert_eq!(10.0, *x.high());
+        assert_eq!(12, x.num_bins());
+        assert_eq!(-5.0, *y.low());
+        assert_eq!(12, y.num_bins());
+
+        // Positive half has double the counts, negative half is gone:
+
+        for i in 0..10 {
+            let x = i as f64 + 0.5;
+            let expected = 2.0 * (i + 1) as f64;
+            assert_eq!(expected, h.value(&(x, 2.5)).unwrap().get());
+            assert_eq!(expected, h.value(&(x, -2.5)).unwrap().get());
+            assert_eq!(0.0, h.value(&(-x, 2.5)).unwrap().get());
+        }
+        let total: f64 = h.values().map(|v| v.get()).sum();
+        assert_eq!(4.0 * 55.0, total);
+
+        // The source is unchanged:
+
+        assert_eq!(
+            1.0,
+            spec.histogram.borrow().value(&(-0.5, 2.5)).unwrap().get()
+        );
+    }
+    #[test]
+    fn fold_2() {
+        let spec = make_symmetric_2d();
+        let folded = fold_y(&spec).unwrap();
+        assert_eq!("sym_yfold", folded.get_name());
+        let h = folded.histogram.borrow();
+        let (x, y) = h.axes().as_tuple();
+        assert_eq!(22, x.num_bins());
+        assert_eq!(0.0, *y.low());
+        assert_eq!(5.0, *y.high());
+        assert_eq!(7, y.num_bins());
+        for i in 0..10 {
+            let x = i as f64 + 0.5;
+            let expected = 2.0 * (i + 1) as f64;
+            assert_eq!(expected, h.value(&(x, 2.5)).unwrap().get());
+            assert_eq!(expected, h.value(&(-x, 2.5)).unwrap().get());
+            assert_eq!(0.0, h.value(&(x, -2.5)).unwrap().get());
+        }
+    }
+    #[test]
+    fn fold_3() {
+        // Underflows fold into overflows:
+
+        let mut spec = make_symmetric_2d();
+        let mut fe = FlatEvent::new();
+        fe.load_event(&vec![
+            EventParameter::new(1, -20.0),
+            EventParameter::new(2, 0.5),
+        ]);
+        spec.handle_event(&fe);
+        fe.load_event(&vec![
+            EventParameter::new(1, 20.0),
+            EventParameter::new(2, 0.5),
+        ]);
+        spec.handle_event(&fe);
+        let folded = fold_x(&spec).unwrap();
+        assert_eq!(
+            2.0,
+            folded.histogram.borrow().value(&(20.0, 0.5)).unwrap().get()
+        );
+    }
+    #[test]
+    fn fold_4() {
+        // Can't fold these:
+
+        let spec = make_test_2d(); // y axis has an even number of bins.
+        assert!(fold_x(&spec).is_ok());
+        assert!(fold_y(&spec).is_ok());
+
+        let mut pdict = ParameterDictionary::new();
+        pdict.add("x").unwrap();
+        pdict.add("y").unwrap();
+        let spec = Twod::new(
+            "2d",
+            "x",
+            "y",
+            &pdict,
+            Some(-10.0),
+            Some(20.0),
+            Some(20), // Not symmetric.
+            Some(-5.0),
+            Some(5.0),
+            Some(11), // Odd.
+        )
+        .unwrap();
+        assert!(fold_x(&spec).is_err());
+        assert!(fold_y(&spec).is_err());
+
+        let spec = Twod::new_log_x(
+            "2d",
+            "x",
+            "y",
+            &pdict,
+            LogUniform::new(10, 1.0, 100.0).unwrap(),
+            Some(AxisDescription {
+                low: -5.0,
+                high: 5.0,
+                bins: 10,
+            }),
+        )
+        .unwrap();
+        assert!(fold_x(&spec).is_err());
+        let folded = fold_y(&spec).unwrap();
+        assert_eq!(spec.get_log_axes(), folded.get_log_axes());
+    }
+    #[test]
+    fn apply_function_1() {
+        let mut spec = make_test_2d();
+        for p in [(0.0, 0.0), (0.0, 0.0), (100.0, 1.0), (-100.0, -1.0)] {
+            spec.histogram.borrow_mut().fill(&p);
+        }
+        spec.histogram.borrow_mut().fill(&(1000.0, 0.0)); // overflow.
+        let total = |s: &Twod| -> f64 { s.histogram.borrow().values().map(|v| v.get()).sum() };
+        assert_eq!(5.0, total(&spec));
+
+        spec.apply_function_2d(&|_, _, v| v * 2.0);
+        assert_eq!(9.0, total(&spec));
+        assert_eq!(
+            4.0,
+            spec.histogram.borrow().value(&(0.0, 0.0)).unwrap().get()
+        );
+
+        // The function gets the bin centers:
+
+        spec.apply_function_2d(&|x, y, v| if x > 50.0 && y > 0.5 { 0.0 } else { v });
+        assert_eq!(7.0, total(&spec));
+    }
+    #[test]
+    fn normalize_1() {
+        let mut spec = make_test_2d();
+        for _ in 0..4 {
+            spec.histogram.borrow_mut().fill(&(0.0, 0.0));
+        }
+        spec.normalize(8.0).unwrap();
+        assert_eq!(
+            0.5,
+            spec.histogram.borrow().value(&(0.0, 0.0)).unwrap().get()
+        );
+        assert!(spec.normalize(0.0).is_err());
+    }
+    #[test]
+    fn axes_1() {
+        let spec = make_test_2d();
+        assert_eq!(
+            AxisDescription {
+                low: -512.0,
+                high: 512.0,
+                bins: 256
+            },
+            spec.x_axis()
+        );
+        assert_eq!(
+            Some(AxisDescription {
+                low: -2.0,
+                high: 2.0,
+                bins: 200
+            }),
+            spec.y_axis()
+        );
+    }
 }
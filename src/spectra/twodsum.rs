@@ -68,6 +68,13 @@ impl Spectrum for TwodSum {
             }
         }
     }
+    fn fill_plan(&self) -> Option<FillPlan> {
+        Some(FillPlan::TwoD {
+            pairs: self.parameters.iter().map(|p| (p.x_id, p.y_id)).collect(),
+            x_log: false,
+            y_log: false,
+        })
+    }
     fn get_name(&self) -> String {
         self.name.clone()
     }
@@ -77,6 +84,20 @@ impl Spectrum for TwodSum {
     fn ungate(&mut self) {
         self.applied_gate.ungate()
     }
+    fn get_gate(&self) -> &SpectrumGate {
+        &self.applied_gate
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn deep_copy(&self) -> Box<dyn Spectrum> {
+        Box::new(TwodSum {
+            applied_gate: self.applied_gate.clone(),
+            name: self.name.clone(),
+            histogram: Rc::new(RefCell::new(self.histogram.borrow().clone())),
+            parameters: self.parameters.clone(),
+        })
+    }
     fn get_histogram_1d(&self) -> Option<H1DContainer> {
         None
     }
@@ -88,6 +109,16 @@ impl Spectrum for TwodSum {
             *c.value = Sum::new();
         }
     }
+    fn description(&self) -> SpectrumDescription {
+        SpectrumDescription::from_2d(
+            &self.name,
+            SpectrumKind::TwodSum,
+            self.parameters.iter().map(|p| p.x_name.clone()).collect(),
+            self.parameters.iter().map(|p| p.y_name.clone()).collect(),
+            &self.histogram,
+            &self.applied_gate,
+        )
+    }
 }
 impl TwodSum {
     pub fn new(
@@ -965,4 +996,59 @@ mod twodsum_tests {
         }
         assert_eq!(3, sum);
     }
+    #[test]
+    fn description_1() {
+        let mut pd = ParameterDictionary::new();
+        for i in 0..4 {
+            let name = format!("param.{}", i);
+            pd.add(&name).unwrap();
+            let p = pd.lookup_mut(&name).unwrap();
+            p.set_limits(0.0, 1024.0);
+            p.set_bins(512);
+        }
+        let names: Vec<String> = (0..4).map(|i| format!("param.{}", i)).collect();
+        let params: XYParameters = vec![
+            (names[0].clone(), names[1].clone()),
+            (names[2].clone(), names[3].clone()),
+        ];
+        let mut spec = TwodSum::new(
+            "test",
+            params,
+            &pd,
+            Some(-10.0),
+            Some(10.0),
+            Some(20),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        let d = spec.description();
+        assert_eq!(String::from("test"), d.name);
+        assert_eq!(SpectrumKind::TwodSum, d.kind);
+        assert_eq!(vec![names[0].clone(), names[2].clone()], d.x_params);
+        assert_eq!(vec![names[1].clone(), names[3].clone()], d.y_params);
+        assert_eq!(
+            Some(AxisDescription {
+                low: -10.0,
+                high: 10.0,
+                bins: 20
+            }),
+            d.x_axis
+        );
+        assert_eq!(
+            Some(AxisDescription {
+                low: 0.0,
+                high: 1024.0,
+                bins: 512
+            }),
+            d.y_axis
+        );
+        assert!(d.gate.is_none());
+
+        let mut cd = ConditionDictionary::new();
+        cd.insert(String::from("true"), Rc::new(RefCell::new(True {})));
+        spec.gate("true", &cd).unwrap();
+        assert_eq!(Some(String::from("true")), spec.description().gate);
+    }
 }
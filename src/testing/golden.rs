@@ -0,0 +1,380 @@
+//!  Golden file regression tests for spectra.  A test fills a spectrum
+//!  with a reproducible sequence of events and calls
+//!  assert_spectrum_matches to compare the bins with those stored in a
+//!  golden file.  The first run (no golden file) writes the file;
+//!  later runs must produce exactly the same bins.  When the bins are
+//!  meant to change (e.g. a deliberate axis change) run
+//!
+//!  ```text
+//!  UPDATE_GOLDENS=1 cargo test
+//!  ```
+//!
+//!  to rewrite the golden files and commit the new ones.
+//!
+//!  The golden format is little endian:
+//!
+//!  *  The magic bytes "HGLD" and a u32 format version (1).
+//!  *  A u32 count of axes (1 or 2).
+//!  *  For each axis the low and high limits (f64) and bins (u32).
+//!  *  A u64 channel count followed by that many f64 channel values.
+//!     These include the under/overflow channels and are in
+//!     histogram index order.
+//!
+use crate::spectra::{AxisDescription, Spectrum};
+use ndhistogram::Histogram;
+use std::path::Path;
+
+/// The environment variable that makes assert_spectrum_matches rewrite
+/// the golden files.
+pub const UPDATE_GOLDENS: &str = "UPDATE_GOLDENS";
+
+const MAGIC: &[u8; 4] = b"HGLD";
+const VERSION: u32 = 1;
+
+#[derive(Debug, PartialEq)]
+struct Golden {
+    axes: Vec<AxisDescription>,
+    channels: Vec<f64>,
+}
+
+impl Golden {
+    fn from_spectrum(spec: &dyn Spectrum) -> Golden {
+        let mut axes = vec![spec.x_axis()];
+        let channels = if let Some(h) = spec.get_histogram_1d() {
+            h.borrow().values().map(|v| v.get()).collect()
+        } else if let Some(h) = spec.get_histogram_2d() {
+            axes.push(spec.y_axis().unwrap());
+            h.borrow().values().map(|v| v.get()).collect()
+        } else {
+            vec![]
+        };
+        Golden { axes, channels }
+    }
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut result = Vec::new();
+        result.extend_from_slice(MAGIC);
+        result.extend_from_slice(&VERSION.to_le_bytes());
+        result.extend_from_slice(&(self.axes.len() as u32).to_le_bytes());
+        for a in &self.axes {
+            result.extend_from_slice(&a.low.to_le_bytes());
+            result.extend_from_slice(&a.high.to_le_bytes());
+            result.extend_from_slice(&a.bins.to_le_bytes());
+        }
+        result.extend_from_slice(&(self.channels.len() as u64).to_le_bytes());
+        for c in &self.channels {
+            result.extend_from_slice(&c.to_le_bytes());
+        }
+        result
+    }
+    fn from_bytes(bytes: &[u8]) -> Result<Golden, String> {
+        let mut reader = Reader { bytes, offset: 0 };
+        if reader.take(4)? != MAGIC {
+            return Err(String::from("Not a golden file"));
+        }
+        let version = reader.u32()?;
+        if version != VERSION {
+            return Err(format!("Unsupported golden file version {}", version));
+        }
+        let mut axes = Vec::new();
+        for _ in 0..reader.u32()? {
+            let low = reader.f64()?;
+            let high = reader.f64()?;
+            let bins = reader.u32()?;
+            axes.push(AxisDescription { low, high, bins });
+        }
+        let mut channels = Vec::new();
+        for _ in 0..reader.u64()? {
+            channels.push(reader.f64()?);
+        }
+        if reader.offset != bytes.len() {
+            return Err(String::from("Golden file has trailing bytes"));
+        }
+        Ok(Golden { axes, channels })
+    }
+}
+
+// Sequential little endian reads from the golden file bytes:
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let result = self
+            .bytes
+            .get(self.offset..self.offset + n)
+            .ok_or_else(|| String::from("Golden file is truncated"))?;
+        self.offset += n;
+        Ok(result)
+    }
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+    fn f64(&mut self) -> Result<f64, String> {
+        Ok(f64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+///
+/// True if the environment variable env_var asks for the golden files
+/// to be regenerated (is set to anything but "" or "0").
+///
+pub fn update_goldens(env_var: &str) -> bool {
+    match std::env::var(env_var) {
+        Ok(value) => !value.is_empty() && value != "0",
+        Err(_) => false,
+    }
+}
+
+///
+/// Compare the axes and channels of spec with the golden file at
+/// golden_path.  If there is no golden file, or UPDATE_GOLDENS is set,
+/// the golden file is written (along with any missing directories)
+/// instead.  Panics, as assertions do, if the spectrum does not match
+/// or the golden file can't be read or written.
+///
+pub fn assert_spectrum_matches(spec: &dyn Spectrum, golden_path: &Path) {
+    let actual = Golden::from_spectrum(spec);
+    if update_goldens(UPDATE_GOLDENS) || !golden_path.exists() {
+        if let Some(dir) = golden_path.parent() {
+            std::fs::create_dir_all(dir)
+                .unwrap_or_else(|e| panic!("Creating {}: {}", dir.display(), e));
+        }
+        std::fs::write(golden_path, actual.to_bytes())
+            .unwrap_or_else(|e| panic!("Writing {}: {}", golden_path.display(), e));
+        return;
+    }
+    let bytes = std::fs::read(golden_path)
+        .unwrap_or_else(|e| panic!("Reading {}: {}", golden_path.display(), e));
+    let expected =
+        Golden::from_bytes(&bytes).unwrap_or_else(|e| panic!("{}: {}", golden_path.display(), e));
+
+    assert_eq!(
+        expected.axes,
+        actual.axes,
+        "Axes of {} differ from {}",
+        spec.get_name(),
+        golden_path.display()
+    );
+    assert_eq!(
+        expected.channels.len(),
+        actual.channels.len(),
+        "Channel count of {} differs from {}",
+        spec.get_name(),
+        golden_path.display()
+    );
+    if let Some(i) =
+        (0..actual.channels.len()).find(|&i| expected.channels[i] != actual.channels[i])
+    {
+        panic!(
+            "Channel {} of {} is {} but {} has {}",
+            i,
+            spec.get_name(),
+            actual.channels[i],
+            golden_path.display(),
+            expected.channels[i]
+        );
+    }
+}
+
+#[cfg(test)]
+mod golden_tests {
+    use super::*;
+    use crate::conditions::*;
+    use crate::parameters::*;
+    use crate::spectra::*;
+    use crate::testing::{EventGenerator, ValueDistribution};
+    use std::cell::RefCell;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::path::PathBuf;
+    use std::rc::Rc;
+
+    fn golden_path(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("testdata/golden")
+            .join(format!("{}.gld", name))
+    }
+
+    // Parameters p.1 ... p.16 with ids 1..16 as the generator expects.
+    // The values are Gaussian so the shape of the spectra mean something.
+
+    fn make_dictionary() -> ParameterDictionary {
+        let mut pdict = ParameterDictionary::new();
+        for i in 1..=16 {
+            let name = format!("p.{}", i);
+            pdict.add(&name).unwrap();
+            pdict
+                .lookup_mut(&name)
+                .unwrap()
+                .set_limits(0.0, 1024.0)
+                .set_bins(1024);
+        }
+        pdict
+    }
+    fn names(ids: std::ops::RangeInclusive<u32>) -> Vec<String> {
+        ids.map(|i| format!("p.{}", i)).collect()
+    }
+    fn events() -> Vec<FlatEvent> {
+        EventGenerator::new(16, 0.75, 627)
+            .with_distribution(ValueDistribution::Gaussian {
+                mean: 512.0,
+                sigma: 100.0,
+            })
+            .unwrap()
+            .event_stream(5000)
+            .collect()
+    }
+    fn fill(spec: &mut dyn Spectrum) {
+        for e in events() {
+            spec.handle_event(&e);
+        }
+    }
+
+    #[test]
+    fn oned_1() {
+        let pdict = make_dictionary();
+        let mut spec = Oned::new("oned", "p.1", &pdict, None, None, Some(256)).unwrap();
+        fill(&mut spec);
+        assert_spectrum_matches(&spec, &golden_path("oned_1"));
+    }
+    #[test]
+    fn oned_2() {
+        // Gated on a cut, a slice of p.2:
+
+        let pdict = make_dictionary();
+        let mut cdict = ConditionDictionary::new();
+        let cut: Container = Rc::new(RefCell::new(Cut::new(2, 400.0, 600.0)));
+        cdict.insert(String::from("cut"), cut);
+        let mut spec = Oned::new("oned", "p.1", &pdict, None, None, Some(256)).unwrap();
+        spec.gate("cut", &cdict).unwrap();
+        for e in events() {
+            invalidate_cache(&mut cdict);
+            spec.handle_event(&e);
+        }
+        assert_spectrum_matches(&spec, &golden_path("oned_2"));
+    }
+    #[test]
+    fn twod_1() {
+        let pdict = make_dictionary();
+        let mut spec = Twod::new(
+            "twod",
+            "p.1",
+            "p.2",
+            &pdict,
+            Some(200.0),
+            Some(800.0),
+            Some(64),
+            None,
+            None,
+            Some(32),
+        )
+        .unwrap();
+        fill(&mut spec);
+        assert_spectrum_matches(&spec, &golden_path("twod_1"));
+    }
+    #[test]
+    fn summary_1() {
+        let pdict = make_dictionary();
+        let mut spec =
+            Summary::new("summary", names(1..=16), &pdict, None, None, Some(128)).unwrap();
+        fill(&mut spec);
+        assert_spectrum_matches(&spec, &golden_path("summary_1"));
+    }
+    #[test]
+    fn pgamma_1() {
+        let pdict = make_dictionary();
+        let mut spec = PGamma::new(
+            "pgamma",
+            &names(1..=4),
+            &names(5..=8),
+            &pdict,
+            None,
+            None,
+            Some(64),
+            None,
+            None,
+            Some(64),
+        )
+        .unwrap();
+        fill(&mut spec);
+        assert_spectrum_matches(&spec, &golden_path("pgamma_1"));
+    }
+
+    // The mechanism itself:
+
+    #[test]
+    fn format_1() {
+        let golden = Golden {
+            axes: vec![
+                AxisDescription {
+                    low: 0.0,
+                    high: 10.0,
+                    bins: 10,
+                },
+                AxisDescription {
+                    low: -1.0,
+                    high: 1.0,
+                    bins: 2,
+                },
+            ],
+            channels: vec![1.0, 2.5, 0.0, -3.0],
+        };
+        let bytes = golden.to_bytes();
+        assert_eq!(MAGIC, &bytes[0..4]);
+        assert_eq!(4 + 4 + 4 + 2 * 20 + 8 + 4 * 8, bytes.len());
+        assert_eq!(golden, Golden::from_bytes(&bytes).unwrap());
+
+        assert!(Golden::from_bytes(&bytes[0..bytes.len() - 1]).is_err());
+        let mut extra = bytes.clone();
+        extra.push(0);
+        assert!(Golden::from_bytes(&extra).is_err());
+        assert!(Golden::from_bytes(b"XXXX").is_err());
+    }
+    #[test]
+    fn assert_1() {
+        // Missing goldens are written, then matched:
+
+        let pdict = make_dictionary();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sub/oned.gld");
+        let mut spec = Oned::new("oned", "p.1", &pdict, None, None, Some(16)).unwrap();
+        fill(&mut spec);
+        assert_spectrum_matches(&spec, &path);
+        assert!(path.exists());
+        assert_spectrum_matches(&spec, &path);
+    }
+    #[test]
+    fn assert_2() {
+        // Differences are caught:
+
+        let pdict = make_dictionary();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("oned.gld");
+        let mut spec = Oned::new("oned", "p.1", &pdict, None, None, Some(16)).unwrap();
+        fill(&mut spec);
+        assert_spectrum_matches(&spec, &path);
+
+        spec.fill_channel_1d(100.0, 1000.0);
+        let result = catch_unwind(AssertUnwindSafe(|| assert_spectrum_matches(&spec, &path)));
+        assert_eq!(!update_goldens(UPDATE_GOLDENS), result.is_err());
+
+        let other = Oned::new("oned", "p.1", &pdict, None, None, Some(32)).unwrap();
+        let result = catch_unwind(AssertUnwindSafe(|| assert_spectrum_matches(&other, &path)));
+        assert_eq!(!update_goldens(UPDATE_GOLDENS), result.is_err());
+    }
+    #[test]
+    fn update_1() {
+        std::env::set_var("HISTOGRAMMER_GOLDEN_TEST_1", "1");
+        assert!(update_goldens("HISTOGRAMMER_GOLDEN_TEST_1"));
+        std::env::set_var("HISTOGRAMMER_GOLDEN_TEST_1", "0");
+        assert!(!update_goldens("HISTOGRAMMER_GOLDEN_TEST_1"));
+        std::env::set_var("HISTOGRAMMER_GOLDEN_TEST_1", "");
+        assert!(!update_goldens("HISTOGRAMMER_GOLDEN_TEST_1"));
+        std::env::remove_var("HISTOGRAMMER_GOLDEN_TEST_1");
+        assert!(!update_goldens("HISTOGRAMMER_GOLDEN_TEST_1"));
+    }
+}
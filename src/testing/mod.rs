@@ -0,0 +1,189 @@
+//!  Support for load testing and benchmarking.
+//!
+//!  EventGenerator makes reproducible synthetic events:  each of the
+//!  parameters in a dictionary of param_count parameters (ids 1 through
+//!  param_count as ParameterDictionary assigns them) is present with
+//!  probability fill_fraction and its value is drawn from a
+//!  ValueDistribution.  Generators made with the same seed produce the
+//!  same events.
+//!
+//!  Submodules:
+//!
+//!  *  golden - golden file regression tests of spectrum contents.
+//!
+use crate::parameters::{Event, EventParameter, FlatEvent};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rand_distr::{Distribution, Exp, Normal};
+
+pub mod golden;
+pub use golden::*;
+
+/// Distributions parameter values can be drawn from:
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ValueDistribution {
+    Uniform { low: f64, high: f64 },
+    Gaussian { mean: f64, sigma: f64 },
+    Exponential { mean: f64 },
+}
+
+pub struct EventGenerator {
+    param_count: u32,
+    fill_fraction: f32,
+    rng_seed: u64,
+    distribution: ValueDistribution,
+    rng: StdRng,
+}
+
+impl EventGenerator {
+    /// Values are uniform on [0, 1024) unless with_distribution
+    /// sets another distribution.  fill_fraction is clamped to [0, 1].
+    pub fn new(param_count: u32, fill_fraction: f32, rng_seed: u64) -> EventGenerator {
+        EventGenerator {
+            param_count,
+            fill_fraction: fill_fraction.clamp(0.0, 1.0),
+            rng_seed,
+            distribution: ValueDistribution::Uniform {
+                low: 0.0,
+                high: 1024.0,
+            },
+            rng: StdRng::seed_from_u64(rng_seed),
+        }
+    }
+    /// Set the distribution of the values.  Err if the distribution's
+    /// parameters are not legal (e.g. high <= low or sigma < 0).
+    pub fn with_distribution(
+        mut self,
+        distribution: ValueDistribution,
+    ) -> Result<EventGenerator, String> {
+        let ok = match distribution {
+            ValueDistribution::Uniform { low, high } => low < high,
+            ValueDistribution::Gaussian { sigma, .. } => sigma >= 0.0 && sigma.is_finite(),
+            ValueDistribution::Exponential { mean } => mean > 0.0,
+        };
+        if ok {
+            self.distribution = distribution;
+            Ok(self)
+        } else {
+            Err(format!("Invalid value distribution {:?}", distribution))
+        }
+    }
+    pub fn param_count(&self) -> u32 {
+        self.param_count
+    }
+    pub fn fill_fraction(&self) -> f32 {
+        self.fill_fraction
+    }
+    pub fn rng_seed(&self) -> u64 {
+        self.rng_seed
+    }
+    // The distribution's parameters were checked in with_distribution
+    // so the unwraps can't fail:
+
+    fn value(&mut self) -> f64 {
+        match self.distribution {
+            ValueDistribution::Uniform { low, high } => self.rng.random_range(low..high),
+            ValueDistribution::Gaussian { mean, sigma } => {
+                Normal::new(mean, sigma).unwrap().sample(&mut self.rng)
+            }
+            ValueDistribution::Exponential { mean } => {
+                Exp::new(1.0 / mean).unwrap().sample(&mut self.rng)
+            }
+        }
+    }
+    /// The next event as id/value pairs, in increasing id order.
+    pub fn next_event(&mut self) -> Event {
+        let mut result = Event::new();
+        for id in 1..=self.param_count {
+            if self.rng.random::<f32>() < self.fill_fraction {
+                let value = self.value();
+                result.push(EventParameter::new(id, value));
+            }
+        }
+        result
+    }
+    /// The next event, flattened.
+    pub fn next_flat_event(&mut self) -> FlatEvent {
+        let mut result = FlatEvent::new_with_capacity(self.param_count);
+        result.load_event(&self.next_event());
+        result
+    }
+    /// The next count flattened events.
+    pub fn event_stream(&mut self, count: usize) -> impl Iterator<Item = FlatEvent> + '_ {
+        (0..count).map(|_| self.next_flat_event())
+    }
+}
+
+#[cfg(test)]
+mod generator_tests {
+    use super::*;
+
+    #[test]
+    fn fill_1() {
+        // The average number of parameters is about fill_fraction * param_count:
+
+        let mut generator = EventGenerator::new(100, 0.25, 1234);
+        let total: usize = generator
+            .event_stream(1000)
+            .map(|e| (1..=100).filter(|id| e[*id].is_some()).count())
+            .sum();
+        let average = total as f64 / 1000.0;
+        assert!((average - 25.0).abs() < 2.5, "average {}", average);
+    }
+    #[test]
+    fn seed_1() {
+        // Same seed, same events;  different seed different events:
+
+        let mut a = EventGenerator::new(10, 0.5, 1);
+        let mut b = EventGenerator::new(10, 0.5, 1);
+        let mut c = EventGenerator::new(10, 0.5, 2);
+        let ea: Vec<Event> = (0..10).map(|_| a.next_event()).collect();
+        let eb: Vec<Event> = (0..10).map(|_| b.next_event()).collect();
+        let ec: Vec<Event> = (0..10).map(|_| c.next_event()).collect();
+        assert_eq!(ea, eb);
+        assert_ne!(ea, ec);
+    }
+    #[test]
+    fn distribution_1() {
+        let mut generator = EventGenerator::new(1, 1.0, 5)
+            .with_distribution(ValueDistribution::Uniform {
+                low: 10.0,
+                high: 20.0,
+            })
+            .unwrap();
+        for _ in 0..100 {
+            let v = generator.next_event()[0].value;
+            assert!((10.0..20.0).contains(&v));
+        }
+
+        let mut generator = EventGenerator::new(1, 1.0, 5)
+            .with_distribution(ValueDistribution::Gaussian {
+                mean: 100.0,
+                sigma: 5.0,
+            })
+            .unwrap();
+        let mean: f64 = (0..1000)
+            .map(|_| generator.next_event()[0].value)
+            .sum::<f64>()
+            / 1000.0;
+        assert!((mean - 100.0).abs() < 1.0);
+
+        let mut generator = EventGenerator::new(1, 1.0, 5)
+            .with_distribution(ValueDistribution::Exponential { mean: 10.0 })
+            .unwrap();
+        let values: Vec<f64> = (0..1000).map(|_| generator.next_event()[0].value).collect();
+        assert!(values.iter().all(|v| *v >= 0.0));
+        let mean = values.iter().sum::<f64>() / 1000.0;
+        assert!((mean - 10.0).abs() < 1.0);
+
+        assert!(EventGenerator::new(1, 1.0, 5)
+            .with_distribution(ValueDistribution::Uniform {
+                low: 1.0,
+                high: 1.0
+            })
+            .is_err());
+        assert!(EventGenerator::new(1, 1.0, 5)
+            .with_distribution(ValueDistribution::Exponential { mean: 0.0 })
+            .is_err());
+    }
+}